@@ -1,10 +1,13 @@
 use codespan::{ByteIndex, ByteOffset, ByteSpan};
 use codespan_reporting::{Diagnostic, Label as DiagnosticLabel};
-use im;
 use failure::Fail;
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use im;
+use moniker::{Binder, BoundTerm, Embed, FreeVar, Ignore, Nest, Scope, Var};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::{Label, Level, LevelShift, Plicity};
 
 use crate::syntax::concrete;
 use crate::syntax::raw;
@@ -20,18 +23,32 @@ pub struct DesugarEnv {
     /// If we arrive at a variable that has not already been assigned a free name,
     /// we assume that it is a global name.
     locals: im::HashMap<String, FreeVar<String>>,
+    /// The names that have been resolved, if we have been asked to record them
+    ///
+    /// This is shared between all copies of the environment, so that the names
+    /// resolved in nested scopes end up in the same index.
+    names: Option<Rc<RefCell<NameIndex>>>,
 }
 
 impl DesugarEnv {
     pub fn new(mappings: im::HashMap<String, FreeVar<String>>) -> DesugarEnv {
-        DesugarEnv { locals: mappings }
+        DesugarEnv {
+            locals: mappings,
+            names: None,
+        }
     }
 
-    pub fn on_item(&mut self, name: &str) -> Binder<String> {
-        if let Some(free_var) = self.locals.get(name) {
-            return Binder(free_var.clone());
+    /// Start recording the names that are resolved from now on
+    pub fn record_names(&mut self) {
+        self.names = Some(Rc::new(RefCell::new(NameIndex::default())));
+    }
+
+    /// Remove the names that have been recorded so far, returning them
+    pub fn take_names(&self) -> NameIndex {
+        match self.names {
+            Some(ref names) => names.replace(NameIndex::default()),
+            None => NameIndex::default(),
         }
-        Binder(self.on_binding(name))
     }
 
     pub fn on_binding(&mut self, name: &str) -> FreeVar<String> {
@@ -41,8 +58,57 @@ impl DesugarEnv {
         free_var
     }
 
+    /// Bind a name that was written in the source code
+    fn on_binder(&mut self, occurrence: Occurrence, name: &str) -> FreeVar<String> {
+        let free_var = self.on_binding(name);
+        self.record_binder(&free_var, occurrence);
+        free_var
+    }
+
+    /// Record another binding site for an existing variable, for example the
+    /// definition that follows a declaration
+    fn record_binder(&self, free_var: &FreeVar<String>, occurrence: Occurrence) {
+        if let Some(ref names) = self.names {
+            names.borrow_mut().insert_binder(free_var, occurrence);
+        }
+    }
+
+    /// Record the documentation of a variable that was bound by an item
+    fn record_doc(&self, free_var: &FreeVar<String>, doc: &str) {
+        if let Some(ref names) = self.names {
+            names.borrow_mut().insert_doc(free_var, doc);
+        }
+    }
+
+    /// Record the names that are in scope throughout the given span
+    fn record_scope(&self, span: ByteSpan) {
+        if let Some(ref names) = self.names {
+            let scope = (span, self.locals.clone());
+            names.borrow_mut().scopes.push(scope);
+        }
+    }
+
+    /// Look up a local name, recording the use if it was found
+    fn lookup(&self, occurrence: Occurrence, name: &str) -> Option<&FreeVar<String>> {
+        let free_var = self.locals.get(name)?;
+        if let Some(ref names) = self.names {
+            names.borrow_mut().insert_use(free_var, occurrence);
+        }
+        Some(free_var)
+    }
+
     pub fn on_name(&self, span: ByteSpan, name: &str, shift: u32) -> raw::RcTerm {
-        let free_var = match self.locals.get(name) {
+        self.on_var(span, Occurrence::name(span.start(), name), name, shift)
+    }
+
+    fn on_var(
+        &self,
+        span: ByteSpan,
+        occurrence: Occurrence,
+        name: &str,
+        shift: u32,
+    ) -> raw::RcTerm {
+        let free_var = match self.lookup(occurrence, name) {
             None => FreeVar::fresh_named(name),
             Some(free_var) => free_var.clone(),
         };
@@ -51,6 +117,146 @@ impl DesugarEnv {
     }
 }
 
+/// How a name was written at one of its occurrences
+///
+/// This lets editor tooling know what to replace when renaming the name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OccurrenceKind {
+    /// A plain name, eg. `x` in `\x => x`
+    Name,
+    /// A label that also binds a name, eg. `x` in `Record { x : Type }`
+    Label,
+    /// A punned field that refers to a name, eg. `x` in `record { x }`
+    PunnedLabel,
+}
+
+/// An occurrence of a name in the source code
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub span: ByteSpan,
+    pub kind: OccurrenceKind,
+}
+
+impl Occurrence {
+    fn name(start: ByteIndex, name: &str) -> Occurrence {
+        Occurrence {
+            span: ByteSpan::from_offset(start, ByteOffset::from_str(name)),
+            kind: OccurrenceKind::Name,
+        }
+    }
+
+    fn label(start: ByteIndex, label: &str) -> Occurrence {
+        Occurrence {
+            kind: OccurrenceKind::Label,
+            ..Occurrence::name(start, label)
+        }
+    }
+
+    fn punned_label(start: ByteIndex, label: &str) -> Occurrence {
+        Occurrence {
+            kind: OccurrenceKind::PunnedLabel,
+            ..Occurrence::name(start, label)
+        }
+    }
+}
+
+/// A name that was bound in the source code, along with the places where it
+/// is referred to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    /// The places where the name is bound
+    ///
+    /// This will usually only contain a single occurrence, but the items in
+    /// `let` and `where` blocks are bound by both their declaration and their
+    /// definition.
+    pub binders: Vec<Occurrence>,
+    /// The places where the name is used
+    pub uses: Vec<Occurrence>,
+    /// The doc comments attached to the item that bound the name, if any
+    pub doc: String,
+}
+
+impl Binding {
+    /// All the occurrences of the name, starting with its binders
+    pub fn occurrences(&self) -> impl Iterator<Item = &Occurrence> {
+        self.binders.iter().chain(self.uses.iter())
+    }
+}
+
+/// An index from the names bound in the source code to the places where they
+/// are used, for use by editor tooling
+#[derive(Debug, Clone, Default)]
+pub struct NameIndex {
+    bindings: Vec<Binding>,
+    /// The positions of the bindings for each variable in `bindings`
+    positions: HashMap<FreeVar<String>, usize>,
+    /// The names that are in scope in each of the spans that were desugared
+    scopes: Vec<(ByteSpan, im::HashMap<String, FreeVar<String>>)>,
+}
+
+impl NameIndex {
+    fn insert_binder(&mut self, free_var: &FreeVar<String>, occurrence: Occurrence) {
+        if let Some(&position) = self.positions.get(free_var) {
+            self.bindings[position].binders.push(occurrence);
+            return;
+        }
+
+        self.positions.insert(free_var.clone(), self.bindings.len());
+        self.bindings.push(Binding {
+            name: free_var.pretty_name.clone().unwrap_or_default(),
+            binders: vec![occurrence],
+            uses: Vec::new(),
+            doc: String::new(),
+        });
+    }
+
+    fn insert_doc(&mut self, free_var: &FreeVar<String>, doc: &str) {
+        if let Some(&position) = self.positions.get(free_var) {
+            self.bindings[position].doc = doc.to_owned();
+        }
+    }
+
+    fn insert_use(&mut self, free_var: &FreeVar<String>, occurrence: Occurrence) {
+        // Uses of names that were bound outside of the recorded source code,
+        // like those in the prelude, are not included
+        if let Some(&position) = self.positions.get(free_var) {
+            self.bindings[position].uses.push(occurrence);
+        }
+    }
+
+    /// The bindings that were recorded, in the order that they were bound
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Returns `true` if no source code was desugared while recording
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// The names that are in scope at the given index, if it is inside the
+    /// source code that was desugared
+    ///
+    /// When scopes share the same span, the names of the most recently
+    /// recorded scope are used, as these include the names that it binds.
+    pub fn scope_at(&self, index: ByteIndex) -> Option<&im::HashMap<String, FreeVar<String>>> {
+        let scopes = self.scopes.iter().rev();
+        let scopes = scopes.filter(|&&(span, _)| span.start() <= index && index <= span.end());
+        let (_, names) = scopes.min_by_key(|&&(span, _)| span.end() - span.start())?;
+        Some(names)
+    }
+
+    /// Find the binding with an occurrence that touches the given index
+    pub fn binding_at(&self, index: ByteIndex) -> Option<&Binding> {
+        self.bindings.iter().find(|binding| {
+            binding.occurrences().any(|occurrence| {
+                occurrence.span.start() <= index && index <= occurrence.span.end()
+            })
+        })
+    }
+}
+
 /// An error produced during resugaring
 #[derive(Debug, Fail, Clone, PartialEq)]
 pub enum DesugarError {
@@ -156,25 +362,39 @@ fn desugar_fun_ty(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
+    for &(plicity, ref names, ref ann) in param_groups {
         let ann = raw::RcTerm::from(ann.desugar(&env)?);
         params.extend(names.iter().map(|&(start, ref name)| {
-            let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            let free_var = env.on_binder(Occurrence::name(start, name), name);
+            (start, plicity, Binder(free_var), ann.clone())
         }));
     }
+    record_params_scope(&env, &params, body);
 
     Ok(params
         .into_iter()
         .rev()
-        .fold(body.desugar(&env)?, |acc, (start, binder, ann)| {
+        .fold(body.desugar(&env)?, |acc, (start, plicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::FunType(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
         }))
 }
 
+/// Record the scope of a group of parameters, which starts at the first
+/// parameter and extends to the end of the body
+fn record_params_scope<T>(
+    env: &DesugarEnv,
+    params: &[(ByteIndex, Plicity, Binder<String>, T)],
+    body: &concrete::Term,
+) {
+    if let Some(&(start, _, _, _)) = params.first() {
+        env.record_scope(ByteSpan::new(start, body.span().end()));
+    }
+}
+
 /// Convert a sugary lambda from something like:
 ///
 /// ```text
@@ -195,17 +415,23 @@ fn desugar_fun_intro(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
+    for &(plicity, ref names, ref ann) in param_groups {
         let ann = match *ann {
-            None => raw::RcTerm::from(raw::Term::Hole(ByteSpan::default())),
-            Some(ref ann) => ann.desugar(&env)?,
+            None => None,
+            Some(ref ann) => Some(ann.desugar(&env)?),
         };
 
         params.extend(names.iter().map(|&(start, ref name)| {
-            let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            // Omitted annotations become holes that point at their binders
+            let ann = ann.clone().unwrap_or_else(|| {
+                let span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                raw::RcTerm::from(raw::Term::Hole(span))
+            });
+            let free_var = env.on_binder(Occurrence::name(start, name), name);
+            (start, plicity, Binder(free_var), ann)
         }));
     }
+    record_params_scope(&env, &params, body);
 
     let body = match return_ann {
         None => body.desugar(&env)?,
@@ -215,9 +441,10 @@ fn desugar_fun_intro(
     Ok(params
         .into_iter()
         .rev()
-        .fold(body, |acc, (start, binder, ann)| {
+        .fold(body, |acc, (start, plicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::FunIntro(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
         }))
@@ -226,7 +453,7 @@ fn desugar_fun_intro(
 fn desugar_items(
     env: &mut DesugarEnv,
     concrete_items: &[concrete::Item],
-) -> Result<Nest<(Binder<String>, Embed<raw::RcTerm>)>, DesugarError> {
+) -> Result<Nest<(Binder<String>, Embed<raw::RcTerm>, Ignore<String>)>, DesugarError> {
     use im::HashMap;
 
     #[derive(Clone)]
@@ -235,21 +462,30 @@ fn desugar_items(
         Defined(ByteSpan),
     }
 
+    // The binders introduced by this block, so that declarations and
+    // definitions of the same name refer to the same variable
+    let mut binders = HashMap::new();
     // Declarations that may be waiting to be defined
     let mut forward_declarations = HashMap::new();
+    // The doc comments of the declarations that are waiting to be defined
+    let mut declaration_docs = HashMap::new();
     // The elaborated items, pre-allocated to improve performance
     let mut items = Vec::with_capacity(concrete_items.len());
-    let hole = raw::RcTerm::from(raw::Term::Hole(ByteSpan::default()));
 
     // Iterate through the items in the module, checking each in turn
     for concrete_item in concrete_items {
         match *concrete_item {
             concrete::Item::Declaration {
+                ref doc,
                 name: (start, ref name),
                 ref ann,
             } => {
-                let binder = env.on_item(name);
+                let binder = binders
+                    .entry(name.clone())
+                    .or_insert_with(|| Binder(env.on_binding(name)))
+                    .clone();
                 let name_span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                env.record_binder(&binder.0, Occurrence::name(start, name));
 
                 // Ensure that this declaration has not already been seen
                 match forward_declarations.get(&binder) {
@@ -280,16 +516,22 @@ fn desugar_items(
                 // Remember the declaration for when we get to a subsequent definition
                 let declaration = ForwardDecl::Pending(name_span, ann.desugar(&env)?);
                 forward_declarations.insert(binder.clone(), declaration);
+                declaration_docs.insert(binder.clone(), doc);
             },
 
             concrete::Item::Definition {
+                ref doc,
                 name: (start, ref name),
                 ref params,
                 ref return_ann,
                 ref body,
             } => {
-                let binder = env.on_item(name);
+                let binder = binders
+                    .entry(name.clone())
+                    .or_insert_with(|| Binder(env.on_binding(name)))
+                    .clone();
                 let name_span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                env.record_binder(&binder.0, Occurrence::name(start, name));
                 let term =
                     desugar_fun_intro(env, params, return_ann.as_ref().map(<_>::as_ref), body)?;
                 let ann = match forward_declarations.get(&binder).cloned() {
@@ -310,17 +552,39 @@ fn desugar_items(
                     // for checking the definition
                     Some(ForwardDecl::Pending(_, ann)) => ann.clone(),
                     // No prior declaration was found, so use a hole instead
-                    None => hole.clone(),
+                    None => raw::RcTerm::from(raw::Term::Hole(name_span)),
                 };
 
                 // We must not remove this from the list of pending
                 // declarations, lest we encounter another declaration or
                 // definition of the same name later on!
                 forward_declarations.insert(binder.clone(), ForwardDecl::Defined(name_span));
-                // Add the definition to the elaborated items
-                items.push((binder, Embed(raw::RcTerm::from(raw::Term::Ann(term, ann)))));
+                // Add the definition to the elaborated items, binding any
+                // recursive occurrences of the definition within itself
+                let term = raw::RcTerm::from(raw::Term::Ann(term, ann));
+                let term = if term.free_vars().contains(&binder.0) {
+                    raw::RcTerm::from(raw::Term::Fix(
+                        ByteSpan::new(start, term.span().end()),
+                        Scope::new(binder.clone(), term),
+                    ))
+                } else {
+                    term
+                };
+                // Combine the doc comments of the declaration and the
+                // definition, separating them with a blank line
+                let doc = declaration_docs
+                    .get(&binder)
+                    .into_iter()
+                    .chain(Some(&doc))
+                    .filter(|doc| !doc.is_empty())
+                    .map(|doc| doc.join("\n"))
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                env.record_doc(&binder.0, &doc);
+                items.push((binder, Embed(term), Ignore(doc)));
             },
-            concrete::Item::Error(_) => unimplemented!("error recovery"),
+            // The parse error has already been reported, so we skip the item
+            concrete::Item::Error(_) => {},
         }
     }
 
@@ -335,9 +599,11 @@ fn desugar_let(
 ) -> Result<raw::RcTerm, DesugarError> {
     let mut env = env.clone();
     let items = desugar_items(&mut env, concrete_items)?;
+    let span = ByteSpan::new(start, body.span().end());
+    env.record_scope(span);
 
     Ok(raw::RcTerm::from(raw::Term::Let(
-        ByteSpan::new(start, body.span().end()),
+        span,
         Scope::new(items, body.desugar(&env)?),
     )))
 }
@@ -350,10 +616,12 @@ fn desugar_where(
 ) -> Result<raw::RcTerm, DesugarError> {
     let mut env = env.clone();
     let items = desugar_items(&mut env, concrete_items)?;
+    let span = ByteSpan::new(body.span().start(), end);
+    env.record_scope(span);
 
     // TODO: Remember formatting
     Ok(raw::RcTerm::from(raw::Term::Let(
-        ByteSpan::new(body.span().start(), end),
+        span,
         Scope::new(items, body.desugar(&env)?),
     )))
 }
@@ -368,11 +636,11 @@ fn desugar_record_ty(
     let fields = fields
         .iter()
         .map(|field| {
-            let (_, ref label) = field.label;
+            let (label_start, ref label) = field.label;
             let ann = field.ann.desugar(&env)?;
             let free_var = match field.binder {
-                Some((_, ref binder)) => env.on_binding(binder),
-                None => env.on_binding(label),
+                Some((start, ref binder)) => env.on_binder(Occurrence::name(start, binder), binder),
+                None => env.on_binder(Occurrence::label(label_start, label), label),
             };
 
             Ok((Label(label.clone()), Binder(free_var), Embed(ann)))
@@ -385,6 +653,38 @@ fn desugar_record_ty(
     )))
 }
 
+fn desugar_variant_ty(
+    env: &DesugarEnv,
+    span: ByteSpan,
+    binder: Option<&(ByteIndex, String)>,
+    arms: &[concrete::VariantTypeArm],
+) -> Result<raw::RcTerm, DesugarError> {
+    let mut env = env.clone();
+    let binder = match binder {
+        Some(&(start, ref name)) => Binder(env.on_binder(Occurrence::name(start, name), name)),
+        None => Binder(FreeVar::fresh_unnamed()),
+    };
+
+    let arms = arms
+        .iter()
+        .map(|arm| {
+            let (_, ref label) = arm.label;
+            let args = arm
+                .args
+                .iter()
+                .map(|arg| arg.desugar(&env))
+                .collect::<Result<_, _>>()?;
+
+            Ok((Label(label.clone()), args))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(raw::RcTerm::from(raw::Term::VariantType(
+        span,
+        Scope::new(binder, arms),
+    )))
+}
+
 fn desugar_record_intro(
     env: &DesugarEnv,
     span: ByteSpan,
@@ -396,10 +696,11 @@ fn desugar_record_intro(
         .iter()
         .map(|field| match field {
             RecordIntroField::Punned {
-                label: (_, ref name),
+                label: (start, ref name),
                 shift,
             } => {
-                let var = env.on_name(span, name, shift.unwrap_or(0));
+                let occurrence = Occurrence::punned_label(*start, name);
+                let var = env.on_var(span, occurrence, name, shift.unwrap_or(0));
                 Ok((Label(name.clone()), var))
             },
             RecordIntroField::Explicit {
@@ -439,36 +740,59 @@ impl Desugar<(raw::RcPattern, DesugarEnv)> for concrete::Pattern {
 
                 Ok((ann_pattern, env))
             },
-            concrete::Pattern::Name(span, ref name, shift) => match (env.locals.get(name), shift) {
-                (Some(free_var), shift) => {
-                    let var = Var::Free(free_var.clone());
-                    let shift = LevelShift(shift.unwrap_or(0));
-                    let pattern = raw::RcPattern::from(raw::Pattern::Var(span, Embed(var), shift));
-
-                    Ok((pattern, env.clone()))
-                },
-                (None, Some(shift)) => {
-                    let var = Var::Free(FreeVar::fresh_named(name.clone()));
-                    let shift = LevelShift(shift);
-                    let pattern = raw::RcPattern::from(raw::Pattern::Var(span, Embed(var), shift));
-
-                    Ok((pattern, env.clone()))
-                },
-                (None, None) => {
-                    let mut env = env.clone();
-                    let free_var = env.on_binding(name);
-                    let binder = Binder(free_var);
-                    let pattern = raw::RcPattern::from(raw::Pattern::Binder(span, binder));
-
-                    Ok((pattern, env))
-                },
+            concrete::Pattern::Name(span, ref name, shift) => {
+                let occurrence = Occurrence::name(span.start(), name);
+                match (env.lookup(occurrence, name), shift) {
+                    (Some(free_var), shift) => {
+                        let var = Var::Free(free_var.clone());
+                        let shift = LevelShift(shift.unwrap_or(0));
+                        let pattern =
+                            raw::RcPattern::from(raw::Pattern::Var(span, Embed(var), shift));
+
+                        Ok((pattern, env.clone()))
+                    },
+                    (None, Some(shift)) => {
+                        let var = Var::Free(FreeVar::fresh_named(name.clone()));
+                        let shift = LevelShift(shift);
+                        let pattern =
+                            raw::RcPattern::from(raw::Pattern::Var(span, Embed(var), shift));
+
+                        Ok((pattern, env.clone()))
+                    },
+                    (None, None) => {
+                        let mut env = env.clone();
+                        let free_var = env.on_binder(occurrence, name);
+                        let binder = Binder(free_var);
+                        let pattern = raw::RcPattern::from(raw::Pattern::Binder(span, binder));
+
+                        Ok((pattern, env))
+                    },
+                }
             },
             concrete::Pattern::Literal(ref literal) => {
                 let literal = raw::RcPattern::from(raw::Pattern::Literal(literal.desugar(env)?));
 
                 Ok((literal, env.clone()))
             },
-            concrete::Pattern::Error(_) => unimplemented!("error recovery"),
+            concrete::Pattern::VariantIntro(span, ref label, ref patterns) => {
+                let mut env = env.clone();
+                let patterns = patterns
+                    .iter()
+                    .map(|pattern| {
+                        let (pattern, pattern_env) = pattern.desugar(&env)?;
+                        env = pattern_env;
+                        Ok(pattern)
+                    })
+                    .collect::<Result<_, _>>()?;
+                let label = Label(label.clone());
+                let pattern =
+                    raw::RcPattern::from(raw::Pattern::VariantIntro(span, label, patterns));
+
+                Ok((pattern, env))
+            },
+            concrete::Pattern::Error(span) => {
+                Ok((raw::RcPattern::from(raw::Pattern::Error(span)), env.clone()))
+            },
         }
     }
 }
@@ -476,6 +800,7 @@ impl Desugar<(raw::RcPattern, DesugarEnv)> for concrete::Pattern {
 impl Desugar<raw::RcTerm> for concrete::Term {
     fn desugar(&self, env: &DesugarEnv) -> Result<raw::RcTerm, DesugarError> {
         let span = self.span();
+        env.record_scope(span);
         match *self {
             concrete::Term::Parens(_, ref term) => term.desugar(env),
             concrete::Term::Ann(ref expr, ref ty) => Ok(raw::RcTerm::from(raw::Term::Ann(
@@ -484,8 +809,11 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             ))),
             concrete::Term::Universe(_, level) => Ok(raw::RcTerm::from(raw::Term::Universe(
                 span,
-                Level(level.unwrap_or(0)),
+                Level::from(level.unwrap_or(0)),
             ))),
+            concrete::Term::UniverseAt(_, ref level) => Ok(raw::RcTerm::from(
+                raw::Term::UniverseAt(span, level.desugar(env)?),
+            )),
             concrete::Term::Literal(ref literal) => {
                 Ok(raw::RcTerm::from(raw::Term::Literal(literal.desugar(env)?)))
             },
@@ -511,6 +839,7 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             concrete::Term::FunArrow(ref ann, ref body) => {
                 Ok(raw::RcTerm::from(raw::Term::FunType(
                     span,
+                    Plicity::Explicit,
                     Scope::new(
                         (Binder(FreeVar::fresh_unnamed()), Embed(ann.desugar(env)?)),
                         body.desugar(env)?,
@@ -518,12 +847,14 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                 )))
             },
             concrete::Term::FunApp(ref head, ref args) => {
-                args.iter().fold(head.desugar(env), |acc, arg| {
-                    Ok(raw::RcTerm::from(raw::Term::FunApp(
-                        acc?,
-                        arg.desugar(env)?,
-                    )))
-                })
+                args.iter()
+                    .fold(head.desugar(env), |acc, &(plicity, ref arg)| {
+                        Ok(raw::RcTerm::from(raw::Term::FunApp(
+                            acc?,
+                            plicity,
+                            arg.desugar(env)?,
+                        )))
+                    })
             },
             concrete::Term::Let(start, ref items, ref body) => desugar_let(env, start, items, body),
             concrete::Term::Where(ref expr, ref items, end) => desugar_where(env, expr, items, end),
@@ -555,7 +886,9 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     clauses
                         .iter()
                         .map(|(pattern, term)| {
+                            let clause_span = pattern.span().to(term.span());
                             let (pattern, env) = pattern.desugar(env)?;
+                            env.record_scope(clause_span);
                             Ok(Scope::new(pattern, term.desugar(&env)?))
                         })
                         .collect::<Result<_, _>>()?,
@@ -574,7 +907,10 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     LevelShift(shift.unwrap_or(0)),
                 )))
             },
-            concrete::Term::Error(_) => unimplemented!("error recovery"),
+            concrete::Term::VariantType(span, ref binder, ref arms) => {
+                desugar_variant_ty(env, span, binder.as_ref(), arms)
+            },
+            concrete::Term::Error(span) => Ok(raw::RcTerm::from(raw::Term::Error(span))),
         }
     }
 }
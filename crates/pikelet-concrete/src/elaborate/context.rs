@@ -1,13 +1,22 @@
+use codespan::ByteSpan;
 use im;
-use moniker::{Binder, FreeVar, Var};
+use moniker::FreeVar;
+use std::cell::RefCell;
 use std::rc::Rc;
 
-use pikelet_core::nbe;
+use pikelet_core::nbe::{self, Unfold};
 use pikelet_core::syntax::core::RcTerm;
-use pikelet_core::syntax::domain::{RcType, RcValue, Value};
-use pikelet_core::syntax::{Import, Literal};
-
+use pikelet_core::syntax::domain::{
+    Closure, Head, LazyValue, Locals, RcType, RcValue, Spine, Value,
+};
+use pikelet_core::syntax::{
+    Import, Level, LevelMeta, LevelShift, Literal, MetaVar, Name, Plicity, VarIndex, VarLevel,
+};
+
+use crate::elaborate::meta::{self, MetaEntry, MetaOrigin};
+use crate::elaborate::{TypeError, TypeWarning};
 use crate::resugar::{Resugar, ResugarEnv};
+use crate::syntax::concrete;
 
 // Some helper traits for marshalling between Rust and Pikelet values
 //
@@ -182,7 +191,21 @@ pub struct Globals {
     ty_s64: RcType,
     ty_f32: RcType,
     ty_f64: RcType,
-    var_array: FreeVar<String>,
+    ty_level: RcType,
+}
+
+/// A binder that is local to the term currently being elaborated
+#[derive(Clone, Debug)]
+struct Local {
+    /// The name of the binder, for use in hole goals
+    name: Name,
+    /// The type of the binder
+    ty: RcType,
+    /// Whether the binder is a parameter, as opposed to a let binding
+    ///
+    /// Metavariables are only abstracted over parameters, seeing as the
+    /// values of let bindings are already known.
+    is_param: bool,
 }
 
 /// The type checking context
@@ -204,16 +227,84 @@ pub struct Context {
     globals: Rc<Globals>,
     /// Imports
     imports: im::HashMap<String, (Import, RcType)>,
-    /// The type annotations of the binders we have passed over
-    declarations: im::HashMap<FreeVar<String>, RcType>,
-    /// Any definitions we have passed over
-    definitions: im::HashMap<FreeVar<String>, RcTerm>,
+    /// The variables that the raw syntax uses to refer to the globals
+    mappings: im::HashMap<String, FreeVar<String>>,
+    /// The type annotations of the globals, which are referred to by name in
+    /// the core syntax
+    declarations: im::HashMap<String, RcType>,
+    /// The definitions of the globals
+    definitions: im::HashMap<String, RcTerm>,
+    /// The binders that are local to the term currently being elaborated, in
+    /// the order that they were introduced
+    ///
+    /// Metavariables are abstracted over the parameters in these, so that
+    /// their solutions may depend on them.
+    locals: im::Vector<Local>,
+    /// The values of the local binders, indexed by their de Bruijn levels
+    values: Locals,
+    /// The de Bruijn levels of the local binders that the raw syntax can refer
+    /// to
+    levels: im::HashMap<FreeVar<String>, VarLevel>,
+    /// The metavariables that have been created during elaboration
+    ///
+    /// This is shared between all copies of the context, so that solutions
+    /// found in one scope are visible once we return to its parent.
+    metas: Rc<RefCell<Vec<MetaEntry>>>,
+    /// The lowest levels that the level metavariables created during
+    /// elaboration are known to be at
+    ///
+    /// These only ever increase as we learn more about the universes that
+    /// they stand for. They may mention level variables, which are kept as
+    /// de Bruijn levels so that they stay valid under more binders, but never
+    /// other level metavariables.
+    level_metas: Rc<RefCell<Vec<Level<VarLevel>>>>,
+    /// The warnings that have been produced during elaboration
+    ///
+    /// Like the metavariables, these are shared between all copies of the
+    /// context.
+    warnings: Rc<RefCell<Vec<TypeWarning>>>,
+    /// The errors that have been recovered from during elaboration
+    ///
+    /// Like the metavariables, these are shared between all copies of the
+    /// context.
+    errors: Rc<RefCell<Vec<TypeError>>>,
+    /// The types of the terms that have been elaborated, if we have been asked
+    /// to record them
+    ///
+    /// Like the metavariables, these are shared between all copies of the
+    /// context.
+    term_types: Option<Rc<RefCell<Vec<TermType>>>>,
+}
+
+/// The type of a term that was elaborated, for use by editor tooling
+#[derive(Clone, Debug)]
+pub struct TermType {
+    /// The span of the term
+    pub span: ByteSpan,
+    /// The type of the term
+    ///
+    /// This may mention metavariables that were solved after the term was
+    /// elaborated, so prefer using `TermType::resugar` for display.
+    pub ty: RcType,
+    /// The number of local binders that were in scope at the term
+    size: VarLevel,
+    /// The names that were in scope at the term
+    resugar_env: ResugarEnv,
+}
+
+impl TermType {
+    /// Resugar the type, substituting the solutions of any metavariables that
+    /// have been solved in the `context`
+    pub fn resugar(&self, context: &Context) -> concrete::Term {
+        match nbe::quote(context, self.size, Unfold::Never, &self.ty) {
+            Ok(ty) => meta::zonk_term_at(context, self.size, &ty).resugar(&self.resugar_env),
+            Err(_) => concrete::Term::Error(ByteSpan::default()),
+        }
+    }
 }
 
 impl Default for Context {
     fn default() -> Context {
-        use moniker::{Embed, Scope};
-
         use pikelet_core::syntax::core::Term;
 
         let var_bool = FreeVar::fresh_named("Bool");
@@ -232,43 +323,53 @@ impl Default for Context {
         let var_f32 = FreeVar::fresh_named("F32");
         let var_f64 = FreeVar::fresh_named("F64");
         let var_array = FreeVar::fresh_named("Array");
+        let var_level = FreeVar::fresh_named("Level");
+        let var_level_max = FreeVar::fresh_named("level-max");
 
         let mut context = Context {
             resugar_env: ResugarEnv::new(),
             globals: Rc::new(Globals {
-                ty_bool: RcValue::from(Value::var(Var::Free(var_bool.clone()), 0)),
-                ty_string: RcValue::from(Value::var(Var::Free(var_string.clone()), 0)),
-                ty_char: RcValue::from(Value::var(Var::Free(var_char.clone()), 0)),
-                ty_u8: RcValue::from(Value::var(Var::Free(var_u8.clone()), 0)),
-                ty_u16: RcValue::from(Value::var(Var::Free(var_u16.clone()), 0)),
-                ty_u32: RcValue::from(Value::var(Var::Free(var_u32.clone()), 0)),
-                ty_u64: RcValue::from(Value::var(Var::Free(var_u64.clone()), 0)),
-                ty_s8: RcValue::from(Value::var(Var::Free(var_s8.clone()), 0)),
-                ty_s16: RcValue::from(Value::var(Var::Free(var_s16.clone()), 0)),
-                ty_s32: RcValue::from(Value::var(Var::Free(var_s32.clone()), 0)),
-                ty_s64: RcValue::from(Value::var(Var::Free(var_s64.clone()), 0)),
-                ty_f32: RcValue::from(Value::var(Var::Free(var_f32.clone()), 0)),
-                ty_f64: RcValue::from(Value::var(Var::Free(var_f64.clone()), 0)),
-                var_array: var_array.clone(),
+                ty_bool: RcValue::from(Value::global("Bool", 0)),
+                ty_string: RcValue::from(Value::global("String", 0)),
+                ty_char: RcValue::from(Value::global("Char", 0)),
+                ty_u8: RcValue::from(Value::global("U8", 0)),
+                ty_u16: RcValue::from(Value::global("U16", 0)),
+                ty_u32: RcValue::from(Value::global("U32", 0)),
+                ty_u64: RcValue::from(Value::global("U64", 0)),
+                ty_s8: RcValue::from(Value::global("S8", 0)),
+                ty_s16: RcValue::from(Value::global("S16", 0)),
+                ty_s32: RcValue::from(Value::global("S32", 0)),
+                ty_s64: RcValue::from(Value::global("S64", 0)),
+                ty_f32: RcValue::from(Value::global("F32", 0)),
+                ty_f64: RcValue::from(Value::global("F64", 0)),
+                ty_level: RcValue::from(Value::global("Level", 0)),
             }),
             imports: im::HashMap::new(),
+            mappings: im::HashMap::new(),
             declarations: im::HashMap::new(),
             definitions: im::HashMap::new(),
+            locals: im::Vector::new(),
+            values: Locals::new(),
+            levels: im::HashMap::new(),
+            metas: Rc::new(RefCell::new(Vec::new())),
+            level_metas: Rc::new(RefCell::new(Vec::new())),
+            warnings: Rc::new(RefCell::new(Vec::new())),
+            errors: Rc::new(RefCell::new(Vec::new())),
+            term_types: None,
         };
 
         let universe0 = RcValue::from(Value::universe(0));
         let bool_ty = context.globals.ty_bool.clone();
         let bool_lit = |value| RcTerm::from(Term::Literal(Literal::Bool(value)));
-        let array_ty = RcValue::from(Value::FunType(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(context.globals.ty_u64.clone()),
-            ),
-            RcValue::from(Value::FunType(Scope::new(
-                (Binder(FreeVar::fresh_unnamed()), Embed(universe0.clone())),
+        let array_ty = RcValue::from(Value::FunType(
+            Plicity::Explicit,
+            context.globals.ty_u64.clone(),
+            Closure::constant(RcValue::from(Value::FunType(
+                Plicity::Explicit,
                 universe0.clone(),
+                Closure::constant(universe0.clone()),
             ))),
-        )));
+        ));
 
         context.insert_declaration(var_bool, universe0.clone());
         context.insert_declaration(var_string, universe0.clone());
@@ -284,6 +385,7 @@ impl Default for Context {
         context.insert_declaration(var_f32, universe0.clone());
         context.insert_declaration(var_f64, universe0.clone());
         context.insert_declaration(var_array, array_ty);
+        context.insert_declaration(var_level, universe0.clone());
 
         context.insert_declaration(var_true.clone(), bool_ty.clone());
         context.insert_declaration(var_false.clone(), bool_ty.clone());
@@ -295,7 +397,7 @@ impl Default for Context {
             ($name:expr, fn($($param_name:ident : $PType:ty),*) -> $RType:ty $body:block) => {{
                 fn interpretation<'a>(params: &'a [RcValue]) -> Option<RcValue> {
                     match params {
-                        [$(ref $param_name),*] if $($param_name.is_nf())&&* => {
+                        [$(ref $param_name),*] => {
                             $(let $param_name = <$PType>::try_from_value_ref($param_name)?;)*
                             Some(<$RType>::into_value($body))
                         }
@@ -305,9 +407,8 @@ impl Default for Context {
 
                 let ty = <$RType>::ty(&context);
                 $(let ty = {
-                    let param_var = FreeVar::fresh_unnamed();
                     let param_ty = <$PType>::ty(&context);
-                    RcValue::from(Value::FunType(Scope::new((Binder(param_var), Embed(param_ty)), ty)))
+                    RcValue::from(Value::FunType(Plicity::Explicit, param_ty, Closure::constant(ty)))
                 };)*
 
                 context.insert_import($name.to_owned(), Import::Prim(interpretation), ty);
@@ -456,6 +557,38 @@ impl Default for Context {
 
         prim_import!("prim/string/append", fn(x: String, y: String) -> String { x.clone() + y }); // FIXME: Clone
 
+        // Levels can be taken the maximum of even when they contain level
+        // variables, so we can't use `prim_import!` for this one
+        fn level_max(params: &[RcValue]) -> Option<RcValue> {
+            match params {
+                [ref level1, ref level2] => {
+                    let level = level1.as_level()?.max(&level2.as_level()?);
+                    Some(RcValue::from(Value::level(level)))
+                },
+                _ => None,
+            }
+        }
+
+        let level_ty = context.globals.ty_level.clone();
+        let level_max_ty = RcValue::from(Value::FunType(
+            Plicity::Explicit,
+            level_ty.clone(),
+            Closure::constant(RcValue::from(Value::FunType(
+                Plicity::Explicit,
+                level_ty.clone(),
+                Closure::constant(level_ty),
+            ))),
+        ));
+        let level_max_import = "prim/level/max".to_owned();
+
+        context.insert_import(
+            level_max_import.clone(),
+            Import::Prim(level_max),
+            level_max_ty.clone(),
+        );
+        context.insert_declaration(var_level_max.clone(), level_max_ty);
+        context.insert_definition(var_level_max, RcTerm::from(Term::Import(level_max_import)));
+
         context
     }
 }
@@ -465,16 +598,64 @@ impl Context {
         src.resugar(&self.resugar_env)
     }
 
-    pub fn mappings(&self) -> im::HashMap<String, FreeVar<String>> {
-        self.declarations
-            .iter()
-            .filter_map(|(free_var, _)| {
-                let pretty_name = free_var.pretty_name.as_ref()?;
-                Some((pretty_name.clone(), free_var.clone()))
-            })
-            .collect()
+    /// The number of local binders that are in scope
+    pub fn size(&self) -> VarLevel {
+        VarLevel(self.values.len())
+    }
+
+    /// The values of the local binders that are in scope
+    pub fn values(&self) -> &Locals {
+        &self.values
+    }
+
+    /// Evaluate a term under the local binders that are in scope
+    pub fn eval(&self, term: &RcTerm) -> Result<RcValue, nbe::NbeError> {
+        nbe::eval(self, &self.values, term)
+    }
+
+    /// Evaluate a universe level under the local binders that are in scope
+    pub fn eval_level(&self, level: &Level) -> Result<Level<VarLevel>, nbe::NbeError> {
+        nbe::eval_level(self, &self.values, LevelShift(0), level)
     }
 
+    /// Reduce a term to its normal form under the local binders that are in
+    /// scope
+    pub fn nf_term(&self, term: &RcTerm) -> Result<RcTerm, nbe::NbeError> {
+        nbe::nf_term(self, &self.values, term)
+    }
+
+    /// Read a value back into a core term in normal form
+    pub fn quote(&self, value: &RcValue) -> Result<RcTerm, nbe::NbeError> {
+        nbe::quote(self, self.size(), Unfold::Always, value)
+    }
+
+    /// Read a value back into a core term, without unfolding the definitions
+    /// that it refers to
+    pub fn quote_folded(&self, value: &RcValue) -> Result<RcTerm, nbe::NbeError> {
+        nbe::quote(self, self.size(), Unfold::Never, value)
+    }
+
+    /// Read a universe level back into the core syntax
+    pub fn quote_level(&self, level: &Level<VarLevel>) -> Level {
+        nbe::quote_level(self.size(), level)
+    }
+
+    /// Resugar a value, reading it back into a core term first
+    ///
+    /// Definitions are left folded, so that types are displayed in terms of
+    /// the names that were used to write them. This only applies to direct
+    /// references to definitions: fields projected out of records, such as
+    /// `p.Eq String`, are displayed unfolded.
+    pub fn resugar_value(&self, value: &RcValue) -> concrete::Term {
+        match self.quote_folded(value) {
+            Ok(term) => self.resugar(&term),
+            Err(_) => concrete::Term::Error(ByteSpan::default()),
+        }
+    }
+
+    pub fn mappings(&self) -> im::HashMap<String, FreeVar<String>> {
+        self.mappings.clone()
+    }
     pub fn bool(&self) -> &RcType {
         &self.globals.ty_bool
     }
@@ -527,17 +708,17 @@ impl Context {
         &self.globals.ty_f64
     }
 
-    pub fn array<'a>(&self, ty: &'a RcType) -> Option<(u64, &'a RcType)> {
-        use pikelet_core::syntax::LevelShift;
+    pub fn level(&self) -> &RcType {
+        &self.globals.ty_level
+    }
 
-        match ty.free_var_app() {
+    pub fn array<'a>(&self, ty: &'a RcType) -> Option<(u64, &'a RcType)> {
+        match ty.global_app() {
             // Conservatively forcing the shift to be zero for now. Perhaps this
             // could be relaxed in the future if it becomes a problem?
-            Some((fv, LevelShift(0), &[ref len, ref elem_ty])) if *fv == self.globals.var_array => {
-                match **len {
-                    Value::Literal(Literal::U64(len)) => Some((len, elem_ty)),
-                    _ => None,
-                }
+            Some(("Array", LevelShift(0), &[(_, ref len), (_, ref elem_ty)])) => match **len {
+                Value::Literal(Literal::U64(len)) => Some((len, elem_ty)),
+                _ => None,
             },
             Some(_) | None => None,
         }
@@ -547,26 +728,224 @@ impl Context {
         self.imports.get(name)
     }
 
-    pub fn get_declaration(&self, free_var: &FreeVar<String>) -> Option<&RcType> {
-        self.declarations.get(free_var)
+    pub fn get_declaration(&self, name: &str) -> Option<&RcType> {
+        self.declarations.get(name)
     }
 
-    pub fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm> {
-        self.definitions.get(free_var)
+    pub fn get_definition(&self, name: &str) -> Option<&RcTerm> {
+        self.definitions.get(name)
     }
 
     pub fn insert_import(&mut self, name: String, import: Import, ty: RcType) {
         self.imports.insert(name, (import, ty));
     }
 
+    /// Declare a global, which the core syntax refers to by the pretty name of
+    /// `free_var`
     pub fn insert_declaration(&mut self, free_var: FreeVar<String>, ty: RcType) {
-        self.resugar_env.on_binder(&Binder(free_var.clone()));
-        self.declarations.insert(free_var, ty);
+        let name = free_var.pretty_name.clone().unwrap_or_default();
+        self.resugar_env.on_global(&name);
+        self.mappings.insert(name.clone(), free_var);
+        self.declarations.insert(name, ty);
     }
 
+    /// Define a global, which the core syntax refers to by the pretty name of
+    /// `free_var`
     pub fn insert_definition(&mut self, free_var: FreeVar<String>, term: RcTerm) {
-        self.resugar_env.on_binder(&Binder(free_var.clone()));
-        self.definitions.insert(free_var, term);
+        let name = free_var.pretty_name.clone().unwrap_or_default();
+        self.resugar_env.on_global(&name);
+        self.mappings.insert(name.clone(), free_var);
+        self.definitions.insert(name, term);
+    }
+
+    /// Look up a global that the raw syntax refers to, returning its name and
+    /// type
+    pub fn lookup_global<'a>(
+        &'a self,
+        free_var: &'a FreeVar<String>,
+    ) -> Option<(&'a str, &'a RcType)> {
+        let name = free_var.pretty_name.as_ref()?;
+        match self.mappings.get(name) {
+            Some(mapped_var) if mapped_var == free_var => {
+                Some((name.as_str(), self.declarations.get(name)?))
+            },
+            Some(_) | None => None,
+        }
+    }
+
+    /// Look up a local binder that the raw syntax refers to, returning its de
+    /// Bruijn index and type
+    pub fn lookup_local(&self, free_var: &FreeVar<String>) -> Option<(VarIndex, &RcType)> {
+        let level = *self.levels.get(free_var)?;
+        let local = self.locals.get(level.0)?;
+        Some((level.to_index(self.size()), &local.ty))
+    }
+
+    /// The name and type of the local binder at the given level
+    pub fn get_local(&self, level: VarLevel) -> Option<(&Name, &RcType)> {
+        let local = self.locals.get(level.0)?;
+        Some((&local.name, &local.ty))
+    }
+
+    /// The levels and types of the local parameters that are in scope
+    pub fn params(&self) -> impl Iterator<Item = (VarLevel, &RcType)> {
+        (self.locals.iter().enumerate())
+            .filter(|&(_, local)| local.is_param)
+            .map(|(level, local)| (VarLevel(level), &local.ty))
+    }
+
+    /// Bring a local parameter into scope that the raw syntax can not refer
+    /// to, returning the variable that stands for it
+    pub fn push_local(&mut self, name: Name, ty: RcType) -> RcValue {
+        let var = RcValue::from(Value::var(self.size(), 0));
+        self.resugar_env.on_binder(&name);
+        self.locals.push_back(Local {
+            name,
+            ty,
+            is_param: true,
+        });
+        self.values.push_back(var.clone());
+        var
+    }
+
+    /// Declare a parameter that is local to the term currently being
+    /// elaborated
+    pub fn insert_local(&mut self, free_var: FreeVar<String>, ty: RcType) {
+        self.levels.insert(free_var.clone(), self.size());
+        self.push_local(Name::from(free_var.pretty_name), ty);
+    }
+
+    /// Define a let binding that is local to the term currently being
+    /// elaborated
+    ///
+    /// The term is only evaluated once its value is needed.
+    pub fn insert_local_definition(&mut self, free_var: FreeVar<String>, ty: RcType, term: RcTerm) {
+        let unfolded = LazyValue::eval(self.values.clone(), LevelShift(0), term);
+        let head = Head::Var(self.size(), LevelShift(0));
+        let value = RcValue::from(Value::Glued(head, Spine::new(), unfolded));
+        let name = Name::from(free_var.pretty_name.clone());
+        self.levels.insert(free_var, self.size());
+        self.resugar_env.on_binder(&name);
+        self.locals.push_back(Local {
+            name,
+            ty,
+            is_param: false,
+        });
+        self.values.push_back(value);
+    }
+
+    /// The number of metavariables that have been created so far
+    pub fn meta_count(&self) -> usize {
+        self.metas.borrow().len()
+    }
+
+    pub fn get_meta(&self, meta: MetaVar) -> MetaEntry {
+        self.metas.borrow()[meta.0 as usize].clone()
+    }
+
+    pub fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        self.metas.borrow()[meta.0 as usize].solution.clone()
+    }
+
+    /// Create a new, unsolved metavariable
+    pub fn insert_meta(&self, origin: MetaOrigin, ty: RcType) -> MetaVar {
+        let params = self
+            .params()
+            .map(|(level, ty)| (level, ty.clone()))
+            .collect();
+
+        let mut metas = self.metas.borrow_mut();
+        let meta = MetaVar(metas.len() as u32);
+        metas.push(MetaEntry {
+            origin,
+            ty,
+            params,
+            size: self.size(),
+            resugar_env: self.resugar_env.clone(),
+            solution: None,
+        });
+        meta
+    }
+
+    pub fn solve_meta(&self, meta: MetaVar, solution: RcTerm) {
+        self.metas.borrow_mut()[meta.0 as usize].solution = Some(solution);
+    }
+
+    /// The number of level metavariables that have been created so far
+    pub fn level_meta_count(&self) -> usize {
+        self.level_metas.borrow().len()
+    }
+
+    pub fn get_level_meta(&self, meta: LevelMeta) -> Level<VarLevel> {
+        self.level_metas.borrow()[meta.0 as usize].clone()
+    }
+
+    /// Create a new level metavariable, starting at the lowest level
+    pub fn insert_level_meta(&self) -> LevelMeta {
+        let mut level_metas = self.level_metas.borrow_mut();
+        let meta = LevelMeta(level_metas.len() as u32);
+        level_metas.push(Level::from(0));
+        meta
+    }
+
+    /// Raise a level metavariable so that it is at least `level`
+    pub fn raise_level_meta(&self, meta: LevelMeta, level: &Level<VarLevel>) {
+        let mut level_metas = self.level_metas.borrow_mut();
+        let current = &mut level_metas[meta.0 as usize];
+        *current = current.max(level);
+    }
+
+    /// Record a warning that was found during elaboration
+    pub fn insert_warning(&self, warning: TypeWarning) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    /// Remove the warnings that have been recorded so far, returning them
+    pub fn take_warnings(&self) -> Vec<TypeWarning> {
+        self.warnings.borrow_mut().drain(..).collect()
+    }
+
+    /// The number of errors that have been recorded so far
+    pub fn error_count(&self) -> usize {
+        self.errors.borrow().len()
+    }
+
+    /// Record an error that was found during elaboration, so that we can
+    /// carry on elaborating the rest of the term
+    pub fn insert_error(&self, error: TypeError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    /// Remove the errors that have been recorded since `errors_start`,
+    /// returning them in the order that they were found
+    pub fn take_errors(&self, errors_start: usize) -> Vec<TypeError> {
+        self.errors.borrow_mut().drain(errors_start..).collect()
+    }
+
+    /// Start recording the types of the terms that are elaborated from now on
+    pub fn record_term_types(&mut self) {
+        self.term_types = Some(Rc::new(RefCell::new(Vec::new())));
+    }
+
+    /// Record the type of a term, if we are recording them
+    pub fn insert_term_type(&self, span: ByteSpan, ty: &RcType) {
+        if let Some(ref term_types) = self.term_types {
+            term_types.borrow_mut().push(TermType {
+                span,
+                ty: ty.clone(),
+                size: self.size(),
+                resugar_env: self.resugar_env.clone(),
+            });
+        }
+    }
+
+    /// Remove the term types that have been recorded so far, returning them
+    /// in the order that they were elaborated
+    pub fn take_term_types(&self) -> Vec<TermType> {
+        match self.term_types {
+            Some(ref term_types) => term_types.borrow_mut().drain(..).collect(),
+            None => Vec::new(),
+        }
     }
 }
 
@@ -575,7 +954,11 @@ impl nbe::Env for Context {
         self.imports.get(name).map(|&(ref import, _)| import)
     }
 
-    fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm> {
-        self.definitions.get(free_var)
+    fn get_definition(&self, name: &str) -> Option<&RcTerm> {
+        self.definitions.get(name)
+    }
+
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        Context::get_meta_solution(self, meta)
     }
 }
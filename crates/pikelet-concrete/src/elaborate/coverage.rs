@@ -0,0 +1,308 @@
+//! Coverage checking for case expressions
+//!
+//! This is based on the _usefulness_ algorithm described in Luc Maranget's
+//! [Warnings for pattern matching](http://moscova.inria.fr/~maranget/papers/warn/warn.pdf).
+//! The clauses of a case expression form the rows of a _pattern matrix_, and
+//! we ask whether a new row could match any values that are not already
+//! matched by the rows that come before it. Clauses that are not useful in
+//! this sense are unreachable, and if a wildcard pattern would still be useful
+//! after all of the clauses have been added then the case expression is not
+//! exhaustive.
+
+use codespan::ByteSpan;
+
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::domain::{RcType, Value};
+use pikelet_core::syntax::{Label, LevelShift, Literal, Name, VarIndex};
+
+use crate::elaborate::{meta, variant_arm_tys, Context, TypeError, TypeWarning};
+
+/// The head of a pattern that only matches specific values
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    /// Matches a single literal value
+    Literal(Literal),
+    /// Matches values that are structurally equal to a local variable that
+    /// has no known definition
+    Var(VarIndex),
+    /// Matches values that are structurally equal to a global that has no
+    /// known definition
+    Global(String),
+    /// Matches the values built from a variant constructor with the given
+    /// number of arguments
+    Variant(Label, usize),
+}
+
+impl Constructor {
+    /// The number of sub-patterns that this constructor takes
+    fn arity(&self) -> usize {
+        match *self {
+            Constructor::Literal(_) | Constructor::Var(_) | Constructor::Global(_) => 0,
+            Constructor::Variant(_, arity) => arity,
+        }
+    }
+}
+
+/// A pattern, simplified for the purposes of coverage checking
+#[derive(Debug, Clone)]
+enum Pat {
+    /// Matches any value
+    Wildcard,
+    /// Matches the values built from a constructor
+    Constructor(Constructor, Vec<Pat>),
+}
+
+impl Pat {
+    fn to_pattern(&self) -> RcPattern {
+        match *self {
+            Pat::Wildcard => RcPattern::from(Pattern::Binder(Name::new("_"))),
+            Pat::Constructor(Constructor::Literal(ref literal), _) => {
+                RcPattern::from(Pattern::Literal(literal.clone()))
+            },
+            Pat::Constructor(Constructor::Var(index), _) => {
+                RcPattern::from(Pattern::Var(index, LevelShift(0)))
+            },
+            Pat::Constructor(Constructor::Global(ref name), _) => {
+                RcPattern::from(Pattern::Global(name.clone(), LevelShift(0)))
+            },
+            Pat::Constructor(Constructor::Variant(ref label, _), ref args) => {
+                let args = args.iter().map(Pat::to_pattern).collect();
+                RcPattern::from(Pattern::VariantIntro(label.clone(), args))
+            },
+        }
+    }
+}
+
+type Row = Vec<Pat>;
+
+/// Simplify a pattern, resolving variables to the literals they are defined
+/// as where possible
+fn lower_pattern(context: &Context, pattern: &RcPattern) -> Result<Pat, TypeError> {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => lower_pattern(context, pattern),
+        // Case expressions with error patterns are not checked for coverage
+        Pattern::Binder(_) | Pattern::Error => Ok(Pat::Wildcard),
+        Pattern::Literal(ref literal) => Ok(Pat::Constructor(
+            Constructor::Literal(literal.clone()),
+            vec![],
+        )),
+        Pattern::Var(index, shift) => {
+            let term = RcTerm::from(Term::Var(index, shift));
+            lower_var(context, &term, Constructor::Var(index))
+        },
+        Pattern::Global(ref name, shift) => {
+            let term = RcTerm::from(Term::Global(name.clone(), shift));
+            lower_var(context, &term, Constructor::Global(name.clone()))
+        },
+        Pattern::VariantIntro(ref label, ref patterns) => {
+            let constructor = Constructor::Variant(label.clone(), patterns.len());
+            let args = patterns
+                .iter()
+                .map(|pattern| lower_pattern(context, pattern))
+                .collect::<Result<_, _>>()?;
+
+            Ok(Pat::Constructor(constructor, args))
+        },
+    }
+}
+
+/// Simplify a variable pattern, using the literal that the variable is
+/// defined as if it has one
+fn lower_var(context: &Context, term: &RcTerm, constructor: Constructor) -> Result<Pat, TypeError> {
+    match *meta::force(context, &context.eval(term)?)? {
+        Value::Literal(ref literal) => Ok(Pat::Constructor(
+            Constructor::Literal(literal.clone()),
+            vec![],
+        )),
+        _ => Ok(Pat::Constructor(constructor, vec![])),
+    }
+}
+
+/// Returns `true` if the pattern contains an error
+fn is_error(pattern: &RcPattern) -> bool {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => is_error(pattern),
+        Pattern::VariantIntro(_, ref patterns) => patterns.iter().any(is_error),
+        Pattern::Error => true,
+        Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) => {
+            false
+        },
+    }
+}
+
+/// The complete set of constructors for a type, if it has a finite number of
+/// them
+fn signature(context: &Context, ty: &RcType) -> Result<Option<Vec<Constructor>>, TypeError> {
+    let ty = meta::force(context, ty)?;
+
+    if ty == *context.bool() {
+        Ok(Some(vec![
+            Constructor::Literal(Literal::Bool(true)),
+            Constructor::Literal(Literal::Bool(false)),
+        ]))
+    } else if let Value::VariantType(ref closure) = *ty.inner {
+        Ok(Some(
+            closure
+                .arms()
+                .map(|(label, arity)| Constructor::Variant(label.clone(), arity))
+                .collect(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The types of the sub-patterns of a constructor
+fn constructor_tys(
+    context: &Context,
+    ty: &RcType,
+    constructor: &Constructor,
+) -> Result<Vec<RcType>, TypeError> {
+    match *constructor {
+        Constructor::Literal(_) | Constructor::Var(_) | Constructor::Global(_) => Ok(vec![]),
+        Constructor::Variant(ref label, _) => {
+            let ty = meta::force(context, ty)?;
+            Ok(variant_arm_tys(context, &ty, label)?.unwrap_or_default())
+        },
+    }
+}
+
+/// The rows of the matrix that match the given constructor, with the
+/// constructor replaced by its sub-patterns
+fn specialize(matrix: &[Row], constructor: &Constructor) -> Vec<Row> {
+    matrix
+        .iter()
+        .filter_map(|row| {
+            let (first, rest) = row.split_first()?;
+            let mut new_row = match *first {
+                Pat::Wildcard => vec![Pat::Wildcard; constructor.arity()],
+                Pat::Constructor(ref other, ref args) if other == constructor => args.clone(),
+                Pat::Constructor(_, _) => return None,
+            };
+            new_row.extend(rest.iter().cloned());
+            Some(new_row)
+        })
+        .collect()
+}
+
+/// The rows of the matrix that match any value in their first column, with
+/// that column removed
+fn default_matrix(matrix: &[Row]) -> Vec<Row> {
+    matrix
+        .iter()
+        .filter_map(|row| match row.split_first()? {
+            (&Pat::Wildcard, rest) => Some(rest.to_vec()),
+            (&Pat::Constructor(_, _), _) => None,
+        })
+        .collect()
+}
+
+/// Put a constructor back together after its sub-patterns were specialized
+fn unspecialize(constructor: &Constructor, mut witness: Row) -> Row {
+    let rest = witness.split_off(constructor.arity());
+    let mut new_witness = vec![Pat::Constructor(constructor.clone(), witness)];
+    new_witness.extend(rest);
+    new_witness
+}
+
+/// Determine if `row` matches any values that are not matched by the rows of
+/// `matrix`, returning an example of one of those values if so
+fn useful(
+    context: &Context,
+    matrix: &[Row],
+    tys: &[RcType],
+    row: &[Pat],
+) -> Result<Option<Row>, TypeError> {
+    let (first, rest) = match row.split_first() {
+        Some(split) => split,
+        None if matrix.is_empty() => return Ok(Some(vec![])),
+        None => return Ok(None),
+    };
+
+    let specialized = |constructor: &Constructor, mut new_row: Row| {
+        let mut new_tys = constructor_tys(context, &tys[0], constructor)?;
+        new_tys.extend(tys[1..].iter().cloned());
+        new_row.extend(rest.iter().cloned());
+
+        let matrix = specialize(matrix, constructor);
+        let witness = useful(context, &matrix, &new_tys, &new_row)?;
+        Ok(witness.map(|witness| unspecialize(constructor, witness)))
+    };
+
+    match *first {
+        Pat::Constructor(ref constructor, ref args) => specialized(constructor, args.clone()),
+        Pat::Wildcard => {
+            let mut used = Vec::<Constructor>::new();
+            for row in matrix {
+                if let Some(&Pat::Constructor(ref constructor, _)) = row.first() {
+                    if !used.contains(constructor) {
+                        used.push(constructor.clone());
+                    }
+                }
+            }
+
+            let all = signature(context, &tys[0])?;
+            match all {
+                Some(ref all) if all.iter().all(|constructor| used.contains(constructor)) => {
+                    for constructor in all {
+                        let args = vec![Pat::Wildcard; constructor.arity()];
+                        if let Some(witness) = specialized(constructor, args)? {
+                            return Ok(Some(witness));
+                        }
+                    }
+                    Ok(None)
+                },
+                _ => {
+                    let matrix = default_matrix(matrix);
+                    let mut witness = match useful(context, &matrix, &tys[1..], rest)? {
+                        Some(witness) => witness,
+                        None => return Ok(None),
+                    };
+                    let missing = all
+                        .and_then(|all| all.into_iter().find(|c| !used.contains(c)))
+                        .map(|constructor| {
+                            let args = vec![Pat::Wildcard; constructor.arity()];
+                            Pat::Constructor(constructor, args)
+                        });
+
+                    witness.insert(0, missing.unwrap_or(Pat::Wildcard));
+                    Ok(Some(witness))
+                },
+            }
+        },
+    }
+}
+
+/// Check that the clauses of a case expression cover every possible value of
+/// the head, warning about any clauses that will never be reached
+pub fn check_case(
+    context: &Context,
+    span: ByteSpan,
+    head_ty: &RcType,
+    patterns: &[(ByteSpan, RcPattern)],
+) -> Result<(), TypeError> {
+    let tys = [meta::force(context, head_ty)?];
+    if *tys[0].inner == Value::Error || patterns.iter().any(|(_, pattern)| is_error(pattern)) {
+        // The clauses can't be trusted to cover anything, and the errors have
+        // already been reported
+        return Ok(());
+    }
+
+    let mut matrix = Vec::with_capacity(patterns.len());
+
+    for &(pattern_span, ref pattern) in patterns {
+        let row = vec![lower_pattern(context, pattern)?];
+        if useful(context, &matrix, &tys, &row)?.is_none() {
+            context.insert_warning(TypeWarning::UnreachablePattern { span: pattern_span });
+        }
+        matrix.push(row);
+    }
+
+    match useful(context, &matrix, &tys, &[Pat::Wildcard])? {
+        None => Ok(()),
+        Some(witness) => Err(TypeError::NonExhaustiveCase {
+            span,
+            missing: Box::new(context.resugar(&witness[0].to_pattern())),
+        }),
+    }
+}
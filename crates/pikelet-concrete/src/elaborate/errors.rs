@@ -2,8 +2,8 @@
 
 use codespan::ByteSpan;
 use codespan_reporting::{Diagnostic, Label};
-use moniker::{Binder, FreeVar, Var};
 use failure::Fail;
+use moniker::{Binder, FreeVar, Var};
 
 use pikelet_core::nbe::NbeError;
 use pikelet_core::syntax;
@@ -88,6 +88,17 @@ pub enum TypeError {
         arg_span: ByteSpan,
         found: Box<concrete::Term>,
     },
+    #[fail(
+        display = "Applied an implicit argument to a term of type `{}`, which has no implicit parameter",
+        found
+    )]
+    UnexpectedImplicitArg {
+        fn_span: ByteSpan,
+        arg_span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
+    #[fail(display = "Unable to infer the implicit argument `{}`", name)]
+    UnableToInferImplicitArg { span: ByteSpan, name: String },
     #[fail(
         display = "Type annotation needed for the function parameter `{}`",
         name
@@ -108,16 +119,33 @@ pub enum TypeError {
         found: raw::Literal,
         expected: Box<concrete::Term>,
     },
+    #[fail(
+        display = "the literal `{}` is out of the range of the type `{}`",
+        found, expected
+    )]
+    LiteralOutOfRange {
+        literal_span: ByteSpan,
+        found: raw::Literal,
+        expected: Box<concrete::Term>,
+        min: syntax::Literal,
+        max: syntax::Literal,
+    },
     #[fail(display = "Ambiguous integer literal")]
     AmbiguousIntLiteral { span: ByteSpan },
     #[fail(display = "Ambiguous floating point literal")]
     AmbiguousFloatLiteral { span: ByteSpan },
     #[fail(display = "Empty case expressions need type annotations.")]
     AmbiguousEmptyCase { span: ByteSpan },
+    #[fail(display = "Non-exhaustive patterns: `{}` not covered", missing)]
+    NonExhaustiveCase {
+        span: ByteSpan,
+        missing: Box<concrete::Pattern>,
+    },
     #[fail(display = "Unable to elaborate hole, expected: `{:?}`", expected)]
     UnableToElaborateHole {
         span: ByteSpan,
         expected: Option<Box<concrete::Term>>,
+        locals: Vec<(String, concrete::Term)>,
     },
     #[fail(
         display = "Type mismatch: found `{}` but `{}` was expected",
@@ -138,6 +166,16 @@ pub enum TypeError {
         span: ByteSpan,
         found: Box<concrete::Term>,
     },
+    #[fail(display = "Unable to reduce `{}` to a universe level", found)]
+    UnreducibleLevel {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
+    #[fail(display = "The universe `Type^{}` is too large to have a type", level)]
+    LevelOverflow {
+        span: ByteSpan,
+        level: syntax::Level,
+    },
     #[fail(display = "Not yet defined: `{}`", free_var)]
     UndefinedName {
         span: ByteSpan,
@@ -183,14 +221,122 @@ pub enum TypeError {
         found_size: u64,
         expected_size: u64,
     },
+    #[fail(
+        display = "The type `{}` does not have a constructor named `{}`.",
+        found, expected_label
+    )]
+    NoConstructorInType {
+        span: ByteSpan,
+        expected_label: syntax::Label,
+        found: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "Mismatched constructor arguments: expected {} arguments to `{}` but found {}",
+        expected_count, label, found_count
+    )]
+    ConstructorArgCountMismatch {
+        span: ByteSpan,
+        label: syntax::Label,
+        found_count: u64,
+        expected_count: u64,
+    },
+    #[fail(display = "Ambiguous constructor pattern `{}`", label)]
+    AmbiguousConstructorPattern {
+        span: ByteSpan,
+        label: syntax::Label,
+    },
+    #[fail(display = "Constructor defined more than once: `{}`", label)]
+    DuplicateConstructors {
+        span: ByteSpan,
+        label: syntax::Label,
+    },
+    #[fail(
+        display = "The variant type `{}` occurs in a position that is not strictly positive",
+        name
+    )]
+    NonPositiveOccurrence { span: ByteSpan, name: String },
+    #[fail(
+        display = "Could not show that the recursive definition `{}` terminates",
+        name
+    )]
+    NonTerminatingRecursion { span: ByteSpan, name: String },
     #[fail(display = "Internal error - this is a bug! {}", _0)]
     Internal(#[cause] InternalError),
 }
 
 impl TypeError {
+    /// A short, stable identifier for the kind of error, for use by tools that
+    /// consume diagnostics
+    ///
+    /// Internal errors are bugs, so they don't have a code.
+    pub fn code(&self) -> Option<&'static str> {
+        Some(match *self {
+            TypeError::Internal(_) => return None,
+            TypeError::DuplicateDeclarations { .. } => "duplicate-declarations",
+            TypeError::DeclarationFollowedDefinition { .. } => "declaration-followed-definition",
+            TypeError::DuplicateDefinitions { .. } => "duplicate-definitions",
+            TypeError::ArgAppliedToNonFunction { .. } => "arg-applied-to-non-function",
+            TypeError::UnexpectedImplicitArg { .. } => "unexpected-implicit-arg",
+            TypeError::UnableToInferImplicitArg { .. } => "unable-to-infer-implicit-arg",
+            TypeError::FunctionParamNeedsAnnotation { .. } => "function-param-needs-annotation",
+            TypeError::BinderNeedsAnnotation { .. } => "binder-needs-annotation",
+            TypeError::LiteralMismatch { .. } => "literal-mismatch",
+            TypeError::LiteralOutOfRange { .. } => "literal-out-of-range",
+            TypeError::AmbiguousIntLiteral { .. } => "ambiguous-int-literal",
+            TypeError::AmbiguousFloatLiteral { .. } => "ambiguous-float-literal",
+            TypeError::AmbiguousEmptyCase { .. } => "ambiguous-empty-case",
+            TypeError::NonExhaustiveCase { .. } => "non-exhaustive-case",
+            TypeError::UnableToElaborateHole { .. } => "unable-to-elaborate-hole",
+            TypeError::Mismatch { .. } => "mismatch",
+            TypeError::UnexpectedFunction { .. } => "unexpected-function",
+            TypeError::ExpectedUniverse { .. } => "expected-universe",
+            TypeError::UnreducibleLevel { .. } => "unreducible-level",
+            TypeError::LevelOverflow { .. } => "level-overflow",
+            TypeError::UndefinedName { .. } => "undefined-name",
+            TypeError::UndefinedImport { .. } => "undefined-import",
+            TypeError::LabelMismatch { .. } => "label-mismatch",
+            TypeError::ArrayLengthMismatch { .. } => "array-length-mismatch",
+            TypeError::AmbiguousArrayLiteral { .. } => "ambiguous-array-literal",
+            TypeError::NoFieldInType { .. } => "no-field-in-type",
+            TypeError::RecordSizeMismatch { .. } => "record-size-mismatch",
+            TypeError::NoConstructorInType { .. } => "no-constructor-in-type",
+            TypeError::ConstructorArgCountMismatch { .. } => "constructor-arg-count-mismatch",
+            TypeError::AmbiguousConstructorPattern { .. } => "ambiguous-constructor-pattern",
+            TypeError::DuplicateConstructors { .. } => "duplicate-constructors",
+            TypeError::NonPositiveOccurrence { .. } => "non-positive-occurrence",
+            TypeError::NonTerminatingRecursion { .. } => "non-terminating-recursion",
+        })
+    }
+
+    /// The resugared types that are mentioned by the error, along with their
+    /// role in the error (eg. `"expected"` or `"found"`)
+    pub fn types(&self) -> Vec<(&'static str, &concrete::Term)> {
+        match *self {
+            TypeError::ArgAppliedToNonFunction { ref found, .. }
+            | TypeError::UnexpectedImplicitArg { ref found, .. }
+            | TypeError::ExpectedUniverse { ref found, .. }
+            | TypeError::UnreducibleLevel { ref found, .. }
+            | TypeError::NoFieldInType { ref found, .. }
+            | TypeError::NoConstructorInType { ref found, .. } => vec![("found", found)],
+            TypeError::LiteralMismatch { ref expected, .. }
+            | TypeError::LiteralOutOfRange { ref expected, .. }
+            | TypeError::UnexpectedFunction { ref expected, .. } => vec![("expected", expected)],
+            TypeError::UnableToElaborateHole {
+                expected: Some(ref expected),
+                ..
+            } => vec![("expected", expected)],
+            TypeError::Mismatch {
+                ref found,
+                ref expected,
+                ..
+            } => vec![("found", found), ("expected", expected)],
+            _ => Vec::new(),
+        }
+    }
+
     /// Convert the error into a diagnostic message
     pub fn to_diagnostic(&self) -> Diagnostic {
-        match *self {
+        let diagnostic = match *self {
             TypeError::Internal(ref err) => err.to_diagnostic(),
             TypeError::DuplicateDeclarations {
                 original_span,
@@ -239,6 +385,22 @@ impl TypeError {
             ))
             .with_label(Label::new_primary(fn_span).with_message("the term"))
             .with_label(Label::new_secondary(arg_span).with_message("the applied argument")),
+            TypeError::UnexpectedImplicitArg {
+                fn_span,
+                arg_span,
+                ref found,
+            } => Diagnostic::new_error(format!(
+                "applied an implicit argument to a term without an implicit parameter - found type `{}`",
+                found,
+            ))
+            .with_label(Label::new_primary(arg_span).with_message("the implicit argument"))
+            .with_label(Label::new_secondary(fn_span).with_message("the term")),
+            TypeError::UnableToInferImplicitArg { span, ref name } => Diagnostic::new_error(
+                format!("unable to infer the implicit argument `{}`", name),
+            )
+            .with_label(
+                Label::new_primary(span).with_message("consider supplying it in braces here"),
+            ),
             TypeError::FunctionParamNeedsAnnotation {
                 param_span,
                 var_span: _, // TODO
@@ -275,6 +437,20 @@ impl TypeError {
                 ))
                 .with_label(Label::new_primary(literal_span).with_message("the literal"))
             },
+            TypeError::LiteralOutOfRange {
+                literal_span,
+                ref found,
+                ref expected,
+                ref min,
+                ref max,
+            } => Diagnostic::new_error(format!(
+                "the literal `{}` is out of the range of the type `{}`",
+                found, expected,
+            ))
+            .with_label(
+                Label::new_primary(literal_span)
+                    .with_message(format!("expected a value between {} and {}", min, max)),
+            ),
             TypeError::AmbiguousIntLiteral { span } => Diagnostic::new_error(
                 "ambiguous integer literal",
             )
@@ -287,19 +463,30 @@ impl TypeError {
                 "empty case expressions need type annotations",
             )
             .with_label(Label::new_primary(span).with_message("type annotation needed here")),
+            TypeError::NonExhaustiveCase { span, ref missing } => Diagnostic::new_error(format!(
+                "non-exhaustive patterns: `{}` not covered",
+                missing,
+            ))
+            .with_label(
+                Label::new_primary(span).with_message(format!("pattern `{}` not covered", missing)),
+            ),
             TypeError::UnableToElaborateHole {
                 span,
                 expected: None,
-                ..
-            } => Diagnostic::new_error("unable to elaborate hole")
-                .with_label(Label::new_primary(span).with_message("the hole")),
+                ref locals,
+            } => Diagnostic::new_error(format!(
+                "unable to elaborate hole{}",
+                local_context_message(locals),
+            ))
+            .with_label(Label::new_primary(span).with_message("the hole")),
             TypeError::UnableToElaborateHole {
                 span,
                 expected: Some(ref expected),
-                ..
+                ref locals,
             } => Diagnostic::new_error(format!(
-                "unable to elaborate hole - expected: `{}`",
+                "unable to elaborate hole - expected: `{}`{}",
                 expected,
+                local_context_message(locals),
             ))
             .with_label(Label::new_primary(span).with_message("the hole")),
             TypeError::UnexpectedFunction {
@@ -322,6 +509,16 @@ impl TypeError {
                 Diagnostic::new_error(format!("expected type, found a value of type `{}`", found))
                     .with_label(Label::new_primary(span).with_message("the value"))
             },
+            TypeError::UnreducibleLevel { ref found, span } => Diagnostic::new_error(format!(
+                "unable to reduce `{}` to a universe level",
+                found,
+            ))
+            .with_label(Label::new_primary(span).with_message("the level")),
+            TypeError::LevelOverflow { span, ref level } => Diagnostic::new_error(format!(
+                "the universe `Type^{}` is too large to have a type",
+                level,
+            ))
+            .with_label(Label::new_primary(span).with_message("universe level overflow")),
             TypeError::UndefinedName { ref free_var, span } => {
                 Diagnostic::new_bug(format!("cannot find `{}` in scope", free_var))
                     .with_label(Label::new_primary(span).with_message("not found in this scope"))
@@ -374,6 +571,57 @@ impl TypeError {
             .with_label(
                 Label::new_primary(span).with_message(format!("record with {} fields", found_size)),
             ),
+            TypeError::NoConstructorInType {
+                span,
+                ref expected_label,
+                ref found,
+            } => Diagnostic::new_error(format!(
+                "the type `{}` does not have a constructor called `{}`",
+                found, expected_label
+            ))
+            .with_label(Label::new_primary(span).with_message("the constructor")),
+            TypeError::ConstructorArgCountMismatch {
+                span,
+                ref label,
+                found_count,
+                expected_count,
+            } => Diagnostic::new_error(format!(
+                "mismatched constructor arguments: expected {} arguments to `{}` but found {}",
+                expected_count, label, found_count
+            ))
+            .with_label(
+                Label::new_primary(span)
+                    .with_message(format!("constructor with {} arguments", found_count)),
+            ),
+            TypeError::AmbiguousConstructorPattern { span, ref label } => {
+                Diagnostic::new_error(format!("ambiguous constructor pattern `{}`", label))
+                    .with_label(
+                        Label::new_primary(span).with_message("type annotations needed here"),
+                    )
+            },
+            TypeError::DuplicateConstructors { span, ref label } => {
+                Diagnostic::new_error(format!("constructor defined more than once `{}`", label))
+                    .with_label(Label::new_primary(span).with_message("in this variant type"))
+            },
+            TypeError::NonPositiveOccurrence { span, ref name } => Diagnostic::new_error(
+                format!("the variant type `{}` occurs in a non-positive position", name),
+            )
+            .with_label(Label::new_primary(span).with_message(
+                "recursive occurrences must not appear to the left of a function arrow, \
+                 or as an argument to an unknown function",
+            )),
+            TypeError::NonTerminatingRecursion { span, ref name } => Diagnostic::new_error(
+                format!("could not show that the recursive definition `{}` terminates", name),
+            )
+            .with_label(Label::new_primary(span).with_message(
+                "recursive calls must pass a structurally smaller argument for one of the \
+                 parameters, ie. a variable bound by a constructor pattern",
+            )),
+        };
+
+        match self.code() {
+            Some(code) => diagnostic.with_code(code),
+            None => diagnostic,
         }
     }
 }
@@ -389,3 +637,47 @@ impl From<NbeError> for TypeError {
         TypeError::from(InternalError::from(src))
     }
 }
+
+/// A warning produced during type checking
+///
+/// Unlike errors, these do not prevent elaboration from succeeding.
+#[derive(Debug, Fail, Clone, PartialEq)]
+pub enum TypeWarning {
+    #[fail(display = "Unreachable pattern")]
+    UnreachablePattern { span: ByteSpan },
+}
+
+impl TypeWarning {
+    /// A short, stable identifier for the kind of warning
+    pub fn code(&self) -> &'static str {
+        match *self {
+            TypeWarning::UnreachablePattern { .. } => "unreachable-pattern",
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match *self {
+            TypeWarning::UnreachablePattern { span } => {
+                Diagnostic::new_warning("unreachable pattern")
+                    .with_label(
+                        Label::new_primary(span).with_message("this pattern will never match"),
+                    )
+                    .with_code(self.code())
+            },
+        }
+    }
+}
+
+/// List the local binders that are in scope at a hole, for inclusion in the
+/// message of a diagnostic
+pub(crate) fn local_context_message(locals: &[(String, concrete::Term)]) -> String {
+    if locals.is_empty() {
+        return String::new();
+    }
+
+    let mut message = String::from("\n\nlocal context:");
+    for &(ref name, ref ty) in locals {
+        message.push_str(&format!("\n    {} : {}", name, ty));
+    }
+    message
+}
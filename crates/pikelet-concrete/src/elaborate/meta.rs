@@ -0,0 +1,999 @@
+//! Metavariables, and the machinery for solving and eliminating them
+//!
+//! Metavariables stand in for terms that the elaborator needs to fill in on
+//! behalf of the programmer, for example holes, omitted type annotations, and
+//! the arguments to implicit parameters. They are solved using higher-order
+//! pattern unification, and their solutions are substituted back into the
+//! elaborated term once we have finished elaborating it (this is known as
+//! _zonking_).
+//!
+//! The universes of holes are not known until the holes are solved, so they
+//! are given _level metavariables_. These start off at the lowest level, and
+//! are raised whenever we find a type that needs to live in a higher universe.
+
+use codespan::ByteSpan;
+use codespan_reporting::{Diagnostic, Label};
+use moniker::FreeVar;
+
+use pikelet_core::nbe::{self, Unfold};
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::domain::{
+    Head, Locals, Neutral, RcNeutral, RcType, RcValue, Spine, Value,
+};
+use pikelet_core::syntax::{Level, LevelShift, MetaVar, Plicity, VarIndex, VarLevel};
+
+use crate::elaborate::errors::local_context_message;
+use crate::elaborate::{Context, TypeError};
+use crate::resugar::{Resugar, ResugarEnv};
+use crate::syntax::concrete;
+
+/// The reason a metavariable was created
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaOrigin {
+    /// A hole, written by the programmer
+    Hole(ByteSpan),
+    /// The type of a hole that was encountered when inferring types
+    HoleType(ByteSpan),
+    /// The type annotation of a function parameter that was omitted
+    ParamAnn(ByteSpan, FreeVar<String>),
+    /// The argument to an implicit parameter, inserted during elaboration
+    ImplicitArg(ByteSpan, String),
+}
+
+impl MetaOrigin {
+    /// Return the span of source code that the metavariable originated from
+    pub fn span(&self) -> ByteSpan {
+        match *self {
+            MetaOrigin::Hole(span)
+            | MetaOrigin::HoleType(span)
+            | MetaOrigin::ParamAnn(span, _)
+            | MetaOrigin::ImplicitArg(span, _) => span,
+        }
+    }
+}
+
+/// The information we track for each metavariable
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaEntry {
+    /// The reason the metavariable was created
+    pub origin: MetaOrigin,
+    /// The type of the metavariable, in the context it was created in
+    pub ty: RcType,
+    /// The levels of the local parameters that the metavariable is
+    /// abstracted over, along with their types
+    pub params: Vec<(VarLevel, RcType)>,
+    /// The number of local binders that were in scope when the metavariable
+    /// was created
+    pub size: VarLevel,
+    /// The names of the local binders that were in scope when the
+    /// metavariable was created
+    pub resugar_env: ResugarEnv,
+    /// The solution, if one has been found
+    pub solution: Option<RcTerm>,
+}
+
+/// The goal of a hole, as presented to the programmer
+#[derive(Debug, Clone, PartialEq)]
+pub struct HoleGoal {
+    /// The location of the hole
+    pub span: ByteSpan,
+    /// The type that the hole is expected to have
+    pub expected: concrete::Term,
+    /// The local binders that are in scope at the hole, along with their types
+    pub locals: Vec<(String, concrete::Term)>,
+    /// The term that the hole was filled with, if a solution was found
+    pub solution: Option<concrete::Term>,
+}
+
+impl HoleGoal {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = match self.solution {
+            None => format!("unsolved hole - expected: `{}`", self.expected),
+            Some(ref solution) => format!(
+                "hole solved as `{}` - expected: `{}`",
+                solution, self.expected,
+            ),
+        };
+
+        Diagnostic::new_note(message + &local_context_message(&self.locals))
+            .with_label(Label::new_primary(self.span).with_message("the hole"))
+    }
+}
+
+/// Create a fresh metavariable of the given type, returning it applied to the
+/// local parameters that are currently in scope
+pub fn fresh_meta(context: &Context, origin: MetaOrigin, ty: &RcType) -> RcTerm {
+    let meta = context.insert_meta(origin, ty.clone());
+    let params = context.params().map(|(level, _)| level);
+
+    meta_app(meta, context.size(), params)
+}
+
+/// Apply a metavariable to a series of local parameters
+fn meta_app(meta: MetaVar, size: VarLevel, params: impl Iterator<Item = VarLevel>) -> RcTerm {
+    params.fold(RcTerm::from(Term::Meta(meta)), |acc, level| {
+        let arg = RcTerm::from(Term::Var(level.to_index(size), LevelShift(0)));
+        RcTerm::from(Term::FunApp(acc, Plicity::Explicit, arg))
+    })
+}
+
+/// Describe the goal of a hole, using the local binders that were in scope
+/// when it was created
+pub fn hole_goal(context: &Context, meta: MetaVar) -> Result<HoleGoal, TypeError> {
+    let entry = context.get_meta(meta);
+    let resugar =
+        |term: &RcTerm| zonk_term_at(context, entry.size, term).resugar(&entry.resugar_env);
+
+    let mut locals = Vec::with_capacity(entry.params.len());
+    for &(level, ref ty) in &entry.params {
+        let ty = nbe::quote(context, entry.size, Unfold::Never, ty)?;
+        locals.push((
+            entry.resugar_env.on_var(level.to_index(entry.size)),
+            resugar(&ty),
+        ));
+    }
+
+    let expected = nbe::quote(context, entry.size, Unfold::Never, &entry.ty)?;
+    let solution = match entry.solution {
+        None => None,
+        Some(_) => {
+            let params = entry.params.iter().map(|&(level, _)| level);
+            Some(resugar(&meta_app(meta, entry.size, params)))
+        },
+    };
+
+    Ok(HoleGoal {
+        span: entry.origin.span(),
+        expected: resugar(&expected),
+        locals,
+        solution,
+    })
+}
+
+/// Create a fresh level metavariable
+pub fn fresh_level(context: &Context) -> Level<VarLevel> {
+    Level::meta(context.insert_level_meta())
+}
+
+/// Unfold the definitions at the head of a value, re-evaluating it if its
+/// computation is blocked on a metavariable that has since been solved
+pub fn force(context: &Context, value: &RcValue) -> Result<RcValue, TypeError> {
+    let value = nbe::force(context, value)?;
+
+    if let Value::Neutral(ref neutral, _) = *value.inner {
+        if let Head::Meta(meta) = *neutral.head() {
+            if context.get_meta_solution(meta).is_some() {
+                return force(context, &context.eval(&context.quote(&value)?)?);
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+/// Returns `true` if the values are applications of the same definition to
+/// the same arguments, in which case they are equal without needing to unfold
+/// the definition
+pub fn is_same_definition(value1: &RcValue, value2: &RcValue) -> bool {
+    match (&*value1.inner, &*value2.inner) {
+        (&Value::Glued(ref head1, ref spine1, _), &Value::Glued(ref head2, ref spine2, _)) => {
+            head1 == head2 && spine1 == spine2
+        },
+        (_, _) => false,
+    }
+}
+
+/// Attempt to make two values equal, solving any metavariables that we
+/// encounter along the way
+pub fn unify(context: &Context, value1: &RcValue, value2: &RcValue) -> Result<bool, TypeError> {
+    if is_same_definition(value1, value2) {
+        return Ok(true);
+    }
+
+    let forced1 = force(context, value1)?;
+    let forced2 = force(context, value2)?;
+
+    // Solve with the unforced values, so that definitions stay folded in the
+    // solution
+    match (forced1.meta_app(), forced2.meta_app()) {
+        (Some((meta1, _)), Some((meta2, _))) if meta1 == meta2 => {},
+        (Some((meta, spine)), _) => return solve(context, meta, spine, value2),
+        (_, Some((meta, spine))) => return solve(context, meta, spine, value1),
+        (None, None) => {},
+    }
+
+    let (value1, value2) = (forced1, forced2);
+
+    match (&*value1.inner, &*value2.inner) {
+        // Errors have already been reported, so we avoid cascading them
+        (&Value::Error, _) | (_, &Value::Error) => Ok(true),
+
+        (&Value::Universe(ref level1), &Value::Universe(ref level2))
+        | (&Value::Level(ref level1), &Value::Level(ref level2)) => {
+            Ok(solve_level_le(context, level1, level2) && solve_level_le(context, level2, level1))
+        },
+        (&Value::Literal(ref literal1), &Value::Literal(ref literal2)) => Ok(literal1 == literal2),
+
+        (
+            &Value::FunType(plicity1, ref ann1, ref closure1),
+            &Value::FunType(plicity2, ref ann2, ref closure2),
+        )
+        | (
+            &Value::FunIntro(plicity1, ref ann1, ref closure1),
+            &Value::FunIntro(plicity2, ref ann2, ref closure2),
+        ) => {
+            if plicity1 != plicity2 {
+                return Ok(false);
+            }
+
+            Ok(unify(context, ann1, ann2)? && {
+                let var = RcValue::from(Value::var(context.size(), 0));
+                let body1 = closure1.apply(context, var.clone())?;
+                let body2 = closure2.apply(context, var)?;
+
+                let mut context = context.clone();
+                context.push_local(closure2.name().clone(), ann2.clone());
+                unify(&context, &body1, &body2)?
+            })
+        },
+
+        (&Value::RecordType(ref closure1), &Value::RecordType(ref closure2)) => {
+            if closure1.len() != closure2.len() {
+                return Ok(false);
+            }
+
+            let fields1 = closure1.fields(context, context.size())?;
+            let fields2 = closure2.fields(context, context.size())?;
+
+            let mut context = context.clone();
+            for ((label1, _, ann1), (label2, name2, ann2)) in
+                Iterator::zip(fields1.into_iter(), fields2.into_iter())
+            {
+                if label1 != label2 || !unify(&context, &ann1, &ann2)? {
+                    return Ok(false);
+                }
+                context.push_local(name2, ann2);
+            }
+
+            Ok(true)
+        },
+
+        (&Value::RecordIntro(ref fields1), &Value::RecordIntro(ref fields2)) => {
+            if fields1.len() != fields2.len() {
+                return Ok(false);
+            }
+
+            for (&(ref label1, ref expr1), &(ref label2, ref expr2)) in
+                Iterator::zip(fields1.iter(), fields2.iter())
+            {
+                if label1 != label2 || !unify(context, expr1, expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::VariantType(ref closure1), &Value::VariantType(ref closure2)) => {
+            if closure1.arms().count() != closure2.arms().count() {
+                return Ok(false);
+            }
+
+            let var = RcValue::from(Value::var(context.size(), 0));
+            let arms1 = closure1.instantiate(context, var.clone())?;
+            let arms2 = closure2.instantiate(context, var)?;
+
+            // The type of the recursive variable is never needed when
+            // comparing the arms, so we leave it as an error
+            let mut context = context.clone();
+            context.push_local(closure2.name().clone(), RcValue::from(Value::Error));
+            let context = &context;
+
+            for (&(ref label1, ref args1), &(ref label2, ref args2)) in
+                Iterator::zip(arms1.iter(), arms2.iter())
+            {
+                if label1 != label2 || args1.len() != args2.len() {
+                    return Ok(false);
+                }
+                for (arg1, arg2) in Iterator::zip(args1.iter(), args2.iter()) {
+                    if !unify(context, arg1, arg2)? {
+                        return Ok(false);
+                    }
+                }
+            }
+
+            Ok(true)
+        },
+
+        (
+            &Value::VariantIntro(ref label1, ref args1),
+            &Value::VariantIntro(ref label2, ref args2),
+        ) => {
+            if label1 != label2 || args1.len() != args2.len() {
+                return Ok(false);
+            }
+
+            for (arg1, arg2) in Iterator::zip(args1.iter(), args2.iter()) {
+                if !unify(context, arg1, arg2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::ArrayIntro(ref elems1), &Value::ArrayIntro(ref elems2)) => {
+            if elems1.len() != elems2.len() {
+                return Ok(false);
+            }
+
+            for (elem1, elem2) in Iterator::zip(elems1.iter(), elems2.iter()) {
+                if !unify(context, elem1, elem2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::Neutral(ref neutral1, ref spine1), &Value::Neutral(ref neutral2, ref spine2)) => {
+            let same_spines =
+                spine1.len() == spine2.len() && unify_neutral(context, neutral1, neutral2)? && {
+                    let mut same_args = true;
+                    for (&(plicity1, ref arg1), &(plicity2, ref arg2)) in
+                        Iterator::zip(spine1.iter(), spine2.iter())
+                    {
+                        if plicity1 != plicity2 || !unify(context, arg1, arg2)? {
+                            same_args = false;
+                            break;
+                        }
+                    }
+                    same_args
+                };
+
+            if same_spines {
+                return Ok(true);
+            }
+
+            // Eta conversion for records, where `r1` is equal to `r2` if all
+            // of their fields are equal, even if they are stuck on different
+            // heads
+            match neutral_ty(context, neutral1, spine1)? {
+                Some(ty) => match *force(context, &ty)?.inner {
+                    Value::RecordType(_) => unify_fields(context, &ty, &value1, &value2),
+                    _ => Ok(false),
+                },
+                None => Ok(false),
+            }
+        },
+
+        // Eta conversion for functions, where `f` is equal to `\x => f x`
+        (&Value::FunIntro(plicity, ref ann, ref closure), &Value::Neutral(..)) => {
+            let var = RcValue::from(Value::var(context.size(), 0));
+            let body1 = closure.apply(context, var.clone())?;
+            let body2 = nbe::apply(context, &value2, plicity, var)?;
+
+            let mut context = context.clone();
+            context.push_local(closure.name().clone(), ann.clone());
+            unify(&context, &body1, &body2)
+        },
+        (&Value::Neutral(..), &Value::FunIntro(plicity, ref ann, ref closure)) => {
+            let var = RcValue::from(Value::var(context.size(), 0));
+            let body1 = nbe::apply(context, &value1, plicity, var.clone())?;
+            let body2 = closure.apply(context, var)?;
+
+            let mut context = context.clone();
+            context.push_local(closure.name().clone(), ann.clone());
+            unify(&context, &body1, &body2)
+        },
+
+        // Eta conversion for records, where `r` is equal to `record { x = r.x }`
+        (&Value::RecordIntro(ref fields), &Value::Neutral(..)) => {
+            for &(ref label, ref expr1) in fields {
+                let expr2 = nbe::record_proj(context, &value2, label, LevelShift(0))?;
+                if !unify(context, expr1, &expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+        (&Value::Neutral(..), &Value::RecordIntro(ref fields)) => {
+            for &(ref label, ref expr2) in fields {
+                let expr1 = nbe::record_proj(context, &value1, label, LevelShift(0))?;
+                if !unify(context, &expr1, expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (_, _) => Ok(false),
+    }
+}
+
+/// Unify two values of the type `ty`, comparing them field by field if `ty`
+/// is a record type
+///
+/// This means that values of the unit record type are equal to each other.
+fn unify_fields(
+    context: &Context,
+    ty: &RcType,
+    value1: &RcValue,
+    value2: &RcValue,
+) -> Result<bool, TypeError> {
+    let ty = force(context, ty)?;
+    let closure = match *ty.inner {
+        Value::RecordType(ref closure) => closure,
+        _ => return unify(context, value1, value2),
+    };
+
+    let mut same_fields = true;
+    closure.for_each_field(context, |label, field_ty| {
+        let expr1 = nbe::record_proj(context, value1, &label, LevelShift(0))?;
+        if same_fields {
+            let expr2 = nbe::record_proj(context, value2, &label, LevelShift(0))?;
+            same_fields = unify_fields(context, &field_ty, &expr1, &expr2)?;
+        }
+        Ok::<_, TypeError>(expr1)
+    })?;
+
+    Ok(same_fields)
+}
+
+/// Look up the type of a neutral value that is applied to `spine`
+///
+/// Returns `None` if the type could not be found, for example if the value
+/// is stuck on a metavariable or a case expression.
+fn neutral_ty(
+    context: &Context,
+    neutral: &RcNeutral,
+    spine: &Spine,
+) -> Result<Option<RcType>, TypeError> {
+    let mut ty = match *neutral.inner {
+        Neutral::Head(ref head) => {
+            let (ty, shift) = match *head {
+                Head::Var(level, shift) => (context.get_local(level).map(|(_, ty)| ty), shift),
+                Head::Global(ref name, shift) => (context.get_declaration(name), shift),
+                Head::Import(ref name, shift) => {
+                    (context.get_import(name).map(|&(_, ref ty)| ty), shift)
+                },
+                Head::Fix(_, ref ann, _) => (Some(ann), LevelShift(0)),
+                Head::Meta(_) => (None, LevelShift(0)),
+            };
+
+            match ty {
+                Some(ty) => {
+                    let mut ty = ty.clone();
+                    ty.shift_universes(shift);
+                    ty
+                },
+                None => return Ok(None),
+            }
+        },
+        Neutral::RecordProj(ref expr, ref label, shift) => {
+            let expr_ty = match neutral_ty(context, expr, &Spine::new())? {
+                Some(expr_ty) => force(context, &expr_ty)?,
+                None => return Ok(None),
+            };
+            let closure = match *expr_ty.inner {
+                Value::RecordType(ref closure) => closure,
+                _ => return Ok(None),
+            };
+
+            let expr = RcValue::from(Value::Neutral(expr.clone(), Spine::new()));
+            let mut field_ty = None;
+            closure.for_each_field(context, |current_label, current_ann| {
+                let value = nbe::record_proj(context, &expr, &current_label, shift)?;
+                if current_label == *label && field_ty.is_none() {
+                    field_ty = Some(current_ann);
+                }
+                Ok::<_, TypeError>(value)
+            })?;
+
+            match field_ty {
+                Some(mut field_ty) => {
+                    field_ty.shift_universes(shift);
+                    field_ty
+                },
+                None => return Ok(None),
+            }
+        },
+        Neutral::Case(..) => return Ok(None),
+    };
+
+    for &(_, ref arg) in spine {
+        ty = match *force(context, &ty)?.inner {
+            Value::FunType(_, _, ref closure) => closure.apply(context, arg.clone())?,
+            _ => return Ok(None),
+        };
+    }
+
+    Ok(Some(ty))
+}
+
+fn unify_neutral(
+    context: &Context,
+    neutral1: &RcNeutral,
+    neutral2: &RcNeutral,
+) -> Result<bool, TypeError> {
+    match (&*neutral1.inner, &*neutral2.inner) {
+        (
+            &Neutral::Head(Head::Var(ref var1, shift1)),
+            &Neutral::Head(Head::Var(ref var2, shift2)),
+        ) => Ok(var1 == var2 && shift1 == shift2),
+        (
+            &Neutral::Head(Head::Global(ref name1, shift1)),
+            &Neutral::Head(Head::Global(ref name2, shift2)),
+        ) => Ok(name1 == name2 && shift1 == shift2),
+        (
+            &Neutral::Head(Head::Import(ref name1, shift1)),
+            &Neutral::Head(Head::Import(ref name2, shift2)),
+        ) => Ok(name1 == name2 && shift1 == shift2),
+        (&Neutral::Head(Head::Meta(meta1)), &Neutral::Head(Head::Meta(meta2))) => {
+            Ok(meta1 == meta2)
+        },
+        (
+            &Neutral::RecordProj(ref expr1, ref label1, shift1),
+            &Neutral::RecordProj(ref expr2, ref label2, shift2),
+        ) => Ok(label1 == label2 && shift1 == shift2 && unify_neutral(context, expr1, expr2)?),
+        (_, _) => Ok(
+            nbe::quote_neutral(context, context.size(), Unfold::Always, neutral1)?
+                == nbe::quote_neutral(context, context.size(), Unfold::Always, neutral2)?,
+        ),
+    }
+}
+
+/// Attempt to solve a problem of the form `?meta x1 ... xn = value`
+///
+/// Solutions are only found when the arguments to the metavariable are
+/// distinct variables, in which case the solution is `\x1 ... xn => value`.
+/// This is known as the _pattern fragment_ of higher-order unification, and
+/// has the nice property of always producing unique solutions.
+fn solve(
+    context: &Context,
+    meta: MetaVar,
+    spine: &[(Plicity, RcValue)],
+    value: &RcValue,
+) -> Result<bool, TypeError> {
+    let mut params = Vec::<VarLevel>::with_capacity(spine.len());
+    for &(_, ref arg) in spine {
+        match arg.var_app() {
+            Some((level, LevelShift(0), [])) => {
+                if params.contains(&level) {
+                    return Ok(false);
+                }
+                params.push(level);
+            },
+            Some(_) | None => return Ok(false),
+        }
+    }
+
+    // Keep the definitions in the solution folded, so that it reads the way it
+    // was written. If the folded solution refers to local definitions, we try
+    // again with them unfolded.
+    let mut solution =
+        match abstract_solution(context, meta, &params, &context.quote_folded(value)?) {
+            Some(solution) => solution,
+            None => match abstract_solution(context, meta, &params, &context.quote(value)?) {
+                Some(solution) => solution,
+                None => return Ok(false),
+            },
+        };
+
+    // Ensure that types live in the universe of the metavariable
+    if let Value::Universe(ref meta_level) = *force(context, &context.get_meta(meta).ty)?.inner {
+        if !solve_level_le(context, &type_level(context, value)?, meta_level) {
+            return Ok(false);
+        }
+    }
+
+    for (position, &level) in params.iter().enumerate().rev() {
+        let (name, ann) = match context.get_local(level) {
+            Some((name, ann)) => (name.clone(), context.quote(ann)?),
+            None => return Ok(false),
+        };
+        let ann = match rename_params(context.size(), &params[..position], &ann) {
+            Some(ann) => ann,
+            None => return Ok(false),
+        };
+        solution = RcTerm::from(Term::FunIntro(Plicity::Explicit, name, ann, solution));
+    }
+
+    context.solve_meta(meta, solution);
+
+    Ok(true)
+}
+
+/// Abstract a candidate solution over the parameters of a metavariable
+///
+/// Returns `None` if the solution would be cyclic, or if it refers to local
+/// binders that were not in scope where the metavariable was created.
+fn abstract_solution(
+    context: &Context,
+    meta: MetaVar,
+    params: &[VarLevel],
+    term: &RcTerm,
+) -> Option<RcTerm> {
+    if occurs(context, meta, term) {
+        return None;
+    }
+
+    rename_params(context.size(), params, term)
+}
+
+/// Attempt to make `level1` less than or equal to `level2`, raising the level
+/// metavariables in `level2` if need be
+pub fn solve_level_le(
+    context: &Context,
+    level1: &Level<VarLevel>,
+    level2: &Level<VarLevel>,
+) -> bool {
+    let lowest1 = zonk_level(context, level1);
+    if lowest1.le(&zonk_level(context, level2)) {
+        return true;
+    }
+
+    match *level2 {
+        Level::Finite(_, _, ref metas) if !metas.is_empty() => {
+            let (meta, shift) = metas[0];
+            context.raise_level_meta(meta, &lowest1.lower(shift));
+            lowest1.le(&zonk_level(context, level2))
+        },
+        Level::Finite(_, _, _) | Level::Omega => false,
+    }
+}
+
+/// Infer the lowest level of the universe that a type could live in
+///
+/// Types that we know nothing about are assumed to live in the lowest
+/// universe.
+fn type_level(context: &Context, ty: &RcType) -> Result<Level<VarLevel>, TypeError> {
+    let ty = force(context, ty)?;
+
+    match *ty.inner {
+        Value::Universe(ref level) => Ok(level.clone() + LevelShift(1)),
+        Value::FunType(_, ref ann, ref closure) => {
+            let ann_level = type_level(context, ann)?;
+
+            let var = RcValue::from(Value::var(context.size(), 0));
+            let body = closure.apply(context, var)?;
+
+            let mut body_context = context.clone();
+            body_context.push_local(closure.name().clone(), ann.clone());
+            let body_level = type_level(&body_context, &body)?;
+
+            // Types that quantify over levels live in `Type^omega`
+            if body_level.mentions(context.size()) {
+                Ok(Level::Omega)
+            } else {
+                Ok(ann_level.max(&body_level))
+            }
+        },
+        Value::RecordType(ref closure) => {
+            let fields = closure.fields(context, context.size())?;
+            let mut max_level = Level::from(0);
+
+            let mut body_context = context.clone();
+            for (_, name, ann) in fields {
+                max_level = max_level.max(&type_level(&body_context, &ann)?);
+                body_context.push_local(name, ann);
+            }
+
+            if (context.size().0..body_context.size().0)
+                .any(|var| max_level.mentions(VarLevel(var)))
+            {
+                Ok(Level::Omega)
+            } else {
+                Ok(max_level)
+            }
+        },
+        Value::Neutral(ref neutral, ref spine) => {
+            let (head_ty, shift) = match **neutral {
+                Neutral::Head(Head::Var(level, shift)) => {
+                    (context.get_local(level).map(|(_, ty)| ty), shift)
+                },
+                Neutral::Head(Head::Global(ref name, shift)) => {
+                    (context.get_declaration(name), shift)
+                },
+                // Metavariables are applied to the locals that were in scope
+                // when they were created, and their types are given in terms
+                // of those locals
+                Neutral::Head(Head::Meta(meta)) => {
+                    return match *force(context, &context.get_meta(meta).ty)?.inner {
+                        Value::Universe(ref level) => Ok(level.clone()),
+                        _ => Ok(Level::from(0)),
+                    };
+                },
+                _ => return Ok(Level::from(0)),
+            };
+            let mut head_ty = match head_ty {
+                Some(ty) => {
+                    let mut ty = ty.clone();
+                    ty.shift_universes(shift);
+                    ty
+                },
+                None => return Ok(Level::from(0)),
+            };
+
+            for &(_, ref arg) in spine {
+                head_ty = match *force(context, &head_ty)?.inner {
+                    Value::FunType(_, _, ref closure) => closure.apply(context, arg.clone())?,
+                    _ => return Ok(Level::from(0)),
+                };
+            }
+
+            match *force(context, &head_ty)?.inner {
+                Value::Universe(ref level) => Ok(level.clone()),
+                _ => Ok(Level::from(0)),
+            }
+        },
+        _ => Ok(Level::from(0)),
+    }
+}
+
+/// Rename the variables of a term that was read back in a context of the
+/// given size, so that they refer to the parameters of a solution instead
+///
+/// Returns `None` if the term refers to a local binder that is not one of
+/// the parameters.
+fn rename_params(size: VarLevel, params: &[VarLevel], term: &RcTerm) -> Option<RcTerm> {
+    term.rename(&|index| {
+        let level = index.to_level(size);
+        let position = params.iter().position(|&param| param == level)?;
+        Some(VarIndex(params.len() - position - 1))
+    })
+}
+
+/// Returns `true` if the metavariable appears in the term
+fn occurs(context: &Context, meta: MetaVar, term: &RcTerm) -> bool {
+    match *term.inner {
+        Term::Meta(other) if other == meta => true,
+        Term::Meta(other) => match context.get_meta_solution(other) {
+            Some(solution) => occurs(context, meta, &solution),
+            None => false,
+        },
+        Term::Universe(_)
+        | Term::Level(_)
+        | Term::Literal(_)
+        | Term::Var(_, _)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::Error => false,
+        Term::Ann(ref expr, ref ty) => occurs(context, meta, expr) || occurs(context, meta, ty),
+        Term::FunType(_, _, ref ann, ref body)
+        | Term::FunIntro(_, _, ref ann, ref body)
+        | Term::Fix(_, _, ref ann, ref body) => {
+            occurs(context, meta, ann) || occurs(context, meta, body)
+        },
+        Term::FunApp(ref head, _, ref arg) => {
+            occurs(context, meta, head) || occurs(context, meta, arg)
+        },
+        Term::RecordType(ref fields) => fields
+            .iter()
+            .any(|&(_, _, ref ann)| occurs(context, meta, ann)),
+        Term::RecordIntro(ref fields) => fields
+            .iter()
+            .any(|&(_, ref expr)| occurs(context, meta, expr)),
+        Term::RecordProj(ref expr, _, _) => occurs(context, meta, expr),
+        Term::VariantType(_, ref arms) => arms
+            .iter()
+            .any(|&(_, ref args)| args.iter().any(|arg| occurs(context, meta, arg))),
+        Term::VariantIntro(_, ref args) => args.iter().any(|arg| occurs(context, meta, arg)),
+        Term::Case(ref head, ref clauses) => {
+            occurs(context, meta, head)
+                || clauses
+                    .iter()
+                    .any(|&(_, ref body)| occurs(context, meta, body))
+        },
+        Term::ArrayIntro(ref elems) => elems.iter().any(|elem| occurs(context, meta, elem)),
+        Term::Let(ref bindings, ref body) => {
+            bindings
+                .iter()
+                .any(|&(_, ref term, _)| occurs(context, meta, term))
+                || occurs(context, meta, body)
+        },
+    }
+}
+
+/// Replace the level metavariables in a level with the lowest levels that they
+/// could be
+pub fn zonk_level(context: &Context, level: &Level<VarLevel>) -> Level<VarLevel> {
+    level.map_metas(|meta| context.get_level_meta(meta))
+}
+
+/// Replace the level metavariables in a level that is found under `size`
+/// local binders with the lowest levels that they could be
+fn zonk_level_at(context: &Context, size: VarLevel, level: &Level) -> Level {
+    level.map_metas(|meta| nbe::quote_level(size, &context.get_level_meta(meta)))
+}
+
+/// Replace the solved metavariables in a term with their solutions
+///
+/// Level metavariables are replaced with the lowest levels that they could be.
+pub fn zonk_term(context: &Context, term: &RcTerm) -> RcTerm {
+    zonk_term_at(context, context.size(), term)
+}
+
+/// Replace the solved metavariables in a term that is found under `size`
+/// local binders
+pub(crate) fn zonk_term_at(context: &Context, size: VarLevel, term: &RcTerm) -> RcTerm {
+    let zonk = |term: &RcTerm| zonk_term_at(context, size, term);
+    let zonk_under =
+        |binders: usize, term: &RcTerm| zonk_term_at(context, VarLevel(size.0 + binders), term);
+
+    match *term.inner {
+        Term::Universe(ref level) => {
+            RcTerm::from(Term::Universe(zonk_level_at(context, size, level)))
+        },
+        Term::Level(ref level) => RcTerm::from(Term::Level(zonk_level_at(context, size, level))),
+        Term::Literal(_) | Term::Var(_, _) | Term::Global(_, _) | Term::Import(_) | Term::Error => {
+            term.clone()
+        },
+        Term::Meta(meta) => match context.get_meta_solution(meta) {
+            Some(ref solution) => zonk(solution),
+            None => term.clone(),
+        },
+        Term::Ann(ref expr, ref ty) => RcTerm::from(Term::Ann(zonk(expr), zonk(ty))),
+        Term::FunType(plicity, ref name, ref ann, ref body) => RcTerm::from(Term::FunType(
+            plicity,
+            name.clone(),
+            zonk(ann),
+            zonk_under(1, body),
+        )),
+        Term::FunIntro(plicity, ref name, ref ann, ref body) => RcTerm::from(Term::FunIntro(
+            plicity,
+            name.clone(),
+            zonk(ann),
+            zonk_under(1, body),
+        )),
+        Term::Fix(index, ref name, ref ann, ref body) => RcTerm::from(Term::Fix(
+            index,
+            name.clone(),
+            zonk(ann),
+            zonk_under(1, body),
+        )),
+        Term::FunApp(_, _, _) => {
+            // Collect the spine of the application
+            let mut head = term;
+            let mut spine = Vec::new();
+            while let Term::FunApp(ref next_head, plicity, ref arg) = *head.inner {
+                spine.push((plicity, zonk(arg)));
+                head = next_head;
+            }
+            spine.reverse();
+
+            match *head.inner {
+                Term::Meta(meta) => match context.get_meta_solution(meta) {
+                    Some(ref solution) => match instantiate(context, size, solution, &spine) {
+                        Ok(term) => term,
+                        Err(_) => apply(head.clone(), spine),
+                    },
+                    None => apply(head.clone(), spine),
+                },
+                _ => apply(zonk(head), spine),
+            }
+        },
+        Term::RecordType(ref fields) => {
+            let fields = (fields.iter().enumerate())
+                .map(|(index, &(ref label, ref name, ref ann))| {
+                    (label.clone(), name.clone(), zonk_under(index, ann))
+                })
+                .collect();
+
+            RcTerm::from(Term::RecordType(fields))
+        },
+        Term::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| (label.clone(), zonk(expr)))
+                .collect();
+
+            RcTerm::from(Term::RecordIntro(fields))
+        },
+        Term::RecordProj(ref expr, ref label, shift) => {
+            RcTerm::from(Term::RecordProj(zonk(expr), label.clone(), shift))
+        },
+        Term::VariantType(ref name, ref arms) => {
+            let arms = arms
+                .iter()
+                .map(|&(ref label, ref args)| {
+                    let args = args.iter().map(|arg| zonk_under(1, arg)).collect();
+                    (label.clone(), args)
+                })
+                .collect();
+
+            RcTerm::from(Term::VariantType(name.clone(), arms))
+        },
+        Term::VariantIntro(ref label, ref args) => RcTerm::from(Term::VariantIntro(
+            label.clone(),
+            args.iter().map(zonk).collect(),
+        )),
+        Term::Case(ref head, ref clauses) => {
+            let clauses = clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| {
+                    let binders = pattern.binders().len();
+                    (
+                        zonk_pattern(context, size, pattern),
+                        zonk_under(binders, body),
+                    )
+                })
+                .collect();
+
+            RcTerm::from(Term::Case(zonk(head), clauses))
+        },
+        Term::ArrayIntro(ref elems) => {
+            RcTerm::from(Term::ArrayIntro(elems.iter().map(zonk).collect()))
+        },
+        Term::Let(ref bindings, ref body) => {
+            let bindings = (bindings.iter().enumerate())
+                .map(|(index, &(ref name, ref term, ref doc))| {
+                    (name.clone(), zonk_under(index, term), doc.clone())
+                })
+                .collect();
+
+            RcTerm::from(Term::Let(bindings, zonk_under(bindings_len(term), body)))
+        },
+    }
+}
+
+/// The number of bindings in a let term
+fn bindings_len(term: &RcTerm) -> usize {
+    match *term.inner {
+        Term::Let(ref bindings, _) => bindings.len(),
+        _ => 0,
+    }
+}
+
+fn zonk_pattern(context: &Context, size: VarLevel, pattern: &RcPattern) -> RcPattern {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, ref ty) => RcPattern::from(Pattern::Ann(
+            zonk_pattern(context, size, pattern),
+            zonk_term_at(context, size, ty),
+        )),
+        Pattern::Binder(_)
+        | Pattern::Var(_, _)
+        | Pattern::Global(_, _)
+        | Pattern::Literal(_)
+        | Pattern::Error => pattern.clone(),
+        Pattern::VariantIntro(ref label, ref patterns) => RcPattern::from(Pattern::VariantIntro(
+            label.clone(),
+            patterns
+                .iter()
+                .map(|pattern| zonk_pattern(context, size, pattern))
+                .collect(),
+        )),
+    }
+}
+
+/// Apply a metavariable's solution to the arguments in its spine, reducing
+/// the lambdas that were introduced when solving it
+///
+/// The arguments are evaluated with the local binders standing for
+/// themselves, so that they are read back unchanged.
+fn instantiate(
+    context: &Context,
+    size: VarLevel,
+    solution: &RcTerm,
+    spine: &[(Plicity, RcTerm)],
+) -> Result<RcTerm, nbe::NbeError> {
+    let locals = (0..size.0)
+        .map(|level| RcValue::from(Value::var(VarLevel(level), 0)))
+        .collect::<Locals>();
+
+    let head = nbe::eval(context, &Locals::new(), solution)?;
+    let value = spine.iter().try_fold(head, |acc, &(plicity, ref arg)| {
+        let arg = nbe::eval(context, &locals, arg)?;
+        nbe::apply(context, &acc, plicity, arg)
+    })?;
+
+    nbe::quote(context, size, Unfold::Never, &value)
+}
+
+fn apply(head: RcTerm, spine: Vec<(Plicity, RcTerm)>) -> RcTerm {
+    spine.into_iter().fold(head, |acc, (plicity, arg)| {
+        RcTerm::from(Term::FunApp(acc, plicity, arg))
+    })
+}
@@ -5,84 +5,329 @@
 //! For more information, check out the theory appendix of the Pikelet book.
 
 use codespan::ByteSpan;
-use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
+use moniker::{Binder, Embed, FreeVar, Ignore, Scope, Var};
+use std::convert::TryFrom;
 
-use pikelet_core::nbe;
+use pikelet_core::nbe::{self, NbeError, Unfold};
 use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
-use pikelet_core::syntax::domain::{RcType, RcValue, Value};
-use pikelet_core::syntax::{Level, Literal};
+use pikelet_core::syntax::domain::{Closure, RcType, RcValue, RecordTypeClosure, Value};
+use pikelet_core::syntax::{
+    Label, Level, LevelShift, Literal, MetaVar, Name, Plicity, VarIndex, VarLevel,
+};
 
 use crate::syntax::raw;
 
 mod context;
+mod coverage;
 mod errors;
+mod meta;
+mod termination;
 
-pub use self::context::{Context, Globals};
-pub use self::errors::{InternalError, TypeError};
+pub use self::context::{Context, Globals, TermType};
+pub use self::errors::{InternalError, TypeError, TypeWarning};
+pub use self::meta::{HoleGoal, MetaEntry, MetaOrigin};
 
 /// Returns true if `ty1` is a subtype of `ty2`
-fn is_subtype(context: &Context, ty1: &RcType, ty2: &RcType) -> bool {
+///
+/// Any metavariables that we encounter along the way will be solved using
+/// unification.
+fn is_subtype(context: &Context, ty1: &RcType, ty2: &RcType) -> Result<bool, TypeError> {
+    if meta::is_same_definition(ty1, ty2) {
+        return Ok(true);
+    }
+
+    let ty1 = meta::force(context, ty1)?;
+    let ty2 = meta::force(context, ty2)?;
+
     match (&*ty1.inner, &*ty2.inner) {
         // ST-TYPE
-        (&Value::Universe(level1), &Value::Universe(level2)) => level1 <= level2,
+        (&Value::Universe(ref level1), &Value::Universe(ref level2)) => {
+            Ok(meta::solve_level_le(context, level1, level2))
+        },
 
         // ST-PI
-        (&Value::FunType(ref scope1), &Value::FunType(ref scope2)) => {
-            let ((_, Embed(ann1)), body1, (Binder(free_var2), Embed(ann2)), body2) =
-                Scope::unbind2(scope1.clone(), scope2.clone());
+        (
+            &Value::FunType(plicity1, ref ann1, ref closure1),
+            &Value::FunType(plicity2, ref ann2, ref closure2),
+        ) if plicity1 == plicity2 => Ok(is_subtype(context, ann2, ann1)? && {
+            let mut context = context.clone();
+            let var = context.push_local(closure2.name().clone(), ann2.clone());
+            let body1 = closure1.apply(&context, var.clone())?;
+            let body2 = closure2.apply(&context, var)?;
 
-            is_subtype(context, &ann2, &ann1) && {
-                let mut context = context.clone();
-                context.insert_declaration(free_var2, ann2);
-                is_subtype(&context, &body1, &body2)
-            }
-        },
+            is_subtype(&context, &body1, &body2)?
+        }),
 
         // ST-RECORD-TYPE, ST-EMPTY-RECORD-TYPE
-        (&Value::RecordType(ref scope1), &Value::RecordType(ref scope2)) => {
-            if scope1.unsafe_pattern.unsafe_patterns.len()
-                != scope2.unsafe_pattern.unsafe_patterns.len()
-            {
-                return false;
+        (&Value::RecordType(ref closure1), &Value::RecordType(ref closure2)) => {
+            if closure1.len() != closure2.len() {
+                return Ok(false);
             }
 
-            let (fields1, (), fields2, ()) = Scope::unbind2(scope1.clone(), scope2.clone());
+            let fields1 = closure1.fields(context, context.size())?;
+            let fields2 = closure2.fields(context, context.size())?;
 
             let mut context = context.clone();
-            for (field1, field2) in
-                Iterator::zip(fields1.unnest().into_iter(), fields2.unnest().into_iter())
+            for ((label1, name1, ty1), (label2, _, ty2)) in
+                Iterator::zip(fields1.into_iter(), fields2.into_iter())
             {
-                let (label1, Binder(free_var1), Embed(ty1)) = field1;
-                let (label2, _, Embed(ty2)) = field2;
-
-                if label1 == label2 && is_subtype(&context, &ty1, &ty2) {
-                    context.insert_declaration(free_var1, ty1);
+                if label1 == label2 && is_subtype(&context, &ty1, &ty2)? {
+                    context.push_local(name1, ty1);
                 } else {
-                    return false;
+                    return Ok(false);
                 }
             }
 
-            true
+            Ok(true)
         },
 
         // ST-ALPHA-EQ
-        (_, _) => RcType::term_eq(ty1, ty2),
+        (_, _) => meta::unify(context, &ty1, &ty2),
     }
 }
 
+/// Apply fresh metavariables to the leading implicit parameters of a term,
+/// returning the applied term and its type
+fn insert_implicit_args(
+    context: &Context,
+    span: ByteSpan,
+    mut term: RcTerm,
+    mut ty: RcType,
+) -> Result<(RcTerm, RcType), TypeError> {
+    loop {
+        let forced_ty = meta::force(context, &ty)?;
+
+        match *forced_ty {
+            Value::FunType(Plicity::Implicit, ref ann, ref closure) => {
+                let name = closure.name().to_string();
+                let arg = meta::fresh_meta(context, MetaOrigin::ImplicitArg(span, name), ann);
+
+                ty = closure.apply(context, context.eval(&arg)?)?;
+                term = RcTerm::from(Term::FunApp(term, Plicity::Implicit, arg));
+            },
+            _ => return Ok((term, ty)),
+        }
+    }
+}
+
+/// Report the first of the metavariables created since `metas_start` that
+/// remains unsolved
+///
+/// The types of holes are only reported if the holes themselves were solved,
+/// seeing as the error for the hole will mention its type anyway.
+fn check_metas_solved(context: &Context, metas_start: usize) -> Result<(), TypeError> {
+    let unsolved = (metas_start..context.meta_count())
+        .map(|index| MetaVar(index as u32))
+        .filter(|&meta| context.get_meta_solution(meta).is_none())
+        .collect::<Vec<_>>();
+
+    let meta = unsolved
+        .iter()
+        .find(|&&meta| !matches!(context.get_meta(meta).origin, MetaOrigin::HoleType(_)))
+        .or_else(|| unsolved.first());
+
+    let meta = match meta {
+        Some(&meta) => meta,
+        None => return Ok(()),
+    };
+
+    Err(match context.get_meta(meta).origin {
+        MetaOrigin::Hole(_) | MetaOrigin::HoleType(_) => {
+            let goal = meta::hole_goal(context, meta)?;
+            TypeError::UnableToElaborateHole {
+                span: goal.span,
+                expected: Some(Box::new(goal.expected)),
+                locals: goal.locals,
+            }
+        },
+        MetaOrigin::ParamAnn(param_span, name) => TypeError::FunctionParamNeedsAnnotation {
+            param_span,
+            var_span: None,
+            name,
+        },
+        MetaOrigin::ImplicitArg(span, name) => TypeError::UnableToInferImplicitArg { span, name },
+    })
+}
+
+/// Describe the goals of the holes that were created since `metas_start`,
+/// regardless of whether they were solved or not
+///
+/// This is useful for interactive development, where holes are used to ask
+/// the type checker what it expects to find at a given location.
+pub fn hole_goals(context: &Context, metas_start: usize) -> Result<Vec<HoleGoal>, TypeError> {
+    (metas_start..context.meta_count())
+        .map(|index| MetaVar(index as u32))
+        .filter(|&meta| matches!(context.get_meta(meta).origin, MetaOrigin::Hole(_)))
+        .map(|meta| meta::hole_goal(context, meta))
+        .collect()
+}
+
 /// Ensures that the given term is a universe, returning the level of that
 /// universe and its elaborated form.
-fn infer_universe(context: &Context, raw_term: &raw::RcTerm) -> Result<(RcTerm, Level), TypeError> {
-    let (term, ty) = infer_term(context, raw_term)?;
+fn infer_universe(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Result<(RcTerm, Level<VarLevel>), TypeError> {
+    if let raw::Term::Hole(span) = *raw_term.inner {
+        let level = meta::fresh_level(context);
+        let ty = RcValue::from(Value::Universe(level.clone()));
+        return Ok((
+            meta::fresh_meta(context, MetaOrigin::Hole(span), &ty),
+            level,
+        ));
+    }
+
+    let (term, ty) = infer(context, raw_term)?;
+    let ty = meta::force(context, &ty)?;
     match *ty {
-        Value::Universe(level) => Ok((term, level)),
-        _ => Err(TypeError::ExpectedUniverse {
-            span: raw_term.span(),
-            found: Box::new(context.resugar(&ty)),
+        Value::Universe(ref level) => return Ok((term, level.clone())),
+        Value::Error => return Ok((term, Level::from(0))),
+        // The type might not be known yet, in which case it can be solved
+        // with a universe of some level
+        _ if ty.meta_app().is_some() => {
+            let level = meta::fresh_level(context);
+            let universe = RcValue::from(Value::Universe(level.clone()));
+            if meta::unify(context, &ty, &universe)? {
+                return Ok((term, level));
+            }
+        },
+        _ => {},
+    }
+
+    Err(TypeError::ExpectedUniverse {
+        span: raw_term.span(),
+        found: Box::new(context.resugar_value(&ty)),
+    })
+}
+
+/// Checks that a term is a universe level, returning the level that it
+/// evaluates to
+///
+/// Level expressions must reduce to a combination of constants, level
+/// variables, shifts, and `level-max`, so that we can compare them when
+/// checking universes.
+fn check_level(context: &Context, raw_level: &raw::RcTerm) -> Result<Level<VarLevel>, TypeError> {
+    let level = check(context, raw_level, context.level())?;
+    let value = meta::force(context, &context.eval(&level)?)?;
+
+    match value.as_level() {
+        Some(level) => Ok(level),
+        None if *value.inner == Value::Error => Ok(Level::from(0)),
+        None => Err(TypeError::UnreducibleLevel {
+            span: raw_level.span(),
+            found: Box::new(context.resugar_value(&value)),
         }),
     }
 }
 
+/// The type of the universe at the given level
+fn universe_ty(
+    context: &Context,
+    span: ByteSpan,
+    level: &Level<VarLevel>,
+) -> Result<RcType, TypeError> {
+    match level.succ() {
+        Some(level) => Ok(RcValue::from(Value::Universe(level))),
+        None => Err(TypeError::LevelOverflow {
+            span,
+            level: context.quote_level(level),
+        }),
+    }
+}
+
+/// Build the type of a definition from the annotations on its parameters and
+/// on its body, using holes where annotations have been omitted
+fn raw_definition_ty(raw_term: &raw::RcTerm) -> raw::RcTerm {
+    match *raw_term.inner {
+        raw::Term::Ann(ref raw_term, ref raw_ty) => match *raw_ty.inner {
+            raw::Term::Hole(_) => raw_definition_ty(raw_term),
+            _ => raw_ty.clone(),
+        },
+        raw::Term::FunIntro(span, plicity, ref scope) => {
+            let ((binder, Embed(raw_ann)), raw_body) = scope.clone().unbind();
+            let raw_body_ty = raw_definition_ty(&raw_body);
+
+            raw::RcTerm::from(raw::Term::FunType(
+                span,
+                plicity,
+                Scope::new((binder, Embed(raw_ann)), raw_body_ty),
+            ))
+        },
+        _ => raw::RcTerm::from(raw::Term::Hole(raw_term.span())),
+    }
+}
+
+/// Elaborate a definition that refers to itself, returning a fixpoint that
+/// has been checked for termination
+fn infer_fix(
+    context: &Context,
+    span: ByteSpan,
+    free_var: &FreeVar<String>,
+    raw_term: &raw::RcTerm,
+) -> Result<(RcTerm, RcType), TypeError> {
+    let (ty, _) = infer_universe(context, &raw_definition_ty(raw_term))?;
+    let value_ty = context.eval(&ty)?;
+
+    let term = {
+        let mut context = context.clone();
+        context.insert_local(free_var.clone(), value_ty.clone());
+        meta::zonk_term(&context, &check(&context, raw_term, &value_ty)?)
+    };
+
+    match termination::decreasing_param(&term) {
+        Some(index) => Ok((
+            RcTerm::from(Term::Fix(
+                index,
+                Name::from(free_var.pretty_name.clone()),
+                ty,
+                term,
+            )),
+            value_ty,
+        )),
+        None => Err(TypeError::NonTerminatingRecursion {
+            span,
+            name: free_var.pretty_name.clone().unwrap_or_default(),
+        }),
+    }
+}
+
+/// Stand in for a definition that failed to elaborate
+///
+/// The definition becomes an error, but if it was given a type annotation we
+/// keep hold of its type so that uses of the definition can still be checked
+/// against it. Otherwise the type becomes an error as well.
+fn recover_definition(context: &Context, raw_term: &raw::RcTerm) -> (RcTerm, RcType) {
+    let error = (RcTerm::from(Term::Error), RcValue::from(Value::Error));
+    let raw_ty = match *raw_term.inner {
+        raw::Term::Fix(_, ref raw_scope) => {
+            return recover_definition(context, &raw_scope.clone().unbind().1);
+        },
+        _ => raw_definition_ty(raw_term),
+    };
+
+    if !is_fully_annotated(&raw_ty) {
+        return error;
+    }
+
+    match infer_universe(context, &raw_ty).and_then(|(ty, _)| Ok(context.eval(&ty)?)) {
+        Ok(ty) => (RcTerm::from(Term::Error), ty),
+        Err(_) => error,
+    }
+}
+
+/// Returns `true` if none of the annotations in the type of a definition were
+/// omitted, as returned by `raw_definition_ty`
+fn is_fully_annotated(raw_ty: &raw::RcTerm) -> bool {
+    match *raw_ty.inner {
+        raw::Term::Hole(_) => false,
+        raw::Term::FunType(_, _, ref scope) => {
+            let ((_, Embed(raw_ann)), raw_body) = scope.clone().unbind();
+            is_fully_annotated(&raw_ann) && is_fully_annotated(&raw_body)
+        },
+        _ => true,
+    }
+}
+
 /// Checks that a literal is compatible with the given type, returning the
 /// elaborated literal if successful
 fn check_literal(
@@ -90,30 +335,85 @@ fn check_literal(
     raw_literal: &raw::Literal,
     expected_ty: &RcType,
 ) -> Result<Literal, TypeError> {
+    let out_of_range = |min, max| TypeError::LiteralOutOfRange {
+        literal_span: raw_literal.span(),
+        found: raw_literal.clone(),
+        expected: Box::new(context.resugar_value(expected_ty)),
+        min,
+        max,
+    };
+
+    // Any literal is accepted in place of an error, so we pick the widest
+    // type that it could have
+    if let Value::Error = *expected_ty.inner {
+        return Ok(match *raw_literal {
+            raw::Literal::String(_, ref val) => Literal::String(val.clone()),
+            raw::Literal::Char(_, val) => Literal::Char(val),
+            raw::Literal::Int(_, val, _) => match (u64::try_from(val), i64::try_from(val)) {
+                (Ok(val), _) => Literal::U64(val),
+                (Err(_), Ok(val)) => Literal::S64(val),
+                (Err(_), Err(_)) => {
+                    return Err(out_of_range(Literal::S64(i64::MIN), Literal::U64(u64::MAX)));
+                },
+            },
+            raw::Literal::Float(_, val, _) => Literal::F64(val),
+        });
+    }
+
     match *raw_literal {
         raw::Literal::String(_, ref val) if context.string() == expected_ty => {
             Ok(Literal::String(val.clone()))
         },
         raw::Literal::Char(_, val) if context.char() == expected_ty => Ok(Literal::Char(val)),
 
-        // FIXME: overflow?
-        raw::Literal::Int(_, v, _) if context.u8() == expected_ty => Ok(Literal::U8(v as u8)),
-        raw::Literal::Int(_, v, _) if context.u16() == expected_ty => Ok(Literal::U16(v as u16)),
-        raw::Literal::Int(_, v, _) if context.u32() == expected_ty => Ok(Literal::U32(v as u32)),
-        raw::Literal::Int(_, v, _) if context.u64() == expected_ty => Ok(Literal::U64(v)),
-        raw::Literal::Int(_, v, _) if context.s8() == expected_ty => Ok(Literal::S8(v as i8)),
-        raw::Literal::Int(_, v, _) if context.s16() == expected_ty => Ok(Literal::S16(v as i16)),
-        raw::Literal::Int(_, v, _) if context.s32() == expected_ty => Ok(Literal::S32(v as i32)),
-        raw::Literal::Int(_, v, _) if context.s64() == expected_ty => Ok(Literal::S64(v as i64)),
+        raw::Literal::Int(_, v, _) if context.u8() == expected_ty => u8::try_from(v)
+            .map(Literal::U8)
+            .map_err(|_| out_of_range(Literal::U8(u8::MIN), Literal::U8(u8::MAX))),
+        raw::Literal::Int(_, v, _) if context.u16() == expected_ty => u16::try_from(v)
+            .map(Literal::U16)
+            .map_err(|_| out_of_range(Literal::U16(u16::MIN), Literal::U16(u16::MAX))),
+        raw::Literal::Int(_, v, _) if context.u32() == expected_ty => u32::try_from(v)
+            .map(Literal::U32)
+            .map_err(|_| out_of_range(Literal::U32(u32::MIN), Literal::U32(u32::MAX))),
+        raw::Literal::Int(_, v, _) if context.u64() == expected_ty => u64::try_from(v)
+            .map(Literal::U64)
+            .map_err(|_| out_of_range(Literal::U64(u64::MIN), Literal::U64(u64::MAX))),
+        raw::Literal::Int(_, v, _) if context.s8() == expected_ty => i8::try_from(v)
+            .map(Literal::S8)
+            .map_err(|_| out_of_range(Literal::S8(i8::MIN), Literal::S8(i8::MAX))),
+        raw::Literal::Int(_, v, _) if context.s16() == expected_ty => i16::try_from(v)
+            .map(Literal::S16)
+            .map_err(|_| out_of_range(Literal::S16(i16::MIN), Literal::S16(i16::MAX))),
+        raw::Literal::Int(_, v, _) if context.s32() == expected_ty => i32::try_from(v)
+            .map(Literal::S32)
+            .map_err(|_| out_of_range(Literal::S32(i32::MIN), Literal::S32(i32::MAX))),
+        raw::Literal::Int(_, v, _) if context.s64() == expected_ty => i64::try_from(v)
+            .map(Literal::S64)
+            .map_err(|_| out_of_range(Literal::S64(i64::MIN), Literal::S64(i64::MAX))),
+
+        // Integers may lose precision when they are converted to floats, but
+        // they never fall out of range
         raw::Literal::Int(_, v, _) if context.f32() == expected_ty => Ok(Literal::F32(v as f32)),
         raw::Literal::Int(_, v, _) if context.f64() == expected_ty => Ok(Literal::F64(v as f64)),
-        raw::Literal::Float(_, v, _) if context.f32() == expected_ty => Ok(Literal::F32(v as f32)),
-        raw::Literal::Float(_, v, _) if context.f64() == expected_ty => Ok(Literal::F64(v)),
+        raw::Literal::Float(_, v, _) if context.f32() == expected_ty => {
+            if v.abs() > f64::from(f32::MAX) || !v.is_finite() {
+                Err(out_of_range(Literal::F32(f32::MIN), Literal::F32(f32::MAX)))
+            } else {
+                Ok(Literal::F32(v as f32))
+            }
+        },
+        raw::Literal::Float(_, v, _) if context.f64() == expected_ty => {
+            if v.is_finite() {
+                Ok(Literal::F64(v))
+            } else {
+                Err(out_of_range(Literal::F64(f64::MIN), Literal::F64(f64::MAX)))
+            }
+        },
 
         _ => Err(TypeError::LiteralMismatch {
             literal_span: raw_literal.span(),
             found: raw_literal.clone(),
-            expected: Box::new(context.resugar(expected_ty)),
+            expected: Box::new(context.resugar_value(expected_ty)),
         }),
     }
 }
@@ -134,6 +434,161 @@ fn infer_literal(
     }
 }
 
+/// Look up the argument types of a constructor of a variant type, replacing
+/// the recursive occurrences of the variant type with the type itself
+fn variant_arm_tys(
+    context: &Context,
+    ty: &RcType,
+    label: &Label,
+) -> Result<Option<Vec<RcType>>, TypeError> {
+    if let Value::VariantType(ref closure) = *ty.inner {
+        for (arm_label, arg_tys) in closure.instantiate(context, ty.clone())? {
+            if arm_label == *label {
+                return Ok(Some(arg_tys));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns `true` if the recursive occurrences of a variant type in the type
+/// of a constructor argument are all strictly positive
+///
+/// Allowing the type to appear to the left of a function arrow would let us
+/// write non-terminating programs without any explicit recursion.
+fn is_strictly_positive(index: VarIndex, ty: &RcTerm) -> bool {
+    if !ty.mentions(index) {
+        return true;
+    }
+
+    match *ty.inner {
+        Term::FunType(_, _, ref ann, ref body) => {
+            !ann.mentions(index) && is_strictly_positive(VarIndex(index.0 + 1), body)
+        },
+        Term::RecordType(ref fields) => {
+            (fields.iter().enumerate()).all(|(offset, &(_, _, ref ann))| {
+                is_strictly_positive(VarIndex(index.0 + offset), ann)
+            })
+        },
+        Term::VariantType(_, ref arms) => arms.iter().all(|&(_, ref args)| {
+            args.iter()
+                .all(|arg| is_strictly_positive(VarIndex(index.0 + 1), arg))
+        }),
+        Term::Var(var, _) => var == index,
+        _ => false,
+    }
+}
+
+/// Bring a type out from under the local binders of `inner_context`, so that
+/// it can be used in `context`
+///
+/// Definitions are unfolded along the way, so this only fails if the type
+/// depends on one of the parameters bound in `inner_context`.
+fn escape_ty(
+    context: &Context,
+    inner_context: &Context,
+    ty: &RcType,
+) -> Result<Option<RcType>, TypeError> {
+    let binders = inner_context.size().0 - context.size().0;
+    let ty = inner_context.quote(ty)?;
+
+    match ty.rename(&|index| index.0.checked_sub(binders).map(VarIndex)) {
+        Some(ty) => Ok(Some(context.eval(&ty)?)),
+        None => Ok(None),
+    }
+}
+
+/// Bring the type of the body of a case clause out from under the variables
+/// bound by its pattern, so that it can be used as the type of the whole case
+/// expression
+fn clause_body_ty(
+    context: &Context,
+    body_context: &Context,
+    span: ByteSpan,
+    body_ty: &RcType,
+) -> Result<RcType, TypeError> {
+    match escape_ty(context, body_context, body_ty)? {
+        Some(body_ty) => Ok(body_ty),
+        None => Err(TypeError::Internal(InternalError::Unimplemented {
+            span: Some(span),
+            message: "inferring case bodies whose types depend on their patterns".to_owned(),
+        })),
+    }
+}
+
+/// Interpret a term as a constructor applied to some arguments, if it is an
+/// application of a name that has not been defined
+fn raw_variant_intro(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Option<(ByteSpan, Label, Vec<raw::RcTerm>)> {
+    let mut head = raw_term;
+    let mut raw_args = Vec::new();
+    while let raw::Term::FunApp(ref next_head, Plicity::Explicit, ref raw_arg) = *head.inner {
+        raw_args.push(raw_arg.clone());
+        head = next_head;
+    }
+    raw_args.reverse();
+
+    match *head.inner {
+        raw::Term::Var(span, Var::Free(ref free_var), LevelShift(0))
+            if context.lookup_local(free_var).is_none()
+                && context.lookup_global(free_var).is_none() =>
+        {
+            let name = free_var.pretty_name.clone()?;
+            Some((span, Label(name), raw_args))
+        },
+        _ => None,
+    }
+}
+
+/// Checks that a constructor pattern is compatible with the given variant
+/// type, returning the elaborated pattern and a vector of the declarations it
+/// introduced if successful
+fn check_variant_pattern(
+    context: &Context,
+    span: ByteSpan,
+    label: &Label,
+    raw_patterns: &[raw::RcPattern],
+    expected_ty: &RcType,
+) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError> {
+    let arg_tys = match variant_arm_tys(context, expected_ty, label)? {
+        Some(arg_tys) => arg_tys,
+        None if *expected_ty.inner == Value::Error => vec![expected_ty.clone(); raw_patterns.len()],
+        None => {
+            return Err(TypeError::NoConstructorInType {
+                span,
+                expected_label: label.clone(),
+                found: Box::new(context.resugar_value(expected_ty)),
+            });
+        },
+    };
+
+    if arg_tys.len() != raw_patterns.len() {
+        return Err(TypeError::ConstructorArgCountMismatch {
+            span,
+            label: label.clone(),
+            found_count: raw_patterns.len() as u64,
+            expected_count: arg_tys.len() as u64,
+        });
+    }
+
+    let mut declarations = Vec::new();
+    let patterns = <_>::zip(raw_patterns.iter(), arg_tys.iter())
+        .map(|(raw_pattern, arg_ty)| {
+            let (pattern, arg_declarations) = check_pattern(context, raw_pattern, arg_ty)?;
+            declarations.extend(arg_declarations);
+            Ok(pattern)
+        })
+        .collect::<Result<_, TypeError>>()?;
+
+    Ok((
+        RcPattern::from(Pattern::VariantIntro(label.clone(), patterns)),
+        declarations,
+    ))
+}
+
 /// Checks that a pattern is compatible with the given type, returning the
 /// elaborated pattern and a vector of the declarations it introduced if successful
 pub fn check_pattern(
@@ -141,10 +596,25 @@ pub fn check_pattern(
     raw_pattern: &raw::RcPattern,
     expected_ty: &RcType,
 ) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError> {
+    let expected_ty = &meta::force(context, expected_ty)?;
+
     match (&*raw_pattern.inner, &*expected_ty.inner) {
+        // Binders that share their name with a constructor match that
+        // constructor, rather than binding a new variable
+        (&raw::Pattern::Binder(span, Binder(ref free_var)), &Value::VariantType(ref closure))
+            if closure
+                .arms()
+                .any(|(label, _)| free_var.pretty_name.as_ref() == Some(&label.0)) =>
+        {
+            let label = Label(free_var.pretty_name.clone().unwrap_or_default());
+            return check_variant_pattern(context, span, &label, &[], expected_ty);
+        },
+        (&raw::Pattern::VariantIntro(span, ref label, ref raw_patterns), _) => {
+            return check_variant_pattern(context, span, label, raw_patterns, expected_ty);
+        },
         (&raw::Pattern::Binder(_, Binder(ref free_var)), _) => {
             return Ok((
-                RcPattern::from(Pattern::Binder(Binder(free_var.clone()))),
+                RcPattern::from(Pattern::Binder(Name::from(free_var.pretty_name.clone()))),
                 vec![(free_var.clone(), expected_ty.clone())],
             ));
         },
@@ -152,17 +622,18 @@ pub fn check_pattern(
             let literal = check_literal(context, raw_literal, expected_ty)?;
             return Ok((RcPattern::from(Pattern::Literal(literal)), vec![]));
         },
+        (&raw::Pattern::Error(_), _) => return Ok((RcPattern::from(Pattern::Error), vec![])),
         _ => {},
     }
 
     let (pattern, inferred_ty, declarations) = infer_pattern(context, raw_pattern)?;
-    if is_subtype(context, &inferred_ty, expected_ty) {
+    if is_subtype(context, &inferred_ty, expected_ty)? {
         Ok((pattern, declarations))
     } else {
         Err(TypeError::Mismatch {
             span: raw_pattern.span(),
-            found: Box::new(context.resugar(&inferred_ty)),
-            expected: Box::new(context.resugar(expected_ty)),
+            found: Box::new(context.resugar_value(&inferred_ty)),
+            expected: Box::new(context.resugar_value(expected_ty)),
         })
     }
 }
@@ -176,11 +647,11 @@ pub fn infer_pattern(
     match *raw_pattern.inner {
         raw::Pattern::Ann(ref raw_pattern, Embed(ref raw_ty)) => {
             let (ty, _) = infer_universe(context, raw_ty)?;
-            let value_ty = nbe::nf_term(context, &ty)?;
+            let value_ty = context.eval(&ty)?;
             let (pattern, declarations) = check_pattern(context, raw_pattern, &value_ty)?;
 
             Ok((
-                RcPattern::from(Pattern::Ann(pattern, Embed(ty))),
+                RcPattern::from(Pattern::Ann(pattern, ty)),
                 value_ty,
                 declarations,
             ))
@@ -190,18 +661,22 @@ pub fn infer_pattern(
             binder: binder.clone(),
         }),
         raw::Pattern::Var(span, Embed(ref var), shift) => match *var {
-            Var::Free(ref free_var) => match context.get_declaration(free_var) {
-                Some(ty) => {
-                    let mut ty = ty.clone();
-                    ty.shift_universes(shift);
-                    let pattern = RcPattern::from(Pattern::Var(Embed(var.clone()), shift));
+            Var::Free(ref free_var) => {
+                let (pattern, ty) = if let Some((index, ty)) = context.lookup_local(free_var) {
+                    (Pattern::Var(index, shift), ty)
+                } else if let Some((name, ty)) = context.lookup_global(free_var) {
+                    (Pattern::Global(name.to_owned(), shift), ty)
+                } else {
+                    return Err(TypeError::UndefinedName {
+                        span,
+                        free_var: free_var.clone(),
+                    });
+                };
 
-                    Ok((pattern, ty, vec![]))
-                },
-                None => Err(TypeError::UndefinedName {
-                    span,
-                    free_var: free_var.clone(),
-                }),
+                let mut ty = ty.clone();
+                ty.shift_universes(shift);
+
+                Ok((RcPattern::from(pattern), ty, vec![]))
             },
 
             // We should always be substituting bound variables with fresh
@@ -217,129 +692,389 @@ pub fn infer_pattern(
             let (literal, ty) = infer_literal(context, literal)?;
             Ok((RcPattern::from(Pattern::Literal(literal)), ty, vec![]))
         },
+        raw::Pattern::VariantIntro(span, ref label, _) => {
+            Err(TypeError::AmbiguousConstructorPattern {
+                span,
+                label: label.clone(),
+            })
+        },
+        raw::Pattern::Error(_) => Ok((
+            RcPattern::from(Pattern::Error),
+            RcValue::from(Value::Error),
+            vec![],
+        )),
     }
 }
 
 /// Checks that a term is compatible with the given type, returning the
 /// elaborated term if successful
+///
+/// The whole term is elaborated, but only the first error that was found is
+/// returned. Use `check_term_recovering` to get all of them.
 pub fn check_term(
     context: &Context,
     raw_term: &raw::RcTerm,
     expected_ty: &RcType,
 ) -> Result<RcTerm, TypeError> {
+    let (term, errors) = check_term_recovering(context, raw_term, expected_ty);
+    match errors.into_iter().next() {
+        None => Ok(term),
+        Some(error) => Err(error),
+    }
+}
+
+/// Synthesize the type of a term, returning the elaborated term and the
+/// inferred type if successful
+///
+/// The whole term is elaborated, but only the first error that was found is
+/// returned. Use `infer_term_recovering` to get all of them.
+pub fn infer_term(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Result<(RcTerm, RcType), TypeError> {
+    let (term, ty, errors) = infer_term_recovering(context, raw_term);
+    match errors.into_iter().next() {
+        None => Ok((term, ty)),
+        Some(error) => Err(error),
+    }
+}
+
+/// Checks that a term is compatible with the given type, carrying on past
+/// the items and record fields that fail to elaborate
+///
+/// Returns the elaborated term, with the parts that failed replaced by
+/// errors, along with the errors that were found in the order that they
+/// were found.
+pub fn check_term_recovering(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+    expected_ty: &RcType,
+) -> (RcTerm, Vec<TypeError>) {
+    let errors_start = context.error_count();
+    let metas_start = context.meta_count();
+    let level_metas_start = context.level_meta_count();
+    let term = check(context, raw_term, expected_ty).unwrap_or_else(|error| {
+        context.insert_error(error);
+        RcTerm::from(Term::Error)
+    });
+
+    let term = finish_term(context, errors_start, metas_start, level_metas_start, term);
+
+    (term, context.take_errors(errors_start))
+}
+
+/// Synthesize the type of a term, carrying on past the items and record
+/// fields that fail to elaborate
+///
+/// Returns the elaborated term and its type, with the parts that failed
+/// replaced by errors, along with the errors that were found in the order
+/// that they were found.
+pub fn infer_term_recovering(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> (RcTerm, RcType, Vec<TypeError>) {
+    let errors_start = context.error_count();
+    let metas_start = context.meta_count();
+    let level_metas_start = context.level_meta_count();
+    let (term, ty) = infer(context, raw_term).unwrap_or_else(|error| {
+        context.insert_error(error);
+        (RcTerm::from(Term::Error), RcValue::from(Value::Error))
+    });
+
+    let term = finish_term(context, errors_start, metas_start, level_metas_start, term);
+    let has_metas =
+        context.meta_count() > metas_start || context.level_meta_count() > level_metas_start;
+    let ty = if has_metas {
+        let ty = context.quote(&ty).map(|ty| meta::zonk_term(context, &ty));
+        ty.and_then(|ty| context.eval(&ty)).unwrap_or_else(|error| {
+            context.insert_error(TypeError::from(error));
+            RcValue::from(Value::Error)
+        })
+    } else {
+        ty
+    };
+
+    (term, ty, context.take_errors(errors_start))
+}
+
+/// Fill in the solutions of the metavariables that were created while
+/// elaborating a term
+///
+/// Unsolved metavariables are only reported if nothing else went wrong, seeing
+/// as they are often left over from the parts of the term that failed.
+fn finish_term(
+    context: &Context,
+    errors_start: usize,
+    metas_start: usize,
+    level_metas_start: usize,
+    term: RcTerm,
+) -> RcTerm {
+    if context.meta_count() == metas_start && context.level_meta_count() == level_metas_start {
+        return term;
+    }
+
+    if context.error_count() == errors_start {
+        if let Err(error) = check_metas_solved(context, metas_start) {
+            context.insert_error(error);
+        }
+    }
+
+    meta::zonk_term(context, &term)
+}
+
+/// Check a term against a type, recording the type for editor tooling
+fn check(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+    expected_ty: &RcType,
+) -> Result<RcTerm, TypeError> {
+    let term = check_inner(context, raw_term, expected_ty)?;
+    context.insert_term_type(raw_term.span(), expected_ty);
+    Ok(term)
+}
+
+fn check_inner(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+    expected_ty: &RcType,
+) -> Result<RcTerm, TypeError> {
+    // Keep the original type around, so that the definitions that it refers
+    // to can be compared and displayed without unfolding them
+    let original_ty = expected_ty;
+    let expected_ty = &meta::force(context, expected_ty)?;
+    let expected_is_meta = expected_ty.meta_app().is_some();
+
     match (&*raw_term.inner, &*expected_ty.inner) {
-        (&raw::Term::Literal(ref raw_literal), _) => {
+        (&raw::Term::Error(_), _) => return Ok(RcTerm::from(Term::Error)),
+
+        (&raw::Term::Literal(ref raw_literal @ raw::Literal::Int(_, value, _)), _)
+            if context.level() == expected_ty =>
+        {
+            return match u32::try_from(value) {
+                Ok(value) => Ok(RcTerm::from(Term::Level(Level::from(value)))),
+                Err(_) => Err(TypeError::LiteralOutOfRange {
+                    literal_span: raw_literal.span(),
+                    found: raw_literal.clone(),
+                    expected: Box::new(context.resugar_value(expected_ty)),
+                    min: Literal::U32(u32::MIN),
+                    max: Literal::U32(u32::MAX),
+                }),
+            };
+        },
+
+        (&raw::Term::Literal(ref raw_literal), _) if !expected_is_meta => {
             let literal = check_literal(context, raw_literal, expected_ty)?;
             return Ok(RcTerm::from(Term::Literal(literal)));
         },
 
         // C-LAM
-        (&raw::Term::FunIntro(_, ref fun_scope), &Value::FunType(ref fun_ty_scope)) => {
-            let (
-                (fun_name, Embed(fun_ann)),
-                fun_body,
-                (Binder(fun_ty_name), Embed(fun_ty_ann)),
-                fun_ty_body,
-            ) = Scope::unbind2(fun_scope.clone(), fun_ty_scope.clone());
-
+        (
+            &raw::Term::FunIntro(_, fun_plicity, ref fun_scope),
+            &Value::FunType(fun_ty_plicity, ref fun_ty_ann, ref fun_ty_closure),
+        ) if fun_plicity == fun_ty_plicity => {
             // Elaborate the hole, if it exists
-            if let raw::Term::Hole(_) = *fun_ann.inner {
-                let fun_ann = RcTerm::from(Term::from(&*fun_ty_ann));
+            let (_, Embed(ref raw_ann)) = fun_scope.unsafe_pattern;
+            if let raw::Term::Hole(_) = *raw_ann.inner {
+                let ((Binder(free_var), _), fun_body) = fun_scope.clone().unbind();
+                let var = RcValue::from(Value::var(context.size(), 0));
+                let fun_ty_body = fun_ty_closure.apply(context, var)?;
+
+                let fun_ann = context.quote(fun_ty_ann)?;
+                let fun_name = Name::from(free_var.pretty_name.clone());
                 let fun_body = {
                     let mut body_context = context.clone();
-                    body_context.insert_declaration(fun_ty_name, fun_ty_ann);
-                    check_term(&body_context, &fun_body, &fun_ty_body)?
+                    body_context.insert_local(free_var, fun_ty_ann.clone());
+                    check(&body_context, &fun_body, &fun_ty_body)?
                 };
-                let fun_scope = Scope::new((fun_name, Embed(fun_ann)), fun_body);
 
-                return Ok(RcTerm::from(Term::FunIntro(fun_scope)));
+                return Ok(RcTerm::from(Term::FunIntro(
+                    fun_plicity,
+                    fun_name,
+                    fun_ann,
+                    fun_body,
+                )));
             }
 
             // TODO: We might want to optimise for this case, rather than
             // falling through to `infer` and unbinding again at I-LAM
         },
-        (&raw::Term::FunIntro(_, _), _) => {
+
+        // C-IMPLICIT-LAM
+        (_, &Value::FunType(Plicity::Implicit, ref fun_ty_ann, ref fun_ty_closure)) => {
+            match *raw_term.inner {
+                raw::Term::FunIntro(_, Plicity::Implicit, _) => {},
+                _ => {
+                    // Terms that already have an implicit function type can
+                    // be compared against the expected type as they are,
+                    // rather than being wrapped in a lambda and applied again
+                    let inferred = match *raw_term.inner {
+                        raw::Term::Var(..)
+                        | raw::Term::Import(..)
+                        | raw::Term::FunApp(..)
+                        | raw::Term::RecordProj(..)
+                        | raw::Term::Ann(..) => {
+                            let (term, inferred_ty) = infer(context, raw_term)?;
+                            match *meta::force(context, &inferred_ty)?.inner {
+                                Value::FunType(Plicity::Implicit, _, _) => {
+                                    return check_inferred(
+                                        context,
+                                        raw_term.span(),
+                                        term,
+                                        &inferred_ty,
+                                        original_ty,
+                                    );
+                                },
+                                _ => Some((term, inferred_ty)),
+                            }
+                        },
+                        _ => None,
+                    };
+
+                    let fun_name = fun_ty_closure.name().clone();
+                    let fun_ann = context.quote(fun_ty_ann)?;
+                    let fun_body = {
+                        let mut body_context = context.clone();
+                        let var = body_context.push_local(fun_name.clone(), fun_ty_ann.clone());
+                        let fun_ty_body = fun_ty_closure.apply(&body_context, var)?;
+                        match inferred {
+                            Some((term, inferred_ty)) => {
+                                let span = raw_term.span();
+                                let (term, inferred_ty) = insert_implicit_args(
+                                    &body_context,
+                                    span,
+                                    term.shift(1),
+                                    inferred_ty,
+                                )?;
+                                check_inferred(
+                                    &body_context,
+                                    span,
+                                    term,
+                                    &inferred_ty,
+                                    &fun_ty_body,
+                                )?
+                            },
+                            None => check(&body_context, raw_term, &fun_ty_body)?,
+                        }
+                    };
+
+                    return Ok(RcTerm::from(Term::FunIntro(
+                        Plicity::Implicit,
+                        fun_name,
+                        fun_ann,
+                        fun_body,
+                    )));
+                },
+            }
+        },
+
+        // Functions are checked against errors so that we can still report
+        // the errors in their bodies
+        (&raw::Term::FunIntro(_, plicity, ref raw_scope), &Value::Error) => {
+            let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
+            let (ann, ann_ty) = match *raw_ann.inner {
+                raw::Term::Hole(_) => (RcTerm::from(Term::Error), expected_ty.clone()),
+                _ => {
+                    let (ann, _) = infer_universe(context, &raw_ann)?;
+                    let ann_ty = context.eval(&ann)?;
+                    (ann, ann_ty)
+                },
+            };
+            let name = Name::from(free_var.pretty_name.clone());
+            let body = {
+                let mut body_context = context.clone();
+                body_context.insert_local(free_var, ann_ty);
+                check(&body_context, &raw_body, expected_ty)?
+            };
+
+            return Ok(RcTerm::from(Term::FunIntro(plicity, name, ann, body)));
+        },
+
+        (&raw::Term::FunIntro(_, _, _), &Value::FunType(_, _, _)) => {},
+        (&raw::Term::FunIntro(_, _, _), _) if !expected_is_meta => {
             return Err(TypeError::UnexpectedFunction {
                 span: raw_term.span(),
-                expected: Box::new(context.resugar(expected_ty)),
+                expected: Box::new(context.resugar_value(expected_ty)),
             });
         },
 
         // C-RECORD
-        (&raw::Term::RecordIntro(span, ref raw_fields), &Value::RecordType(ref raw_ty_scope)) => {
-            let (raw_ty_fields, ()) = {
-                let expected_size = raw_ty_scope.unsafe_pattern.binders().len();
-                if raw_fields.len() == raw_ty_scope.unsafe_pattern.binders().len() {
-                    raw_ty_scope.clone().unbind()
-                } else {
-                    return Err(TypeError::RecordSizeMismatch {
-                        span,
-                        found_size: raw_fields.len() as u64,
-                        expected_size: expected_size as u64,
-                    });
-                }
-            };
-
-            let raw_ty_fields = raw_ty_fields.unnest();
+        (&raw::Term::RecordIntro(span, ref raw_fields), &Value::RecordType(ref ty_closure)) => {
+            if raw_fields.len() != ty_closure.len() {
+                return Err(TypeError::RecordSizeMismatch {
+                    span,
+                    found_size: raw_fields.len() as u64,
+                    expected_size: ty_closure.len() as u64,
+                });
+            }
 
             // FIXME: Check that record is well-formed?
-            let fields = {
-                let mut mappings = Vec::with_capacity(raw_fields.len());
-                <_>::zip(raw_fields.iter(), raw_ty_fields.into_iter())
-                    .map(|(field, ty_field)| {
-                        let &(ref label, ref raw_expr) = field;
-                        let (ty_label, Binder(ty_free_var), Embed(ann)) = ty_field;
-
-                        if *label == ty_label {
-                            let ann = nbe::nf_term(context, &ann.substs(&mappings))?;
-                            let expr = check_term(context, &raw_expr, &ann)?;
-                            mappings.push((ty_free_var, expr.clone()));
-                            Ok((label.clone(), expr))
-                        } else {
-                            Err(TypeError::LabelMismatch {
-                                span,
-                                found: label.clone(),
-                                expected: ty_label,
-                            })
-                        }
+            let mut fields = Vec::with_capacity(raw_fields.len());
+            ty_closure.for_each_field(context, |ty_label, ann| {
+                let (ref label, ref raw_expr) = raw_fields[fields.len()];
+
+                if *label == ty_label {
+                    let expr = check(context, &raw_expr, &ann).unwrap_or_else(|error| {
+                        context.insert_error(error);
+                        RcTerm::from(Term::Error)
+                    });
+                    let value = context.eval(&expr)?;
+                    fields.push((label.clone(), expr));
+                    Ok(value)
+                } else {
+                    Err(TypeError::LabelMismatch {
+                        span,
+                        found: label.clone(),
+                        expected: ty_label,
                     })
-                    .collect::<Result<_, _>>()?
-            };
+                }
+            })?;
 
             return Ok(RcTerm::from(Term::RecordIntro(fields)));
         },
 
-        (&raw::Term::Case(_, ref raw_head, ref raw_clauses), _) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+        (&raw::Term::Case(span, ref raw_head, ref raw_clauses), _) => {
+            let (head, head_ty) = infer(context, raw_head)?;
+            let mut patterns = Vec::with_capacity(raw_clauses.len());
 
-            // TODO: ensure that patterns are exhaustive
             let clauses = raw_clauses
                 .iter()
                 .map(|raw_clause| {
                     let (raw_pattern, raw_body) = raw_clause.clone().unbind();
                     let (pattern, declarations) = check_pattern(context, &raw_pattern, &head_ty)?;
+                    patterns.push((raw_pattern.span(), pattern.clone()));
 
                     let body = {
                         let mut body_context = context.clone();
                         for (free_var, ty) in declarations {
-                            body_context.insert_declaration(free_var, ty);
+                            body_context.insert_local(free_var, ty);
                         }
-                        check_term(&body_context, &raw_body, expected_ty)?
+                        check(&body_context, &raw_body, expected_ty)?
                     };
 
-                    Ok(Scope::new(pattern, body))
+                    Ok((pattern, body))
                 })
                 .collect::<Result<_, TypeError>>()?;
 
+            coverage::check_case(context, span, &head_ty, &patterns)?;
+
             return Ok(RcTerm::from(Term::Case(head, clauses)));
         },
 
-        (&raw::Term::ArrayIntro(span, ref elems), _) => {
+        (&raw::Term::ArrayIntro(_, ref elems), &Value::Error) => {
+            let elems = elems
+                .iter()
+                .map(|elem| check(context, elem, expected_ty))
+                .collect::<Result<_, _>>()?;
+
+            return Ok(RcTerm::from(Term::ArrayIntro(elems)));
+        },
+
+        (&raw::Term::ArrayIntro(span, ref elems), _) if !expected_is_meta => {
             return match context.array(expected_ty) {
                 Some((len, elem_ty)) if len == elems.len() as u64 => {
                     let elems = elems
                         .iter()
-                        .map(|elem| check_term(context, elem, elem_ty))
+                        .map(|elem| check(context, elem, elem_ty))
                         .collect::<Result<_, _>>()?;
 
                     Ok(RcTerm::from(Term::ArrayIntro(elems)))
@@ -357,60 +1092,136 @@ pub fn check_term(
         },
 
         (&raw::Term::Hole(span), _) => {
-            let expected = Some(Box::new(context.resugar(expected_ty)));
-            return Err(TypeError::UnableToElaborateHole { span, expected });
+            return Ok(meta::fresh_meta(
+                context,
+                MetaOrigin::Hole(span),
+                expected_ty,
+            ));
+        },
+
+        // C-VARIANT
+        (_, &Value::VariantType(_)) => {
+            if let Some((span, label, raw_args)) = raw_variant_intro(context, raw_term) {
+                let arg_tys = match variant_arm_tys(context, expected_ty, &label)? {
+                    Some(arg_tys) => arg_tys,
+                    None => {
+                        return Err(TypeError::NoConstructorInType {
+                            span,
+                            expected_label: label,
+                            found: Box::new(context.resugar_value(expected_ty)),
+                        });
+                    },
+                };
+
+                if arg_tys.len() != raw_args.len() {
+                    return Err(TypeError::ConstructorArgCountMismatch {
+                        span: raw_term.span(),
+                        label,
+                        found_count: raw_args.len() as u64,
+                        expected_count: arg_tys.len() as u64,
+                    });
+                }
+
+                let args = <_>::zip(raw_args.iter(), arg_tys.iter())
+                    .map(|(raw_arg, arg_ty)| check(context, raw_arg, arg_ty))
+                    .collect::<Result<_, _>>()?;
+
+                return Ok(RcTerm::from(Term::VariantIntro(label, args)));
+            }
+        },
+
+        // Constructors are checked against errors so that we can still report
+        // the errors in their arguments
+        (_, &Value::Error) => {
+            if let Some((_, _, raw_args)) = raw_variant_intro(context, raw_term) {
+                for raw_arg in &raw_args {
+                    check(context, raw_arg, expected_ty)?;
+                }
+
+                return Ok(RcTerm::from(Term::Error));
+            }
         },
 
         _ => {},
     }
 
     // C-CONV
-    let (term, inferred_ty) = infer_term(context, raw_term)?;
-    if is_subtype(context, &inferred_ty, expected_ty) {
+    let (term, inferred_ty) = infer(context, raw_term)?;
+    let (term, inferred_ty) = match *raw_term.inner {
+        raw::Term::FunIntro(_, Plicity::Implicit, _) => (term, inferred_ty),
+        _ => insert_implicit_args(context, raw_term.span(), term, inferred_ty)?,
+    };
+
+    check_inferred(context, raw_term.span(), term, &inferred_ty, original_ty)
+}
+
+/// Check that the inferred type of an elaborated term is a subtype of the
+/// expected type, returning the term if successful
+fn check_inferred(
+    context: &Context,
+    span: ByteSpan,
+    term: RcTerm,
+    inferred_ty: &RcType,
+    expected_ty: &RcType,
+) -> Result<RcTerm, TypeError> {
+    if is_subtype(context, inferred_ty, expected_ty)? {
         Ok(term)
     } else {
         Err(TypeError::Mismatch {
-            span: raw_term.span(),
-            found: Box::new(context.resugar(&inferred_ty)),
-            expected: Box::new(context.resugar(expected_ty)),
+            span,
+            found: Box::new(context.resugar_value(inferred_ty)),
+            expected: Box::new(context.resugar_value(expected_ty)),
         })
     }
 }
 
-/// Synthesize the type of a term, returning the elaborated term and the
-/// inferred type if successful
-pub fn infer_term(
-    context: &Context,
-    raw_term: &raw::RcTerm,
-) -> Result<(RcTerm, RcType), TypeError> {
-    use std::cmp;
+/// Infer the type of a term, recording it for editor tooling
+fn infer(context: &Context, raw_term: &raw::RcTerm) -> Result<(RcTerm, RcType), TypeError> {
+    let (term, ty) = infer_inner(context, raw_term)?;
+    context.insert_term_type(raw_term.span(), &ty);
+    Ok((term, ty))
+}
 
+fn infer_inner(context: &Context, raw_term: &raw::RcTerm) -> Result<(RcTerm, RcType), TypeError> {
     match *raw_term.inner {
         //  I-ANN
         raw::Term::Ann(ref raw_term, ref raw_ty) => {
             if let raw::Term::Hole(_) = *raw_ty.inner {
-                let (term, value_ty) = infer_term(context, &raw_term)?;
-                let ty = RcTerm::from(&*value_ty);
+                let (term, value_ty) = infer(context, &raw_term)?;
+                let ty = context.quote(&value_ty)?;
 
                 Ok((RcTerm::from(Term::Ann(term, ty)), value_ty))
             } else {
                 let (ty, _) = infer_universe(context, &raw_ty)?;
-                let value_ty = nbe::nf_term(context, &ty)?;
-                let term = check_term(context, raw_term, &value_ty)?;
+                let value_ty = context.eval(&ty)?;
+                let term = check(context, raw_term, &value_ty)?;
 
                 Ok((RcTerm::from(Term::Ann(term, ty)), value_ty))
             }
         },
 
         // I-TYPE
-        raw::Term::Universe(_, level) => Ok((
-            RcTerm::from(Term::Universe(level)),
-            RcValue::from(Value::Universe(level.succ())),
+        raw::Term::Universe(span, ref level) => Ok((
+            RcTerm::from(Term::Universe(level.clone())),
+            universe_ty(context, span, &context.eval_level(level)?)?,
         )),
 
+        // I-TYPE-LEVEL
+        raw::Term::UniverseAt(span, ref raw_level) => {
+            let level = check_level(context, raw_level)?;
+
+            Ok((
+                RcTerm::from(Term::Universe(context.quote_level(&level))),
+                universe_ty(context, span, &level)?,
+            ))
+        },
+
         raw::Term::Hole(span) => {
-            let expected = None;
-            Err(TypeError::UnableToElaborateHole { span, expected })
+            let ty_ty = RcValue::from(Value::Universe(meta::fresh_level(context)));
+            let ty = meta::fresh_meta(context, MetaOrigin::HoleType(span), &ty_ty);
+            let ty = context.eval(&ty)?;
+
+            Ok((meta::fresh_meta(context, MetaOrigin::Hole(span), &ty), ty))
         },
 
         raw::Term::Literal(ref raw_literal) => {
@@ -420,17 +1231,22 @@ pub fn infer_term(
 
         // I-VAR
         raw::Term::Var(span, ref var, shift) => match *var {
-            Var::Free(ref free_var) => match context.get_declaration(free_var) {
-                Some(ty) => {
-                    let mut ty = ty.clone();
-                    ty.shift_universes(shift);
+            Var::Free(ref free_var) => {
+                let (term, ty) = if let Some((index, ty)) = context.lookup_local(free_var) {
+                    (Term::Var(index, shift), ty)
+                } else if let Some((name, ty)) = context.lookup_global(free_var) {
+                    (Term::Global(name.to_owned(), shift), ty)
+                } else {
+                    return Err(TypeError::UndefinedName {
+                        span,
+                        free_var: free_var.clone(),
+                    });
+                };
 
-                    Ok((RcTerm::from(Term::Var(var.clone(), shift)), ty))
-                },
-                None => Err(TypeError::UndefinedName {
-                    span,
-                    free_var: free_var.clone(),
-                }),
+                let mut ty = ty.clone();
+                ty.shift_universes(shift);
+
+                Ok((RcTerm::from(term), ty))
             },
 
             // We should always be substituting bound variables with fresh
@@ -452,52 +1268,66 @@ pub fn infer_term(
         },
 
         // I-PI
-        raw::Term::FunType(_, ref raw_scope) => {
+        raw::Term::FunType(_, plicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
 
+            let name = Name::from(free_var.pretty_name.clone());
             let (ann, ann_level) = infer_universe(context, &raw_ann)?;
             let (body, body_level) = {
-                let ann = nbe::nf_term(context, &ann)?;
+                let ann = context.eval(&ann)?;
                 let mut body_context = context.clone();
-                body_context.insert_declaration(free_var.clone(), ann);
+                body_context.insert_local(free_var, ann);
                 infer_universe(&body_context, &raw_body)?
             };
 
-            let param = (Binder(free_var), Embed(ann));
+            // Types that quantify over levels are bigger than any of the
+            // universes that they can be instantiated with
+            let level = if body_level.mentions(context.size()) {
+                Level::Omega
+            } else {
+                ann_level.max(&body_level)
+            };
 
             Ok((
-                RcTerm::from(Term::FunType(Scope::new(param, body))),
-                RcValue::from(Value::Universe(cmp::max(ann_level, body_level))),
+                RcTerm::from(Term::FunType(plicity, name, ann, body)),
+                RcValue::from(Value::Universe(level)),
             ))
         },
 
         // I-LAM
-        raw::Term::FunIntro(_, ref raw_scope) => {
+        raw::Term::FunIntro(_, plicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
 
-            // Check for holes before entering to ensure we get a nice error
-            if let raw::Term::Hole(_) = *raw_ann {
-                return Err(TypeError::FunctionParamNeedsAnnotation {
-                    param_span: ByteSpan::default(), // TODO: param.span(),
-                    var_span: None,
-                    name: free_var.clone(),
-                });
-            }
+            // Omitted annotations will hopefully be solved based on how the
+            // parameter is used in the body of the function
+            let fun_ann = match *raw_ann {
+                raw::Term::Hole(param_span) => {
+                    let origin = MetaOrigin::ParamAnn(param_span, free_var.clone());
+                    let ty_ty = RcValue::from(Value::Universe(meta::fresh_level(context)));
 
-            let (fun_ann, _) = infer_universe(context, &raw_ann)?;
-            let fun_ty_ann = nbe::nf_term(context, &fun_ann)?;
+                    meta::fresh_meta(context, origin, &ty_ty)
+                },
+                _ => infer_universe(context, &raw_ann)?.0,
+            };
+            let fun_name = Name::from(free_var.pretty_name.clone());
+            let fun_ty_ann = context.eval(&fun_ann)?;
             let (fun_body, fun_ty_body) = {
                 let mut body_context = context.clone();
-                body_context.insert_declaration(free_var.clone(), fun_ty_ann.clone());
-                infer_term(&body_context, &raw_body)?
+                body_context.insert_local(free_var, fun_ty_ann.clone());
+                let (fun_body, fun_ty_body) = infer(&body_context, &raw_body)?;
+                (fun_body, body_context.quote(&fun_ty_body)?)
             };
 
-            let fun_param = (Binder(free_var.clone()), Embed(fun_ann));
-            let fun_ty_param = (Binder(free_var.clone()), Embed(fun_ty_ann));
+            let fun_ty_closure = Closure::new(
+                context.values().clone(),
+                LevelShift(0),
+                fun_name.clone(),
+                fun_ty_body,
+            );
 
             Ok((
-                RcTerm::from(Term::FunIntro(Scope::new(fun_param, fun_body))),
-                RcValue::from(Value::FunType(Scope::new(fun_ty_param, fun_ty_body))),
+                RcTerm::from(Term::FunIntro(plicity, fun_name, fun_ann, fun_body)),
+                RcValue::from(Value::FunType(plicity, fun_ty_ann, fun_ty_closure)),
             ))
         },
 
@@ -506,22 +1336,32 @@ pub fn infer_term(
             let (raw_fields, raw_body) = raw_scope.clone().unbind();
 
             let (term, ty) = {
+                let outer_context = context;
                 let mut context = context.clone();
                 let bindings = raw_fields
                     .unnest()
                     .into_iter()
-                    .map(|(Binder(free_var), Embed(raw_term))| {
-                        let (term, term_ty) = infer_term(&context, &raw_term)?;
+                    .map(|(Binder(free_var), Embed(raw_term), Ignore(doc))| {
+                        let (term, term_ty) = infer(&context, &raw_term).unwrap_or_else(|error| {
+                            context.insert_error(error);
+                            recover_definition(&context, &raw_term)
+                        });
 
-                        context.insert_definition(free_var.clone(), term.clone());
-                        context.insert_declaration(free_var.clone(), term_ty);
+                        let name = Name::from(free_var.pretty_name.clone());
+                        context.insert_local_definition(free_var, term_ty, term.clone());
 
-                        Ok((Binder(free_var), Embed(term)))
+                        (name, term, doc)
                     })
-                    .collect::<Result<_, TypeError>>()?;
+                    .collect();
 
-                let (body, ty) = infer_term(&context, &raw_body)?;
-                let term = RcTerm::from(Term::Let(Scope::new(Nest::new(bindings), body)));
+                let (body, ty) = infer(&context, &raw_body)?;
+                let term = RcTerm::from(Term::Let(bindings, body));
+
+                // Let bindings don't bind any parameters, so the type can
+                // always be brought out of their scope
+                let ty = escape_ty(outer_context, &context, &ty)?.ok_or_else(|| {
+                    NbeError::new("the type of a let body depended on its bindings")
+                })?;
 
                 (term, ty)
             };
@@ -529,23 +1369,44 @@ pub fn infer_term(
             Ok((term, ty))
         },
 
+        // I-FIX
+        raw::Term::Fix(span, ref raw_scope) => {
+            let (Binder(free_var), raw_term) = raw_scope.clone().unbind();
+            infer_fix(context, span, &free_var, &raw_term)
+        },
+
         // I-APP
-        raw::Term::FunApp(ref raw_head, ref raw_arg) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+        raw::Term::FunApp(ref raw_head, plicity, ref raw_arg) => {
+            let (head, head_ty) = infer(context, raw_head)?;
+            let (head, head_ty) = match plicity {
+                Plicity::Explicit => insert_implicit_args(context, raw_head.span(), head, head_ty)?,
+                Plicity::Implicit => (head, head_ty),
+            };
+            let head_ty = meta::force(context, &head_ty)?;
 
             match *head_ty {
-                Value::FunType(ref scope) => {
-                    let ((Binder(free_var), Embed(ann)), body) = scope.clone().unbind();
-
-                    let arg = check_term(context, raw_arg, &ann)?;
-                    let body = nbe::nf_term(context, &body.substs(&[(free_var, arg.clone())]))?;
+                Value::FunType(fun_plicity, ref ann, ref closure) if fun_plicity == plicity => {
+                    let arg = check(context, raw_arg, ann)?;
+                    let body = closure.apply(context, context.eval(&arg)?)?;
 
-                    Ok((RcTerm::from(Term::FunApp(head, arg)), body))
+                    Ok((RcTerm::from(Term::FunApp(head, plicity, arg)), body))
                 },
+                Value::Error => {
+                    let arg = check(context, raw_arg, &head_ty)?;
+                    Ok((
+                        RcTerm::from(Term::FunApp(head, plicity, arg)),
+                        head_ty.clone(),
+                    ))
+                },
+                _ if plicity == Plicity::Implicit => Err(TypeError::UnexpectedImplicitArg {
+                    fn_span: raw_head.span(),
+                    arg_span: raw_arg.span(),
+                    found: Box::new(context.resugar_value(&head_ty)),
+                }),
                 _ => Err(TypeError::ArgAppliedToNonFunction {
                     fn_span: raw_head.span(),
                     arg_span: raw_arg.span(),
-                    found: Box::new(context.resugar(&head_ty)),
+                    found: Box::new(context.resugar_value(&head_ty)),
                 }),
             }
         },
@@ -553,7 +1414,7 @@ pub fn infer_term(
         // I-RECORD-TYPE, I-EMPTY-RECORD-TYPE
         raw::Term::RecordType(_, ref raw_scope) => {
             let (raw_fields, ()) = raw_scope.clone().unbind();
-            let mut max_level = Level(0);
+            let mut max_level = Level::from(0);
 
             // FIXME: Check that record is well-formed?
             let fields = {
@@ -562,19 +1423,31 @@ pub fn infer_term(
                     .unnest()
                     .into_iter()
                     .map(|(label, Binder(free_var), Embed(raw_ann))| {
-                        let (ann, ann_level) = infer_universe(&context, &raw_ann)?;
-                        let nf_ann = nbe::nf_term(&context, &ann)?;
+                        let (ann, ann_level) =
+                            infer_universe(&context, &raw_ann).unwrap_or_else(|error| {
+                                context.insert_error(error);
+                                (RcTerm::from(Term::Error), Level::from(0))
+                            });
+                        let nf_ann = context.eval(&ann)?;
+                        let name = Name::from(free_var.pretty_name.clone());
 
-                        max_level = cmp::max(max_level, ann_level);
-                        context.insert_declaration(free_var.clone(), nf_ann);
+                        max_level = max_level.max(&ann_level);
+                        context.insert_local(free_var, nf_ann);
 
-                        Ok((label, Binder(free_var), Embed(ann)))
+                        Ok((label, name, ann))
                     })
-                    .collect::<Result<_, TypeError>>()?
+                    .collect::<Result<Vec<_>, TypeError>>()?
             };
 
+            // Like function types, records that quantify over levels are
+            // bigger than any of the universes they can be instantiated with
+            let size = context.size();
+            if (0..fields.len()).any(|offset| max_level.mentions(VarLevel(size.0 + offset))) {
+                max_level = Level::Omega;
+            }
+
             Ok((
-                RcTerm::from(Term::RecordType(Scope::new(Nest::new(fields), ()))),
+                RcTerm::from(Term::RecordType(fields)),
                 RcValue::from(Value::Universe(max_level)),
             ))
         },
@@ -585,100 +1458,183 @@ pub fn infer_term(
             let mut ty_fields = Vec::with_capacity(raw_fields.len());
 
             // FIXME: error on duplicate field names
-            {
-                let mut ty_mappings = Vec::with_capacity(raw_fields.len());
-                for &(ref label, ref raw_term) in raw_fields {
-                    let free_var = FreeVar::fresh_named(label.0.clone());
-                    let (term, term_ty) = infer_term(context, &raw_term)?;
-                    let term_ty = nbe::nf_term(context, &term_ty.substs(&ty_mappings))?;
-
-                    fields.push((label.clone(), term.clone()));
-                    ty_fields.push((label.clone(), Binder(free_var.clone()), Embed(term_ty)));
-                    ty_mappings.push((free_var, term));
-                }
+            for &(ref label, ref raw_term) in raw_fields {
+                let (term, term_ty) = infer(context, &raw_term).unwrap_or_else(|error| {
+                    context.insert_error(error);
+                    (RcTerm::from(Term::Error), RcValue::from(Value::Error))
+                });
+                // The types of the fields don't depend on each other, so we
+                // read them back underneath the binders of the earlier fields
+                let size = VarLevel(context.size().0 + ty_fields.len());
+                let term_ty = nbe::quote(context, size, Unfold::Never, &term_ty)?;
+
+                fields.push((label.clone(), term));
+                ty_fields.push((label.clone(), Name::new(label.0.clone()), term_ty));
             }
 
             Ok((
                 RcTerm::from(Term::RecordIntro(fields)),
-                RcValue::from(Value::RecordType(Scope::new(Nest::new(ty_fields), ()))),
+                RcValue::from(Value::RecordType(RecordTypeClosure::new(
+                    context.values().clone(),
+                    LevelShift(0),
+                    ty_fields,
+                ))),
             ))
         },
 
         // I-PROJ
         raw::Term::RecordProj(_, ref expr, label_span, ref label, shift) => {
-            let (expr, ty) = infer_term(context, expr)?;
-
-            if let Value::RecordType(ref scope) = *ty.inner {
-                let (fields, ()) = scope.clone().unbind();
-                let mut mappings = vec![];
-
-                for (current_label, Binder(free_var), Embed(current_ann)) in fields.unnest() {
-                    if current_label == *label {
-                        let expr = RcTerm::from(Term::RecordProj(expr, current_label, shift));
-                        let mut ty = nbe::nf_term(context, &current_ann.substs(&mappings))?;
-                        ty.shift_universes(shift);
-
-                        return Ok((expr, ty));
-                    } else {
-                        mappings.push((
-                            free_var,
-                            // NOTE: Not sure if we should be shifting here...
-                            RcTerm::from(Term::RecordProj(expr.clone(), current_label, shift)),
-                        ));
+            let (expr, ty) = infer(context, expr)?;
+            let ty = meta::force(context, &ty)?;
+
+            if let Value::RecordType(ref closure) = *ty.inner {
+                let expr_value = context.eval(&expr)?;
+                let mut field_ty = None;
+
+                closure.for_each_field(context, |current_label, current_ann| {
+                    // NOTE: Not sure if we should be shifting here...
+                    let value = nbe::record_proj(context, &expr_value, &current_label, shift)?;
+                    if current_label == *label && field_ty.is_none() {
+                        field_ty = Some(current_ann);
                     }
+                    Ok::<_, TypeError>(value)
+                })?;
+
+                if let Some(mut ty) = field_ty {
+                    let expr = RcTerm::from(Term::RecordProj(expr, label.clone(), shift));
+                    ty.shift_universes(shift);
+
+                    return Ok((expr, ty));
                 }
             }
 
+            if let Value::Error = *ty.inner {
+                let expr = RcTerm::from(Term::RecordProj(expr, label.clone(), shift));
+                return Ok((expr, ty.clone()));
+            }
+
             Err(TypeError::NoFieldInType {
                 label_span,
                 expected_label: label.clone(),
-                found: Box::new(context.resugar(&ty)),
+                found: Box::new(context.resugar_value(&ty)),
             })
         },
 
         // I-CASE
         raw::Term::Case(span, ref raw_head, ref raw_clauses) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+            let (head, head_ty) = infer(context, raw_head)?;
             let mut ty = None;
+            let mut patterns = Vec::with_capacity(raw_clauses.len());
 
-            // TODO: ensure that patterns are exhaustive
             let clauses = raw_clauses
                 .iter()
                 .map(|raw_clause| {
                     let (raw_pattern, raw_body) = raw_clause.clone().unbind();
                     let (pattern, declarations) = check_pattern(context, &raw_pattern, &head_ty)?;
+                    patterns.push((raw_pattern.span(), pattern.clone()));
 
                     let (body, body_ty) = {
                         let mut body_context = context.clone();
                         for (free_var, ty) in declarations {
-                            body_context.insert_declaration(free_var, ty);
+                            body_context.insert_local(free_var, ty);
                         }
-                        infer_term(&body_context, &raw_body)?
+                        let (body, body_ty) = infer(&body_context, &raw_body)?;
+                        let span = raw_body.span();
+                        (
+                            body,
+                            clause_body_ty(context, &body_context, span, &body_ty)?,
+                        )
                     };
 
                     match ty {
                         None => ty = Some(body_ty),
                         // FIXME: use common subtype?
-                        Some(ref ty) if RcValue::term_eq(&body_ty, ty) => {},
                         Some(ref ty) => {
-                            return Err(TypeError::Mismatch {
-                                span: raw_body.span(),
-                                found: Box::new(context.resugar(&body_ty)),
-                                expected: Box::new(context.resugar(ty)),
-                            });
+                            if !meta::unify(context, &body_ty, ty)? {
+                                return Err(TypeError::Mismatch {
+                                    span: raw_body.span(),
+                                    found: Box::new(context.resugar_value(&body_ty)),
+                                    expected: Box::new(context.resugar_value(ty)),
+                                });
+                            }
                         },
                     }
 
-                    Ok(Scope::new(pattern, body))
+                    Ok((pattern, body))
                 })
                 .collect::<Result<_, TypeError>>()?;
 
             match ty {
-                Some(ty) => Ok((RcTerm::from(Term::Case(head, clauses)), ty)),
+                Some(ty) => {
+                    coverage::check_case(context, span, &head_ty, &patterns)?;
+                    Ok((RcTerm::from(Term::Case(head, clauses)), ty))
+                },
                 None => Err(TypeError::AmbiguousEmptyCase { span }),
             }
         },
 
+        // I-VARIANT-TYPE
+        raw::Term::VariantType(span, ref raw_scope) => {
+            let (Binder(free_var), raw_arms) = raw_scope.clone().unbind();
+            let name = Name::from(free_var.pretty_name.clone());
+
+            for (index, &(ref label, _)) in raw_arms.iter().enumerate() {
+                if raw_arms[..index]
+                    .iter()
+                    .any(|&(ref other, _)| other == label)
+                {
+                    return Err(TypeError::DuplicateConstructors {
+                        span,
+                        label: label.clone(),
+                    });
+                }
+            }
+
+            // Recursive occurrences of the variant type live in the same
+            // universe as the type itself, so if one of the arguments turns
+            // out to live in a higher universe we need to check the arms again
+            let mut level = Level::from(0);
+            loop {
+                let mut arm_context = context.clone();
+                let universe = RcValue::from(Value::Universe(level.clone()));
+                arm_context.insert_local(free_var.clone(), universe);
+
+                let mut max_level = level.clone();
+                let mut arms = Vec::with_capacity(raw_arms.len());
+                for &(ref label, ref raw_args) in &raw_arms {
+                    let mut args = Vec::with_capacity(raw_args.len());
+                    for raw_arg in raw_args {
+                        let (arg, arg_level) = infer_universe(&arm_context, raw_arg)?;
+                        let arg_ty = arm_context.nf_term(&arg)?;
+
+                        if !is_strictly_positive(VarIndex(0), &arg_ty) {
+                            return Err(TypeError::NonPositiveOccurrence {
+                                span: raw_arg.span(),
+                                name: free_var.pretty_name.clone().unwrap_or_default(),
+                            });
+                        }
+
+                        max_level = max_level.max(&arg_level);
+                        args.push(arg);
+                    }
+                    arms.push((label.clone(), args));
+                }
+
+                // Holes in the arguments are elaborated afresh on each pass,
+                // so we compare the levels that they have been raised to
+                let max_level = meta::zonk_level(context, &max_level);
+                if max_level == level {
+                    return Ok((
+                        RcTerm::from(Term::VariantType(name, arms)),
+                        RcValue::from(Value::Universe(level)),
+                    ));
+                }
+                level = max_level;
+            }
+        },
+
         raw::Term::ArrayIntro(span, _) => Err(TypeError::AmbiguousArrayLiteral { span }),
+
+        raw::Term::Error(_) => Ok((RcTerm::from(Term::Error), RcValue::from(Value::Error))),
     }
 }
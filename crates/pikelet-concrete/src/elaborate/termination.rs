@@ -0,0 +1,203 @@
+//! Termination checking for recursive definitions
+//!
+//! We only accept definitions that are _structurally recursive_: there must be
+//! some parameter of the definition that every recursive call passes a strict
+//! sub-term of. The sub-terms of a parameter are the variables that are bound
+//! by constructor patterns in case expressions on that parameter (or on one of
+//! its sub-terms). This is enough to guarantee that unfolding the definition
+//! will eventually stop, keeping type checking decidable.
+
+use im;
+
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::VarIndex;
+
+/// The size of a variable, relative to the parameter being recursed on
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Size {
+    /// The variable is the parameter itself
+    Equal,
+    /// The variable is a strict sub-term of the parameter
+    Smaller,
+}
+
+/// The sizes of the variables bound inside the recursive definition, keyed by
+/// their levels relative to the binder of the recursive definition
+type Sizes = im::HashMap<usize, Size>;
+
+/// Find a parameter of a recursive definition that all of the recursive calls
+/// in its body structurally decrease on, returning its index in the parameter
+/// list
+///
+/// The `term` is expected to be under the binder of the recursive definition,
+/// which is referred to by the variable at index zero.
+pub fn decreasing_param(term: &RcTerm) -> Option<usize> {
+    let mut param_count = 0;
+    let mut body = term.clone();
+    loop {
+        body = match *body.inner {
+            Term::Ann(ref term, _) if param_count == 0 => term.clone(),
+            Term::FunIntro(_, _, _, ref next_body) => {
+                param_count += 1;
+                next_body.clone()
+            },
+            _ => break,
+        };
+    }
+
+    (0..param_count).find(|&index| {
+        let checker = Checker { index };
+        let mut sizes = Sizes::new();
+        sizes.insert(index + 1, Size::Equal);
+
+        checker.is_guarded(param_count + 1, &sizes, &body)
+    })
+}
+
+/// The level of a variable, relative to the binder of the recursive
+/// definition, given the number of binders that are in scope since then
+///
+/// Returns `None` if the variable was bound outside of the definition.
+fn relative_level(depth: usize, index: VarIndex) -> Option<usize> {
+    depth.checked_sub(index.0 + 1)
+}
+
+struct Checker {
+    /// The index of the parameter that we are checking
+    index: usize,
+}
+
+impl Checker {
+    fn size_of(&self, depth: usize, sizes: &Sizes, term: &RcTerm) -> Option<Size> {
+        match *term.inner {
+            Term::Var(index, _) => sizes.get(&relative_level(depth, index)?).cloned(),
+            Term::Ann(ref term, _) => self.size_of(depth, sizes, term),
+            _ => None,
+        }
+    }
+
+    /// Record the sizes of the variables bound by a pattern that matches on a
+    /// value of the given size, returning the number of binders in scope after
+    /// the pattern
+    fn bind_pattern(
+        &self,
+        depth: usize,
+        sizes: &mut Sizes,
+        pattern: &RcPattern,
+        size: Option<Size>,
+    ) -> usize {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, _) => self.bind_pattern(depth, sizes, pattern, size),
+            Pattern::Binder(_) => {
+                if let Some(size) = size {
+                    sizes.insert(depth, size);
+                }
+                depth + 1
+            },
+            Pattern::VariantIntro(_, ref patterns) => {
+                let size = size.map(|_| Size::Smaller);
+                patterns.iter().fold(depth, |depth, pattern| {
+                    self.bind_pattern(depth, sizes, pattern, size)
+                })
+            },
+            Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) | Pattern::Error => {
+                depth
+            },
+        }
+    }
+
+    /// Returns `true` if every recursive call in the term passes a smaller
+    /// argument for the parameter being checked
+    fn is_guarded(&self, depth: usize, sizes: &Sizes, term: &RcTerm) -> bool {
+        match *term.inner {
+            // Recursive occurrences that are not applied to enough arguments
+            // could be passed anything at all!
+            Term::Var(index, _) => relative_level(depth, index) != Some(0),
+            Term::Universe(_)
+            | Term::Level(_)
+            | Term::Literal(_)
+            | Term::Global(_, _)
+            | Term::Import(_)
+            | Term::Meta(_)
+            | Term::Error => true,
+            Term::Ann(ref term, ref ty) => {
+                self.is_guarded(depth, sizes, term) && self.is_guarded(depth, sizes, ty)
+            },
+            Term::FunType(_, _, ref ann, ref body)
+            | Term::FunIntro(_, _, ref ann, ref body)
+            | Term::Fix(_, _, ref ann, ref body) => {
+                self.is_guarded(depth, sizes, ann) && self.is_guarded(depth + 1, sizes, body)
+            },
+            Term::FunApp(_, _, _) => {
+                let mut head = term;
+                let mut args = Vec::new();
+                while let Term::FunApp(ref next_head, _, ref arg) = *head.inner {
+                    args.push(arg.clone());
+                    head = next_head;
+                }
+                args.reverse();
+
+                let is_recursive_call = match *head.inner {
+                    Term::Var(index, _) => relative_level(depth, index) == Some(0),
+                    _ => false,
+                };
+
+                if is_recursive_call {
+                    let is_decreasing = match args.get(self.index) {
+                        Some(arg) => self.size_of(depth, sizes, arg) == Some(Size::Smaller),
+                        None => false,
+                    };
+                    is_decreasing && args.iter().all(|arg| self.is_guarded(depth, sizes, arg))
+                } else {
+                    self.is_guarded(depth, sizes, head)
+                        && args.iter().all(|arg| self.is_guarded(depth, sizes, arg))
+                }
+            },
+            Term::RecordType(ref fields) => fields
+                .iter()
+                .enumerate()
+                .all(|(index, &(_, _, ref ann))| self.is_guarded(depth + index, sizes, ann)),
+            Term::RecordIntro(ref fields) => fields
+                .iter()
+                .all(|&(_, ref term)| self.is_guarded(depth, sizes, term)),
+            Term::RecordProj(ref term, _, _) => self.is_guarded(depth, sizes, term),
+            Term::VariantType(_, ref arms) => arms.iter().all(|&(_, ref args)| {
+                args.iter()
+                    .all(|arg| self.is_guarded(depth + 1, sizes, arg))
+            }),
+            Term::VariantIntro(_, ref args) => {
+                args.iter().all(|arg| self.is_guarded(depth, sizes, arg))
+            },
+            Term::Case(ref head, ref clauses) => {
+                let head_size = self.size_of(depth, sizes, head);
+
+                self.is_guarded(depth, sizes, head)
+                    && clauses.iter().all(|&(ref pattern, ref body)| {
+                        let mut sizes = sizes.clone();
+                        let depth = self.bind_pattern(depth, &mut sizes, pattern, head_size);
+                        self.is_guarded(depth, &sizes, body)
+                    })
+            },
+            Term::ArrayIntro(ref elems) => {
+                elems.iter().all(|elem| self.is_guarded(depth, sizes, elem))
+            },
+            Term::Let(ref bindings, ref body) => {
+                let mut sizes = sizes.clone();
+                let mut depth = depth;
+
+                for &(_, ref term, _) in bindings {
+                    if !self.is_guarded(depth, &sizes, term) {
+                        return false;
+                    }
+                    // Aliases of sized variables have the same size
+                    if let Some(size) = self.size_of(depth, &sizes, term) {
+                        sizes.insert(depth, size);
+                    }
+                    depth += 1;
+                }
+
+                self.is_guarded(depth, &sizes, body)
+            },
+        }
+    }
+}
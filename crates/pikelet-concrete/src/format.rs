@@ -0,0 +1,662 @@
+//! A source code formatter for the concrete syntax
+//!
+//! The formatter normalizes the whitespace between tokens, while leaving the
+//! overall shape of the code up to its author: blocks that were written on a
+//! single line stay on a single line, and blocks that span multiple lines are
+//! laid out with one element per line (apart from elements that were already
+//! sharing a line, like a list of punned record fields). Comments and doc
+//! comments are preserved, along with the blank lines between elements.
+
+use codespan::{ByteIndex, ByteOffset, ByteSpan, FileMap};
+use pretty::{BoxDoc, Doc};
+
+use pikelet_core::syntax::Plicity;
+
+use crate::parse::{self, Comment, ParseError};
+use crate::syntax::concrete::{
+    FunIntroParamGroup, FunTypeParamGroup, Item, Pattern, RecordIntroField, RecordTypeField, Term,
+    VariantTypeArm,
+};
+use crate::syntax::{PRETTY_FALLBACK_WIDTH, PRETTY_INDENT_WIDTH};
+
+type FormatDoc = Doc<'static, BoxDoc<'static, ()>>;
+
+/// Format the contents of a file
+///
+/// Files that contain parse errors are not formatted, because we would risk
+/// throwing away the code that could not be parsed.
+pub fn format(filemap: &FileMap) -> Result<String, Vec<ParseError>> {
+    let (term, _, errors) = parse::term(filemap);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut formatter = Formatter {
+        filemap,
+        comments: parse::comments(filemap),
+        next_comment: 0,
+    };
+    let doc = Doc::nil()
+        .append(formatter.comments_before(term.span().start()))
+        .append(formatter.term(&term))
+        .append(formatter.comments_after(Some(term.span().end()), None, false));
+
+    let mut output = String::new();
+    doc.render_fmt(PRETTY_FALLBACK_WIDTH, &mut output).unwrap();
+
+    // Blank lines are rendered with the indentation of the surrounding block,
+    // so we trim them back down to nothing
+    let mut formatted = String::with_capacity(output.len() + 1);
+    for line in output.lines() {
+        if !line.trim().is_empty() {
+            formatted.push_str(line);
+        }
+        formatted.push('\n');
+    }
+
+    Ok(formatted)
+}
+
+/// The state of the formatter as it works through a file
+struct Formatter<'a> {
+    filemap: &'a FileMap,
+    /// The comments in the file, in the order that they appear
+    comments: Vec<Comment>,
+    /// The index of the first comment that has not yet been printed
+    next_comment: usize,
+}
+
+/// A block of elements that are separated by semicolons, for example the
+/// fields of a record or the items of a `let`
+struct Block<'e, T> {
+    elems: &'e [T],
+    /// The position of the token that closes the block, before which any
+    /// leftover comments should be printed
+    close: ByteIndex,
+    /// Whether the block was originally written over multiple lines
+    multiline: bool,
+    /// Whether the last element is followed by a semicolon when the block is
+    /// printed on a single line
+    terminated: bool,
+}
+
+impl<'a> Formatter<'a> {
+    /// The source code between two positions
+    fn src(&self, start: ByteIndex, end: ByteIndex) -> &'a str {
+        if start < end {
+            self.filemap.src_slice(ByteSpan::new(start, end)).unwrap()
+        } else {
+            ""
+        }
+    }
+
+    fn is_multiline(&self, start: ByteIndex, end: ByteIndex) -> bool {
+        self.src(start, end).contains('\n')
+    }
+
+    /// Line breaks for the blank lines between two positions
+    fn blank_lines(&self, start: ByteIndex, end: ByteIndex) -> FormatDoc {
+        let count = self.src(start, end).matches('\n').count();
+        (1..count).fold(Doc::nil(), |doc, _| doc.append(Doc::newline()))
+    }
+
+    /// The separator to use between two elements, preserving line breaks
+    /// and blank lines from the original source code
+    fn separator(&self, prev_end: Option<ByteIndex>, next_start: ByteIndex) -> FormatDoc {
+        match prev_end {
+            None => Doc::newline(),
+            Some(prev_end) if !self.is_multiline(prev_end, next_start) => Doc::text(" "),
+            Some(prev_end) => Doc::newline().append(self.blank_lines(prev_end, next_start)),
+        }
+    }
+
+    /// The position of the first token after `pos` that is not a comment
+    fn next_token(&self, mut pos: ByteIndex, end: ByteIndex) -> ByteIndex {
+        let mut comments = self.comments[self.next_comment..].iter();
+        loop {
+            let src = self.src(pos, end);
+            pos += ByteOffset::from_str(&src[..src.len() - src.trim_start().len()]);
+            match comments.find(|comment| comment.span.start() >= pos) {
+                Some(comment) if comment.span.start() == pos => pos = comment.span.end(),
+                Some(_) | None => return pos,
+            }
+        }
+    }
+
+    /// Print a keyword that separates two parts of a term, along with the
+    /// comments that come before it
+    ///
+    /// Comments stay with the part that they follow, so a keyword that comes
+    /// after a comment is moved onto the next line.
+    fn keyword(
+        &mut self,
+        prev_end: ByteIndex,
+        next_start: ByteIndex,
+        keyword: &'static str,
+    ) -> FormatDoc {
+        let first_comment = self.next_comment;
+        let keyword_start = self.next_token(prev_end, next_start);
+        let comments = self.comments_after(Some(prev_end), Some(keyword_start), false);
+
+        match self.next_comment > first_comment {
+            true => comments.append(Doc::newline()).append(keyword).append(" "),
+            false => Doc::text(" ").append(keyword).append(" "),
+        }
+    }
+
+    /// Returns the next comment that has not yet been printed, if it starts
+    /// before the given position
+    fn peek_comment(&self, before: Option<ByteIndex>) -> Option<&Comment> {
+        let comment = self.comments.get(self.next_comment)?;
+        match before {
+            Some(before) if before <= comment.span.start() => None,
+            Some(_) | None => Some(comment),
+        }
+    }
+
+    /// Print the comments that start before `pos`, each on their own line
+    fn comments_before(&mut self, pos: ByteIndex) -> FormatDoc {
+        let mut doc = Doc::nil();
+
+        while let Some(comment) = self.peek_comment(Some(pos)).cloned() {
+            self.next_comment += 1;
+            let next_start = self
+                .peek_comment(Some(pos))
+                .map_or(pos, |next| next.span.start());
+
+            doc = doc
+                .append(comment.text)
+                .append(Doc::newline())
+                .append(self.blank_lines(comment.span.end(), next_start));
+        }
+
+        doc
+    }
+
+    /// Print the comments that follow an element, up until the position
+    /// `before`
+    ///
+    /// If `same_line` is set, only a comment that is on the same line as the
+    /// end of the previous element will be printed.
+    fn comments_after(
+        &mut self,
+        mut prev_end: Option<ByteIndex>,
+        before: Option<ByteIndex>,
+        same_line: bool,
+    ) -> FormatDoc {
+        let mut doc = Doc::nil();
+
+        while let Some(comment) = self.peek_comment(before).cloned() {
+            if same_line {
+                match prev_end {
+                    Some(end) if !self.is_multiline(end, comment.span.start()) => {},
+                    _ => break,
+                }
+            }
+
+            self.next_comment += 1;
+            doc = doc
+                .append(self.separator(prev_end, comment.span.start()))
+                .append(comment.text);
+            prev_end = Some(comment.span.end());
+        }
+
+        doc
+    }
+
+    /// Print a block of elements, without the surrounding delimiters
+    ///
+    /// Multiline blocks begin with a line break, and should be indented by
+    /// the caller. Returns `None` if the block has neither elements nor
+    /// comments.
+    fn block<T>(
+        &mut self,
+        block: Block<'_, T>,
+        span_of: impl Fn(&T) -> ByteSpan,
+        mut print: impl FnMut(&mut Self, &T) -> FormatDoc,
+    ) -> Option<FormatDoc> {
+        if block.elems.is_empty() && self.peek_comment(Some(block.close)).is_none() {
+            return None;
+        }
+
+        if !block.multiline {
+            let mut doc = Doc::nil();
+            for (index, elem) in block.elems.iter().enumerate() {
+                if index > 0 {
+                    doc = doc.append("; ");
+                }
+                doc = doc.append(print(self, elem));
+            }
+            return Some(match block.terminated {
+                true => doc.append(";"),
+                false => doc,
+            });
+        }
+
+        let mut doc = Doc::nil();
+        let mut prev_end = None;
+
+        for (index, elem) in block.elems.iter().enumerate() {
+            let span = span_of(elem);
+            let next_start = block
+                .elems
+                .get(index + 1)
+                .map_or(block.close, |next| span_of(next).start());
+            let start = self
+                .peek_comment(Some(span.start()))
+                .map_or(span.start(), |comment| comment.span.start());
+
+            doc = doc
+                .append(self.separator(prev_end, start))
+                .append(self.comments_before(span.start()))
+                .append(print(self, elem))
+                .append(";");
+
+            let trailing = self.comments_after(Some(span.end()), Some(next_start), true);
+            doc = doc.append(trailing);
+            prev_end = Some(match self.next_comment {
+                0 => span.end(),
+                next => std::cmp::max(span.end(), self.comments[next - 1].span.end()),
+            });
+        }
+
+        Some(doc.append(self.comments_after(prev_end, Some(block.close), false)))
+    }
+
+    fn item(&mut self, item: &Item) -> FormatDoc {
+        match *item {
+            // Doc comments are printed along with the other comments
+            Item::Declaration {
+                name: (_, ref name),
+                ref ann,
+                ..
+            } => Doc::as_string(name).append(" : ").append(self.term(ann)),
+            Item::Definition {
+                name: (_, ref name),
+                ref params,
+                ref return_ann,
+                ref body,
+                ..
+            } => Doc::as_string(name)
+                .append(self.fun_intro_params(params, true))
+                .append(self.return_ann(return_ann))
+                .append(" = ")
+                .append(self.term(body)),
+            Item::Error(span) => Doc::text(self.src(span.start(), span.end()).to_owned()),
+        }
+    }
+
+    fn items(&mut self, items: &[Item], close: ByteIndex, multiline: bool) -> FormatDoc {
+        let block = Block {
+            elems: items,
+            close,
+            multiline,
+            terminated: true,
+        };
+
+        // The grammar requires at least one item
+        self.block(block, Item::span, Formatter::item)
+            .unwrap_or_else(Doc::nil)
+    }
+
+    fn return_ann(&mut self, return_ann: &Option<Box<Term>>) -> FormatDoc {
+        match *return_ann {
+            Some(ref return_ann) => Doc::text(" : ").append(self.term(return_ann)),
+            None => Doc::nil(),
+        }
+    }
+
+    /// Print the parameters of a function introduction, preceded by a space
+    /// if `leading_space` is set and there are any parameters
+    fn fun_intro_params(
+        &mut self,
+        params: &[FunIntroParamGroup],
+        leading_space: bool,
+    ) -> FormatDoc {
+        let mut doc = Doc::nil();
+
+        for (index, &(plicity, ref names, ref ann)) in params.iter().enumerate() {
+            if index > 0 || leading_space {
+                doc = doc.append(" ");
+            }
+            doc = doc.append(match (plicity, ann) {
+                (Plicity::Explicit, None) if names.len() == 1 => Doc::as_string(&names[0].1),
+                (Plicity::Explicit, _) => Doc::text("(")
+                    .append(param_names(names))
+                    .append(self.param_ann(ann))
+                    .append(")"),
+                (Plicity::Implicit, _) => Doc::text("{")
+                    .append(param_names(names))
+                    .append(self.param_ann(ann))
+                    .append("}"),
+            });
+        }
+
+        doc
+    }
+
+    fn param_ann(&mut self, ann: &Option<Box<Term>>) -> FormatDoc {
+        match *ann {
+            Some(ref ann) => Doc::text(" : ").append(self.term(ann)),
+            None => Doc::nil(),
+        }
+    }
+
+    fn fun_ty_params(&mut self, params: &[FunTypeParamGroup]) -> FormatDoc {
+        let mut doc = Doc::nil();
+
+        for (index, &(plicity, ref names, ref ann)) in params.iter().enumerate() {
+            let (open, close) = match plicity {
+                Plicity::Explicit => ("(", ")"),
+                Plicity::Implicit => ("{", "}"),
+            };
+
+            if index > 0 {
+                doc = doc.append(" ");
+            }
+            doc = doc
+                .append(open)
+                .append(param_names(names))
+                .append(" : ")
+                .append(self.term(ann))
+                .append(close);
+        }
+
+        doc
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> FormatDoc {
+        match *pattern {
+            Pattern::Parens(_, ref pattern) => {
+                Doc::text("(").append(self.pattern(pattern)).append(")")
+            },
+            Pattern::Ann(ref pattern, ref ty) => {
+                self.pattern(pattern).append(" : ").append(self.term(ty))
+            },
+            Pattern::Literal(ref literal) => self.src_doc(literal.span()),
+            Pattern::Name(_, ref name, shift) => shifted_name(name, shift),
+            Pattern::VariantIntro(_, ref label, ref patterns) => {
+                let mut doc = Doc::as_string(label);
+                for pattern in patterns {
+                    doc = doc.append(" ").append(self.pattern(pattern));
+                }
+                doc
+            },
+            Pattern::Error(span) => self.src_doc(span),
+        }
+    }
+
+    fn record_type_field(&mut self, field: &RecordTypeField) -> FormatDoc {
+        Doc::as_string(&field.label.1)
+            .append(match field.binder {
+                Some((_, ref binder)) => Doc::text(" as ").append(Doc::as_string(binder)),
+                None => Doc::nil(),
+            })
+            .append(" : ")
+            .append(self.term(&field.ann))
+    }
+
+    fn record_intro_field(&mut self, field: &RecordIntroField) -> FormatDoc {
+        match *field {
+            RecordIntroField::Punned {
+                label: (_, ref label),
+                shift,
+            } => shifted_name(label, shift),
+            RecordIntroField::Explicit {
+                label: (_, ref label),
+                ref params,
+                ref return_ann,
+                ref term,
+            } => Doc::as_string(label)
+                .append(self.fun_intro_params(params, true))
+                .append(self.return_ann(return_ann))
+                .append(" = ")
+                .append(self.term(term)),
+        }
+    }
+
+    fn variant_type_arm(&mut self, arm: &VariantTypeArm) -> FormatDoc {
+        let mut doc = Doc::as_string(&arm.label.1);
+        for arg in &arm.args {
+            doc = doc.append(" ").append(self.term(arg));
+        }
+        doc
+    }
+
+    fn src_doc(&self, span: ByteSpan) -> FormatDoc {
+        Doc::text(self.src(span.start(), span.end()).to_owned())
+    }
+
+    fn term(&mut self, term: &Term) -> FormatDoc {
+        let first_comment = self.next_comment;
+        let comments = self.comments_before(term.span().start());
+        let has_comments = self.next_comment > first_comment;
+        let doc = match *term {
+            Term::Parens(_, ref term) => Doc::text("(").append(self.term(term)).append(")"),
+            Term::Ann(ref term, ref ty) => self
+                .term(term)
+                .append(self.keyword(term.span().end(), ty.span().start(), ":"))
+                .append(self.term(ty)),
+            Term::Universe(_, None) => Doc::text("Type"),
+            Term::Universe(_, Some(level)) => Doc::text(format!("Type^{}", level)),
+            Term::UniverseAt(_, ref level) => Doc::text("Type^").append(self.term(level)),
+            Term::Literal(ref literal) => self.src_doc(literal.span()),
+            Term::ArrayIntro(span, ref elems) => {
+                let multiline = self.is_multiline(span.start(), span.end());
+                let block = Block {
+                    elems,
+                    close: span.end() - ByteOffset(1),
+                    multiline,
+                    terminated: false,
+                };
+                match (self.block(block, Term::span, Formatter::term), multiline) {
+                    (None, _) => Doc::text("[]"),
+                    (Some(elems), true) => Doc::text("[")
+                        .append(elems.nest(PRETTY_INDENT_WIDTH))
+                        .append(Doc::newline())
+                        .append("]"),
+                    (Some(elems), false) => Doc::text("[").append(elems).append("]"),
+                }
+            },
+            Term::Hole(_) => Doc::text("?"),
+            Term::Name(_, ref name, shift) => shifted_name(name, shift),
+            Term::Import(_, path_span, _) => Doc::text("import ").append(self.src_doc(path_span)),
+            Term::FunIntro(_, ref params, ref body) => Doc::text("\\")
+                .append(self.fun_intro_params(params, false))
+                .append(" => ")
+                .append(self.term(body)),
+            Term::FunType(_, ref params, ref body) => self
+                .fun_ty_params(params)
+                .append(" -> ")
+                .append(self.term(body)),
+            Term::FunArrow(ref ann, ref body) => self
+                .term(ann)
+                .append(self.keyword(ann.span().end(), body.span().start(), "->"))
+                .append(self.term(body)),
+            Term::FunApp(ref head, ref args) => {
+                let mut doc = self.term(head);
+                for &(plicity, ref arg) in args {
+                    doc = doc.append(match plicity {
+                        Plicity::Explicit => Doc::text(" ").append(self.term(arg)),
+                        Plicity::Implicit => Doc::text(" {").append(self.term(arg)).append("}"),
+                    });
+                }
+                doc
+            },
+            Term::Let(start, ref items, ref body) => {
+                let items_end = items.last().map_or(start, |item| item.span().end());
+                let multiline = self.is_multiline(start, items_end);
+
+                // Skip past the semicolon after the last item, which is
+                // printed along with the items
+                let body_start = body.span().start();
+                let items_end = match self.src(items_end, body_start).find(';') {
+                    Some(index) => items_end + ByteOffset(index as i64 + 1),
+                    None => items_end,
+                };
+                let in_start = self.next_token(items_end, body_start);
+                let items = self.items(items, in_start, multiline);
+
+                match multiline {
+                    true => {
+                        let in_end = in_start + ByteOffset::from_str("in");
+                        let in_comment = self.comments_after(Some(in_end), Some(body_start), true);
+
+                        Doc::text("let")
+                            .append(items.nest(PRETTY_INDENT_WIDTH))
+                            .append(Doc::newline())
+                            .append("in")
+                            .append(in_comment)
+                            .append(
+                                Doc::newline()
+                                    .append(self.term(body))
+                                    .nest(PRETTY_INDENT_WIDTH),
+                            )
+                    },
+                    false => Doc::text("let ")
+                        .append(items)
+                        .append(self.keyword(items_end, body_start, "in"))
+                        .append(self.term(body)),
+                }
+            },
+            Term::Where(ref expr, ref items, end) => {
+                let multiline = self.is_multiline(expr.span().end(), end);
+                let expr = self.term(expr);
+                let items = self.items(items, end - ByteOffset(1), multiline);
+
+                delimited(expr.append(" where"), Some(items), multiline)
+            },
+            Term::If(_, ref cond, ref if_true, ref if_false) => Doc::text("if ")
+                .append(self.term(cond))
+                .append(self.keyword(cond.span().end(), if_true.span().start(), "then"))
+                .append(self.term(if_true))
+                .append(self.keyword(if_true.span().end(), if_false.span().start(), "else"))
+                .append(self.term(if_false)),
+            Term::Case(span, ref head, ref clauses) => {
+                let multiline = self.is_multiline(head.span().end(), span.end());
+                let head = Doc::text("case ").append(self.term(head));
+                let block = Block {
+                    elems: clauses,
+                    close: span.end() - ByteOffset(1),
+                    multiline,
+                    terminated: false,
+                };
+                let clauses = self.block(
+                    block,
+                    |&(ref pattern, ref body)| pattern.span().to(body.span()),
+                    |this, &(ref pattern, ref body)| {
+                        this.pattern(pattern).append(" => ").append(this.term(body))
+                    },
+                );
+
+                delimited(head, clauses, multiline)
+            },
+            Term::RecordType(span, ref fields) => {
+                let multiline = self.is_multiline(span.start(), span.end());
+                let block = Block {
+                    elems: fields,
+                    close: span.end() - ByteOffset(1),
+                    multiline,
+                    terminated: false,
+                };
+                let fields = self.block(
+                    block,
+                    |field| ByteSpan::new(field.label.0, field.ann.span().end()),
+                    Formatter::record_type_field,
+                );
+
+                delimited(Doc::text("Record"), fields, multiline)
+            },
+            Term::RecordIntro(span, ref fields) => {
+                let multiline = self.is_multiline(span.start(), span.end());
+                let block = Block {
+                    elems: fields,
+                    close: span.end() - ByteOffset(1),
+                    multiline,
+                    terminated: false,
+                };
+                let fields = self.block(
+                    block,
+                    |field| match *field {
+                        RecordIntroField::Punned {
+                            label: (start, ref label),
+                            ..
+                        } => ByteSpan::from_offset(start, ByteOffset::from_str(label)),
+                        RecordIntroField::Explicit {
+                            label: (start, _),
+                            ref term,
+                            ..
+                        } => ByteSpan::new(start, term.span().end()),
+                    },
+                    Formatter::record_intro_field,
+                );
+
+                delimited(Doc::text("record"), fields, multiline)
+            },
+            Term::RecordProj(_, ref expr, _, ref label, shift) => self
+                .term(expr)
+                .append(".")
+                .append(shifted_name(label, shift)),
+            Term::VariantType(span, ref binder, ref arms) => {
+                let multiline = self.is_multiline(span.start(), span.end());
+                let block = Block {
+                    elems: arms,
+                    close: span.end() - ByteOffset(1),
+                    multiline,
+                    terminated: false,
+                };
+                let arms = self.block(
+                    block,
+                    |arm| match arm.args.last() {
+                        Some(arg) => ByteSpan::new(arm.label.0, arg.span().end()),
+                        None => {
+                            ByteSpan::from_offset(arm.label.0, ByteOffset::from_str(&arm.label.1))
+                        },
+                    },
+                    Formatter::variant_type_arm,
+                );
+                let keyword = match *binder {
+                    Some((_, ref binder)) => Doc::text("Variant ").append(Doc::as_string(binder)),
+                    None => Doc::text("Variant"),
+                };
+
+                delimited(keyword, arms, multiline)
+            },
+            Term::Error(span) => self.src_doc(span),
+        };
+
+        // Comments in the middle of a term break it over multiple lines, so
+        // the rest of the term is indented as a continuation
+        match has_comments {
+            true => comments.append(doc).nest(PRETTY_INDENT_WIDTH),
+            false => doc,
+        }
+    }
+}
+
+/// Wrap the contents of a block in braces, following the given keyword
+fn delimited(keyword: FormatDoc, contents: Option<FormatDoc>, multiline: bool) -> FormatDoc {
+    match (contents, multiline) {
+        (None, _) => keyword.append(" {}"),
+        (Some(contents), true) => keyword
+            .append(" {")
+            .append(contents.nest(PRETTY_INDENT_WIDTH))
+            .append(Doc::newline())
+            .append("}"),
+        (Some(contents), false) => keyword.append(" { ").append(contents).append(" }"),
+    }
+}
+
+fn param_names(names: &[(ByteIndex, String)]) -> FormatDoc {
+    Doc::intersperse(
+        names.iter().map(|name| Doc::as_string(&name.1)),
+        Doc::text(" "),
+    )
+}
+
+fn shifted_name(name: &str, shift: Option<u32>) -> FormatDoc {
+    match shift {
+        None => Doc::text(name.to_owned()),
+        Some(shift) => Doc::text(format!("{}^{}", name, shift)),
+    }
+}
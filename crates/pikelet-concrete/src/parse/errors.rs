@@ -13,6 +13,8 @@ pub enum ParseError {
     Lexer(#[cause] LexerError),
     #[fail(display = "An identifier was expected when parsing a pi type.")]
     IdentifierExpectedInPiType { span: ByteSpan },
+    #[fail(display = "The level `{}` is out of range.", value)]
+    LevelOutOfRange { span: ByteSpan, value: i128 },
     #[fail(display = "Unknown repl command `:{}` found.", command)]
     UnknownReplCommand { span: ByteSpan, command: String },
     #[fail(display = "Unexpected EOF, expected one of: {}.", expected)]
@@ -74,6 +76,7 @@ impl ParseError {
         match *self {
             ParseError::Lexer(ref err) => err.span(),
             ParseError::IdentifierExpectedInPiType { span }
+            | ParseError::LevelOutOfRange { span, .. }
             | ParseError::UnknownReplCommand { span, .. }
             | ParseError::UnexpectedToken { span, .. }
             | ParseError::ExtraToken { span, .. } => span,
@@ -91,6 +94,12 @@ impl ParseError {
                         Label::new_primary(span).with_message("ill-formed dependent function type"),
                     )
             },
+            ParseError::LevelOutOfRange { span, value } => {
+                Diagnostic::new_error(format!("the level `{}` is out of range", value)).with_label(
+                    Label::new_primary(span)
+                        .with_message(format!("expected a level between 0 and {}", u32::MAX)),
+                )
+            },
             ParseError::UnknownReplCommand { span, ref command } => {
                 Diagnostic::new_error(format!("unknown repl command `:{}`", command))
                     .with_label(Label::new_primary(span).with_message("unexpected command"))
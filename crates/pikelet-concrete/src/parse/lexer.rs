@@ -8,6 +8,8 @@ use std::str::{CharIndices, FromStr};
 use codespan::{ByteIndex, ByteOffset, RawOffset};
 use unicode_xid::UnicodeXID;
 
+use crate::syntax;
+
 fn is_symbol(ch: char) -> bool {
     match ch {
         '&' | '!' | ':' | ',' | '.' | '=' | '/' | '>' | '<' | '-' | '|' | '+' | ';' | '*' | '^'
@@ -61,11 +63,24 @@ pub enum LexerError {
     EmptyCharLiteral { span: ByteSpan },
     #[fail(display = "An unknown escape code \\{} was found.", found)]
     UnknownEscapeCode { start: ByteIndex, found: char },
+    #[fail(display = "A malformed unicode escape was found.")]
+    MalformedUnicodeEscape { span: ByteSpan },
+    #[fail(
+        display = "The unicode escape {:X} is not a valid unicode scalar value.",
+        value
+    )]
+    InvalidUnicodeEscape { span: ByteSpan, value: u32 },
+    #[fail(display = "A malformed byte escape was found.")]
+    MalformedByteEscape { span: ByteSpan },
+    #[fail(display = "The byte escape {:X} is out of range.", value)]
+    ByteEscapeOutOfRange { span: ByteSpan, value: u8 },
     #[fail(
         display = "An integer literal {} was too large for the target type.",
         value
     )]
     IntegerLiteralOverflow { span: ByteSpan, value: String },
+    #[fail(display = "A malformed float literal {} was found.", value)]
+    MalformedFloatLiteral { span: ByteSpan, value: String },
 }
 
 impl LexerError {
@@ -83,7 +98,12 @@ impl LexerError {
             | LexerError::UnterminatedOctLiteral { span }
             | LexerError::UnterminatedHexLiteral { span }
             | LexerError::EmptyCharLiteral { span }
-            | LexerError::IntegerLiteralOverflow { span, .. } => span,
+            | LexerError::MalformedUnicodeEscape { span }
+            | LexerError::InvalidUnicodeEscape { span, .. }
+            | LexerError::MalformedByteEscape { span }
+            | LexerError::ByteEscapeOutOfRange { span, .. }
+            | LexerError::IntegerLiteralOverflow { span, .. }
+            | LexerError::MalformedFloatLiteral { span, .. } => span,
         }
     }
 
@@ -125,10 +145,36 @@ impl LexerError {
                 Diagnostic::new_error(format!("unknown escape code \\{}", found))
                     .with_label(Label::new_primary(char_span))
             },
+            LexerError::MalformedUnicodeEscape { span } => {
+                Diagnostic::new_error("malformed unicode escape").with_label(
+                    Label::new_primary(span)
+                        .with_message("expected 1 to 6 hexadecimal digits, eg. `\\u{1F600}`"),
+                )
+            },
+            LexerError::InvalidUnicodeEscape { span, value } => {
+                Diagnostic::new_error(format!("invalid unicode escape `\\u{{{:X}}}`", value,))
+                    .with_label(Label::new_primary(span).with_message("not a unicode scalar value"))
+            },
+            LexerError::MalformedByteEscape { span } => {
+                Diagnostic::new_error("malformed byte escape").with_label(
+                    Label::new_primary(span)
+                        .with_message("expected 2 hexadecimal digits, eg. `\\x7F`"),
+                )
+            },
+            LexerError::ByteEscapeOutOfRange { span, value } => {
+                Diagnostic::new_error(format!("byte escape `\\x{:02X}` is out of range", value,))
+                    .with_label(
+                        Label::new_primary(span).with_message("expected a value up to `\\x7F`"),
+                    )
+            },
             LexerError::IntegerLiteralOverflow { span, ref value } => {
                 Diagnostic::new_error(format!("integer literal overflow with value `{}`", value))
                     .with_label(Label::new_primary(span).with_message("overflowing literal"))
             },
+            LexerError::MalformedFloatLiteral { span, ref value } => {
+                Diagnostic::new_error(format!("malformed float literal `{}`", value))
+                    .with_label(Label::new_primary(span))
+            },
         }
     }
 }
@@ -141,25 +187,26 @@ pub enum Token<S> {
     DocComment(S),
     StringLiteral(String),
     CharLiteral(char),
-    BinIntLiteral(u64),
-    OctIntLiteral(u64),
-    DecIntLiteral(u64),
-    HexIntLiteral(u64),
+    BinIntLiteral(i128),
+    OctIntLiteral(i128),
+    DecIntLiteral(i128),
+    HexIntLiteral(i128),
     DecFloatLiteral(f64),
 
     // Keywords
-    As,         // as
-    Case,       // case
-    Else,       // else
-    If,         // if
-    Import,     // import
-    In,         // in
-    Let,        // let
-    Record,     // record
-    RecordType, // Record
-    Then,       // then
-    Type,       // Type
-    Where,      // where
+    As,          // as
+    Case,        // case
+    Else,        // else
+    If,          // if
+    Import,      // import
+    In,          // in
+    Let,         // let
+    Record,      // record
+    RecordType,  // Record
+    Then,        // then
+    Type,        // Type
+    VariantType, // Variant
+    Where,       // where
 
     // Symbols
     BSlash,    // \
@@ -188,12 +235,12 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
         match *self {
             Token::Ident(ref name) => write!(f, "{}", name),
             Token::DocComment(ref comment) => write!(f, "||| {}", comment),
-            Token::StringLiteral(ref value) => write!(f, "{:?}", value),
-            Token::CharLiteral(ref value) => write!(f, "'{:?}'", value),
-            Token::BinIntLiteral(ref value) => write!(f, "{:b}", value),
-            Token::OctIntLiteral(ref value) => write!(f, "{:o}", value),
+            Token::StringLiteral(ref value) => write!(f, "{}", syntax::string_literal(value)),
+            Token::CharLiteral(value) => write!(f, "{}", syntax::char_literal(value)),
+            Token::BinIntLiteral(value) => write!(f, "{}{:b}", sign(value), value.abs()),
+            Token::OctIntLiteral(value) => write!(f, "{}{:o}", sign(value), value.abs()),
             Token::DecIntLiteral(ref value) => write!(f, "{}", value),
-            Token::HexIntLiteral(ref value) => write!(f, "{:x}", value),
+            Token::HexIntLiteral(value) => write!(f, "{}{:x}", sign(value), value.abs()),
             Token::DecFloatLiteral(ref value) => write!(f, "{}", value),
             Token::As => write!(f, "as"),
             Token::Case => write!(f, "case"),
@@ -206,6 +253,7 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
             Token::RecordType => write!(f, "Record"),
             Token::Then => write!(f, "then"),
             Token::Type => write!(f, "Type"),
+            Token::VariantType => write!(f, "Variant"),
             Token::Where => write!(f, "where"),
             Token::BSlash => write!(f, "\\"),
             Token::Caret => write!(f, "^"),
@@ -228,6 +276,15 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
     }
 }
 
+/// The sign to print before the magnitude of an integer
+fn sign(value: i128) -> &'static str {
+    if value < 0 {
+        "-"
+    } else {
+        ""
+    }
+}
+
 impl<'input> From<Token<&'input str>> for Token<String> {
     fn from(src: Token<&'input str>) -> Token<String> {
         match src {
@@ -251,6 +308,7 @@ impl<'input> From<Token<&'input str>> for Token<String> {
             Token::RecordType => Token::RecordType,
             Token::Then => Token::Then,
             Token::Type => Token::Type,
+            Token::VariantType => Token::VariantType,
             Token::Where => Token::Where,
             Token::BSlash => Token::BSlash,
             Token::Caret => Token::Caret,
@@ -273,12 +331,25 @@ impl<'input> From<Token<&'input str>> for Token<String> {
     }
 }
 
+/// A line comment or doc comment, as it appeared in the source code
+///
+/// Comments are not needed by the parser, but we keep them around for tools
+/// like the formatter that need to reproduce them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The span of the comment, excluding the trailing newline
+    pub span: ByteSpan,
+    /// The text of the comment, including the leading `--` or `|||`
+    pub text: String,
+}
+
 /// An iterator over a source string that yields `Token`s for subsequent use by
 /// the parser
 pub struct Lexer<'input> {
     filemap: &'input FileMap,
     chars: CharIndices<'input>,
     lookahead: Option<(usize, char)>,
+    comments: Vec<Comment>,
 }
 
 impl<'input> Lexer<'input> {
@@ -290,9 +361,25 @@ impl<'input> Lexer<'input> {
             filemap,
             lookahead: chars.next(),
             chars,
+            comments: Vec::new(),
         }
     }
 
+    /// Take the comments that have been encountered so far
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Remember a comment that spans from `start` to `end`
+    fn push_comment(&mut self, start: ByteIndex, end: ByteIndex) {
+        let text = self.slice(start, end).trim_end();
+        let end = start + ByteOffset::from_str(text);
+        let span = ByteSpan::new(start, end);
+        let text = text.to_owned();
+
+        self.comments.push(Comment { span, text });
+    }
+
     /// Returns the index of the end of the file
     fn eof(&self) -> ByteIndex {
         self.filemap.span().end()
@@ -361,6 +448,7 @@ impl<'input> Lexer<'input> {
     fn doc_comment(&mut self, start: ByteIndex) -> SpannedToken<'input> {
         let (end, mut comment) =
             self.take_until(start + ByteOffset::from_str("|||"), |ch| ch == '\n');
+        self.push_comment(start, end);
 
         // Skip preceding space
         if comment.starts_with(' ') {
@@ -386,6 +474,7 @@ impl<'input> Lexer<'input> {
             "Record" => Token::RecordType,
             "then" => Token::Then,
             "Type" => Token::Type,
+            "Variant" => Token::VariantType,
             "where" => Token::Where,
             ident => Token::Ident(ident),
         };
@@ -403,12 +492,79 @@ impl<'input> Lexer<'input> {
             Some((_, 'n')) => Ok('\n'),
             Some((_, 'r')) => Ok('\r'),
             Some((_, 't')) => Ok('\t'),
-            // TODO: Unicode escape codes
+            Some((_, '0')) => Ok('\0'),
+            Some((_, 'x')) => self.byte_escape(start),
+            Some((_, 'u')) => self.unicode_escape(start),
             Some((start, ch)) => Err(LexerError::UnknownEscapeCode { start, found: ch }),
             None => Err(LexerError::UnexpectedEof { end: start }),
         }
     }
 
+    /// Consume the digits of a byte escape, eg. `\x7F`, where `start` is the
+    /// position of the backslash
+    ///
+    /// Only ASCII characters can be written this way.
+    fn byte_escape(&mut self, start: ByteIndex) -> Result<char, LexerError> {
+        let digits_start = start + ByteOffset::from_str("\\x");
+        let mut end = digits_start;
+
+        for _ in 0..2 {
+            match self.lookahead() {
+                Some((_, ch)) if is_hex_digit(ch) => {
+                    self.bump();
+                    end += ByteOffset::from_char_utf8(ch);
+                },
+                _ => {
+                    return Err(LexerError::MalformedByteEscape {
+                        span: ByteSpan::new(start, end),
+                    });
+                },
+            }
+        }
+
+        let span = ByteSpan::new(start, end);
+        match u8::from_str_radix(self.slice(digits_start, end), 16).unwrap() {
+            value if value > 0x7F => Err(LexerError::ByteEscapeOutOfRange { span, value }),
+            value => Ok(char::from(value)),
+        }
+    }
+
+    /// Consume the braces and digits of a unicode escape, eg. `\u{1F600}`,
+    /// where `start` is the position of the backslash
+    fn unicode_escape(&mut self, start: ByteIndex) -> Result<char, LexerError> {
+        let open_start = start + ByteOffset::from_str("\\u");
+        match self.lookahead() {
+            Some((_, '{')) => self.bump(),
+            _ => {
+                return Err(LexerError::MalformedUnicodeEscape {
+                    span: ByteSpan::new(start, open_start),
+                });
+            },
+        };
+
+        let digits_start = open_start + ByteOffset::from_str("{");
+        let (digits_end, digits) = self.take_while(digits_start, is_hex_digit);
+        let end = match self.lookahead() {
+            Some((_, '}')) => {
+                self.bump();
+                digits_end + ByteOffset::from_str("}")
+            },
+            _ => {
+                return Err(LexerError::MalformedUnicodeEscape {
+                    span: ByteSpan::new(start, digits_end),
+                });
+            },
+        };
+
+        let span = ByteSpan::new(start, end);
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(LexerError::MalformedUnicodeEscape { span });
+        }
+
+        let value = u32::from_str_radix(digits, 16).unwrap();
+        std::char::from_u32(value).ok_or(LexerError::InvalidUnicodeEscape { span, value })
+    }
+
     /// Consume a string literal
     fn string_literal(&mut self, start: ByteIndex) -> Result<SpannedToken<'input>, LexerError> {
         let mut string = String::new();
@@ -454,6 +610,36 @@ impl<'input> Lexer<'input> {
         }
     }
 
+    /// Consume a numeric literal, starting with the digit `ch`
+    fn numeric_literal(
+        &mut self,
+        start: ByteIndex,
+        ch: char,
+    ) -> Result<SpannedToken<'input>, LexerError> {
+        match ch {
+            '0' if self.test_lookahead(|x| x == 'b') => self.bin_literal(start),
+            '0' if self.test_lookahead(|x| x == 'o') => self.oct_literal(start),
+            '0' if self.test_lookahead(|x| x == 'x') => self.hex_literal(start),
+            _ => self.dec_literal(start),
+        }
+    }
+
+    /// Consume a numeric literal that is preceded by a minus sign
+    fn negative_literal(&mut self, start: ByteIndex) -> Result<SpannedToken<'input>, LexerError> {
+        let (digits_start, ch) = self.bump().unwrap(); // the first digit
+        let (_, token, end) = self.numeric_literal(digits_start, ch)?;
+        let token = match token {
+            Token::BinIntLiteral(value) => Token::BinIntLiteral(-value),
+            Token::OctIntLiteral(value) => Token::OctIntLiteral(-value),
+            Token::DecIntLiteral(value) => Token::DecIntLiteral(-value),
+            Token::HexIntLiteral(value) => Token::HexIntLiteral(-value),
+            Token::DecFloatLiteral(value) => Token::DecFloatLiteral(-value),
+            _ => unreachable!("non-numeric token: {:?}", token),
+        };
+
+        Ok((start, token, end))
+    }
+
     /// Consume a binary literal token
     fn bin_literal(
         &mut self,
@@ -466,7 +652,7 @@ impl<'input> Lexer<'input> {
                 span: ByteSpan::new(start, end),
             })
         } else {
-            let int = u64::from_str_radix(src, 2).unwrap();
+            let int = int_literal(ByteSpan::new(start, end), src, 2)?;
             Ok((start, Token::BinIntLiteral(int), end))
         }
     }
@@ -483,7 +669,7 @@ impl<'input> Lexer<'input> {
                 span: ByteSpan::new(start, end),
             })
         } else {
-            let int = u64::from_str_radix(src, 8).unwrap();
+            let int = int_literal(ByteSpan::new(start, end), src, 8)?;
             Ok((start, Token::OctIntLiteral(int), end))
         }
     }
@@ -496,18 +682,11 @@ impl<'input> Lexer<'input> {
             self.bump(); // skip '.'
             let (end, src) = self.take_while(start, is_dec_digit);
 
-            match f64::from_str(src) {
-                Ok(value) => Ok((start, Token::DecFloatLiteral(value), end)),
-                Err(_) => unimplemented!(),
-            }
+            let float = float_literal(ByteSpan::new(start, end), src)?;
+            Ok((start, Token::DecFloatLiteral(float), end))
         } else {
-            match u64::from_str_radix(src, 10) {
-                Ok(value) => Ok((start, Token::DecIntLiteral(value), end)),
-                Err(_) => Err(LexerError::IntegerLiteralOverflow {
-                    span: ByteSpan::new(start, end),
-                    value: src.to_string(),
-                }),
-            }
+            let int = int_literal(ByteSpan::new(start, end), src, 10)?;
+            Ok((start, Token::DecIntLiteral(int), end))
         }
     }
 
@@ -523,12 +702,37 @@ impl<'input> Lexer<'input> {
                 span: ByteSpan::new(start, end),
             })
         } else {
-            let int = u64::from_str_radix(src, 16).unwrap();
+            let int = int_literal(ByteSpan::new(start, end), src, 16)?;
             Ok((start, Token::HexIntLiteral(int), end))
         }
     }
 }
 
+/// Parse the digits of an integer literal in the given radix
+///
+/// Literals are checked against the range of their types during elaboration,
+/// but we need to draw a line somewhere, so we refuse the ones that don't fit
+/// in a `U64`.
+fn int_literal(span: ByteSpan, src: &str, radix: u32) -> Result<i128, LexerError> {
+    match u64::from_str_radix(src, radix) {
+        Ok(value) => Ok(i128::from(value)),
+        Err(_) => Err(LexerError::IntegerLiteralOverflow {
+            span,
+            value: src.to_owned(),
+        }),
+    }
+}
+
+fn float_literal(span: ByteSpan, src: &str) -> Result<f64, LexerError> {
+    match f64::from_str(src) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(LexerError::MalformedFloatLiteral {
+            span,
+            value: src.to_owned(),
+        }),
+    }
+}
+
 pub type SpannedToken<'input> = (ByteIndex, Token<&'input str>, ByteIndex);
 
 impl<'input> Iterator for Lexer<'input> {
@@ -540,6 +744,7 @@ impl<'input> Iterator for Lexer<'input> {
             let end = start + ByteOffset::from_char_utf8(ch);
 
             return Some(match ch {
+                '-' if self.test_lookahead(is_dec_digit) => self.negative_literal(start),
                 ch if is_symbol(ch) => {
                     let (end, symbol) = self.take_while(start, is_symbol);
 
@@ -556,7 +761,8 @@ impl<'input> Iterator for Lexer<'input> {
                         ";" => Ok((start, Token::Semi, end)),
                         symbol if symbol.starts_with("|||") => Ok(self.doc_comment(start)),
                         symbol if symbol.starts_with("--") => {
-                            self.take_until(start, |ch| ch == '\n');
+                            let (end, _) = self.take_until(start, |ch| ch == '\n');
+                            self.push_comment(start, end);
                             continue;
                         },
                         _ => Err(LexerError::UnexpectedCharacter { start, found: ch }),
@@ -571,11 +777,8 @@ impl<'input> Iterator for Lexer<'input> {
                 ']' => Ok((start, Token::RBracket, end)),
                 '"' => self.string_literal(start),
                 '\'' => self.char_literal(start),
-                '0' if self.test_lookahead(|x| x == 'b') => self.bin_literal(start),
-                '0' if self.test_lookahead(|x| x == 'o') => self.oct_literal(start),
-                '0' if self.test_lookahead(|x| x == 'x') => self.hex_literal(start),
                 ch if is_ident_start(ch) => Ok(self.ident(start)),
-                ch if is_dec_digit(ch) => self.dec_literal(start),
+                ch if is_dec_digit(ch) => self.numeric_literal(start, ch),
                 ch if ch.is_whitespace() => continue,
                 _ => Err(LexerError::UnexpectedCharacter { start, found: ch }),
             });
@@ -652,6 +855,56 @@ mod tests {
         };
     }
 
+    #[test]
+    fn escapes() {
+        test! {
+            r#"  "\u{1F600}\x41\0" '\u{e9}'  "#,
+            r#"  ~~~~~~~~~~~~~~~~~           "# => Token::StringLiteral("\u{1F600}A\0".to_owned()),
+            r#"                    ~~~~~~~~  "# => Token::CharLiteral('\u{e9}'),
+        };
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        let first_error = |src: &str| {
+            let mut codemap = CodeMap::new();
+            let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+            Lexer::new(&filemap).find_map(Result::err).unwrap()
+        };
+        let span = |start, end| ByteSpan::new(ByteIndex(start), ByteIndex(end));
+
+        assert_eq!(
+            first_error(r#""\u{}""#),
+            LexerError::MalformedUnicodeEscape { span: span(2, 6) },
+        );
+        assert_eq!(
+            first_error(r#""\u1F600""#),
+            LexerError::MalformedUnicodeEscape { span: span(2, 4) },
+        );
+        assert_eq!(
+            first_error(r#""\u{1F600""#),
+            LexerError::MalformedUnicodeEscape { span: span(2, 10) },
+        );
+        assert_eq!(
+            first_error(r#""\u{D800}""#),
+            LexerError::InvalidUnicodeEscape {
+                span: span(2, 10),
+                value: 0xD800,
+            },
+        );
+        assert_eq!(
+            first_error(r#"'\x4'"#),
+            LexerError::MalformedByteEscape { span: span(2, 5) },
+        );
+        assert_eq!(
+            first_error(r#"'\xFF'"#),
+            LexerError::ByteEscapeOutOfRange {
+                span: span(2, 6),
+                value: 0xFF,
+            },
+        );
+    }
+
     #[test]
     fn bin_literal() {
         test! {
@@ -676,6 +929,17 @@ mod tests {
         };
     }
 
+    #[test]
+    fn negative_literals() {
+        test! {
+            "  -123 -0x1F -1.5 ->  ",
+            "  ~~~~                " => Token::DecIntLiteral(-123),
+            "       ~~~~~          " => Token::HexIntLiteral(-0x1F),
+            "             ~~~~     " => Token::DecFloatLiteral(-1.5),
+            "                  ~~  " => Token::LArrow,
+        };
+    }
+
     #[test]
     fn hex_literal() {
         test! {
@@ -692,22 +956,36 @@ mod tests {
         };
     }
 
+    #[test]
+    fn malformed_float_literal() {
+        let span = ByteSpan::new(ByteIndex(3), ByteIndex(8));
+
+        assert_eq!(
+            super::float_literal(span, "1.2.3"),
+            Err(LexerError::MalformedFloatLiteral {
+                span,
+                value: "1.2.3".to_owned(),
+            }),
+        );
+    }
+
     #[test]
     fn keywords() {
         test! {
-            "  as case else if import in let record Record then Type where  ",
-            "  ~~                                                              " => Token::As,
-            "     ~~~~                                                         " => Token::Case,
-            "          ~~~~                                                    " => Token::Else,
-            "               ~~                                                 " => Token::If,
-            "                  ~~~~~~                                          " => Token::Import,
-            "                         ~~                                       " => Token::In,
-            "                            ~~~                                   " => Token::Let,
-            "                                ~~~~~~                            " => Token::Record,
-            "                                       ~~~~~~                     " => Token::RecordType,
-            "                                              ~~~~                " => Token::Then,
-            "                                                   ~~~~           " => Token::Type,
-            "                                                        ~~~~~     " => Token::Where,
+            "  as case else if import in let record Record then Type Variant where  ",
+            "  ~~                                                                      " => Token::As,
+            "     ~~~~                                                                 " => Token::Case,
+            "          ~~~~                                                            " => Token::Else,
+            "               ~~                                                         " => Token::If,
+            "                  ~~~~~~                                                  " => Token::Import,
+            "                         ~~                                               " => Token::In,
+            "                            ~~~                                           " => Token::Let,
+            "                                ~~~~~~                                    " => Token::Record,
+            "                                       ~~~~~~                             " => Token::RecordType,
+            "                                              ~~~~                        " => Token::Then,
+            "                                                   ~~~~                   " => Token::Type,
+            "                                                        ~~~~~~~           " => Token::VariantType,
+            "                                                                ~~~~~     " => Token::Where,
         };
     }
 
@@ -1,7 +1,10 @@
 //! Parser utilities
 
-use codespan::{ByteIndex, ByteSpan, FileMap};
+use codespan::{ByteIndex, ByteSpan, FileMap, FileName};
 use lalrpop_util::ParseError as LalrpopError;
+use std::convert::TryFrom;
+
+use pikelet_core::syntax::Plicity;
 
 use crate::parse::lexer::Lexer;
 use crate::syntax::concrete;
@@ -10,13 +13,16 @@ mod errors;
 mod lexer;
 
 pub use self::errors::{ExpectedTokens, ParseError};
-pub use self::lexer::{LexerError, Token};
+pub use self::lexer::{Comment, LexerError, Token};
 
 macro_rules! parser {
     ($name:ident, $output:ident, $parser_name:ident) => {
+        /// Parse the contents of a file, returning the parsed syntax, the
+        /// paths of the imports that it refers to, and any errors that were
+        /// encountered along the way
         pub fn $name<'input>(
             filemap: &'input FileMap,
-        ) -> (concrete::$output, Vec<String>, Vec<ParseError>) {
+        ) -> (concrete::$output, Vec<(ByteSpan, String)>, Vec<ParseError>) {
             let mut import_paths = Vec::new();
             let mut errors = Vec::new();
             let lexer = Lexer::new(filemap).map(|x| x.map_err(ParseError::from));
@@ -35,12 +41,45 @@ macro_rules! parser {
 parser!(pattern, Pattern, PatternParser);
 parser!(term, Term, TermParser);
 
+/// Collect the line comments and doc comments in a file, in the order that
+/// they appear
+///
+/// Lexer errors are skipped over, so this can be used on files that fail to
+/// parse.
+pub fn comments(filemap: &FileMap) -> Vec<Comment> {
+    let mut lexer = Lexer::new(filemap);
+    while lexer.next().is_some() {}
+    lexer.take_comments()
+}
+
+/// Check whether `src` consists of exactly one identifier, without any
+/// surrounding whitespace
+pub fn is_identifier(src: &str) -> bool {
+    let filemap = FileMap::new(FileName::virtual_("identifier"), src.to_owned());
+    let mut tokens = Lexer::new(&filemap);
+
+    match (tokens.next(), tokens.next()) {
+        (Some(Ok((start, Token::Ident(_), end))), None) => {
+            start == filemap.span().start() && end == filemap.span().end()
+        },
+        _ => false,
+    }
+}
+
 mod grammar {
     #![allow(clippy::all)]
 
     include!(concat!(env!("OUT_DIR"), "/parse/grammar.rs"));
 }
 
+/// Convert the literal of a universe level or level shift to a `u32`,
+/// reporting an error if it is out of range
+fn level<L, T>(span: ByteSpan, value: i128) -> Result<u32, LalrpopError<L, T, ParseError>> {
+    u32::try_from(value).map_err(|_| LalrpopError::User {
+        error: ParseError::LevelOutOfRange { span, value },
+    })
+}
+
 /// This is an ugly hack that cobbles together a pi type from a binder term and
 /// a body. See the comments on the `PiTerm` rule in the `grammar.lalrpop` for
 /// more information.
@@ -51,61 +90,105 @@ fn reparse_fun_ty_hack<L, T>(
 ) -> Result<concrete::Term, LalrpopError<L, T, ParseError>> {
     use crate::syntax::concrete::Term;
 
-    fn fun_ty_binder<L, T>(
-        binder: &Term,
-    ) -> Result<Option<concrete::FunTypeParamGroup>, LalrpopError<L, T, ParseError>> {
-        match *binder {
-            Term::Parens(_, ref term) => match **term {
-                Term::Ann(ref params, ref ann) => {
-                    let mut names = Vec::new();
-                    param_names(&**params, &mut names)?;
-                    Ok(Some((names, (**ann).clone())))
-                },
-                _ => Ok(None),
-            },
-            _ => Ok(None),
-        }
-    }
-
-    fn param_names<L, T>(
-        term: &Term,
-        names: &mut Vec<(ByteIndex, String)>,
-    ) -> Result<(), LalrpopError<L, T, ParseError>> {
-        match *term {
-            Term::Name(span, ref name, None) => names.push((span.start(), name.clone())),
-            Term::FunApp(ref head, ref args) => {
-                param_names(head, names)?;
-                for arg in args {
-                    param_names(arg, names)?;
-                }
-            },
-            _ => {
-                return Err(LalrpopError::User {
-                    error: ParseError::IdentifierExpectedInPiType { span: term.span() },
-                });
-            },
-        }
-        Ok(())
-    }
-
     match binder {
         Term::FunApp(ref head, ref args) => {
             use std::iter;
 
             let mut binders = Vec::with_capacity(args.len() + 1);
+            let params = iter::once((Plicity::Explicit, &**head))
+                .chain(args.iter().map(|&(plicity, ref arg)| (plicity, arg)));
 
-            for next in iter::once(&**head).chain(args).map(fun_ty_binder) {
-                match next? {
-                    Some((names, ann)) => binders.push((names, ann)),
+            for (plicity, param) in params {
+                match fun_ty_binder(plicity, param)? {
+                    Some(binder) => binders.push(binder),
                     None => return Ok(Term::FunArrow(Box::new(binder.clone()), Box::new(body))),
                 }
             }
 
             Ok(Term::FunType(span.start(), binders, Box::new(body)))
         },
-        binder => match fun_ty_binder(&binder)? {
+        binder => match fun_ty_binder(Plicity::Explicit, &binder)? {
             Some(binder) => Ok(Term::FunType(span.start(), vec![binder], Box::new(body))),
             None => Ok(Term::FunArrow(binder.into(), Box::new(body))),
         },
     }
 }
+
+/// Cobbles together a pi type that begins with an implicit parameter group,
+/// reparsing the remaining arguments as parameter groups
+fn reparse_implicit_fun_ty_hack<L, T>(
+    span: ByteSpan,
+    first: concrete::FunTypeParamGroup,
+    args: Vec<(Plicity, concrete::Term)>,
+    body: concrete::Term,
+) -> Result<concrete::Term, LalrpopError<L, T, ParseError>> {
+    let mut binders = Vec::with_capacity(args.len() + 1);
+    binders.push(first);
+
+    for (plicity, arg) in args {
+        match fun_ty_binder(plicity, &arg)? {
+            Some(binder) => binders.push(binder),
+            None => {
+                return Err(LalrpopError::User {
+                    error: ParseError::IdentifierExpectedInPiType { span: arg.span() },
+                });
+            },
+        }
+    }
+
+    Ok(concrete::Term::FunType(
+        span.start(),
+        binders,
+        Box::new(body),
+    ))
+}
+
+fn fun_ty_binder<L, T>(
+    plicity: Plicity,
+    binder: &concrete::Term,
+) -> Result<Option<concrete::FunTypeParamGroup>, LalrpopError<L, T, ParseError>> {
+    use crate::syntax::concrete::Term;
+
+    let (params, ann) = match (plicity, binder) {
+        (Plicity::Explicit, &Term::Parens(_, ref term)) => match **term {
+            Term::Ann(ref params, ref ann) => (params, ann),
+            _ => return Ok(None),
+        },
+        (Plicity::Implicit, &Term::Ann(ref params, ref ann)) => (params, ann),
+        (_, _) => return Ok(None),
+    };
+
+    let mut names = Vec::new();
+    param_names(&**params, &mut names)?;
+    Ok(Some((plicity, names, (**ann).clone())))
+}
+
+fn param_names<L, T>(
+    term: &concrete::Term,
+    names: &mut Vec<(ByteIndex, String)>,
+) -> Result<(), LalrpopError<L, T, ParseError>> {
+    use crate::syntax::concrete::Term;
+
+    match *term {
+        Term::Name(span, ref name, None) => names.push((span.start(), name.clone())),
+        Term::FunApp(ref head, ref args) => {
+            param_names(head, names)?;
+            for &(plicity, ref arg) in args {
+                match plicity {
+                    Plicity::Explicit => param_names(arg, names)?,
+                    Plicity::Implicit => {
+                        return Err(LalrpopError::User {
+                            error: ParseError::IdentifierExpectedInPiType { span: arg.span() },
+                        });
+                    },
+                }
+            }
+        },
+        _ => {
+            return Err(LalrpopError::User {
+                error: ParseError::IdentifierExpectedInPiType { span: term.span() },
+            });
+        },
+    }
+    Ok(())
+}
@@ -1,95 +1,95 @@
 use codespan::{ByteIndex, ByteSpan};
 use im;
-use moniker::{Binder, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
 
-use pikelet_core::syntax::{core, domain};
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::core;
+use pikelet_core::syntax::{Label, Level, LevelShift, Name, Plicity, VarIndex};
 
 use crate::syntax::{concrete, FloatFormat, IntFormat};
 
 /// The environment used when resugaring from the core to the concrete syntax
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ResugarEnv {
     usages: im::HashMap<String, u32>,
-    renames: im::HashMap<FreeVar<String>, String>,
+    /// The names chosen for the local binders, indexed by their de Bruijn
+    /// levels
+    names: im::Vector<String>,
 }
 
 const KEYWORDS: &[&str] = &[
-    "as", "case", "else", "if", "import", "in", "let", "record", "Record", "then", "Type", "where",
+    "as", "case", "else", "if", "import", "in", "let", "record", "Record", "then", "Type",
+    "Variant", "where",
 ];
 
 impl ResugarEnv {
     pub fn new() -> ResugarEnv {
         ResugarEnv {
             usages: KEYWORDS.iter().map(|&kw| (kw.to_owned(), 0)).collect(),
-            renames: im::HashMap::new(),
+            names: im::Vector::new(),
         }
     }
 
-    pub fn on_item(&mut self, label: &Label, binder: &Binder<String>) -> String {
-        let Label(ref name) = *label;
-        let Binder(ref free_var) = *binder;
-
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
-            match self.usages.get(name).cloned() {
-                Some(count) => {
-                    let count = count + 1;
-                    let mapped_name = format!("{}{}", name, count);
+    /// Pick a name based on `name` that has not been used yet
+    fn fresh_name(&mut self, name: &str) -> String {
+        match self.usages.get(name).cloned() {
+            Some(count) => {
+                let count = count + 1;
+                let mapped_name = format!("{}{}", name, count);
 
-                    self.usages.insert(name.clone(), count);
-                    self.usages.insert(mapped_name.clone(), count);
-                    self.renames.insert(free_var.clone(), mapped_name.clone());
+                self.usages.insert(name.to_owned(), count);
+                self.usages.insert(mapped_name.clone(), count);
 
-                    mapped_name
-                },
-                None => {
-                    self.usages.insert(name.clone(), 0);
-                    self.renames.insert(free_var.clone(), name.clone());
+                mapped_name
+            },
+            None => {
+                self.usages.insert(name.to_owned(), 0);
 
-                    name.clone()
-                },
-            }
-        })
+                name.to_owned()
+            },
+        }
     }
 
-    // pub fn on_binder(&mut self, binder: &Binder<String>, free_vars: &HashSet<String>) -> String {
-    pub fn on_binder(&mut self, binder: &Binder<String>) -> String {
-        let Binder(ref free_var) = *binder;
-
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
-            let pretty_name = match free_var.pretty_name {
-                Some(ref name) => name.clone(),
-                None => "a".to_owned(),
-            };
-
-            match self.usages.get(&pretty_name).cloned() {
-                Some(count) => {
-                    let count = count + 1;
-                    let mapped_name = format!("{}{}", pretty_name, count);
+    /// Record the name of a global definition, so that local binders will be
+    /// renamed rather than shadowing it
+    pub fn on_global(&mut self, name: &str) {
+        if !self.usages.contains_key(name) {
+            self.usages.insert(name.to_owned(), 0);
+        }
+    }
 
-                    self.usages.insert(pretty_name, count);
-                    self.usages.insert(mapped_name.clone(), count);
-                    self.renames.insert(free_var.clone(), mapped_name.clone());
+    /// Bring the binder of a record field into scope, naming it after the
+    /// label of the field
+    pub fn on_item(&mut self, label: &Label) -> String {
+        let Label(ref name) = *label;
+        let mapped_name = self.fresh_name(name);
+        self.names.push_back(mapped_name.clone());
+        mapped_name
+    }
 
-                    mapped_name
-                },
-                None => {
-                    self.usages.insert(pretty_name.clone(), 0);
-                    self.renames.insert(free_var.clone(), pretty_name.clone());
+    /// Bring a binder into scope, returning the name that it was given
+    pub fn on_binder(&mut self, name: &Name) -> String {
+        let mapped_name = self.fresh_name(name.as_str().unwrap_or("a"));
+        self.names.push_back(mapped_name.clone());
+        mapped_name
+    }
 
-                    pretty_name
-                },
-            }
-        })
+    /// Bring a binder into scope that can never be referred to
+    fn on_wildcard(&mut self) {
+        self.names.push_back("_".to_owned());
     }
 
-    pub fn on_free_var(&self, free_var: &FreeVar<String>) -> String {
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
-            panic!(
-                "on_free_var: expected {} to be bound in resugar environment",
-                free_var,
-            );
-        })
+    /// The name of the local binder at `index`
+    pub fn on_var(&self, index: VarIndex) -> String {
+        let size = self.names.len();
+        match size
+            .checked_sub(index.0 + 1)
+            .and_then(|level| self.names.get(level))
+        {
+            Some(name) => name.clone(),
+            None => panic!(
+                "on_var: expected {} to be bound in resugar environment",
+                index,
+            ),
+        }
     }
 }
 
@@ -128,33 +128,48 @@ fn parens_if(should_wrap: bool, inner: concrete::Term) -> concrete::Term {
     }
 }
 
+fn parens_pattern_if(should_wrap: bool, inner: concrete::Pattern) -> concrete::Pattern {
+    if should_wrap {
+        concrete::Pattern::Parens(ByteSpan::default(), Box::new(inner))
+    } else {
+        inner
+    }
+}
+
+/// Resugar a pattern, bringing its binders into scope in `env`
+///
+/// The variables that the pattern refers to are looked up in `outer`, the
+/// environment from before the pattern.
 fn resugar_pattern(
+    outer: &ResugarEnv,
     env: &mut ResugarEnv,
     pattern: &core::Pattern,
-    _prec: Prec,
+    prec: Prec,
 ) -> concrete::Pattern {
     match *pattern {
-        core::Pattern::Ann(ref pattern, Embed(ref ty)) => concrete::Pattern::Ann(
-            Box::new(resugar_pattern(env, pattern, Prec::NO_WRAP)),
-            Box::new(resugar_term(env, ty, Prec::LAM)),
+        core::Pattern::Ann(ref pattern, ref ty) => parens_pattern_if(
+            Prec::ANN < prec,
+            concrete::Pattern::Ann(
+                Box::new(resugar_pattern(outer, env, pattern, Prec::NO_WRAP)),
+                Box::new(resugar_term(outer, ty, Prec::LAM)),
+            ),
         ),
-        core::Pattern::Binder(ref binder) => {
-            let name = env.on_binder(binder);
+        core::Pattern::Binder(ref name) if name.as_str() == Some("_") => {
+            env.on_wildcard();
+            concrete::Pattern::Name(ByteSpan::default(), "_".to_owned(), None)
+        },
+        core::Pattern::Binder(ref name) => {
+            let name = env.on_binder(name);
             concrete::Pattern::Name(ByteSpan::default(), name, None)
         },
-        core::Pattern::Var(Embed(Var::Free(ref free_var)), shift) => {
-            let shift = match shift {
-                LevelShift(0) => None,
-                LevelShift(shift) => Some(shift),
-            };
-
-            let name = env.on_free_var(free_var);
-            concrete::Pattern::Name(ByteSpan::default(), name, shift)
+        core::Pattern::Var(index, shift) => {
+            let name = outer.on_var(index);
+            concrete::Pattern::Name(ByteSpan::default(), name, resugar_shift(shift))
         },
-        core::Pattern::Var(Embed(Var::Bound(_)), _) => {
-            // TODO: Better message
-            panic!("Tried to convert a term that was not locally closed");
+        core::Pattern::Global(ref name, shift) => {
+            concrete::Pattern::Name(ByteSpan::default(), name.clone(), resugar_shift(shift))
         },
+        core::Pattern::Error => concrete::Pattern::Error(ByteSpan::default()),
         core::Pattern::Literal(ref literal) => {
             use pikelet_core::syntax::Literal;
 
@@ -171,45 +186,65 @@ fn resugar_pattern(
                 Literal::String(ref val) => Pattern::Literal(String(span, val.clone())),
                 Literal::Char(val) => Pattern::Literal(Char(span, val)),
 
-                Literal::U8(val) => Pattern::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U16(val) => Pattern::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U32(val) => Pattern::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U64(val) => Pattern::Literal(Int(span, val, IntFormat::Dec)),
+                Literal::U8(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U16(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U32(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U64(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
 
-                // FIXME: Underflow for negative numbers
-                Literal::S8(val) => Pattern::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S16(val) => Pattern::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S32(val) => Pattern::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S64(val) => Pattern::Literal(Int(span, val as u64, IntFormat::Dec)),
+                Literal::S8(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S16(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S32(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S64(val) => Pattern::Literal(Int(span, i128::from(val), IntFormat::Dec)),
 
                 Literal::F32(v) => Pattern::Literal(Float(span, f64::from(v), FloatFormat::Dec)),
                 Literal::F64(v) => Pattern::Literal(Float(span, v, FloatFormat::Dec)),
             }
         },
+        core::Pattern::VariantIntro(Label(ref label), ref patterns) if patterns.is_empty() => {
+            concrete::Pattern::Name(ByteSpan::default(), label.clone(), None)
+        },
+        core::Pattern::VariantIntro(Label(ref label), ref patterns) => parens_pattern_if(
+            Prec::APP < prec,
+            concrete::Pattern::VariantIntro(
+                ByteSpan::default(),
+                label.clone(),
+                patterns
+                    .iter()
+                    .map(|pattern| resugar_pattern(outer, env, pattern, Prec::ATOMIC))
+                    .collect(),
+            ),
+        ),
+    }
+}
+
+fn resugar_shift(shift: LevelShift) -> Option<u32> {
+    match shift {
+        LevelShift(0) => None,
+        LevelShift(shift) => Some(shift),
     }
 }
 
 fn resugar_fun_ty(
     env: &ResugarEnv,
-    scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
+    plicity: Plicity,
+    name: &Name,
+    ann: &core::RcTerm,
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
 
-    let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
-    let body_fvs = body.free_vars();
-
-    // Only use explicit parameter names if the body is dependent on
-    // the parameter or there is a human-readable name given.
+    // Only use explicit parameter names if the parameter is implicit, the body
+    // is dependent on the parameter, or there is a human-readable name given.
     //
     // We'll be checking for readable names as we go, because if they've
     // survived until now they're probably desirable to retain!
-    if body_fvs.contains(&binder.0) || binder.0.pretty_name.is_some() {
-        let name = env.on_binder(&binder);
-        let mut params = vec![(
-            vec![(ByteIndex::default(), name)],
-            resugar_term(&env, &ann, Prec::APP),
-        )];
+    if plicity == Plicity::Implicit || body.mentions(VarIndex(0)) || name.as_str().is_some() {
+        let ann_term = resugar_term(&env, ann, Prec::APP);
+        let name = env.on_binder(name);
+        let mut params = vec![(plicity, vec![(ByteIndex::default(), name)], ann_term)];
+        let mut ann = ann.clone();
+        let mut body = body.clone();
 
         // Parameter resugaring
         //
@@ -220,39 +255,53 @@ fn resugar_fun_ty(
         // (a : Type) -> (b : Type -> Type) -> ...
         // (a : Type) (b : Type -> Type) -> ...
         // ```
-        while let core::Term::FunType(ref scope) = *body {
-            let ((next_binder, Embed(next_ann)), next_body) = scope.clone().unbind();
+        loop {
+            let (next_plicity, next_name, next_ann, next_body) = match *body.inner {
+                core::Term::FunType(next_plicity, ref next_name, ref next_ann, ref next_body) => {
+                    (next_plicity, next_name.clone(), next_ann.clone(), next_body.clone())
+                },
+                _ => break,
+            };
 
-            if core::Term::term_eq(&ann, &next_ann) && next_binder.0.pretty_name.is_some() {
-                // Combine the parameters if the type annotations are
-                // alpha-equivalent. For example:
+            if params.last().unwrap().0 == next_plicity
+                && next_ann == ann.shift(1)
+                && next_name.as_str().is_some()
+            {
+                // Combine the parameters if they have the same plicity and
+                // the type annotations are alpha-equivalent. For example:
                 //
                 // ```
                 // (a : Type) (b : Type) -> ...
                 // (a b : Type) -> ...
                 // ```
-                let next_name = env.on_binder(&next_binder);
+                let next_name = env.on_binder(&next_name);
                 let next_param = (ByteIndex::default(), next_name);
-                params.last_mut().unwrap().0.push(next_param);
-            } else if next_body.free_vars().contains(&next_binder.0)
-                || next_binder.0.pretty_name.is_some()
+                params.last_mut().unwrap().1.push(next_param);
+            } else if next_plicity == Plicity::Implicit
+                || next_body.mentions(VarIndex(0))
+                || next_name.as_str().is_some()
             {
-                // Add a new parameter if the body is dependent on the parameter
-                // or there is a human-readable name given
-                let next_name = env.on_binder(&next_binder);
+                // Add a new parameter if it is implicit, the body is dependent
+                // on the parameter, or there is a human-readable name given
+                let next_ann_term = resugar_term(&env, &next_ann, Prec::APP);
+                let next_name = env.on_binder(&next_name);
                 params.push((
+                    next_plicity,
                     vec![(ByteIndex::default(), next_name)],
-                    resugar_term(&env, &next_ann, Prec::APP),
+                    next_ann_term,
                 ));
             } else {
                 // Stop collapsing parameters if we encounter a non-dependent pi type.
+                let next_ann_term = resugar_term(&env, &next_ann, Prec::APP);
+                env.on_binder(&next_name);
+
                 return parens_if(
                     Prec::PI < prec,
                     concrete::Term::FunType(
                         ByteIndex::default(),
                         params,
                         Box::new(concrete::Term::FunArrow(
-                            Box::new(resugar_term(&env, &next_ann, Prec::APP)),
+                            Box::new(next_ann_term),
                             Box::new(resugar_term(&env, &next_body, Prec::LAM)),
                         )),
                     ),
@@ -279,11 +328,14 @@ fn resugar_fun_ty(
         // (a : Type) -> Type
         // Type -> Type
         // ```
+        let ann_term = resugar_term(&env, ann, Prec::APP);
+        env.on_binder(name);
+
         parens_if(
             Prec::PI < prec,
             concrete::Term::FunArrow(
-                Box::new(resugar_term(&env, &ann, Prec::APP)),
-                Box::new(resugar_term(&env, &body, Prec::LAM)),
+                Box::new(ann_term),
+                Box::new(resugar_term(&env, body, Prec::LAM)),
             ),
         )
     }
@@ -291,18 +343,23 @@ fn resugar_fun_ty(
 
 fn resugar_fun_intro(
     env: &ResugarEnv,
-    scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
+    plicity: Plicity,
+    name: &Name,
+    ann: &core::RcTerm,
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
 
-    let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
-
-    let name = env.on_binder(&binder);
+    let ann_term = resugar_term(&env, ann, Prec::LAM);
+    let name = env.on_binder(name);
     let mut params = vec![(
+        plicity,
         vec![(ByteIndex::default(), name)],
-        Some(Box::new(resugar_term(&env, &ann, Prec::LAM))),
+        Some(Box::new(ann_term)),
     )];
+    let mut ann = ann.clone();
+    let mut body = body.clone();
 
     // Parameter resugaring
     //
@@ -313,24 +370,32 @@ fn resugar_fun_intro(
     // \(a : Type) => \(b : Type -> Type) => ...
     // \(a : Type) (b : Type -> Type) => ...
     // ```
-    while let core::Term::FunIntro(ref scope) = *body {
-        let ((next_binder, Embed(next_ann)), next_body) = scope.clone().unbind();
+    loop {
+        let (next_plicity, next_name, next_ann, next_body) = match *body.inner {
+            core::Term::FunIntro(next_plicity, ref next_name, ref next_ann, ref next_body) => {
+                (next_plicity, next_name.clone(), next_ann.clone(), next_body.clone())
+            },
+            _ => break,
+        };
 
-        // Combine the parameters if the type annotations are alpha-equivalent.
-        // For example:
+        // Combine the parameters if they have the same plicity and the type
+        // annotations are alpha-equivalent. For example:
         //
         // ```
         // \(a : Type) (b : Type) => ...
         // \(a b : Type) => ...
         // ```
-        let next_name = env.on_binder(&next_binder);
-        if core::Term::term_eq(&ann, &next_ann) {
+        if params.last().unwrap().0 == next_plicity && next_ann == ann.shift(1) {
+            let next_name = env.on_binder(&next_name);
             let next_param = (ByteIndex::default(), next_name);
-            params.last_mut().unwrap().0.push(next_param);
+            params.last_mut().unwrap().1.push(next_param);
         } else {
+            let next_ann_term = resugar_term(&env, &next_ann, Prec::LAM);
+            let next_name = env.on_binder(&next_name);
             params.push((
+                next_plicity,
                 vec![(ByteIndex::default(), next_name)],
-                Some(Box::new(resugar_term(&env, &next_ann, Prec::LAM))),
+                Some(Box::new(next_ann_term)),
             ));
         }
 
@@ -348,97 +413,96 @@ fn resugar_fun_intro(
     )
 }
 
+/// Split the doc comments of a binding back into lines
+fn doc_lines(doc: &str) -> Vec<String> {
+    doc.lines().map(str::to_owned).collect()
+}
+
+/// Pull the parameters of a function introduction out from its body, so that
+/// they can be placed on a definition instead
+fn definition_params(term: concrete::Term) -> (concrete::FunIntroParams, concrete::Term) {
+    match term {
+        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
+        term_body => (vec![], term_body),
+    }
+}
+
+/// Resugar a let binding into items, bringing its binder into scope in `env`
+///
+/// Recursive definitions are turned back into annotated definitions, with
+/// the recursive occurrences referring to the binder of the item.
+fn resugar_let_binding(
+    env: &mut ResugarEnv,
+    items: &mut Vec<concrete::Item>,
+    name: &Name,
+    term: &core::RcTerm,
+    doc: &str,
+) {
+    let (ann, name, body) = match *term.inner {
+        core::Term::Fix(_, _, ref ann, ref body) => {
+            let ann = resugar_term(env, ann, Prec::ANN);
+            let name = env.on_binder(name);
+            (Some(ann), name, resugar_term(env, body, Prec::NO_WRAP))
+        },
+        core::Term::Ann(ref term, ref ann) => {
+            let ann = resugar_term(env, ann, Prec::ANN);
+            let body = resugar_term(env, term, Prec::NO_WRAP);
+            (Some(ann), env.on_binder(name), body)
+        },
+        _ => {
+            let body = resugar_term(env, term, Prec::NO_WRAP);
+            (None, env.on_binder(name), body)
+        },
+    };
+
+    // pull lambda arguments from the body into the definition
+    let (term_params, term_body) = definition_params(body);
+
+    let definition_doc = match ann {
+        Some(ann) => {
+            items.push(concrete::Item::Declaration {
+                doc: doc_lines(doc),
+                name: (ByteIndex::default(), name.clone()),
+                ann,
+            });
+            Vec::new()
+        },
+        None => doc_lines(doc),
+    };
+
+    items.push(concrete::Item::Definition {
+        doc: definition_doc,
+        name: (ByteIndex::default(), name),
+        params: term_params,
+        return_ann: None,
+        body: term_body,
+    });
+}
+
 fn resugar_let(
     env: &ResugarEnv,
-    scope: &Scope<Nest<(Binder<String>, Embed<core::RcTerm>)>, core::RcTerm>,
+    bindings: &[(Name, core::RcTerm, String)],
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
-
-    let (bindings, mut body) = scope.clone().unbind();
-    let bindings = bindings.unnest();
-
     let mut items = Vec::with_capacity(bindings.len() * 2);
 
-    for (binder, Embed(term)) in bindings {
-        let name = env.on_binder(&binder);
-
-        match *term.inner {
-            core::Term::Ann(ref term, ref ann) => {
-                // pull lambda arguments from the body into the definition
-                let (term_params, term_body) = match resugar_term(&env, term, Prec::NO_WRAP) {
-                    concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                    term_body => (vec![], term_body),
-                };
-
-                items.push(concrete::Item::Declaration {
-                    name: (ByteIndex::default(), name.clone()),
-                    ann: resugar_term(&env, &ann, Prec::ANN),
-                });
-                items.push(concrete::Item::Definition {
-                    name: (ByteIndex::default(), name),
-                    params: term_params,
-                    return_ann: None,
-                    body: term_body,
-                });
-            },
-            _ => {
-                // pull lambda arguments from the body into the definition
-                let (term_params, term_body) = match resugar_term(&env, &term, Prec::NO_WRAP) {
-                    concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                    term_body => (vec![], term_body),
-                };
-
-                items.push(concrete::Item::Definition {
-                    name: (ByteIndex::default(), name),
-                    params: term_params,
-                    return_ann: None,
-                    body: term_body,
-                });
-            },
-        }
+    for &(ref name, ref term, ref doc) in bindings {
+        resugar_let_binding(&mut env, &mut items, name, term, doc);
     }
 
-    while let core::Term::Let(ref scope) = *body {
-        let (bindings, next_body) = scope.clone().unbind();
-
-        for (binder, Embed(term)) in bindings.unnest() {
-            let next_name = env.on_binder(&binder);
-            match *term.inner {
-                core::Term::Ann(ref term, ref ann) => {
-                    // pull lambda arguments from the body into the definition
-                    let (term_params, term_body) = match resugar_term(&env, term, Prec::NO_WRAP) {
-                        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                        term_body => (vec![], term_body),
-                    };
-
-                    items.push(concrete::Item::Declaration {
-                        name: (ByteIndex::default(), next_name.clone()),
-                        ann: resugar_term(&env, &ann, Prec::ANN),
-                    });
-                    items.push(concrete::Item::Definition {
-                        name: (ByteIndex::default(), next_name),
-                        params: term_params,
-                        return_ann: None,
-                        body: term_body,
-                    });
-                },
-                _ => {
-                    // pull lambda arguments from the body into the definition
-                    let (term_params, term_body) = match resugar_term(&env, &term, Prec::NO_WRAP) {
-                        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                        term_body => (vec![], term_body),
-                    };
-
-                    items.push(concrete::Item::Definition {
-                        name: (ByteIndex::default(), next_name),
-                        params: term_params,
-                        return_ann: None,
-                        body: term_body,
-                    });
-                },
-            }
-        }
+    let mut body = body.clone();
+    loop {
+        let next_body = match *body.inner {
+            core::Term::Let(ref bindings, ref next_body) => {
+                for &(ref name, ref term, ref doc) in bindings {
+                    resugar_let_binding(&mut env, &mut items, name, term, doc);
+                }
+                next_body.clone()
+            },
+            _ => break,
+        };
 
         body = next_body;
     }
@@ -453,6 +517,50 @@ fn resugar_let(
     )
 }
 
+/// Replace any level metavariables that have not been zonked away with the
+/// lowest level that they could be
+fn without_level_metas(level: &Level) -> Level {
+    level.map_metas(|_| Level::from(0))
+}
+
+fn resugar_level(env: &ResugarEnv, level: &Level, prec: Prec) -> concrete::Term {
+    let span = ByteSpan::default();
+
+    let level = without_level_metas(level);
+    let (constant, vars) = match level {
+        Level::Finite(constant, ref vars, _) => (constant, vars),
+        // FIXME: Draw this name from some environment?
+        Level::Omega => return concrete::Term::Name(span, "omega".to_owned(), None),
+    };
+
+    let mut components = Vec::with_capacity(vars.len() + 1);
+    if constant > 0 || vars.is_empty() {
+        let literal = concrete::Literal::Int(span, i128::from(constant), IntFormat::Dec);
+        components.push(concrete::Term::Literal(literal));
+    }
+    for &(index, shift) in vars {
+        let name = env.on_var(index);
+        components.push(concrete::Term::Name(span, name, resugar_shift(shift)));
+    }
+
+    // Combine the components using nested applications of `level-max`
+    let last = components.pop().unwrap();
+    let is_app = !components.is_empty();
+    let level = components.into_iter().rev().fold(last, |acc, component| {
+        let acc = match acc {
+            concrete::Term::FunApp(_, _) => parens_if(true, acc),
+            acc => acc,
+        };
+
+        concrete::Term::FunApp(
+            Box::new(concrete::Term::Name(span, "level-max".to_owned(), None)),
+            vec![(Plicity::Explicit, component), (Plicity::Explicit, acc)],
+        )
+    });
+
+    parens_if(is_app && Prec::APP < prec, level)
+}
+
 fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Term {
     match *term {
         core::Term::Ann(ref term, ref ty) => parens_if(
@@ -462,17 +570,18 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 Box::new(resugar_term(env, ty, Prec::ANN)),
             ),
         ),
-        core::Term::Universe(level) => {
-            let level = match level {
-                Level(0) => None,
-                Level(level) => Some(level),
-            };
-
-            parens_if(
-                Prec::APP < prec && level.is_some(),
-                concrete::Term::Universe(ByteSpan::default(), level),
-            )
+        core::Term::Universe(ref level) => match without_level_metas(level).as_constant() {
+            Some(0) => concrete::Term::Universe(ByteSpan::default(), None),
+            Some(level) => parens_if(
+                Prec::APP < prec,
+                concrete::Term::Universe(ByteSpan::default(), Some(level)),
+            ),
+            None => concrete::Term::UniverseAt(
+                ByteSpan::default(),
+                Box::new(resugar_level(env, level, Prec::ATOMIC)),
+            ),
         },
+        core::Term::Level(ref level) => resugar_level(env, level, prec),
         core::Term::Literal(ref literal) => {
             use pikelet_core::syntax::Literal;
 
@@ -489,64 +598,72 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 Literal::String(ref val) => Term::Literal(String(span, val.clone())),
                 Literal::Char(val) => Term::Literal(Char(span, val)),
 
-                Literal::U8(val) => Term::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U16(val) => Term::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U32(val) => Term::Literal(Int(span, u64::from(val), IntFormat::Dec)),
-                Literal::U64(val) => Term::Literal(Int(span, val, IntFormat::Dec)),
+                Literal::U8(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U16(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U32(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::U64(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
 
-                // FIXME: Underflow for negative numbers
-                Literal::S8(val) => Term::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S16(val) => Term::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S32(val) => Term::Literal(Int(span, val as u64, IntFormat::Dec)),
-                Literal::S64(val) => Term::Literal(Int(span, val as u64, IntFormat::Dec)),
+                Literal::S8(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S16(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S32(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
+                Literal::S64(val) => Term::Literal(Int(span, i128::from(val), IntFormat::Dec)),
 
                 Literal::F32(val) => Term::Literal(Float(span, f64::from(val), FloatFormat::Dec)),
                 Literal::F64(val) => Term::Literal(Float(span, val, FloatFormat::Dec)),
             }
         },
-        core::Term::Var(Var::Free(ref free_var), shift) => {
-            let shift = match shift {
-                LevelShift(0) => None,
-                LevelShift(shift) => Some(shift),
-            };
-
-            let name = env.on_free_var(free_var);
-            concrete::Term::Name(ByteSpan::default(), name, shift)
+        core::Term::Var(index, shift) => {
+            let name = env.on_var(index);
+            concrete::Term::Name(ByteSpan::default(), name, resugar_shift(shift))
         },
-        core::Term::Var(Var::Bound(_), _) => {
-            // TODO: Better message
-            panic!("Tried to convert a term that was not locally closed");
+        core::Term::Global(ref name, shift) => {
+            concrete::Term::Name(ByteSpan::default(), name.clone(), resugar_shift(shift))
         },
         core::Term::Import(ref name) => parens_if(
             Prec::LAM < prec,
             concrete::Term::Import(ByteSpan::default(), ByteSpan::default(), name.clone()),
         ),
-        core::Term::FunType(ref scope) => resugar_fun_ty(env, scope, prec),
-        core::Term::FunIntro(ref scope) => resugar_fun_intro(env, scope, prec),
-        core::Term::FunApp(ref head, ref arg) => parens_if(
+        core::Term::Meta(_) => concrete::Term::Hole(ByteSpan::default()),
+        core::Term::Error => concrete::Term::Error(ByteSpan::default()),
+        core::Term::FunType(plicity, ref name, ref ann, ref body) => {
+            resugar_fun_ty(env, plicity, name, ann, body, prec)
+        },
+        core::Term::FunIntro(plicity, ref name, ref ann, ref body) => {
+            resugar_fun_intro(env, plicity, name, ann, body, prec)
+        },
+        core::Term::FunApp(ref head, plicity, ref arg) => parens_if(
             Prec::APP < prec,
             concrete::Term::FunApp(
                 Box::new(resugar_term(env, head, Prec::NO_WRAP)),
-                vec![resugar_term(env, arg, Prec::NO_WRAP)], // TODO
+                vec![(plicity, resugar_term(env, arg, Prec::NO_WRAP))], // TODO
             ),
         ),
-        core::Term::Let(ref scope) => resugar_let(env, scope, prec),
-        core::Term::RecordType(ref scope) => {
+        core::Term::Let(ref bindings, ref body) => resugar_let(env, bindings, body, prec),
+        core::Term::Fix(_, ref name, _, _) => {
+            // Bind the definition with a let so that it can refer to itself
+            let binding = (
+                name.clone(),
+                core::RcTerm::from(term.clone()),
+                String::new(),
+            );
+            let var = core::RcTerm::from(core::Term::var(0, 0));
+
+            resugar_let(env, &[binding], &var, prec)
+        },
+        core::Term::RecordType(ref fields) => {
             let mut env = env.clone();
-            let (scope, ()) = scope.clone().unbind();
 
-            let fields = scope
-                .unnest()
-                .into_iter()
-                .map(|(label, binder, Embed(ann))| {
-                    let ann = resugar_term(&env, &ann, Prec::NO_WRAP);
-                    let name = env.on_item(&label, &binder);
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref name, ref ann)| {
+                    let ann = resugar_term(&env, ann, Prec::NO_WRAP);
+                    let mapped_name = env.on_item(label);
 
                     concrete::RecordTypeField {
                         label: (ByteIndex::default(), label.0.clone()),
-                        binder: match binder.0.pretty_name {
-                            Some(ref pretty_name) if *pretty_name == name => None,
-                            None | Some(_) => Some((ByteIndex::default(), name)),
+                        binder: match name.as_str() {
+                            Some(name) if name == mapped_name => None,
+                            None | Some(_) => Some((ByteIndex::default(), mapped_name)),
                         },
                         ann,
                     }
@@ -577,32 +694,58 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
             // TODO: Add let to rename shadowed globals?
             concrete::Term::RecordIntro(ByteSpan::default(), fields)
         },
-        core::Term::RecordProj(ref expr, Label(ref label), shift) => {
-            let shift = match shift {
-                LevelShift(0) => None,
-                LevelShift(shift) => Some(shift),
-            };
+        core::Term::RecordProj(ref expr, Label(ref label), shift) => concrete::Term::RecordProj(
+            ByteSpan::default(),
+            Box::new(resugar_term(env, expr, Prec::ATOMIC)),
+            ByteIndex::default(),
+            label.clone(),
+            resugar_shift(shift),
+        ),
+        core::Term::VariantType(ref name, ref arms) => {
+            let mut env = env.clone();
+            let mapped_name = env.on_binder(name);
+            let binder = name.as_str().map(|_| (ByteIndex::default(), mapped_name));
 
-            concrete::Term::RecordProj(
-                ByteSpan::default(),
-                Box::new(resugar_term(env, expr, Prec::ATOMIC)),
-                ByteIndex::default(),
-                label.clone(),
-                shift,
-            )
+            let arms = arms
+                .iter()
+                .map(|&(Label(ref label), ref args)| concrete::VariantTypeArm {
+                    label: (ByteIndex::default(), label.clone()),
+                    args: args
+                        .iter()
+                        .map(|arg| resugar_term(&env, arg, Prec::ATOMIC))
+                        .collect(),
+                })
+                .collect();
+
+            concrete::Term::VariantType(ByteSpan::default(), binder, arms)
         },
+        core::Term::VariantIntro(Label(ref label), ref args) if args.is_empty() => {
+            concrete::Term::Name(ByteSpan::default(), label.clone(), None)
+        },
+        core::Term::VariantIntro(Label(ref label), ref args) => parens_if(
+            Prec::APP < prec,
+            concrete::Term::FunApp(
+                Box::new(concrete::Term::Name(
+                    ByteSpan::default(),
+                    label.clone(),
+                    None,
+                )),
+                args.iter()
+                    .map(|arg| (Plicity::Explicit, resugar_term(env, arg, Prec::ATOMIC)))
+                    .collect(),
+            ),
+        ),
         // TODO: Resugar boolean patterns into if-then-else expressions?
         core::Term::Case(ref head, ref clauses) => concrete::Term::Case(
             ByteSpan::default(),
             Box::new(resugar_term(env, head, Prec::NO_WRAP)),
             clauses
                 .iter()
-                .map(|scope| {
-                    let (pattern, term) = scope.clone().unbind();
-                    let mut env = env.clone();
+                .map(|&(ref pattern, ref body)| {
+                    let mut clause_env = env.clone();
                     (
-                        resugar_pattern(&mut env, &pattern, Prec::NO_WRAP),
-                        resugar_term(&env, &term, Prec::NO_WRAP),
+                        resugar_pattern(env, &mut clause_env, pattern, Prec::NO_WRAP),
+                        resugar_term(&clause_env, body, Prec::NO_WRAP),
                     )
                 })
                 .collect(),
@@ -623,33 +766,19 @@ impl Resugar<concrete::Term> for core::Term {
     }
 }
 
-impl Resugar<concrete::Term> for domain::Value {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        // FIXME: Make this more efficient?
-        resugar_term(env, &core::Term::from(self), Prec::NO_WRAP)
-    }
-}
-
-impl Resugar<concrete::Term> for domain::Neutral {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        // FIXME: Make this more efficient?
-        resugar_term(env, &core::Term::from(self), Prec::NO_WRAP)
+impl Resugar<concrete::Pattern> for core::Pattern {
+    fn resugar(&self, env: &ResugarEnv) -> concrete::Pattern {
+        resugar_pattern(env, &mut env.clone(), self, Prec::NO_WRAP)
     }
 }
 
-impl Resugar<concrete::Term> for core::RcTerm {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
+impl Resugar<concrete::Pattern> for core::RcPattern {
+    fn resugar(&self, env: &ResugarEnv) -> concrete::Pattern {
         self.inner.resugar(env)
     }
 }
 
-impl Resugar<concrete::Term> for domain::RcValue {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        self.inner.resugar(env)
-    }
-}
-
-impl Resugar<concrete::Term> for domain::RcNeutral {
+impl Resugar<concrete::Term> for core::RcTerm {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
         self.inner.resugar(env)
     }
@@ -4,16 +4,18 @@ use codespan::{ByteIndex, ByteSpan};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 
-use crate::syntax::{FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH, PRETTY_INDENT_WIDTH};
+use pikelet_core::syntax::Plicity;
+
+use crate::syntax::{self, FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH, PRETTY_INDENT_WIDTH};
 
 /// A group of lambda parameters that share an annotation
-pub type FunIntroParamGroup = (Vec<(ByteIndex, String)>, Option<Box<Term>>);
+pub type FunIntroParamGroup = (Plicity, Vec<(ByteIndex, String)>, Option<Box<Term>>);
 
 /// The parameters to a lambda abstraction
 pub type FunIntroParams = Vec<FunIntroParamGroup>;
 
 /// A group of parameters to a dependent function that share an annotation
-pub type FunTypeParamGroup = (Vec<(ByteIndex, String)>, Term);
+pub type FunTypeParamGroup = (Plicity, Vec<(ByteIndex, String)>, Term);
 
 /// The parameters to a dependent function type
 pub type FunTypeParams = Vec<FunTypeParamGroup>;
@@ -25,6 +27,12 @@ pub struct RecordTypeField {
     pub ann: Term,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantTypeArm {
+    pub label: (ByteIndex, String),
+    pub args: Vec<Term>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RecordIntroField {
     Punned {
@@ -48,6 +56,8 @@ pub enum Item {
     /// foo : some-type
     /// ```
     Declaration {
+        /// The doc comments that preceded the declaration, one per line
+        doc: Vec<String>,
         name: (ByteIndex, String),
         ann: Term,
     },
@@ -56,8 +66,11 @@ pub enum Item {
     /// ```text
     /// foo = some-body
     /// foo x (y : some-type) = some-body
+    /// foo {a} (y : a) = some-body
     /// ```
     Definition {
+        /// The doc comments that preceded the definition, one per line
+        doc: Vec<String>,
         name: (ByteIndex, String),
         params: FunIntroParams,
         return_ann: Option<Box<Term>>,
@@ -81,13 +94,26 @@ impl Item {
             | Item::Declaration {
                 name: (start, _),
                 ann: ref term,
+                ..
             } => ByteSpan::new(start, term.span().end()),
             Item::Error(span) => span,
         }
     }
 
-    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+    /// Return the doc comments that preceded this item, one per line
+    pub fn doc(&self) -> &[String] {
         match *self {
+            Item::Declaration { ref doc, .. } | Item::Definition { ref doc, .. } => doc,
+            Item::Error(_) => &[],
+        }
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        let doc = Doc::concat(self.doc().iter().map(|line| {
+            Doc::text(format!("||| {}", line).trim_end().to_owned()).append(Doc::newline())
+        }));
+
+        doc.append(match *self {
             Item::Declaration {
                 name: (_, ref name),
                 ref ann,
@@ -102,6 +128,7 @@ impl Item {
                 ref params,
                 ref return_ann,
                 ref body,
+                ..
             } => Doc::as_string(name)
                 .append(Doc::space())
                 .append(match params[..] {
@@ -117,7 +144,7 @@ impl Item {
                 .append(Doc::space())
                 .append(body.to_doc().nest(PRETTY_INDENT_WIDTH)),
             Item::Error(_) => Doc::text("<error>"),
-        }
+        })
         .append(";")
     }
 }
@@ -139,7 +166,7 @@ pub enum Literal {
     Char(ByteSpan, char),
     /// Integer literals
     // TODO: Preserve digit separators?
-    Int(ByteSpan, u64, IntFormat),
+    Int(ByteSpan, i128, IntFormat),
     /// Floating point literals
     // TODO: Preserve digit separators?
     Float(ByteSpan, f64, FloatFormat),
@@ -158,12 +185,9 @@ impl Literal {
 
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Literal::String(_, ref value) => Doc::text(format!("{:?}", value)),
-            Literal::Char(_, value) => Doc::text(format!("{:?}", value)),
-            Literal::Int(_, value, IntFormat::Bin) => Doc::text(format!("0b{:b}", value)),
-            Literal::Int(_, value, IntFormat::Oct) => Doc::text(format!("0o{:o}", value)),
-            Literal::Int(_, value, IntFormat::Dec) => Doc::text(format!("{}", value)),
-            Literal::Int(_, value, IntFormat::Hex) => Doc::text(format!("0x{:x}", value)),
+            Literal::String(_, ref value) => Doc::text(syntax::string_literal(value)),
+            Literal::Char(_, value) => Doc::text(syntax::char_literal(value)),
+            Literal::Int(_, value, format) => Doc::text(format.display(value)),
             Literal::Float(_, value, FloatFormat::Dec) => Doc::text(format!("{}", value)),
         }
     }
@@ -195,6 +219,14 @@ pub enum Pattern {
     /// false
     /// ```
     Name(ByteSpan, String, Option<u32>),
+    /// Patterns that match a variant constructor that was applied to some
+    /// arguments
+    ///
+    /// ```text
+    /// some x
+    /// cons x (cons y xs)
+    /// ```
+    VariantIntro(ByteSpan, String, Vec<Pattern>),
     /// Terms that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -205,7 +237,10 @@ impl Pattern {
     /// Return the span of source code that this pattern originated from
     pub fn span(&self) -> ByteSpan {
         match *self {
-            Pattern::Parens(span, _) | Pattern::Name(span, _, _) | Pattern::Error(span) => span,
+            Pattern::Parens(span, _)
+            | Pattern::Name(span, _, _)
+            | Pattern::VariantIntro(span, _, _)
+            | Pattern::Error(span) => span,
             Pattern::Ann(ref pattern, ref ty) => pattern.span().to(ty.span()),
             Pattern::Literal(ref literal) => literal.span(),
         }
@@ -223,11 +258,24 @@ impl Pattern {
             Pattern::Name(_, ref name, None) => Doc::text(format!("{}", name)),
             Pattern::Name(_, ref name, Some(shift)) => Doc::text(format!("{}^{}", name, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::VariantIntro(_, ref label, ref patterns) => Doc::nil()
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    patterns.iter().map(Pattern::to_doc),
+                    Doc::space(),
+                )),
             Pattern::Error(_) => Doc::text("<error>"),
         }
     }
 }
 
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
 /// Terms
 #[derive(Debug, Clone, PartialEq)]
 pub enum Term {
@@ -249,6 +297,13 @@ pub enum Term {
     /// Type
     /// ```
     Universe(ByteSpan, Option<u32>),
+    /// Type of types at a level given by a level expression
+    ///
+    /// ```text
+    /// Type^l
+    /// Type^(level-max l m)
+    /// ```
+    UniverseAt(ByteSpan, Box<Term>),
     /// Literals
     Literal(Literal),
     /// Array literals
@@ -277,6 +332,8 @@ pub enum Term {
     /// ```text
     /// (x : t1) -> t2
     /// (x y : t1) -> t2
+    /// {x : t1} -> t2
+    /// {x : t1} (y : x) -> t2
     /// ```
     FunType(ByteIndex, FunTypeParams, Box<Term>),
     /// Non-Dependent function type
@@ -293,14 +350,16 @@ pub enum Term {
     /// \x : t1 => t2
     /// \(x : t1) y (z : t2) => t3
     /// \(x y : t1) => t3
+    /// \{x} y => t2
     /// ```
     FunIntro(ByteIndex, FunIntroParams, Box<Term>),
     /// Function application
     ///
     /// ```text
     /// e1 e2
+    /// e1 {e2}
     /// ```
-    FunApp(Box<Term>, Vec<Term>),
+    FunApp(Box<Term>, Vec<(Plicity, Term)>),
     /// Let binding
     ///
     /// ```text
@@ -315,8 +374,8 @@ pub enum Term {
     /// ```text
     /// id "hello"
     /// where {
-    ///     id : (A : Type) -> A -> A;
-    ///     id A x = x;
+    ///     id : {A : Type} -> A -> A;
+    ///     id x = x;
     /// }
     /// ```
     Where(Box<Term>, Vec<Item>, ByteIndex),
@@ -352,6 +411,13 @@ pub enum Term {
     /// e.l^1
     /// ```
     RecordProj(ByteSpan, Box<Term>, ByteIndex, String, Option<u32>),
+    /// Variant type
+    ///
+    /// ```text
+    /// Variant { none; some t1 }
+    /// Variant Nat { zero; succ Nat }
+    /// ```
+    VariantType(ByteSpan, Option<(ByteIndex, String)>, Vec<VariantTypeArm>),
     /// Terms that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -364,6 +430,7 @@ impl Term {
         match *self {
             Term::Parens(span, ..)
             | Term::Universe(span, ..)
+            | Term::UniverseAt(span, ..)
             | Term::Hole(span)
             | Term::Name(span, ..)
             | Term::Import(span, ..)
@@ -371,6 +438,7 @@ impl Term {
             | Term::RecordType(span, ..)
             | Term::RecordIntro(span, ..)
             | Term::RecordProj(span, ..)
+            | Term::VariantType(span, ..)
             | Term::ArrayIntro(span, ..)
             | Term::Error(span) => span,
             Term::Literal(ref literal) => literal.span(),
@@ -381,7 +449,7 @@ impl Term {
             Term::Where(ref expr, _, end) => ByteSpan::new(expr.span().start(), end),
             Term::Ann(ref term, ref ty) => term.span().to(ty.span()),
             Term::FunArrow(ref ann, ref body) => ann.span().to(body.span()),
-            Term::FunApp(ref head, ref arg) => head.span().to(arg.last().unwrap().span()),
+            Term::FunApp(ref head, ref args) => head.span().to(args.last().unwrap().1.span()),
         }
     }
 
@@ -396,6 +464,7 @@ impl Term {
                 .append(ty.to_doc()),
             Term::Universe(_, None) => Doc::text("Type"),
             Term::Universe(_, Some(level)) => Doc::text(format!("Type^{}", level)),
+            Term::UniverseAt(_, ref level) => Doc::text("Type^").append(level.to_doc()),
             Term::Literal(ref literal) => literal.to_doc(),
             Term::ArrayIntro(_, ref elems) => Doc::nil()
                 .append("[")
@@ -410,7 +479,7 @@ impl Term {
             Term::Import(_, _, ref name) => Doc::nil()
                 .append("import")
                 .append(Doc::space())
-                .append(format!("{:?}", name)),
+                .append(syntax::string_literal(name)),
             Term::FunIntro(_, ref params, ref body) => Doc::nil()
                 .append("\\")
                 .append(pretty_fun_intro_params(params))
@@ -430,9 +499,15 @@ impl Term {
                 .append("->")
                 .append(Doc::space())
                 .append(body.to_doc()),
-            Term::FunApp(ref head, ref args) => head.to_doc().append(Doc::space()).append(
-                Doc::intersperse(args.iter().map(|arg| arg.to_doc()), Doc::space()),
-            ),
+            Term::FunApp(ref head, ref args) => {
+                head.to_doc().append(Doc::space()).append(Doc::intersperse(
+                    args.iter().map(|&(plicity, ref arg)| match plicity {
+                        Plicity::Explicit => arg.to_doc(),
+                        Plicity::Implicit => Doc::text("{").append(arg.to_doc()).append("}"),
+                    }),
+                    Doc::space(),
+                ))
+            },
             Term::Let(_, ref items, ref body) => {
                 Doc::nil()
                     .append("let")
@@ -568,6 +643,26 @@ impl Term {
                 .append(expr.to_doc())
                 .append(".")
                 .append(format!("{}^{}", label, shift)),
+            Term::VariantType(_, ref binder, ref arms) => Doc::nil()
+                .append("Variant")
+                .append(Doc::space())
+                .append(match *binder {
+                    Some((_, ref binder)) => Doc::as_string(binder).append(Doc::space()),
+                    None => Doc::nil(),
+                })
+                .append("{")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    arms.iter().map(|arm| {
+                        Doc::group(Doc::as_string(&arm.label.1).append(Doc::concat(
+                            arm.args.iter().map(|arg| Doc::space().append(arg.to_doc())),
+                        )))
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .nest(PRETTY_INDENT_WIDTH)
+                .append(Doc::space())
+                .append("}"),
             Term::Error(_) => Doc::text("<error>"),
         }
     }
@@ -579,41 +674,61 @@ impl fmt::Display for Term {
     }
 }
 
+fn pretty_param_names(names: &[(ByteIndex, String)]) -> Doc<BoxDoc<()>> {
+    Doc::intersperse(
+        names.iter().map(|name| Doc::as_string(&name.1)),
+        Doc::space(),
+    )
+}
+
 fn pretty_fun_intro_params(params: &[FunIntroParamGroup]) -> Doc<BoxDoc<()>> {
     Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| match *ann {
-            None if names.len() == 1 => Doc::as_string(&names[0].1),
-            None => unreachable!(), // FIXME - shouldn't be possible in AST
-            Some(ref ann) => Doc::nil()
-                .append("(")
-                .append(Doc::intersperse(
-                    names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                ))
-                .append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ann.to_doc())
-                .append(")"),
-        }),
+        params
+            .iter()
+            .map(|&(plicity, ref names, ref ann)| match (plicity, ann) {
+                (Plicity::Explicit, None) if names.len() == 1 => Doc::as_string(&names[0].1),
+                (Plicity::Explicit, None) => unreachable!(), // FIXME - shouldn't be possible in AST
+                (Plicity::Explicit, Some(ref ann)) => Doc::nil()
+                    .append("(")
+                    .append(pretty_param_names(names))
+                    .append(Doc::space())
+                    .append(":")
+                    .append(Doc::space())
+                    .append(ann.to_doc())
+                    .append(")"),
+                (Plicity::Implicit, None) => Doc::nil()
+                    .append("{")
+                    .append(pretty_param_names(names))
+                    .append("}"),
+                (Plicity::Implicit, Some(ref ann)) => Doc::nil()
+                    .append("{")
+                    .append(pretty_param_names(names))
+                    .append(Doc::space())
+                    .append(":")
+                    .append(Doc::space())
+                    .append(ann.to_doc())
+                    .append("}"),
+            }),
         Doc::space(),
     )
 }
 
 fn pretty_fun_ty_params(params: &[FunTypeParamGroup]) -> Doc<BoxDoc<()>> {
     Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| {
+        params.iter().map(|&(plicity, ref names, ref ann)| {
+            let (open, close) = match plicity {
+                Plicity::Explicit => ("(", ")"),
+                Plicity::Implicit => ("{", "}"),
+            };
+
             Doc::nil()
-                .append("(")
-                .append(Doc::intersperse(
-                    names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                ))
+                .append(open)
+                .append(pretty_param_names(names))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
                 .append(ann.to_doc())
-                .append(")")
+                .append(close)
         }),
         Doc::space(),
     )
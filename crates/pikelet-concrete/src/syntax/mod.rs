@@ -3,7 +3,7 @@ use moniker::{Binder, BoundPattern, BoundTerm, OnBoundFn, OnFreeFn, ScopeState,
 pub mod concrete;
 pub mod raw;
 
-const PRETTY_INDENT_WIDTH: usize = 4;
+pub(crate) const PRETTY_INDENT_WIDTH: usize = 4;
 
 /// An effectively 'infinite' line length for when we don't have an explicit
 /// width provided for pretty printing.
@@ -12,6 +12,46 @@ const PRETTY_INDENT_WIDTH: usize = 4;
 /// `usize::MAX`, so we'll just use a really big number instead...
 pub const PRETTY_FALLBACK_WIDTH: usize = 1_000_000;
 
+/// Write out a string literal, escaping its contents so that it will be
+/// lexed back to the same string
+pub fn string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for ch in value.chars() {
+        push_escaped(&mut literal, ch, '"');
+    }
+    literal.push('"');
+    literal
+}
+
+/// Write out a character literal, escaping it so that it will be lexed back
+/// to the same character
+pub fn char_literal(value: char) -> String {
+    let mut literal = String::with_capacity(4);
+    literal.push('\'');
+    push_escaped(&mut literal, value, '\'');
+    literal.push('\'');
+    literal
+}
+
+/// Push a character onto the contents of a literal that is delimited by
+/// `quote`, escaping it if needed
+fn push_escaped(literal: &mut String, ch: char, quote: char) {
+    match ch {
+        '\\' => literal.push_str("\\\\"),
+        '\n' => literal.push_str("\\n"),
+        '\r' => literal.push_str("\\r"),
+        '\t' => literal.push_str("\\t"),
+        '\0' => literal.push_str("\\0"),
+        ch if ch == quote => {
+            literal.push('\\');
+            literal.push(ch);
+        },
+        ch if ch.is_control() => literal.push_str(&format!("\\u{{{:X}}}", ch as u32)),
+        ch => literal.push(ch),
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum IntFormat {
     Bin,
@@ -20,6 +60,22 @@ pub enum IntFormat {
     Hex,
 }
 
+impl IntFormat {
+    /// Write out an integer in this format, placing any minus sign before the
+    /// radix prefix, eg. `-0x1F`
+    pub fn display(self, value: i128) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+
+        match self {
+            IntFormat::Bin => format!("{}0b{:b}", sign, magnitude),
+            IntFormat::Oct => format!("{}0o{:o}", sign, magnitude),
+            IntFormat::Dec => format!("{}{}", sign, magnitude),
+            IntFormat::Hex => format!("{}0x{:x}", sign, magnitude),
+        }
+    }
+}
+
 impl<N: Clone + PartialEq> BoundTerm<N> for IntFormat {
     fn term_eq(&self, _: &IntFormat) -> bool {
         true
@@ -2,22 +2,25 @@
 //! be elaborated in a type-directed way during type checking and inference
 
 use codespan::ByteSpan;
-use moniker::{Binder, BoundPattern, BoundTerm, Embed, Nest, Scope, Var};
+use moniker::{
+    Binder, BoundPattern, BoundTerm, Embed, Ignore, Nest, OnBoundFn, OnFreeFn, Scope, ScopeState,
+    Var,
+};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 use std::ops;
 use std::rc::Rc;
 
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::{Label, Level, LevelShift, Plicity};
 
-use crate::syntax::{FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH};
+use crate::syntax::{self, FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH};
 
 /// Literals
-#[derive(Debug, Clone, PartialEq, PartialOrd, BoundTerm, BoundPattern)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Literal {
     String(ByteSpan, String),
     Char(ByteSpan, char),
-    Int(ByteSpan, u64, IntFormat),
+    Int(ByteSpan, i128, IntFormat),
     Float(ByteSpan, f64, FloatFormat),
 }
 
@@ -34,17 +37,48 @@ impl Literal {
 
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Literal::String(_, ref value) => Doc::text(format!("{:?}", value)),
-            Literal::Char(_, value) => Doc::text(format!("{:?}", value)),
-            Literal::Int(_, value, IntFormat::Bin) => Doc::text(format!("0b{:b}", value)),
-            Literal::Int(_, value, IntFormat::Oct) => Doc::text(format!("0o{:o}", value)),
-            Literal::Int(_, value, IntFormat::Dec) => Doc::text(format!("{}", value)),
-            Literal::Int(_, value, IntFormat::Hex) => Doc::text(format!("0x{:x}", value)),
+            Literal::String(_, ref value) => Doc::text(syntax::string_literal(value)),
+            Literal::Char(_, value) => Doc::text(syntax::char_literal(value)),
+            Literal::Int(_, value, format) => Doc::text(format.display(value)),
             Literal::Float(_, value, FloatFormat::Dec) => Doc::text(format!("{}", value)),
         }
     }
 }
 
+// Moniker can't derive these for `i128`, so we implement them by hand.
+// Literals don't contain any variables, and spans and formats are ignored
+// when comparing them, just like they would be by the derived versions.
+
+impl BoundTerm<String> for Literal {
+    fn term_eq(&self, other: &Literal) -> bool {
+        match (self, other) {
+            (&Literal::String(_, ref lhs), &Literal::String(_, ref rhs)) => lhs == rhs,
+            (&Literal::Char(_, lhs), &Literal::Char(_, rhs)) => lhs == rhs,
+            (&Literal::Int(_, lhs, _), &Literal::Int(_, rhs, _)) => lhs == rhs,
+            (&Literal::Float(_, lhs, _), &Literal::Float(_, rhs, _)) => lhs == rhs,
+            (_, _) => false,
+        }
+    }
+
+    fn close_term(&mut self, _: ScopeState, _: &impl OnFreeFn<String>) {}
+    fn open_term(&mut self, _: ScopeState, _: &impl OnBoundFn<String>) {}
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<String>)) {}
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<String>)) {}
+}
+
+impl BoundPattern<String> for Literal {
+    fn pattern_eq(&self, other: &Literal) -> bool {
+        BoundTerm::term_eq(self, other)
+    }
+
+    fn close_pattern(&mut self, _: ScopeState, _: &impl OnFreeFn<String>) {}
+    fn open_pattern(&mut self, _: ScopeState, _: &impl OnBoundFn<String>) {}
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<String>)) {}
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<String>)) {}
+    fn visit_binders(&self, _: &mut impl FnMut(&Binder<String>)) {}
+    fn visit_mut_binders(&mut self, _: &mut impl FnMut(&mut Binder<String>)) {}
+}
+
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
@@ -61,6 +95,10 @@ pub enum Pattern {
     Var(ByteSpan, Embed<Var<String>>, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Patterns that match the values built from a variant constructor
+    VariantIntro(ByteSpan, Label, Vec<RcPattern>),
+    /// Patterns that could not be parsed, which have already been reported
+    Error(ByteSpan),
 }
 
 impl Pattern {
@@ -68,7 +106,10 @@ impl Pattern {
     pub fn span(&self) -> ByteSpan {
         match *self {
             Pattern::Ann(ref pattern, Embed(ref ty)) => pattern.span().to(ty.span()),
-            Pattern::Var(span, _, _) | Pattern::Binder(span, _) => span,
+            Pattern::Var(span, _, _)
+            | Pattern::Binder(span, _)
+            | Pattern::VariantIntro(span, _, _)
+            | Pattern::Error(span) => span,
             Pattern::Literal(ref literal) => literal.span(),
         }
     }
@@ -81,6 +122,13 @@ impl Pattern {
                 .append(":")
                 .append(Doc::space())
                 .append(ty.to_doc_expr()),
+            Pattern::VariantIntro(_, ref label, ref patterns) => {
+                Doc::nil().append(Doc::as_string(label)).append(Doc::concat(
+                    patterns
+                        .iter()
+                        .map(|pattern| Doc::space().append(pattern.to_doc_atomic())),
+                ))
+            },
             ref pattern => pattern.to_doc_atomic(),
         }
     }
@@ -90,6 +138,7 @@ impl Pattern {
             Pattern::Binder(_, ref binder) => Doc::as_string(binder),
             Pattern::Var(_, Embed(ref var), shift) => Doc::as_string(format!("{}^{}", var, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::Error(_) => Doc::text("<error>"),
             ref pattern => Doc::text("(").append(pattern.to_doc()).append(")"),
         }
     }
@@ -139,6 +188,8 @@ pub enum Term {
     Ann(RcTerm, RcTerm),
     /// Universes
     Universe(ByteSpan, Level),
+    /// Universes at a level given by a level expression
+    UniverseAt(ByteSpan, RcTerm),
     /// Literals
     Literal(Literal),
     /// A hole
@@ -148,11 +199,19 @@ pub enum Term {
     /// An imported definition
     Import(ByteSpan, ByteSpan, String),
     /// Dependent function types
-    FunType(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    FunType(
+        ByteSpan,
+        Plicity,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
     /// Function introductions
-    FunIntro(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    FunIntro(
+        ByteSpan,
+        Plicity,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
     /// Function application
-    FunApp(RcTerm, RcTerm),
+    FunApp(RcTerm, Plicity, RcTerm),
     /// Dependent record types
     RecordType(
         ByteSpan,
@@ -162,21 +221,28 @@ pub enum Term {
     RecordIntro(ByteSpan, Vec<(Label, RcTerm)>),
     /// Record field projection
     RecordProj(ByteSpan, RcTerm, ByteSpan, Label, LevelShift),
+    /// Variant types, with a binder for recursive occurrences of the type
+    VariantType(ByteSpan, Scope<Binder<String>, Vec<(Label, Vec<RcTerm>)>>),
     /// Case expressions
     Case(ByteSpan, RcTerm, Vec<Scope<RcPattern, RcTerm>>),
     /// Array literals
     ArrayIntro(ByteSpan, Vec<RcTerm>),
-    /// Let bindings
+    /// Let bindings, along with the doc comments of each binding
     Let(
         ByteSpan,
-        Scope<Nest<(Binder<String>, Embed<RcTerm>)>, RcTerm>,
+        Scope<Nest<(Binder<String>, Embed<RcTerm>, Ignore<String>)>, RcTerm>,
     ),
+    /// Definitions that refer to themselves
+    Fix(ByteSpan, Scope<Binder<String>, RcTerm>),
+    /// Terms that could not be parsed, which have already been reported
+    Error(ByteSpan),
 }
 
 impl Term {
     pub fn span(&self) -> ByteSpan {
         match *self {
             Term::Universe(span, ..)
+            | Term::UniverseAt(span, ..)
             | Term::Hole(span)
             | Term::Var(span, ..)
             | Term::Import(span, ..)
@@ -185,12 +251,15 @@ impl Term {
             | Term::RecordType(span, ..)
             | Term::RecordIntro(span, ..)
             | Term::RecordProj(span, ..)
+            | Term::VariantType(span, ..)
             | Term::Case(span, ..)
             | Term::ArrayIntro(span, ..)
-            | Term::Let(span, ..) => span,
+            | Term::Let(span, ..)
+            | Term::Fix(span, ..)
+            | Term::Error(span) => span,
             Term::Literal(ref literal) => literal.span(),
             Term::Ann(ref expr, ref ty) => expr.span().to(ty.span()),
-            Term::FunApp(ref head, ref arg) => head.span().to(arg.span()),
+            Term::FunApp(ref head, _, ref arg) => head.span().to(arg.span()),
         }
     }
 
@@ -211,8 +280,8 @@ impl Term {
             Term::Import(_, _, ref name) => Doc::nil()
                 .append("import")
                 .append(Doc::space())
-                .append(format!("{:?}", name)),
-            Term::FunIntro(_, ref scope) => Doc::nil()
+                .append(syntax::string_literal(name)),
+            Term::FunIntro(_, Plicity::Explicit, ref scope) => Doc::nil()
                 .append("\\")
                 .append(Doc::as_string(&scope.unsafe_pattern.0))
                 .append(Doc::space())
@@ -223,6 +292,18 @@ impl Term {
                 .append("=>")
                 .append(Doc::space())
                 .append(scope.unsafe_body.to_doc_expr()),
+            Term::FunIntro(_, Plicity::Implicit, ref scope) => Doc::nil()
+                .append("\\{")
+                .append(Doc::as_string(&scope.unsafe_pattern.0))
+                .append(Doc::space())
+                .append(":")
+                .append(Doc::space())
+                .append((scope.unsafe_pattern.1).0.to_doc_arrow())
+                .append("}")
+                .append(Doc::space())
+                .append("=>")
+                .append(Doc::space())
+                .append(scope.unsafe_body.to_doc_expr()),
             Term::Case(_, ref head, ref clauses) => Doc::nil()
                 .append("case")
                 .append(Doc::space())
@@ -249,7 +330,7 @@ impl Term {
                 .append(Doc::space())
                 .append(Doc::intersperse(
                     scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref binder, Embed(ref term))| {
+                        |&(ref binder, Embed(ref term), _)| {
                             Doc::nil()
                                 .append(Doc::as_string(binder))
                                 .append(Doc::space())
@@ -264,20 +345,34 @@ impl Term {
                 .append("in")
                 .append(Doc::space())
                 .append(scope.unsafe_body.to_doc_expr()),
+            Term::Fix(_, ref scope) => Doc::nil()
+                .append("fix")
+                .append(Doc::space())
+                .append(Doc::as_string(&scope.unsafe_pattern))
+                .append(Doc::space())
+                .append("=>")
+                .append(Doc::space())
+                .append(scope.unsafe_body.to_doc_expr()),
             ref term => term.to_doc_arrow(),
         }
     }
 
     fn to_doc_arrow(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunType(_, ref scope) => Doc::nil()
-                .append("(")
+            Term::FunType(_, plicity, ref scope) => Doc::nil()
+                .append(match plicity {
+                    Plicity::Explicit => "(",
+                    Plicity::Implicit => "{",
+                })
                 .append(Doc::as_string(&scope.unsafe_pattern.0))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
                 .append((scope.unsafe_pattern.1).0.to_doc_arrow())
-                .append(")")
+                .append(match plicity {
+                    Plicity::Explicit => ")",
+                    Plicity::Implicit => "}",
+                })
                 .append(Doc::space())
                 .append("->")
                 .append(Doc::space())
@@ -288,17 +383,26 @@ impl Term {
 
     fn to_doc_app(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunApp(ref fun, ref arg) => Doc::nil()
+            Term::FunApp(ref fun, Plicity::Explicit, ref arg) => Doc::nil()
                 .append(fun.to_doc_atomic())
                 .append(Doc::space())
                 .append(arg.to_doc_atomic()),
+            Term::FunApp(ref fun, Plicity::Implicit, ref arg) => Doc::nil()
+                .append(fun.to_doc_atomic())
+                .append(Doc::space())
+                .append("{")
+                .append(arg.to_doc())
+                .append("}"),
             ref term => term.to_doc_atomic(),
         }
     }
 
     fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::Universe(_, level) => Doc::text(format!("Type^{}", level)),
+            Term::Universe(_, ref level) => Doc::text("Type^").append(level.to_doc()),
+            Term::UniverseAt(_, ref level) => {
+                Doc::text("Type^(").append(level.to_doc()).append(")")
+            },
             Term::ArrayIntro(_, ref elems) => Doc::nil()
                 .append("[")
                 .append(Doc::intersperse(
@@ -308,6 +412,7 @@ impl Term {
                 .append("]"),
             Term::Var(_, ref var, ref level) => Doc::text(format!("{}^{}", var, level)),
             Term::Hole(_) => Doc::text("_"),
+            Term::Error(_) => Doc::text("<error>"),
             Term::RecordType(_, ref scope) => Doc::nil()
                 .append("Record {")
                 .append(Doc::space())
@@ -350,6 +455,24 @@ impl Term {
                 .append(expr.to_doc_atomic())
                 .append(".")
                 .append(format!("{}^{}", label, shift)),
+            Term::VariantType(_, ref scope) => Doc::nil()
+                .append("Variant")
+                .append(Doc::space())
+                .append(Doc::as_string(&scope.unsafe_pattern))
+                .append(Doc::space())
+                .append("{")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    scope.unsafe_body.iter().map(|&(ref label, ref args)| {
+                        Doc::as_string(label).append(Doc::concat(
+                            args.iter()
+                                .map(|arg| Doc::space().append(arg.to_doc_atomic())),
+                        ))
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
             ref term => Doc::text("(").append(term.to_doc()).append(")"),
         }
     }
@@ -1,4 +1,5 @@
 use codespan::CodeMap;
+use moniker::FreeVar;
 
 use pikelet_concrete::desugar::{Desugar, DesugarEnv};
 use pikelet_concrete::elaborate::{self, Context, TypeError};
@@ -13,7 +14,7 @@ fn record_intro() {
     let expected_ty = r"Record { t : Type; x : String }";
     let given_expr = r#"record { t = String; x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -26,7 +27,7 @@ fn record_intro_field_mismatch_lt() {
     let expected_ty = r"Record { x : String; y : String }";
     let given_expr = r#"record { x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -47,7 +48,7 @@ fn record_intro_field_mismatch_gt() {
     let expected_ty = r"Record { x : String }";
     let given_expr = r#"record { x = "hello"; y = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -67,7 +68,7 @@ fn record_intro_dependent_record_ty() {
     let expected_ty = r"Record { t : Type; x : t }";
     let given_expr = r#"record { t = String; x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -79,10 +80,88 @@ fn record_intro_dependent_record_ty_propagate_types() {
     let expected_ty = r"Record { t : Type; x : t }";
     let given_expr = r#"record { t = S32; x = 1 }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
+#[test]
+fn implicit_fun_intro_inserted() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn implicit_fun_intro_explicit() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\{a} x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn implicit_fun_app_solved_to_definition() {
+    let mut codemap = CodeMap::new();
+    let mut context = Context::default();
+
+    let universe0 = support::parse_eval_term(&mut codemap, &context, "Type");
+    let string = support::parse_infer_term(&mut codemap, &context, "String").0;
+    let greeting = FreeVar::fresh_named("Greeting");
+    context.insert_declaration(greeting.clone(), universe0);
+    context.insert_definition(greeting, string);
+
+    let expected_ty =
+        r"(F : Type -> Type) (f : {a : Type} -> F a -> F a) -> F Greeting -> F Greeting";
+    let given_expr = r"\F f x => f x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&DesugarEnv::new(context.mappings()))
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Ok(term) => assert!(
+            term.to_string().contains("{Greeting^0}"),
+            "expected the implicit argument to stay folded in: {}",
+            term,
+        ),
+        Err(err) => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn hole_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"Record { x : String }";
+    let given_expr = r"record { x = ? }";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::UnableToElaborateHole {
+            expected: Some(ref expected),
+            ..
+        }) if expected.to_string() == "String" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
 #[test]
 fn case_expr() {
     let mut codemap = CodeMap::new();
@@ -95,7 +174,7 @@ fn case_expr() {
         greeting => (import "prim/string/append") greeting "!!";
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -111,7 +190,7 @@ fn case_expr_bad_literal() {
         1 => "byee";
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -133,7 +212,7 @@ fn case_expr_wildcard() {
         _ => 123;
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -142,11 +221,21 @@ fn case_expr_empty() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
+    let desugar_env = DesugarEnv::new(context.mappings());
+
     let expected_ty = r"String";
     let given_expr = r#"case "helloo" {}"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
-    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::NonExhaustiveCase { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
 }
 
 #[test]
@@ -157,7 +246,7 @@ fn array_intro_0_string() {
     let expected_ty = r"Array 0 String";
     let given_expr = r#"[]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -169,7 +258,7 @@ fn array_intro_3_string() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"; "byee"]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -182,7 +271,7 @@ fn array_intro_len_mismatch() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -203,7 +292,7 @@ fn array_intro_elem_ty_mismatch() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"; 4]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -213,3 +302,205 @@ fn array_intro_elem_ty_mismatch() {
         Ok(term) => panic!("expected error but found: {}", term),
     }
 }
+
+#[test]
+fn literal_negative() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { a : S8; b : S64; c : F32 }";
+    let given_expr = r"record { a = -128; b = -0x7FFFFFFFFFFFFFFF; c = -1.5 }";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn literal_out_of_range() {
+    use pikelet_core::syntax::Literal;
+
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let cases = [
+        ("U8", "256", Literal::U8(0), Literal::U8(255)),
+        ("U32", "-1", Literal::U32(0), Literal::U32(u32::MAX)),
+        ("S8", "-129", Literal::S8(-128), Literal::S8(127)),
+        (
+            "S64",
+            "0xFFFFFFFFFFFFFFFF",
+            Literal::S64(i64::MIN),
+            Literal::S64(i64::MAX),
+        ),
+        (
+            "F32",
+            "400000000000000000000000000000000000000.0",
+            Literal::F32(f32::MIN),
+            Literal::F32(f32::MAX),
+        ),
+    ];
+
+    for &(expected_ty, given_expr, ref expected_min, ref expected_max) in &cases {
+        let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+        let raw_term = support::parse_term(&mut codemap, given_expr)
+            .desugar(&desugar_env)
+            .unwrap();
+
+        match elaborate::check_term(&context, &raw_term, &expected_ty) {
+            Err(TypeError::LiteralOutOfRange {
+                ref min, ref max, ..
+            }) if min == expected_min && max == expected_max => {},
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(term) => panic!("expected error but found: {}", term),
+        }
+    }
+}
+
+#[test]
+fn literal_out_of_range_infinite_float() {
+    use pikelet_core::syntax::Literal;
+
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    // `1e400`, which is too large to be represented by either float type
+    let given_expr = format!("1{}.0", "0".repeat(400));
+
+    let cases = [
+        ("F32", Literal::F32(f32::MIN), Literal::F32(f32::MAX)),
+        ("F64", Literal::F64(f64::MIN), Literal::F64(f64::MAX)),
+    ];
+
+    for &(expected_ty, ref expected_min, ref expected_max) in &cases {
+        let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+        let raw_term = support::parse_term(&mut codemap, &given_expr)
+            .desugar(&desugar_env)
+            .unwrap();
+
+        match elaborate::check_term(&context, &raw_term, &expected_ty) {
+            Err(TypeError::LiteralOutOfRange {
+                ref min, ref max, ..
+            }) if min == expected_min && max == expected_max => {},
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(term) => panic!("expected error but found: {}", term),
+        }
+    }
+}
+
+#[test]
+fn literal_out_of_range_error_ty() {
+    use pikelet_core::syntax::domain::{RcValue, Value};
+    use pikelet_core::syntax::Literal;
+
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = RcValue::from(Value::Error);
+    let given_expr = r"-0xFFFFFFFFFFFFFFFF";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::LiteralOutOfRange {
+            min: Literal::S64(i64::MIN),
+            max: Literal::U64(u64::MAX),
+            ..
+        }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn fun_eta() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"\F f x => x";
+
+    for expected_ty in &[
+        r"(F : (String -> String) -> Type) (f : String -> String) -> F f -> F (\x => f x)",
+        r"(F : (String -> String) -> Type) (f : String -> String) -> F (\x => f x) -> F f",
+    ] {
+        let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+        support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+    }
+}
+
+#[test]
+fn record_eta() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"\F r x => x";
+
+    for expected_ty in &[
+        r"(F : Record { a : String } -> Type) (r : Record { a : String }) -> F r -> F (record { a = r.a })",
+        r"(F : Record { a : String } -> Type) (r : Record { a : String }) -> F (record { a = r.a }) -> F r",
+    ] {
+        let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+        support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+    }
+}
+
+#[test]
+fn record_eta_unit() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(F : Record {} -> Type) (r1 r2 : Record {}) -> F r1 -> F r2";
+    let given_expr = r"\F r1 r2 x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn record_eta_case_mismatch() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r#"(F : Record { a : String } -> Type) (s : String) ->
+        F (case s { "x" => record { a = "x" }; _ => record { a = "y" } }) ->
+        F (record { a = "x" })"#;
+    let given_expr = r"\F s x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn implicit_fun_not_eta_expanded() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"({a : Type} -> a -> a) -> {a : Type} -> a -> a";
+    let given_expr = r"\f => f";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&DesugarEnv::new(context.mappings()))
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Ok(term) => assert_eq!(
+            term.to_string(),
+            r"\f : {a : Type^0} -> (_ : @0^0) -> @1^0 => @0^0",
+        ),
+        Err(err) => panic!("unexpected error: {:?}", err),
+    }
+}
@@ -9,7 +9,7 @@ use pikelet_concrete::desugar::{Desugar, DesugarEnv, DesugarError};
 use pikelet_concrete::parse;
 use pikelet_concrete::syntax::raw::{RcTerm, Term};
 use pikelet_concrete::syntax::{concrete, raw};
-use pikelet_core::syntax::{Level, LevelShift};
+use pikelet_core::syntax::{Level, LevelShift, Plicity};
 
 fn golden(filename: &str, literal: &str) {
     let path = "tests/goldenfiles";
@@ -60,7 +60,7 @@ fn var(x: &FreeVar<String>) -> RcTerm {
 }
 
 fn u0() -> RcTerm {
-    RcTerm::from(Term::Universe(ByteSpan::default(), Level(0)))
+    RcTerm::from(Term::Universe(ByteSpan::default(), Level::from(0)))
 }
 
 #[test]
@@ -115,11 +115,13 @@ fn fun_intro_ann() {
         parse_desugar_term(&env, r"\x : Type -> Type => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
@@ -141,11 +143,13 @@ fn fun_intro() {
         parse_desugar_term(&env, r"\x : (\y => y) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunIntro(
                         ByteSpan::default(),
+                        Plicity::Explicit,
                         Scope::new((Binder(y.clone()), Embed(hole())), var(&y)),
                     )))
                 ),
@@ -166,10 +170,12 @@ fn fun_intro2_ann() {
         parse_desugar_term(&env, r"\(x y : Type) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                 )),
             ),
@@ -185,6 +191,7 @@ fn arrow() {
         parse_desugar_term(&env, r"Type -> Type"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
         )),
     );
@@ -200,11 +207,13 @@ fn fun_ty() {
         parse_desugar_term(&env, r"(x : Type -> Type) -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
@@ -225,10 +234,12 @@ fn fun_ty2() {
         parse_desugar_term(&env, r"(x y : Type) -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                 )),
             ),
@@ -246,10 +257,12 @@ fn fun_ty_arrow() {
         parse_desugar_term(&env, r"(x : Type) -> x -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&x))), var(&x)),
                 )),
             ),
@@ -268,19 +281,22 @@ fn fun_intro_fun_app() {
         parse_desugar_term(&env, r"\(x : Type -> Type) (y : Type) => x y"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new(
                         (Binder(y.clone()), Embed(u0())),
-                        RcTerm::from(Term::FunApp(var(&x), var(&y))),
+                        RcTerm::from(Term::FunApp(var(&x), Plicity::Explicit, var(&y))),
                     ),
                 )),
             ),
@@ -299,10 +315,12 @@ fn id() {
         parse_desugar_term(&env, r"\(a : Type) (x : a) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (Binder(a.clone()), Embed(u0())),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new((Binder(x.clone()), Embed(var(&a))), var(&x)),
                 )),
             ),
@@ -320,10 +338,12 @@ fn id_ty() {
         parse_desugar_term(&env, r"(a : Type) -> a -> a"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
             Scope::new(
                 (Binder(a.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
                     Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&a))), var(&a)),
                 )),
             ),
@@ -517,3 +537,159 @@ mod sugar {
         )
     }
 }
+
+mod names {
+    use codespan::CodeMap;
+    use pretty_assertions::assert_eq;
+
+    use pikelet_concrete::desugar::OccurrenceKind::{self, Label, Name, PunnedLabel};
+    use pikelet_concrete::desugar::{Binding, Desugar, DesugarEnv, Occurrence};
+
+    type Occurrences = Vec<(usize, OccurrenceKind)>;
+
+    /// Desugar `src`, returning the names that were bound, along with the
+    /// offsets of their binders and uses
+    fn names(src: &str) -> Vec<(String, Occurrences, Occurrences)> {
+        let mut codemap = CodeMap::new();
+        let mut env = DesugarEnv::new(im::HashMap::new());
+        env.record_names();
+        super::parse_term(&mut codemap, src).desugar(&env).unwrap();
+
+        let file_start = codemap.iter().last().unwrap().span().start();
+        let offsets = |binding: &Binding, occurrences: &[Occurrence]| {
+            let occurrences = occurrences.iter().map(|occurrence| {
+                let start = (occurrence.span.start() - file_start).to_usize();
+                let end = (occurrence.span.end() - file_start).to_usize();
+                assert_eq!(&src[start..end], binding.name);
+                (start, occurrence.kind)
+            });
+            occurrences.collect()
+        };
+
+        let names = env.take_names();
+        let bindings = names.bindings().iter().map(|binding| {
+            let binders = offsets(binding, &binding.binders);
+            let uses = offsets(binding, &binding.uses);
+            (binding.name.clone(), binders, uses)
+        });
+        bindings.collect()
+    }
+
+    fn binding(
+        name: &str,
+        binders: Occurrences,
+        uses: Occurrences,
+    ) -> (String, Occurrences, Occurrences) {
+        (name.to_owned(), binders, uses)
+    }
+
+    #[test]
+    fn let_items() {
+        assert_eq!(
+            names("let id : Type -> Type; id a = a; in id Type"),
+            vec![
+                binding("id", vec![(4, Name), (23, Name)], vec![(36, Name)]),
+                binding("a", vec![(26, Name)], vec![(30, Name)]),
+            ],
+        );
+    }
+
+    #[test]
+    fn where_items() {
+        assert_eq!(
+            names("id where { id a = a; }"),
+            vec![
+                binding("id", vec![(11, Name)], vec![(0, Name)]),
+                binding("a", vec![(14, Name)], vec![(18, Name)]),
+            ],
+        );
+    }
+
+    #[test]
+    fn fun_params() {
+        assert_eq!(
+            names(r"\(a : Type) (x : a) => x"),
+            vec![
+                binding("a", vec![(2, Name)], vec![(17, Name)]),
+                binding("x", vec![(13, Name)], vec![(23, Name)]),
+            ],
+        );
+    }
+
+    #[test]
+    fn record_labels() {
+        assert_eq!(
+            names("Record { t : Type; x : t; y as z : t; w : z }"),
+            vec![
+                binding("t", vec![(9, Label)], vec![(23, Name), (35, Name)]),
+                binding("x", vec![(19, Label)], vec![]),
+                binding("z", vec![(31, Name)], vec![(42, Name)]),
+                binding("w", vec![(38, Label)], vec![]),
+            ],
+        );
+    }
+
+    #[test]
+    fn record_field_puns() {
+        assert_eq!(
+            names(r"\x => record { x; y = x }"),
+            vec![binding(
+                "x",
+                vec![(1, Name)],
+                vec![(15, PunnedLabel), (22, Name)]
+            )],
+        );
+    }
+
+    #[test]
+    fn globals_are_skipped() {
+        assert_eq!(
+            names(r"\x => f x"),
+            vec![binding("x", vec![(1, Name)], vec![(8, Name)])],
+        );
+    }
+
+    #[test]
+    fn docs() {
+        let src =
+            "let\n||| An id\n|||\n||| For types\nid : Type -> Type;\n||| Defined\nid a = a;\nin id";
+        let mut codemap = CodeMap::new();
+        let mut env = DesugarEnv::new(im::HashMap::new());
+        env.record_names();
+        super::parse_term(&mut codemap, src).desugar(&env).unwrap();
+
+        let names = env.take_names();
+        let docs = names
+            .bindings()
+            .iter()
+            .map(|binding| (&binding.name[..], &binding.doc[..]));
+
+        assert_eq!(
+            docs.collect::<Vec<_>>(),
+            vec![("id", "An id\n\nFor types\n\nDefined"), ("a", "")],
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let src = r"\x => let y = x; in f y";
+        let mut codemap = CodeMap::new();
+        let mut env = DesugarEnv::new(im::HashMap::new());
+        env.record_names();
+        super::parse_term(&mut codemap, src).desugar(&env).unwrap();
+
+        let file_start = codemap.iter().last().unwrap().span().start();
+        let names = env.take_names();
+        let scope_at = |offset| {
+            let scope = names.scope_at(file_start + codespan::ByteOffset(offset))?;
+            let mut names = scope.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            Some(names)
+        };
+
+        assert_eq!(scope_at(0), Some(vec![]));
+        assert_eq!(scope_at(5), Some(vec!["x".to_owned()]));
+        assert_eq!(scope_at(22), Some(vec!["x".to_owned(), "y".to_owned()]));
+        assert_eq!(scope_at(30), None);
+    }
+}
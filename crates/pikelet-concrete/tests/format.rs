@@ -0,0 +1,160 @@
+use codespan::{CodeMap, FileName};
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::{format, parse};
+
+fn format_src(src: &str) -> String {
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+    match format::format(&filemap) {
+        Ok(formatted) => formatted,
+        Err(errors) => panic!("parse errors: {:?}", errors),
+    }
+}
+
+/// Check that formatting `src` gives `expected`, and that formatting is
+/// idempotent
+fn assert_formats_to(src: &str, expected: &str) {
+    let formatted = format_src(src);
+    assert_eq!(formatted, expected);
+    assert_eq!(format_src(&formatted), expected);
+}
+
+#[test]
+fn normalizes_whitespace() {
+    assert_formats_to(
+        r"\x   (y:S32) {z}=>  f  x {y}   z.l^1 : Type ->Type",
+        "\\x (y : S32) {z} => f x {y} z.l^1 : Type -> Type\n",
+    );
+}
+
+#[test]
+fn single_line_blocks() {
+    assert_formats_to(
+        r#"record {x=[1;2;3] ;y=case x {true=>"a";false=>"b"} ;  z=Variant {a;b String}}"#,
+        "record { x = [1; 2; 3]; y = case x { true => \"a\"; false => \"b\" }; z = Variant { a; b String } }\n",
+    );
+    assert_formats_to("Record {\n}", "Record {}\n");
+}
+
+#[test]
+fn multiline_blocks() {
+    let src = r#"
+        record { id; const;
+          compose = \f g x => f (g x);
+
+
+          Unit = Record { };
+          Pair =Record
+          { fst : Type; snd : Type }
+        }
+    "#;
+    let expected = r#"record {
+    id; const;
+    compose = \f g x => f (g x);
+
+
+    Unit = Record {};
+    Pair = Record {
+        fst : Type; snd : Type;
+    };
+}
+"#;
+
+    assert_formats_to(src, expected);
+}
+
+#[test]
+fn let_and_where() {
+    assert_formats_to("let x = 1; y = x; in y", "let x = 1; y = x; in y\n");
+    assert_formats_to(
+        "let\n  x : S32; x = 1;\nin x",
+        "let\n    x : S32; x = 1;\nin\n    x\n",
+    );
+    assert_formats_to(
+        "id x where {\n  id : {a : Type} -> a -> a; id x = x;\n}",
+        "id x where {\n    id : {a : Type} -> a -> a; id x = x;\n}\n",
+    );
+}
+
+#[test]
+fn preserves_comments() {
+    let src = r#"-- A header comment
+
+Record {
+    ||| The first field
+    |||
+    ||| It has a long description
+    first : String;   -- a trailing comment
+    second : String; -- another one
+    -- a dangling comment
+}
+-- the end
+"#;
+
+    assert_formats_to(src, &src.replace("String;   --", "String; --"));
+}
+
+#[test]
+fn trailing_comments_before_keywords() {
+    assert_formats_to("if a -- c\nthen b else d", "if a -- c\nthen b else d\n");
+    assert_formats_to(
+        "if a then -- c\n b else d",
+        "if a then -- c\n    b else d\n",
+    );
+    assert_formats_to("let x = 1; -- c\nin x", "let x = 1; -- c\nin x\n");
+    assert_formats_to("f x -- c\n  : S32", "f x -- c\n: S32\n");
+}
+
+#[test]
+fn trailing_comments_after_in() {
+    assert_formats_to(
+        "let\n    x = 1;\nin -- c\n    x",
+        "let\n    x = 1;\nin -- c\n    x\n",
+    );
+    assert_formats_to("let x = 1; in -- c\nx", "let x = 1; in -- c\n    x\n");
+}
+
+#[test]
+fn comment_continuation_indent() {
+    let expected = r#"record {
+    y = (\a => a : String -> String) -- inside
+        "b";
+}
+"#;
+
+    assert_formats_to(expected, expected);
+    assert_formats_to(
+        "record {\n  y = (\\a => a : String -> String) -- inside\n\"b\";\n}",
+        expected,
+    );
+}
+
+#[test]
+fn library_files() {
+    let prim = include_str!("../../pikelet-library/src/prim.pi");
+    let prelude = include_str!("../../pikelet-library/src/prelude.pi");
+
+    assert_formats_to(prim, prim);
+
+    let formatted = format_src(prelude);
+    assert_eq!(format_src(&formatted), formatted);
+
+    // The formatted prelude should parse to the same term as the original
+    let mut codemap = CodeMap::new();
+    let original = codemap.add_filemap(FileName::virtual_("original"), prelude.into());
+    let formatted = codemap.add_filemap(FileName::virtual_("formatted"), formatted);
+    assert_eq!(
+        parse::term(&original).0.to_string(),
+        parse::term(&formatted).0.to_string(),
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), "record { x = ; }".into());
+
+    assert!(format::format(&filemap).is_err());
+}
@@ -1,5 +1,5 @@
-use codespan::{ByteIndex, ByteSpan, CodeMap};
-use moniker::{assert_term_eq, FreeVar, Var};
+use codespan::{ByteIndex, ByteOffset, ByteSpan, CodeMap};
+use moniker::{FreeVar, Var};
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::desugar::{Desugar, DesugarEnv};
@@ -57,7 +57,7 @@ fn ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -71,7 +71,154 @@ fn ty_levels() {
     let expected_ty = r"Type^1";
     let given_expr = r"Type^0 : Type^1 : Type^2 : Type^3"; //... Type^∞       ...+:｡(ﾉ･ω･)ﾉﾞ
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_var() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(l : Level) -> Type^(l^1)";
+    let given_expr = r"\(l : Level) => Type^l";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_var_app() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^3";
+    let given_expr = r"(\(l : Level) => Type^l) 2";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_max() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(l : Level) -> Type^(level-max 3 (l^1))";
+    let given_expr = r"\(l : Level) => Type^(level-max 2 l)";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_var_cumulative() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(l : Level) -> Type^l -> Type^(l^1)";
+    let given_expr = r"(\l a => a) : (l : Level) -> Type^l -> Type^(l^1)";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_var_not_cumulative() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"(\l a => a) : (l : Level) -> Type^(l^1) -> Type^l";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn ty_level_poly_id() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1 -> Type^1";
+    let given_expr = r"
+        let
+            id : (l : Level) (a : Type^l) -> a -> a;
+            id l a x = x;
+        in
+            id 2 (Type^1)
+    ";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn ty_level_unreducible() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"\(f : U32 -> Level) => Type^(f 1)";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UnreducibleLevel { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn ty_level_overflow() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"Type^4294967295";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::LevelOverflow { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn ty_level_var_hole() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(l : Level) (A : Type^l) -> A -> A";
+    let given_expr = r"\(l : Level) (A : Type^l) x => (x : A)";
+
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -85,7 +232,7 @@ fn ann_ty_id() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\a => a) : Type -> Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -99,7 +246,7 @@ fn ann_arrow_ty_id() {
     let expected_ty = r"(Type -> Type) -> (Type -> Type)";
     let given_expr = r"(\a => a) : (Type -> Type) -> (Type -> Type)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -131,7 +278,7 @@ fn fun_app() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\a : Type^1 => a) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -167,7 +314,7 @@ fn fun_intro() {
     let expected_ty = r"(a : Type) -> Type";
     let given_expr = r"\a : Type => a";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -181,7 +328,7 @@ fn fun_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(a : Type) -> a";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -195,7 +342,7 @@ fn id() {
     let expected_ty = r"(a : Type) -> a -> a";
     let given_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -209,7 +356,7 @@ fn id_ann() {
     let expected_ty = r"(a : Type) -> a -> a";
     let given_expr = r"(\a (x : a) => x) : (A : Type) -> A -> A";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -225,7 +372,7 @@ fn id_fun_app_ty() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\(a : Type^1) (x : a) => x) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -240,7 +387,7 @@ fn id_fun_app_ty_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -254,7 +401,7 @@ fn id_fun_app_ty_arr_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) (Type -> Type)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -268,7 +415,7 @@ fn id_fun_app_arr_fun_ty() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\(a : Type^1) (x : a) => x) (Type -> Type) (\x => x)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -282,7 +429,7 @@ fn apply() {
     let expected_ty = r"(a b : Type) -> (a -> b) -> a -> b";
     let given_expr = r"\(a b : Type) (f : a -> b) (x : a) => f x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -296,7 +443,7 @@ fn const_() {
     let expected_ty = r"(a b : Type) -> a -> b -> a";
     let given_expr = r"\(a b : Type) (x : a) (y : b) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -310,7 +457,7 @@ fn const_flipped() {
     let expected_ty = r"(a b : Type) -> a -> b -> b";
     let given_expr = r"\(a b : Type) (x : a) (y : b) => y";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -324,7 +471,7 @@ fn flip() {
     let expected_ty = r"(a b c : Type) -> (a -> b -> c) -> (b -> a -> c)";
     let given_expr = r"\(a b c : Type) (f : a -> b -> c) (y : b) (x : a) => f x y";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -338,288 +485,886 @@ fn compose() {
     let expected_ty = r"(a b c : Type) -> (b -> c) -> (a -> b) -> (a -> c)";
     let given_expr = r"\(a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_expr_1() {
+fn implicit_fun_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\{a : Type} (x : a) => x";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn implicit_fun_app_inferred() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
     let given_expr = r#"
-        let x = "helloo";
+        let id : {a : Type} -> a -> a;
+            id x = x;
         in
-            x
+            id "hello"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_expr_2() {
+fn implicit_fun_app_explicit() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
     let given_expr = r#"
-        let x = "helloo";
-            y = x;
+        let id : {a : Type} -> a -> a;
+            id x = x;
         in
-            x
+            id {String} "hello"
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn implicit_fun_app_higher_order() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String -> String";
+    let given_expr = r#"
+        let compose : {a b c : Type} -> (b -> c) -> (a -> b) -> (a -> c);
+            compose f g x = f (g x);
+            id : {a : Type} -> a -> a;
+            id x = x;
+        in
+            compose id (\x : String => x)
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn implicit_fun_app_unexpected() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"(\x : String => x) {String}"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UnexpectedImplicitArg { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn implicit_fun_app_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let const-hello : {a : Type} -> String -> String;
+            const-hello x = "hello";
+        in
+            const-hello "world"
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UnableToInferImplicitArg { ref name, .. }) if name == "a" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn hole_solved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { x : String }";
+    let given_expr = r#"record { x = "hello" } : Record { x : ? }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn hole_implicit_arg_solved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"
+        let id : {a : Type} -> a -> a;
+            id x = x;
+        in
+            id {?} "hello"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn hole_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"(\x : String => x) ?"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UnableToElaborateHole {
+            expected: Some(ref expected),
+            ..
+        }) if expected.to_string() == "String" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn hole_solved_higher_universe() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(F : Type -> Type) -> Type^1";
+    let given_expr = r"\(F : Type -> Type) => (A : ?) -> F A";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn fun_intro_param_ann_solved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\x => x) "hello""#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn fun_intro_param_ann_solved_universe() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\A (a : A) => a) String "x""#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn fun_intro_param_ann_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"\x => x";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    let file_start = codemap.iter().last().unwrap().span().start();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::FunctionParamNeedsAnnotation { param_span, .. }) => {
+            assert_eq!((param_span.start() - file_start).to_usize(), 1);
+            assert_eq!(param_span.end() - param_span.start(), ByteOffset::from(1));
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn let_expr_1() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"
+        let x = "helloo";
+        in
+            x
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_expr_2() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"
+        let x = "helloo";
+            y = x;
+        in
+            x
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_unfold_definitions() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"
+        let
+            Id (A : Type) = A;
+            x : Id String = "hello";
+        in
+            x : String
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_shift_universes() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test1 = id String "hello";
+            test2 = id S32 1;
+            test3 = id^1 Type String;
+            test4 = id^2 Type String;
+            test5 = id^2 Type^1 String;
+            test6 = id^2 Type^1 Type;
+
+            id1 : (a : Type^1) -> a -> a = id^1;
+            id2 : (a : Type^2) -> a -> a = id^2;
+            id11 : (a : Type^2) -> a -> a = id1^1;
+            id22 : (a : Type^4) -> a -> a = id2^2;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_id_self_application() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    // Here is a curious example from the Idris docs:
+    // http://docs.idris-lang.org/en/v1.3.0/tutorial/miscellany.html#cumulativity
+    //
+    // ```idris
+    // myid : (a : Type) -> a -> a
+    // myid _ x = x
+    //
+    // idid : (a : Type) -> a -> a
+    // idid = myid _ myid
+    // ```
+    //
+    // This would cause a cycle in the universe hierarchy in Idris, but is
+    // perfectly ok when implemented using explicit universe shifting.
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            id-id : (a : Type) -> a -> a;
+            id-id = id^1 ((a : Type) -> a -> a) id;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_literals() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test2 = "hello" : id^1 Type String;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_literals_bad() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test2 = "hello" : id^2 Type^1 String^1;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::LiteralMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn let_shift_universes_too_little() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test1 = id^1 Type^1 Type;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn let_shift_universes_too_much() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test1 = id^2 Type String;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn case_expr() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" {
+        "hi" => "haha";
+        "hello" => "byee";
+        greeting => (import "prim/string/append") greeting "!!";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_bool() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case true {
+        true => "hello";
+        false => "hi";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_bool_bad() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case "hello" {
+        true => "hello";
+        false => "hi";
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_wildcard() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" {
+        test => test;
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_bool_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case true {
+        true => "hello";
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustiveCase { ref missing, .. }) if missing.to_string() == "false" => {
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_literal_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case "hello" {
+        "hi" => "haha";
+        "hello" => "byee";
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustiveCase { ref missing, .. }) if missing.to_string() == "_" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_unreachable() {
+    use pikelet_concrete::elaborate::TypeWarning;
+
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case true {
+        true => "hello";
+        false => "hi";
+        _ => "unreachable";
+        true => "also unreachable";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+
+    match context.take_warnings()[..] {
+        [TypeWarning::UnreachablePattern { .. }, TypeWarning::UnreachablePattern { .. }] => {},
+        ref warnings => panic!("unexpected warnings: {:?}", warnings),
+    }
+}
+
+#[test]
+fn case_expr_empty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case "helloo" {}"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousEmptyCase { .. }) => {},
+        other => panic!("unexpected result: {:#?}", other),
+    }
+}
+
+#[test]
+fn variant_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type";
+    let given_expr = r"Variant { none; some String }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn variant_ty_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type";
+    let given_expr = r"Variant Nat { zero; succ Nat }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn variant_ty_level() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"Variant Tree { leaf Type; node Tree Tree }";
+
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_shift_universes() {
+fn variant_ty_non_positive() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test1 = id String "hello";
-            test2 = id S32 1;
-            test3 = id^1 Type String;
-            test4 = id^2 Type String;
-            test5 = id^2 Type^1 String;
-            test6 = id^2 Type^1 Type;
+    let given_expr = r"Variant Bad { mk (Bad -> String) }";
 
-            id1 : (a : Type^1) -> a -> a = id^1;
-            id2 : (a : Type^2) -> a -> a = id^2;
-            id11 : (a : Type^2) -> a -> a = id1^1;
-            id22 : (a : Type^4) -> a -> a = id2^2;
-        in
-            record {}
-    "#;
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonPositiveOccurrence { ref name, .. }) if name == "Bad" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn let_shift_universes_id_self_application() {
+fn variant_ty_duplicate_constructors() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    // Here is a curious example from the Idris docs:
-    // http://docs.idris-lang.org/en/v1.3.0/tutorial/miscellany.html#cumulativity
-    //
-    // ```idris
-    // myid : (a : Type) -> a -> a
-    // myid _ x = x
-    //
-    // idid : (a : Type) -> a -> a
-    // idid = myid _ myid
-    // ```
-    //
-    // This would cause a cycle in the universe hierarchy in Idris, but is
-    // perfectly ok when implemented using explicit universe shifting.
-
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
+    let given_expr = r"Variant { none; some String; none }";
 
-            id-id : (a : Type) -> a -> a;
-            id-id = id^1 ((a : Type) -> a -> a) id;
-        in
-            record {}
-    "#;
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::DuplicateConstructors { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn let_shift_universes_literals() {
+fn variant_intro() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test2 = "hello" : id^1 Type String;
-        in
-            record {}
-    "#;
+    let expected_ty = r"Variant List { nil; cons String List }";
+    let given_expr = r#"cons "hello" (cons "world" nil) : Variant List { nil; cons String List }"#;
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
 }
 
 #[test]
-fn let_shift_universes_literals_bad() {
+fn variant_intro_no_constructor() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test2 = "hello" : id^2 Type^1 String^1;
-        in
-            record {}
-    "#;
+    let given_expr = r#"nothing : Variant { none; some String }"#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::LiteralMismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+        Err(TypeError::NoConstructorInType {
+            ref expected_label, ..
+        }) if expected_label.0 == "nothing" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn let_shift_universes_too_little() {
+fn variant_intro_arg_count_mismatch() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test1 = id^1 Type^1 Type;
-        in
-            record {}
-    "#;
+    let given_expr = r#"some "hello" "world" : Variant { none; some String }"#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::Mismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+        Err(TypeError::ConstructorArgCountMismatch {
+            found_count: 2,
+            expected_count: 1,
+            ..
+        }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn let_shift_universes_too_much() {
+fn case_expr_variant() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
+    let expected_ty = r"(Variant { none; some String }) -> String";
+    let given_expr = r#"\x : Variant { none; some String } => case x {
+        some value => value;
+        none => "default";
+    }"#;
 
-            test1 = id^2 Type String;
-        in
-            record {}
-    "#;
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+#[test]
+fn case_expr_variant_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"\xs : Variant List { nil; cons String List } => case xs {
+        nil => "empty";
+        cons x nil => x;
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustiveCase { ref missing, .. })
+            if missing.to_string() == "cons _ (cons _ _)" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn case_expr() {
+fn let_rec() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
-    let given_expr = r#"case "helloo" {
-        "hi" => "haha";
-        "hello" => "byee";
-        greeting => (import "prim/string/append") greeting "!!";
-    }"#;
+    let given_expr = r#"
+        let List = Variant List { nil; cons String List };
+            last (default : String) (xs : List) : String = case xs {
+                nil => default;
+                cons x rest => last x rest;
+            };
+        in
+            last "none" (cons "hello" (cons "world" nil))
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn case_expr_bool() {
+fn let_rec_implicit_params() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
-    let given_expr = r#"case true {
-        true => "hello";
-        false => "hi";
-    }"#;
+    let given_expr = r#"
+        let List (a : Type) = Variant List { nil; cons a List };
+            last {a : Type} (default : a) (xs : List a) : a = case xs {
+                nil => default;
+                cons x rest => last x rest;
+            };
+        in
+            last "none" (cons "hello" (cons "world" nil))
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn case_expr_bool_bad() {
+fn let_rec_non_terminating() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"case "hello" {
-        true => "hello";
-        false => "hi";
-    }"#;
+    let given_expr = r#"
+        let List = Variant List { nil; cons String List };
+            loop (xs : List) : String = loop (cons "hi" xs);
+        in
+            loop nil
+    "#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::Mismatch { .. }) => {},
+        Err(TypeError::NonTerminatingRecursion { ref name, .. }) if name == "loop" => {},
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn case_expr_wildcard() {
-    let mut codemap = CodeMap::new();
-    let context = Context::default();
-
-    let expected_ty = r"String";
-    let given_expr = r#"case "helloo" {
-        test => test;
-    }"#;
-
-    assert_term_eq!(
-        support::parse_infer_term(&mut codemap, &context, given_expr).1,
-        support::parse_nf_term(&mut codemap, &context, expected_ty),
-    );
-}
-
-#[test]
-fn case_expr_empty() {
+fn let_rec_unapplied() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"case "helloo" {}"#;
+    let given_expr = r#"
+        let List = Variant List { nil; cons String List };
+            apply (f : List -> String) (xs : List) : String = f xs;
+            escape (xs : List) : String = apply escape xs;
+        in
+            escape nil
+    "#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::AmbiguousEmptyCase { .. }) => {},
-        other => panic!("unexpected result: {:#?}", other),
+        Err(TypeError::NonTerminatingRecursion { ref name, .. }) if name == "escape" => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
@@ -739,7 +1484,7 @@ fn empty_record_ty() {
     let expected_ty = r"Type";
     let given_expr = r"Record {}";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -753,7 +1498,7 @@ fn empty_record_intro() {
     let expected_ty = r"Record {}";
     let given_expr = r"record {}";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -767,7 +1512,7 @@ fn dependent_record_ty() {
     let expected_ty = r"Type^2";
     let given_expr = r"Record { t : Type^1; x : t }";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -781,7 +1526,7 @@ fn record_intro() {
     let expected_ty = r"Record { t : Type; x : String }";
     let given_expr = r#"record { t = String; x = "Hello" }"#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -795,7 +1540,7 @@ fn record_proj() {
     let expected_ty = r"String";
     let given_expr = r#"(record { t = String; x = "hello" } : Record { t : Type; x : String }).x"#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -835,7 +1580,7 @@ fn record_proj_weird1() {
         test : f data.x;
     }";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -856,7 +1601,7 @@ fn record_proj_weird2() {
         test2 : test1 (inner-prod t.n t.x t.y);
     }";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -872,7 +1617,7 @@ fn record_proj_shift() {
         id = \(a : Type) (x : a) => x;
     }.id^1";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -896,3 +1641,98 @@ fn array_intro_ambiguous() {
         Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
+
+#[test]
+fn recover_let_items() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            a : S32 = "hello";
+            b : String = a;
+            c = a 1;
+            d = "world";
+            e (x : S32) : S32 = "hello";
+        in
+            record { a = a; b = b; d = d; e = e }
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    let (_, ty, errors) = elaborate::infer_term_recovering(&context, &raw_term);
+
+    match errors[..] {
+        [TypeError::LiteralMismatch { .. }, TypeError::Mismatch { .. }, TypeError::ArgAppliedToNonFunction { .. }, TypeError::LiteralMismatch { .. }] =>
+            {},
+        _ => panic!("unexpected errors: {:?}", errors),
+    }
+
+    // The types of the items that were annotated are kept
+    assert_eq!(
+        context.quote(&ty).unwrap(),
+        support::parse_nf_term(
+            &mut codemap,
+            &context,
+            r"Record { a : S32; b : String; d : String; e : S32 -> S32 }"
+        ),
+    );
+}
+
+#[test]
+fn recover_let_items_constructor_args() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            Nat = Variant Nat { zero; suc Nat };
+            f = "hello" 1;
+        in
+            f (suc zero)
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    let (_, _, errors) = elaborate::infer_term_recovering(&context, &raw_term);
+
+    // The constructors are not reported as undefined names
+    match errors[..] {
+        [TypeError::ArgAppliedToNonFunction { .. }] => {},
+        _ => panic!("unexpected errors: {:?}", errors),
+    }
+}
+
+#[test]
+fn recover_record_fields() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr =
+        r#"record { a = "hello"; b = "world"; c = 'x' } : Record { a : S32; b : S32; c : Char }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    let (_, _, errors) = elaborate::infer_term_recovering(&context, &raw_term);
+
+    match errors[..] {
+        [TypeError::LiteralMismatch { .. }, TypeError::LiteralMismatch { .. }] => {},
+        _ => panic!("unexpected errors: {:?}", errors),
+    }
+
+    // Only the first error is reported when we aren't recovering
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::LiteralMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
@@ -1,23 +1,36 @@
 use codespan::CodeMap;
-use moniker::{assert_term_eq, Binder, Embed, FreeVar, Scope, Var};
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::elaborate::Context;
+use pikelet_core::nbe;
 use pikelet_core::syntax::core::{RcTerm, Term};
-use pikelet_core::syntax::domain::{Neutral, RcNeutral, RcValue, Value};
+use pikelet_core::syntax::domain::{RcValue, Value};
+use pikelet_core::syntax::{Import, LevelShift, Name, Plicity};
 
 mod support;
 
 #[test]
 fn var() {
+    let mut context = Context::default();
+    context.push_local(Name::new("x"), RcValue::from(Value::universe(0)));
+
+    let var = RcTerm::from(Term::var(0, 0));
+
+    assert_eq!(
+        context.nf_term(&var).unwrap(),
+        RcTerm::from(Term::var(0, 0))
+    );
+}
+
+#[test]
+fn global() {
     let context = Context::default();
 
-    let x = FreeVar::fresh_named("x");
-    let var = RcTerm::from(Term::var(Var::Free(x.clone()), 0));
+    let var = RcTerm::from(Term::global("x", 0));
 
     assert_eq!(
-        pikelet_core::nbe::nf_term(&context, &var).unwrap(),
-        RcValue::from(Value::var(Var::Free(x), 0)),
+        context.nf_term(&var).unwrap(),
+        RcTerm::from(Term::global("x", 0))
     );
 }
 
@@ -28,39 +41,108 @@ fn ty() {
 
     assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"Type"),
-        RcValue::from(Value::universe(0)),
+        RcTerm::from(Term::universe(0)),
     );
 }
 
 #[test]
-fn fun_intro() {
+fn ty_level_max() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    assert_eq!(
+        support::parse_nf_term(
+            &mut codemap,
+            &context,
+            r"Type^(level-max 1 (level-max 3 2))"
+        ),
+        RcTerm::from(Term::universe(3)),
+    );
+}
+
+#[test]
+fn ty_level_var_app() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let x = FreeVar::fresh_named("x");
+    let given_expr = r"(\(l : Level) (m : Level) => Type^(level-max (l^1) m)) 2 (1 : Level)";
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        RcTerm::from(Term::universe(3)),
+    );
+}
 
-    assert_term_eq!(
+#[test]
+fn fun_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"\x : Type => x"),
-        RcValue::from(Value::FunIntro(Scope::new(
-            (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-            RcValue::from(Value::var(Var::Free(x), 0)),
-        ))),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Name::new("x"),
+            RcTerm::from(Term::universe(0)),
+            RcTerm::from(Term::var(0, 0)),
+        )),
     );
 }
 
 #[test]
-fn fun_ty() {
+fn fun_intro_var_shift() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let x = FreeVar::fresh_named("x");
+    let given_expr = r"\(a : Type) => (\(b : Type) => b^1) a";
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Name::new("a"),
+            RcTerm::from(Term::universe(0)),
+            RcTerm::from(Term::var(0, 1)),
+        )),
+    );
+}
+
+#[test]
+fn import_fun_app_shift() {
+    let mut codemap = CodeMap::new();
+    let mut context = Context::default();
+
+    // Evaluate the import under a shift before it has been defined, so that
+    // its application gets stuck
+    let import = RcTerm::from(Term::Import("const-type".to_owned()));
+    let head = nbe::eval_term(&context, context.values(), LevelShift(1), &import).unwrap();
+
+    let (term, ty) = support::parse_infer_term(&mut codemap, &context, r"\(a : Type) => Type");
+    let ty = context.eval(&ty).unwrap();
+    context.insert_import("const-type".to_owned(), Import::Term(term), ty);
+
+    let arg = RcValue::from(Value::universe(0));
+    let value = nbe::apply(&context, &head, Plicity::Explicit, arg).unwrap();
+
+    assert_eq!(
+        context.quote(&value).unwrap(),
+        RcTerm::from(Term::universe(1)),
+    );
+}
+
+#[test]
+fn fun_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"(x : Type) -> x"),
-        RcValue::from(Value::FunType(Scope::new(
-            (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-            RcValue::from(Value::var(Var::Free(x), 0)),
-        ))),
+        RcTerm::from(Term::FunType(
+            Plicity::Explicit,
+            Name::new("x"),
+            RcTerm::from(Term::universe(0)),
+            RcTerm::from(Term::var(0, 0)),
+        )),
     );
 }
 
@@ -71,28 +153,30 @@ fn fun_intro_fun_app() {
 
     let given_expr = r"\(x : Type -> Type) (y : Type) => x y";
 
-    let x = FreeVar::fresh_named("x");
-    let y = FreeVar::fresh_named("y");
-    let ty_arr = RcValue::from(Value::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(RcValue::from(Value::universe(0))),
-        ),
-        RcValue::from(Value::universe(0)),
-    )));
+    let ty_arr = RcTerm::from(Term::FunType(
+        Plicity::Explicit,
+        Name::default(),
+        RcTerm::from(Term::universe(0)),
+        RcTerm::from(Term::universe(0)),
+    ));
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr,),
-        RcValue::from(Value::FunIntro(Scope::new(
-            (Binder(x.clone()), Embed(ty_arr)),
-            RcValue::from(Value::FunIntro(Scope::new(
-                (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::Neutral(
-                    RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                    vec![RcValue::from(Value::var(Var::Free(y), 0))],
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Name::new("x"),
+            ty_arr,
+            RcTerm::from(Term::FunIntro(
+                Plicity::Explicit,
+                Name::new("y"),
+                RcTerm::from(Term::universe(0)),
+                RcTerm::from(Term::FunApp(
+                    RcTerm::from(Term::var(1, 0)),
+                    Plicity::Explicit,
+                    RcTerm::from(Term::var(0, 0)),
                 )),
-            ))),
-        ))),
+            )),
+        )),
     );
 }
 
@@ -103,28 +187,30 @@ fn fun_ty_fun_app() {
 
     let given_expr = r"(x : Type -> Type) -> (y : Type) -> x y";
 
-    let x = FreeVar::fresh_named("x");
-    let y = FreeVar::fresh_named("y");
-    let ty_arr = RcValue::from(Value::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(RcValue::from(Value::universe(0))),
-        ),
-        RcValue::from(Value::universe(0)),
-    )));
+    let ty_arr = RcTerm::from(Term::FunType(
+        Plicity::Explicit,
+        Name::default(),
+        RcTerm::from(Term::universe(0)),
+        RcTerm::from(Term::universe(0)),
+    ));
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
-        RcValue::from(Value::FunType(Scope::new(
-            (Binder(x.clone()), Embed(ty_arr)),
-            RcValue::from(Value::FunType(Scope::new(
-                (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::Neutral(
-                    RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                    vec![RcValue::from(Value::var(Var::Free(y), 0))],
+        RcTerm::from(Term::FunType(
+            Plicity::Explicit,
+            Name::new("x"),
+            ty_arr,
+            RcTerm::from(Term::FunType(
+                Plicity::Explicit,
+                Name::new("y"),
+                RcTerm::from(Term::universe(0)),
+                RcTerm::from(Term::FunApp(
+                    RcTerm::from(Term::var(1, 0)),
+                    Plicity::Explicit,
+                    RcTerm::from(Term::var(0, 0)),
                 )),
-            ))),
-        ))),
+            )),
+        )),
     );
 }
 
@@ -138,7 +224,7 @@ fn id_fun_app_ty() {
     let given_expr = r"(\(a : Type^1) (x : a) => x) Type";
     let expected_expr = r"\x : Type => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -153,7 +239,7 @@ fn id_fun_app_ty_ty() {
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) Type";
     let expected_expr = r"Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -169,7 +255,7 @@ fn id_fun_app_ty_arr_ty() {
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) (Type -> Type)";
     let expected_expr = r"Type -> Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -188,7 +274,7 @@ fn id_fun_app_id() {
         ";
     let expected_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -210,7 +296,7 @@ fn const_fun_app_id_ty() {
     ";
     let expected_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -224,7 +310,7 @@ fn horrifying_fun_app_1() {
     let given_expr = r"(\(t : Type) (f : (a : Type) -> Type) => f t) String (\(a : Type) => a)";
     let expected_expr = r"String";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -238,7 +324,7 @@ fn horrifying_fun_app_2() {
     let given_expr = r#"(\(t: String) (f: String -> String) => f t) "hello""#;
     let expected_expr = r#"\(f : String -> String) => f "hello""#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -258,7 +344,7 @@ fn let_expr_1() {
         "helloo"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -279,7 +365,7 @@ fn let_expr_2() {
         "helloo"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -297,7 +383,7 @@ fn if_true() {
         "true"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -315,7 +401,7 @@ fn if_false() {
         "false"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -344,7 +430,7 @@ fn if_eval_cond() {
         }
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -374,12 +460,86 @@ fn case_expr_bool() {
         }
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn case_expr_variant() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let Option (a : Type) : Type = Variant { none; some a };
+            List (a : Type) : Type = Variant List { nil; cons a List };
+            head (xs : List String) : Option String = case xs {
+                nil => none;
+                cons x _ => some x;
+            };
+        in
+            record {
+                test-nil = head nil;
+                test-cons = head (cons "hello" (cons "world" nil));
+            }
+    "#;
+    let expected_expr = r#"
+        record {
+            test-nil = none : Variant { none; some String };
+            test-cons = some "hello" : Variant { none; some String };
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn let_rec() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let Nat = Variant Nat { zero; succ Nat };
+            add (m : Nat) (n : Nat) : Nat = case m {
+                zero => n;
+                succ pred => succ (add pred n);
+            };
+        in
+            add (succ (succ zero)) (succ zero)
+    "#;
+    let expected_expr = r#"
+        succ (succ (succ zero)) : Variant Nat { zero; succ Nat }
+    "#;
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
 }
 
+#[test]
+fn let_rec_stuck() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    // Recursive calls on neutral arguments are not unfolded
+    let given_expr = r#"
+        let Nat = Variant Nat { zero; succ Nat };
+            add (m : Nat) (n : Nat) : Nat = case m {
+                zero => n;
+                succ pred => succ (add pred n);
+            };
+        in
+            \m : Nat => add m zero
+    "#;
+
+    support::parse_nf_term(&mut codemap, &context, given_expr);
+}
+
 #[test]
 fn record_ty_shadow() {
     let mut codemap = CodeMap::new();
@@ -388,7 +548,7 @@ fn record_ty_shadow() {
     let given_expr = r"(\t : Type => Record { String : Type; x : t; y : String }) String";
     let expected_expr = r#"Record { String as String1 : Type; x : String; y : String1 }"#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -20,7 +20,16 @@ fn imports() {
 
     assert_eq!(
         parse_result.1,
-        vec!["prims.pi".to_owned(), "prelude.pi".to_owned()],
+        vec![
+            (
+                ByteSpan::new(ByteIndex(54), ByteIndex(64)),
+                "prims.pi".to_owned(),
+            ),
+            (
+                ByteSpan::new(ByteIndex(99), ByteIndex(111)),
+                "prelude.pi".to_owned(),
+            ),
+        ],
     );
 }
 
@@ -84,3 +93,42 @@ fn integer_overflow() {
         )
     );
 }
+
+#[test]
+fn level_out_of_range() {
+    let src = "Type^4294967296";
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+    let parse_result = parse::term(&filemap);
+
+    assert_eq!(
+        parse_result,
+        (
+            concrete::Term::Error(ByteSpan::new(ByteIndex(1), ByteIndex(16))),
+            vec![],
+            vec![ParseError::LevelOutOfRange {
+                span: ByteSpan::new(ByteIndex(6), ByteIndex(16)),
+                value: 4294967296,
+            }],
+        )
+    );
+}
+
+#[test]
+fn literal_escapes_round_trip() {
+    let src = r#"record { s = "tab\t quote\" bell\u{7} \u{1F600}"; c = '\''; d = '\0' }"#;
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (term, _, errors) = parse::term(&filemap);
+    assert_eq!(errors, vec![]);
+
+    let printed = term.to_string();
+    let filemap = codemap.add_filemap(FileName::virtual_("printed"), printed.clone());
+    let (reparsed, _, errors) = parse::term(&filemap);
+    assert_eq!(errors, vec![]);
+
+    // Compare the printed forms, seeing as the spans will differ
+    assert_eq!(reparsed.to_string(), printed);
+    assert!(printed.contains(r#""tab\t quote\" bell\u{7} 😀""#));
+}
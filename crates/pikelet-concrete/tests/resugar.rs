@@ -1,10 +1,9 @@
 use codespan::{ByteIndex, ByteSpan};
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::resugar::{Resugar, ResugarEnv};
-use pikelet_concrete::syntax::concrete;
-use pikelet_core::syntax::{core, Label, LevelShift, Literal};
+use pikelet_concrete::syntax::{concrete, IntFormat};
+use pikelet_core::syntax::{core, Label, LevelShift, Literal, Name, Plicity};
 
 fn span() -> ByteSpan {
     ByteSpan::default()
@@ -74,11 +73,10 @@ fn lit_string() {
 
 #[test]
 fn var() {
-    let free_var = FreeVar::fresh_named("x");
     let mut env = ResugarEnv::new();
-    env.on_item(&Label("x".to_owned()), &Binder(free_var.clone()));
+    env.on_item(&Label("x".to_owned()));
 
-    let core_term = core::Term::var(Var::Free(free_var), 0);
+    let core_term = core::Term::var(0, 0);
     let concrete_term = concrete::Term::Name(span(), "x".to_owned(), None);
 
     assert_eq!(core_term.resugar(&env), concrete_term);
@@ -86,11 +84,10 @@ fn var() {
 
 #[test]
 fn var_shadow_keyword() {
-    let free_var = FreeVar::fresh_named("if");
     let mut env = ResugarEnv::new();
-    env.on_item(&Label("if".to_owned()), &Binder(free_var.clone()));
+    env.on_item(&Label("if".to_owned()));
 
-    let core_term = core::Term::var(Var::Free(free_var), 0);
+    let core_term = core::Term::var(0, 0);
     let concrete_term = concrete::Term::Name(span(), "if1".to_owned(), None);
 
     assert_eq!(core_term.resugar(&env), concrete_term);
@@ -108,15 +105,12 @@ fn import() {
 
 #[test]
 fn arrow() {
-    let core_term = core::RcTerm::from(core::Term::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(core::RcTerm::from(core::RcTerm::from(
-                core::Term::universe(0),
-            ))),
-        ),
-        core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-    )));
+    let core_term = core::RcTerm::from(core::Term::FunType(
+        Plicity::Explicit,
+        Name::default(),
+        core::RcTerm::from(core::Term::universe(0)),
+        core::RcTerm::from(core::Term::universe(0)),
+    ));
 
     let concrete_term = concrete::Term::FunArrow(
         Box::new(concrete::Term::Universe(span(), None)),
@@ -128,21 +122,17 @@ fn arrow() {
 
 #[test]
 fn arrow_parens() {
-    let core_term = core::Term::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(core::RcTerm::from(core::Term::FunType(Scope::new(
-                (
-                    Binder(FreeVar::fresh_unnamed()),
-                    Embed(core::RcTerm::from(core::RcTerm::from(
-                        core::Term::universe(0),
-                    ))),
-                ),
-                core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-            )))),
-        ),
-        core::RcTerm::from(core::RcTerm::from(core::Term::universe(1))),
-    ));
+    let core_term = core::Term::FunType(
+        Plicity::Explicit,
+        Name::default(),
+        core::RcTerm::from(core::Term::FunType(
+            Plicity::Explicit,
+            Name::default(),
+            core::RcTerm::from(core::Term::universe(0)),
+            core::RcTerm::from(core::Term::universe(0)),
+        )),
+        core::RcTerm::from(core::Term::universe(1)),
+    );
 
     let concrete_term = concrete::Term::FunArrow(
         Box::new(concrete::Term::Parens(
@@ -163,33 +153,34 @@ fn arrow_parens() {
 
 #[test]
 fn let_shadow_keyword() {
-    let var_else1 = FreeVar::fresh_named("else");
-    let var_else2 = FreeVar::fresh_named("else");
-
-    let core_module = core::Term::Let(Scope::new(
-        Nest::new(vec![
+    let core_module = core::Term::Let(
+        vec![
             (
-                Binder(var_else1.clone()),
-                Embed(core::RcTerm::from(core::Term::universe(0))),
+                Name::new("else"),
+                core::RcTerm::from(core::Term::universe(0)),
+                "The first universe".to_owned(),
             ),
             (
-                Binder(var_else2.clone()),
-                Embed(core::RcTerm::from(core::Term::universe(0))),
+                Name::new("else"),
+                core::RcTerm::from(core::Term::universe(0)),
+                String::new(),
             ),
-        ]),
+        ],
         core::RcTerm::from(core::Term::RecordIntro(vec![])),
-    ));
+    );
 
     let concrete_module = concrete::Term::Let(
         index(),
         vec![
             concrete::Item::Definition {
+                doc: vec!["The first universe".to_owned()],
                 name: (index(), "else1".to_owned()),
                 params: vec![],
                 return_ann: None,
                 body: concrete::Term::Universe(span(), None),
             },
             concrete::Item::Definition {
+                doc: vec![],
                 name: (index(), "else2".to_owned()),
                 params: vec![],
                 return_ann: None,
@@ -204,7 +195,7 @@ fn let_shadow_keyword() {
 
 #[test]
 fn record_ty_empty() {
-    let core_term = core::Term::RecordType(Scope::new(Nest::new(vec![]), ()));
+    let core_term = core::Term::RecordType(vec![]);
     let concrete_term = concrete::Term::RecordType(span(), vec![]);
 
     assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
@@ -213,34 +204,20 @@ fn record_ty_empty() {
 #[test]
 fn record_ty() {
     let mut env = ResugarEnv::new();
-    env.on_item(
-        &Label("String".to_owned()),
-        &Binder(FreeVar::fresh_named("String")),
-    );
+    env.on_item(&Label("String".to_owned()));
 
-    let var_string = FreeVar::fresh_named("String");
-    let var_x = FreeVar::fresh_named("x");
-
-    let core_term = core::Term::RecordType(Scope::new(
-        Nest::new(vec![
-            (
-                Label("String".to_owned()),
-                Binder(var_string.clone()),
-                Embed(core::RcTerm::from(core::RcTerm::from(
-                    core::Term::universe(0),
-                ))),
-            ),
-            (
-                Label("x".to_owned()),
-                Binder(var_x.clone()),
-                Embed(core::RcTerm::from(core::RcTerm::from(core::Term::var(
-                    Var::Free(var_string),
-                    0,
-                )))),
-            ),
-        ]),
-        (),
-    ));
+    let core_term = core::Term::RecordType(vec![
+        (
+            Label("String".to_owned()),
+            Name::new("String"),
+            core::RcTerm::from(core::Term::universe(0)),
+        ),
+        (
+            Label("x".to_owned()),
+            Name::new("x"),
+            core::RcTerm::from(core::Term::var(0, 0)),
+        ),
+    ]);
     let concrete_term = concrete::Term::RecordType(
         span(),
         vec![
@@ -330,3 +307,12 @@ fn array_intro() {
 
     assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
 }
+
+#[test]
+fn lit_negative() {
+    let core_term = core::Term::Literal(Literal::S32(-42));
+    let concrete_term =
+        concrete::Term::Literal(concrete::Literal::Int(span(), -42, IntFormat::Dec));
+
+    assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
+}
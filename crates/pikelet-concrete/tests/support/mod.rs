@@ -8,7 +8,6 @@ use pikelet_concrete::desugar::{Desugar, DesugarEnv};
 use pikelet_concrete::elaborate::{self, Context};
 use pikelet_concrete::parse;
 use pikelet_concrete::syntax::concrete;
-use pikelet_core::nbe;
 use pikelet_core::syntax::core::RcTerm;
 use pikelet_core::syntax::domain::{RcType, RcValue};
 
@@ -27,12 +26,15 @@ pub fn parse_term(codemap: &mut CodeMap, src: &str) -> concrete::Term {
     concrete_term
 }
 
-pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) -> (RcTerm, RcType) {
+pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) -> (RcTerm, RcTerm) {
     let raw_term = parse_term(codemap, src)
         .desugar(&DesugarEnv::new(context.mappings()))
         .unwrap();
     match elaborate::infer_term(context, &raw_term) {
-        Ok((term, ty)) => (term, ty),
+        Ok((term, ty)) => match context.quote(&ty) {
+            Ok(ty) => (term, ty),
+            Err(error) => panic!("quote error: {}", error),
+        },
         Err(error) => {
             let writer = StandardStream::stdout(ColorChoice::Always);
             codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
@@ -41,14 +43,22 @@ pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) ->
     }
 }
 
-pub fn parse_nf_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcValue {
+pub fn parse_nf_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcTerm {
     let term = parse_infer_term(codemap, context, src).0;
-    match nbe::nf_term(context, &term) {
-        Ok(value) => value,
+    match context.nf_term(&term) {
+        Ok(term) => term,
         Err(error) => panic!("normalize error: {}", error),
     }
 }
 
+pub fn parse_eval_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcValue {
+    let term = parse_infer_term(codemap, context, src).0;
+    match context.eval(&term) {
+        Ok(value) => value,
+        Err(error) => panic!("evaluation error: {}", error),
+    }
+}
+
 pub fn parse_check_term(codemap: &mut CodeMap, context: &Context, src: &str, expected: &RcType) {
     let raw_term = parse_term(codemap, src)
         .desugar(&DesugarEnv::new(context.mappings()))
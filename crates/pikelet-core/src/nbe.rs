@@ -1,9 +1,11 @@
 use failure::Fail;
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
 
 use crate::syntax::core::{Pattern, RcPattern, RcTerm, Term};
-use crate::syntax::domain::{Head, Neutral, RcNeutral, RcValue, Value};
-use crate::syntax::Import;
+use crate::syntax::domain::{
+    CaseClosure, Closure, Head, LazyValue, Locals, Neutral, RcNeutral, RcValue, RecordTypeClosure,
+    Spine, Value, VariantTypeClosure,
+};
+use crate::syntax::{Import, Label, Level, LevelShift, MetaVar, Plicity, VarIndex, VarLevel};
 
 /// An error produced during normalization
 ///
@@ -26,202 +28,249 @@ impl NbeError {
 /// An environment where normalization happens
 pub trait Env {
     fn get_import(&self, name: &str) -> Option<&Import>;
-    fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm>;
+    fn get_definition(&self, name: &str) -> Option<&RcTerm>;
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm>;
 }
 
-/// Reduce a term to its normal form
-pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
+/// Whether definitions should be unfolded when reading values back into terms
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unfold {
+    /// Unfold every definition, producing terms in normal form
+    Always,
+    /// Leave definitions folded where possible, producing terms that are
+    /// closer to what was originally written
+    Never,
+}
+
+/// Reduce a term to its normal form, under the given local variables
+pub fn nf_term(env: &dyn Env, locals: &Locals, term: &RcTerm) -> Result<RcTerm, NbeError> {
+    let size = VarLevel(locals.len());
+    quote(env, size, Unfold::Always, &eval(env, locals, term)?)
+}
+
+/// Evaluate a term into a value, leaving the bodies of binders in closures
+pub fn eval(env: &dyn Env, locals: &Locals, term: &RcTerm) -> Result<RcValue, NbeError> {
+    eval_term(env, locals, LevelShift(0), term)
+}
+
+/// Unfold the definitions at the head of a value, until we reach a value that
+/// is not glued
+pub fn force(env: &dyn Env, value: &RcValue) -> Result<RcValue, NbeError> {
+    match *value.inner {
+        Value::Glued(_, _, ref unfolded) => force(env, &unfolded.force(env)?),
+        _ => Ok(value.clone()),
+    }
+}
+
+/// Look up the value of the local variable at `index`
+fn get_local(locals: &Locals, index: VarIndex, shift: LevelShift) -> Result<RcValue, NbeError> {
+    let size = VarLevel(locals.len());
+    let value = if index.0 < size.0 {
+        locals.get(index.to_level(size).0)
+    } else {
+        None
+    };
+
+    match value {
+        Some(value) => {
+            let mut value = value.clone();
+            value.shift_universes(shift);
+            Ok(value)
+        },
+        None => Err(NbeError::new(format!("unbound local variable `{}`", index))),
+    }
+}
+
+/// Replace the level variables with the levels that their values stand for
+pub fn eval_level(
+    env: &dyn Env,
+    locals: &Locals,
+    shift: LevelShift,
+    level: &Level,
+) -> Result<Level<VarLevel>, NbeError> {
+    match *level {
+        Level::Finite(constant, ref vars, ref metas) => {
+            let init = Level::Finite(constant, Vec::new(), metas.clone());
+            vars.iter()
+                .try_fold(init, |acc, &(index, var_shift)| {
+                    match force(env, &get_local(locals, index, var_shift)?)?.as_level() {
+                        Some(level) => Ok(acc.max(&level)),
+                        None => Err(NbeError::new(format!("expected a level for `{}`", index))),
+                    }
+                })
+                .map(|level| level + shift)
+        },
+        Level::Omega => Ok(Level::Omega),
+    }
+}
+
+/// Evaluate a term under the given local variables, shifting the universes it
+/// mentions by `shift`
+pub fn eval_term(
+    env: &dyn Env,
+    locals: &Locals,
+    shift: LevelShift,
+    term: &RcTerm,
+) -> Result<RcValue, NbeError> {
     match *term.inner {
         // E-ANN
-        Term::Ann(ref expr, _) => nf_term(env, expr),
+        Term::Ann(ref expr, _) => eval_term(env, locals, shift, expr),
 
         // E-TYPE
-        Term::Universe(level) => Ok(RcValue::from(Value::Universe(level))),
+        Term::Universe(ref level) => Ok(RcValue::from(Value::Universe(eval_level(
+            env, locals, shift, level,
+        )?))),
+
+        Term::Level(ref level) => Ok(RcValue::from(Value::level(eval_level(
+            env, locals, shift, level,
+        )?))),
 
         Term::Literal(ref lit) => Ok(RcValue::from(Value::Literal(lit.clone()))),
 
-        // E-VAR, E-VAR-DEF
-        Term::Var(ref var, shift) => match *var {
-            Var::Free(ref name) => match env.get_definition(name) {
-                Some(term) => {
-                    let mut value = nf_term(env, term)?;
-                    value.shift_universes(shift);
-                    Ok(value)
-                },
-                None => Ok(RcValue::from(Value::var(var.clone(), shift))),
-            },
+        // E-VAR
+        Term::Var(index, var_shift) => get_local(locals, index, var_shift),
 
-            // We should always be substituting bound variables with fresh
-            // variables when entering scopes using `unbind`, so if we've
-            // encountered one here this is definitely a bug!
-            Var::Bound(_) => Err(NbeError::new(format!("unexpected bound var `{}`", var))),
+        // E-GLOBAL, E-GLOBAL-DEF
+        //
+        // Definitions are only unfolded once their values are needed
+        Term::Global(ref name, var_shift) => match env.get_definition(name) {
+            Some(term) => {
+                let shift = shift + var_shift;
+                let unfolded = LazyValue::eval(Locals::new(), shift, term.clone());
+                let head = Head::Global(name.clone(), shift);
+                Ok(RcValue::from(Value::Glued(head, Spine::new(), unfolded)))
+            },
+            None => Ok(RcValue::from(Value::global(name.clone(), var_shift))),
         },
 
+        // Imported terms are treated like definitions, and are only unfolded
+        // once their values are needed
         Term::Import(ref name) => match env.get_import(name) {
-            Some(&Import::Term(ref term)) => nf_term(env, term),
+            Some(&Import::Term(ref term)) => {
+                let unfolded = LazyValue::eval(Locals::new(), shift, term.clone());
+                let head = Head::Import(name.clone(), shift);
+                Ok(RcValue::from(Value::Glued(head, Spine::new(), unfolded)))
+            },
             Some(&Import::Prim(ref interpretation)) => match interpretation(&[]) {
                 Some(value) => Ok(value),
                 None => Ok(RcValue::from(Value::from(Neutral::Head(Head::Import(
                     name.clone(),
+                    shift,
                 ))))),
             },
             None => Ok(RcValue::from(Value::from(Neutral::Head(Head::Import(
                 name.clone(),
+                shift,
             ))))),
         },
 
-        // E-PI
-        Term::FunType(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
+        // Solutions are closed terms, abstracted over the local variables
+        // that they are applied to
+        Term::Meta(meta) => match env.get_meta_solution(meta) {
+            Some(term) => eval_term(env, &Locals::new(), shift, &term),
+            None => Ok(RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))))),
+        },
 
-            Ok(RcValue::from(Value::FunType(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+        // E-PI
+        Term::FunType(plicity, ref name, ref ann, ref body) => {
+            let ann = eval_term(env, locals, shift, ann)?;
+            let closure = Closure::new(locals.clone(), shift, name.clone(), body.clone());
+            Ok(RcValue::from(Value::FunType(plicity, ann, closure)))
         },
 
         // E-LAM
-        Term::FunIntro(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
-
-            Ok(RcValue::from(Value::FunIntro(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+        Term::FunIntro(plicity, ref name, ref ann, ref body) => {
+            let ann = eval_term(env, locals, shift, ann)?;
+            let closure = Closure::new(locals.clone(), shift, name.clone(), body.clone());
+            Ok(RcValue::from(Value::FunIntro(plicity, ann, closure)))
         },
 
         // E-APP
-        Term::FunApp(ref head, ref arg) => {
-            match *nf_term(env, head)?.inner {
-                Value::FunIntro(ref scope) => {
-                    // FIXME: do a local unbind here
-                    let ((Binder(free_var), Embed(_)), body) = scope.clone().unbind();
-                    nf_term(env, &body.substs(&[(free_var, arg.clone())]))
-                },
-                Value::Neutral(ref neutral, ref spine) => {
-                    let arg = nf_term(env, arg)?;
-                    let mut spine = spine.clone();
-
-                    match *neutral.inner {
-                        Neutral::Head(Head::Import(ref name)) => {
-                            spine.push(arg);
-
-                            match env.get_import(name) {
-                                Some(&Import::Term(ref _term)) => {
-                                    // nf_term(env, term)
-                                    unimplemented!("import applications")
-                                },
-                                Some(&Import::Prim(ref interpretation)) => {
-                                    match interpretation(&spine) {
-                                        Some(value) => return Ok(value),
-                                        None => {},
-                                    }
-                                },
-                                None => {},
-                            }
-                        },
-                        Neutral::Head(Head::Var(..))
-                        | Neutral::RecordProj(..)
-                        | Neutral::Case(..) => spine.push(arg),
-                    }
-
-                    Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
-                },
-                _ => Err(NbeError::new("argument applied to non function")),
-            }
+        Term::FunApp(ref head, plicity, ref arg) => {
+            let head = eval_term(env, locals, shift, head)?;
+            let arg = eval_term(env, locals, shift, arg)?;
+            apply(env, &head, plicity, arg)
         },
 
         // E-LET
-        Term::Let(ref scope) => {
-            let (bindings, body) = scope.clone().unbind();
-            let mut mappings = Vec::with_capacity(bindings.unsafe_patterns.len());
+        Term::Let(ref bindings, ref body) => {
+            let mut locals = locals.clone();
 
-            for (Binder(free_var), Embed(term)) in bindings.unnest() {
-                let value = nf_term(env, &term.substs(&mappings))?;
-                mappings.push((free_var, RcTerm::from(&*value.inner)));
+            for &(_, ref term, _) in bindings {
+                let value = eval_term(env, &locals, shift, term)?;
+                locals.push_back(value);
             }
 
-            nf_term(env, &body.substs(&mappings))
+            eval_term(env, &locals, shift, body)
         },
 
-        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
-        Term::RecordType(ref scope) => {
-            let (fields, ()) = scope.clone().unbind();
-            let fields = Nest::new(
-                fields
-                    .unnest()
-                    .into_iter()
-                    .map(|(label, binder, Embed(ann))| {
-                        Ok((label, binder, Embed(nf_term(env, &ann)?)))
-                    })
-                    .collect::<Result<_, _>>()?,
-            );
-
-            Ok(RcValue::from(Value::RecordType(Scope::new(fields, ()))))
+        // E-FIX
+        Term::Fix(index, ref name, ref ann, ref body) => {
+            let ann = eval_term(env, locals, shift, ann)?;
+            let closure = Closure::new(locals.clone(), shift, name.clone(), body.clone());
+            Ok(RcValue::from(Value::from(Neutral::Head(Head::Fix(
+                index, ann, closure,
+            )))))
         },
 
+        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
+        Term::RecordType(ref fields) => Ok(RcValue::from(Value::RecordType(
+            RecordTypeClosure::new(locals.clone(), shift, fields.clone()),
+        ))),
+
         // E-RECORD, E-EMPTY-RECORD
         Term::RecordIntro(ref fields) => {
             let fields = fields
                 .iter()
-                .map(|&(ref label, ref term)| Ok((label.clone(), nf_term(env, &term)?)))
+                .map(|&(ref label, ref term)| {
+                    Ok((label.clone(), eval_term(env, locals, shift, term)?))
+                })
                 .collect::<Result<_, _>>()?;
 
             Ok(RcValue::from(Value::RecordIntro(fields)))
         },
 
         // E-PROJ
-        Term::RecordProj(ref expr, ref label, shift) => {
-            match *nf_term(env, expr)? {
-                Value::Neutral(ref neutral, ref spine) => {
-                    return Ok(RcValue::from(Value::Neutral(
-                        RcNeutral::from(Neutral::RecordProj(neutral.clone(), label.clone(), shift)),
-                        spine.clone(),
-                    )));
-                },
-                Value::RecordIntro(ref fields) => {
-                    for &(ref current_label, ref current_expr) in fields {
-                        if current_label == label {
-                            return Ok(current_expr.clone());
-                        }
-                    }
-                },
-                _ => {},
-            }
+        Term::RecordProj(ref expr, ref label, label_shift) => record_proj(
+            env,
+            &eval_term(env, locals, shift, expr)?,
+            label,
+            label_shift,
+        ),
 
-            Err(NbeError::new(format!(
-                "projected on non existent field `{}`",
-                label
-            )))
-        },
+        // E-VARIANT-TYPE
+        Term::VariantType(ref name, ref arms) => Ok(RcValue::from(Value::VariantType(
+            VariantTypeClosure::new(locals.clone(), shift, name.clone(), arms.clone()),
+        ))),
+
+        // E-VARIANT
+        Term::VariantIntro(ref label, ref args) => Ok(RcValue::from(Value::VariantIntro(
+            label.clone(),
+            args.iter()
+                .map(|arg| eval_term(env, locals, shift, arg))
+                .collect::<Result<_, _>>()?,
+        ))),
 
         // E-CASE
         Term::Case(ref head, ref clauses) => {
-            let head = nf_term(env, head)?;
+            let head = force(env, &eval_term(env, locals, shift, head)?)?;
 
-            if let Value::Neutral(ref neutral, ref spine) = *head {
+            if let Value::Error = *head {
+                Ok(head)
+            } else if let Value::Neutral(ref neutral, ref spine) = *head {
                 Ok(RcValue::from(Value::Neutral(
                     RcNeutral::from(Neutral::Case(
                         neutral.clone(),
-                        clauses
-                            .iter()
-                            .map(|clause| {
-                                let (pattern, body) = clause.clone().unbind();
-                                Ok(Scope::new(pattern, nf_term(env, &body)?))
-                            })
-                            .collect::<Result<_, _>>()?,
+                        CaseClosure::new(locals.clone(), shift, clauses.clone()),
                     )),
                     spine.clone(),
                 )))
             } else {
-                for clause in clauses {
-                    let (pattern, body) = clause.clone().unbind();
-                    if let Some(mappings) = match_value(env, &pattern, &head)? {
-                        let mappings = mappings
-                            .into_iter()
-                            .map(|(free_var, value)| (free_var, RcTerm::from(&*value.inner)))
-                            .collect::<Vec<_>>();
-                        return nf_term(env, &body.substs(&mappings));
+                for &(ref pattern, ref body) in clauses {
+                    if let Some(values) = match_value(env, locals, shift, pattern, &head)? {
+                        let mut locals = locals.clone();
+                        locals.extend(values);
+                        return eval_term(env, &locals, shift, body);
                     }
                 }
                 Err(NbeError::new("no patterns applicable"))
@@ -232,35 +281,434 @@ pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
         Term::ArrayIntro(ref elems) => Ok(RcValue::from(Value::ArrayIntro(
             elems
                 .iter()
-                .map(|elem| nf_term(env, elem))
+                .map(|elem| eval_term(env, locals, shift, elem))
                 .collect::<Result<_, _>>()?,
         ))),
+
+        Term::Error => Ok(RcValue::from(Value::Error)),
     }
 }
 
-/// If the pattern matches the value, this function returns the substitutions
-/// needed to apply the pattern to some body expression
+/// Apply a value to an argument
+pub fn apply(
+    env: &dyn Env,
+    head: &RcValue,
+    plicity: Plicity,
+    arg: RcValue,
+) -> Result<RcValue, NbeError> {
+    match *head.inner {
+        Value::FunIntro(_, _, ref closure) => closure.apply(env, arg),
+        Value::Glued(ref head, ref spine, ref unfolded) => {
+            let mut spine = spine.clone();
+            spine.push((plicity, arg.clone()));
+            let unfolded = unfolded.apply(plicity, arg);
+
+            Ok(RcValue::from(Value::Glued(head.clone(), spine, unfolded)))
+        },
+        Value::Neutral(ref neutral, ref spine) => {
+            let mut spine = spine.clone();
+            spine.push((plicity, arg));
+
+            match *neutral.inner {
+                Neutral::Head(Head::Import(ref name, shift)) => match env.get_import(name) {
+                    Some(&Import::Term(ref term)) => {
+                        // The import has been defined since the application
+                        // got stuck, so we can unfold it and apply it to the
+                        // arguments that were waiting for it, remembering the
+                        // shift that it was originally evaluated under
+                        let head = eval_term(env, &Locals::new(), shift, term)?;
+
+                        return spine
+                            .into_iter()
+                            .try_fold(head, |acc, (plicity, arg)| apply(env, &acc, plicity, arg));
+                    },
+                    Some(&Import::Prim(ref interpretation)) => {
+                        let args = spine
+                            .iter()
+                            .map(|&(_, ref arg)| force(env, arg))
+                            .collect::<Result<Vec<_>, _>>()?;
+
+                        if let Some(value) = interpretation(&args) {
+                            return Ok(value);
+                        }
+                    },
+                    None => {},
+                },
+                Neutral::Head(Head::Fix(index, _, ref closure)) => {
+                    // Only unfold the definition once the decreasing
+                    // argument has been built from a constructor, so
+                    // that we don't unfold recursive occurrences forever
+                    if let Some(&(_, ref arg)) = spine.get(index) {
+                        if let Value::VariantIntro(..) = *force(env, arg)?.inner {
+                            let fix = RcValue::from(Value::Neutral(neutral.clone(), Spine::new()));
+                            let body = closure.apply(env, fix)?;
+
+                            return spine.into_iter().try_fold(body, |acc, (plicity, arg)| {
+                                apply(env, &acc, plicity, arg)
+                            });
+                        }
+                    }
+                },
+                Neutral::Head(Head::Var(..))
+                | Neutral::Head(Head::Global(..))
+                | Neutral::Head(Head::Meta(..))
+                | Neutral::RecordProj(..)
+                | Neutral::Case(..) => {},
+            }
+
+            Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
+        },
+        Value::Error => Ok(RcValue::from(Value::Error)),
+        _ => Err(NbeError::new("argument applied to non function")),
+    }
+}
+
+/// Project a field from a record value
+pub fn record_proj(
+    env: &dyn Env,
+    value: &RcValue,
+    label: &Label,
+    shift: LevelShift,
+) -> Result<RcValue, NbeError> {
+    match *force(env, value)?.inner {
+        Value::Neutral(ref neutral, ref spine) => {
+            return Ok(RcValue::from(Value::Neutral(
+                RcNeutral::from(Neutral::RecordProj(neutral.clone(), label.clone(), shift)),
+                spine.clone(),
+            )));
+        },
+        Value::RecordIntro(ref fields) => {
+            for &(ref current_label, ref current_expr) in fields {
+                if current_label == label {
+                    return Ok(current_expr.clone());
+                }
+            }
+        },
+        Value::Error => return Ok(RcValue::from(Value::Error)),
+        _ => {},
+    }
+
+    Err(NbeError::new(format!(
+        "projected on non existent field `{}`",
+        label
+    )))
+}
+
+/// Read a level back into the core syntax, given the number of variables in
+/// scope
+pub fn quote_level(size: VarLevel, level: &Level<VarLevel>) -> Level {
+    level.map_vars(|var| Level::var(var.to_index(size)))
+}
+
+/// Read a value back into a term, given the number of variables in scope
+///
+/// The term will be in normal form if `unfold` is `Unfold::Always`.
+pub fn quote(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    value: &RcValue,
+) -> Result<RcTerm, NbeError> {
+    match *value.inner {
+        Value::Universe(ref level) => Ok(RcTerm::from(Term::Universe(quote_level(size, level)))),
+        Value::Level(ref level) => Ok(RcTerm::from(Term::Level(quote_level(size, level)))),
+        Value::Literal(ref lit) => Ok(RcTerm::from(Term::Literal(lit.clone()))),
+        Value::FunType(plicity, ref ann, ref closure) => {
+            let (ann, body) = quote_closure(env, size, unfold, ann, closure)?;
+            Ok(RcTerm::from(Term::FunType(
+                plicity,
+                closure.name().clone(),
+                ann,
+                body,
+            )))
+        },
+        Value::FunIntro(plicity, ref ann, ref closure) => {
+            let (ann, body) = quote_closure(env, size, unfold, ann, closure)?;
+            Ok(RcTerm::from(Term::FunIntro(
+                plicity,
+                closure.name().clone(),
+                ann,
+                body,
+            )))
+        },
+        Value::RecordType(ref closure) => {
+            let fields = closure
+                .fields(env, size)?
+                .into_iter()
+                .enumerate()
+                .map(|(index, (label, name, ty))| {
+                    Ok((
+                        label,
+                        name,
+                        quote(env, VarLevel(size.0 + index), unfold, &ty)?,
+                    ))
+                })
+                .collect::<Result<_, NbeError>>()?;
+
+            Ok(RcTerm::from(Term::RecordType(fields)))
+        },
+        Value::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| Ok((label.clone(), quote(env, size, unfold, expr)?)))
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::RecordIntro(fields)))
+        },
+        Value::VariantType(ref closure) => {
+            let ty = RcValue::from(Value::var(size, 0));
+            let arms = closure
+                .instantiate(env, ty)?
+                .into_iter()
+                .map(|(label, args)| {
+                    let args = args
+                        .iter()
+                        .map(|arg| quote(env, size.succ(), unfold, arg))
+                        .collect::<Result<_, _>>()?;
+                    Ok((label, args))
+                })
+                .collect::<Result<_, NbeError>>()?;
+
+            Ok(RcTerm::from(Term::VariantType(
+                closure.name().clone(),
+                arms,
+            )))
+        },
+        Value::VariantIntro(ref label, ref args) => Ok(RcTerm::from(Term::VariantIntro(
+            label.clone(),
+            args.iter()
+                .map(|arg| quote(env, size, unfold, arg))
+                .collect::<Result<_, _>>()?,
+        ))),
+        Value::ArrayIntro(ref elems) => Ok(RcTerm::from(Term::ArrayIntro(
+            elems
+                .iter()
+                .map(|elem| quote(env, size, unfold, elem))
+                .collect::<Result<_, _>>()?,
+        ))),
+        Value::Error => Ok(RcTerm::from(Term::Error)),
+        Value::Neutral(ref neutral, ref spine) => {
+            let head = quote_neutral(env, size, unfold, neutral)?;
+            quote_spine(env, size, unfold, head, spine)
+        },
+        Value::Glued(ref head, ref spine, ref unfolded) => match (unfold, head) {
+            // Definitions that have gone out of scope can't be referred to, so
+            // we have no choice but to unfold them
+            (Unfold::Never, &Head::Var(level, _)) if level.0 >= size.0 => {
+                quote(env, size, unfold, &unfolded.force(env)?)
+            },
+            // Imports can't be shifted in the core syntax, so we unfold them
+            // if they were evaluated under a shift
+            (Unfold::Never, &Head::Import(_, shift)) if shift != LevelShift(0) => {
+                quote(env, size, unfold, &unfolded.force(env)?)
+            },
+            (Unfold::Never, _) => {
+                let head = quote_head(env, size, unfold, head)?;
+                quote_spine(env, size, unfold, head, spine)
+            },
+            (Unfold::Always, _) => quote(env, size, unfold, &unfolded.force(env)?),
+        },
+    }
+}
+
+/// Read the arguments that a head is applied to back into the core syntax
+fn quote_spine(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    head: RcTerm,
+    spine: &Spine,
+) -> Result<RcTerm, NbeError> {
+    spine.iter().try_fold(head, |acc, &(plicity, ref arg)| {
+        Ok(RcTerm::from(Term::FunApp(
+            acc,
+            plicity,
+            quote(env, size, unfold, arg)?,
+        )))
+    })
+}
+
+/// Read the head of an application back into the core syntax
+fn quote_head(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    head: &Head,
+) -> Result<RcTerm, NbeError> {
+    match *head {
+        Head::Var(var, shift) => Ok(RcTerm::from(Term::Var(var.to_index(size), shift))),
+        Head::Global(ref name, shift) => Ok(RcTerm::from(Term::Global(name.clone(), shift))),
+        Head::Import(ref name, _) => Ok(RcTerm::from(Term::Import(name.clone()))),
+        Head::Meta(meta) => Ok(RcTerm::from(Term::Meta(meta))),
+        Head::Fix(index, ref ann, ref closure) => {
+            let (ann, body) = quote_closure(env, size, unfold, ann, closure)?;
+            Ok(RcTerm::from(Term::Fix(
+                index,
+                closure.name().clone(),
+                ann,
+                body,
+            )))
+        },
+    }
+}
+
+/// Read a neutral value back into a term, given the number of variables in
+/// scope
+pub fn quote_neutral(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    neutral: &RcNeutral,
+) -> Result<RcTerm, NbeError> {
+    match *neutral.inner {
+        Neutral::Head(ref head) => quote_head(env, size, unfold, head),
+        Neutral::RecordProj(ref expr, ref label, shift) => Ok(RcTerm::from(Term::RecordProj(
+            quote_neutral(env, size, unfold, expr)?,
+            label.clone(),
+            shift,
+        ))),
+        Neutral::Case(ref head, ref closure) => {
+            let clauses = closure
+                .clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| {
+                    let binders = pattern.binders().len();
+                    let mut locals = closure.locals.clone();
+                    for level in size.0..size.0 + binders {
+                        locals.push_back(RcValue::from(Value::var(VarLevel(level), 0)));
+                    }
+                    let body = eval_term(env, &locals, closure.shift, body)?;
+                    let body_size = VarLevel(size.0 + binders);
+                    let pattern =
+                        quote_pattern(env, size, unfold, &closure.locals, closure.shift, pattern)?;
+
+                    Ok((pattern, quote(env, body_size, unfold, &body)?))
+                })
+                .collect::<Result<_, NbeError>>()?;
+
+            let head = quote_neutral(env, size, unfold, head)?;
+            Ok(RcTerm::from(Term::Case(head, clauses)))
+        },
+    }
+}
+
+/// Read the annotation and body of a binder back into the core syntax
+fn quote_closure(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    ann: &RcValue,
+    closure: &Closure,
+) -> Result<(RcTerm, RcTerm), NbeError> {
+    let var = RcValue::from(Value::var(size, 0));
+    let body = quote(env, size.succ(), unfold, &closure.apply(env, var)?)?;
+
+    Ok((quote(env, size, unfold, ann)?, body))
+}
+
+/// Read a pattern from a stuck case expression back into the core syntax,
+/// moving the variables it refers to from the locals of the case expression
+/// to the current scope
+fn quote_pattern(
+    env: &dyn Env,
+    size: VarLevel,
+    unfold: Unfold,
+    locals: &Locals,
+    shift: LevelShift,
+    pattern: &RcPattern,
+) -> Result<RcPattern, NbeError> {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, ref ty) => Ok(RcPattern::from(Pattern::Ann(
+            quote_pattern(env, size, unfold, locals, shift, pattern)?,
+            quote(env, size, unfold, &eval_term(env, locals, shift, ty)?)?,
+        ))),
+        Pattern::Var(index, var_shift) => {
+            let value = get_local(locals, index, var_shift)?;
+            match *quote(env, size, unfold, &value)?.inner {
+                Term::Var(index, shift) => Ok(RcPattern::from(Pattern::Var(index, shift))),
+                Term::Global(ref name, shift) => {
+                    Ok(RcPattern::from(Pattern::Global(name.clone(), shift)))
+                },
+                Term::Literal(ref literal) => {
+                    Ok(RcPattern::from(Pattern::Literal(literal.clone())))
+                },
+                _ => Err(NbeError::new(format!(
+                    "unexpected pattern variable `{}`",
+                    index
+                ))),
+            }
+        },
+        Pattern::VariantIntro(ref label, ref patterns) => {
+            Ok(RcPattern::from(Pattern::VariantIntro(
+                label.clone(),
+                patterns
+                    .iter()
+                    .map(|pattern| quote_pattern(env, size, unfold, locals, shift, pattern))
+                    .collect::<Result<_, _>>()?,
+            )))
+        },
+        Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) | Pattern::Error => {
+            Ok(pattern.clone())
+        },
+    }
+}
+
+/// If the pattern matches the value, this function returns the values of the
+/// variables that it binds, in the order that they are brought into scope
 pub fn match_value(
     env: &dyn Env,
+    locals: &Locals,
+    shift: LevelShift,
     pattern: &RcPattern,
     value: &RcValue,
-) -> Result<Option<Vec<(FreeVar<String>, RcValue)>>, NbeError> {
+) -> Result<Option<Vec<RcValue>>, NbeError> {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => return match_value(env, locals, shift, pattern, value),
+        Pattern::Binder(_) => return Ok(Some(vec![value.clone()])),
+        _ => {},
+    }
+
+    // The remaining patterns need to inspect the value, so we need to unfold
+    // any definitions that it is glued to
+    let value = force(env, value)?;
+
     match (&*pattern.inner, &*value.inner) {
-        (&Pattern::Binder(Binder(ref free_var)), _) => {
-            Ok(Some(vec![(free_var.clone(), value.clone())]))
-        },
-        (&Pattern::Var(Embed(Var::Free(ref free_var)), _), _) => {
-            match env.get_definition(free_var).map(|term| nf_term(env, term)) {
-                Some(Ok(ref term)) if term == value => Ok(Some(vec![])),
-                Some(Ok(_)) | None => Ok(None),
-                Some(Err(err)) => Err(err),
+        (&Pattern::Var(index, var_shift), _) => {
+            if force(env, &get_local(locals, index, var_shift)?)? == value {
+                Ok(Some(vec![]))
+            } else {
+                Ok(None)
             }
         },
+        (&Pattern::Global(ref name, var_shift), _) => match env.get_definition(name) {
+            Some(term) => {
+                let term_value = eval_term(env, &Locals::new(), shift + var_shift, term)?;
+                if force(env, &term_value)? == value {
+                    Ok(Some(vec![]))
+                } else {
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        },
         (&Pattern::Literal(ref pattern_lit), &Value::Literal(ref value_lit))
             if pattern_lit == value_lit =>
         {
             Ok(Some(vec![]))
         },
+        (
+            &Pattern::VariantIntro(ref pattern_label, ref patterns),
+            &Value::VariantIntro(ref value_label, ref values),
+        ) if pattern_label == value_label && patterns.len() == values.len() => {
+            let mut bound_values = Vec::new();
+            for (pattern, value) in Iterator::zip(patterns.iter(), values.iter()) {
+                match match_value(env, locals, shift, pattern, value)? {
+                    Some(pattern_values) => bound_values.extend(pattern_values),
+                    None => return Ok(None),
+                }
+            }
+            Ok(Some(bound_values))
+        },
         (_, _) => Ok(None),
     }
 }
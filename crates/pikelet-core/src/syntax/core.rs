@@ -1,44 +1,96 @@
 //! The core syntax of the language
+//!
+//! Variables that are bound locally are referred to by their de Bruijn
+//! indices, and the names of binders are only kept around for pretty printing
+//! and resugaring. Global definitions are referred to by name.
 
-use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 use std::ops;
 use std::rc::Rc;
 
-use crate::syntax::domain::{Head, Neutral, Value};
-use crate::syntax::{Label, Level, LevelShift, Literal, PRETTY_FALLBACK_WIDTH};
+use crate::syntax::{
+    Label, Level, LevelShift, Literal, MetaVar, Name, Plicity, VarIndex, PRETTY_FALLBACK_WIDTH,
+};
 
-#[derive(Debug, Clone, PartialEq, BoundPattern)]
+/// Patterns
+///
+/// The variables in the type annotations and in `Var` patterns refer to the
+/// binders that are in scope outside of the pattern. The binders of a pattern
+/// are brought into scope for the body of its clause from left to right.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Pattern {
     /// Patterns annotated with types
-    Ann(RcPattern, Embed<RcTerm>),
+    Ann(RcPattern, RcTerm),
     /// Patterns that bind variables
-    Binder(Binder<String>),
-    /// Patterns to be compared structurally with a variable in scope
-    Var(Embed<Var<String>>, LevelShift),
+    Binder(Name),
+    /// Patterns to be compared structurally with a local variable in scope
+    Var(VarIndex, LevelShift),
+    /// Patterns to be compared structurally with a global definition
+    Global(String, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Patterns that match the values built from a variant constructor
+    VariantIntro(Label, Vec<RcPattern>),
+    /// Patterns that failed to parse or elaborate, which never match
+    Error,
 }
 
 impl Pattern {
+    /// The names of the variables bound by the pattern, in the order that
+    /// they are brought into scope
+    pub fn binders(&self) -> Vec<&Name> {
+        fn go<'a>(pattern: &'a Pattern, binders: &mut Vec<&'a Name>) {
+            match *pattern {
+                Pattern::Ann(ref pattern, _) => go(pattern, binders),
+                Pattern::Binder(ref name) => binders.push(name),
+                Pattern::VariantIntro(_, ref patterns) => {
+                    for pattern in patterns {
+                        go(pattern, binders);
+                    }
+                },
+                Pattern::Var(_, _)
+                | Pattern::Global(_, _)
+                | Pattern::Literal(_)
+                | Pattern::Error => {},
+            }
+        }
+
+        let mut binders = Vec::new();
+        go(self, &mut binders);
+        binders
+    }
+
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Pattern::Ann(ref pattern, Embed(ref ty)) => Doc::nil()
+            Pattern::Ann(ref pattern, ref ty) => Doc::nil()
                 .append(pattern.to_doc())
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
                 .append(ty.to_doc()), // fun-intro?
+            Pattern::VariantIntro(ref label, ref patterns) if !patterns.is_empty() => {
+                Doc::as_string(label)
+                    .append(Doc::space())
+                    .append(Doc::intersperse(
+                        patterns.iter().map(|pattern| pattern.to_doc_atomic()),
+                        Doc::space(),
+                    ))
+            },
             ref pattern => pattern.to_doc_atomic(),
         }
     }
 
     fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Pattern::Binder(ref binder) => Doc::as_string(binder),
-            Pattern::Var(Embed(ref var), shift) => Doc::as_string(format!("{}^{}", var, shift)),
+            Pattern::Binder(ref name) => Doc::as_string(name),
+            Pattern::Var(index, shift) => Doc::as_string(format!("{}^{}", index, shift)),
+            Pattern::Global(ref name, shift) => Doc::as_string(format!("{}^{}", name, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::Error => Doc::text("<error>"),
+            Pattern::VariantIntro(ref label, ref patterns) if patterns.is_empty() => {
+                Doc::as_string(label)
+            },
             ref pattern => Doc::text("(").append(pattern.to_doc()).append(")"),
         }
     }
@@ -51,11 +103,56 @@ impl fmt::Display for Pattern {
 }
 
 /// Reference counted patterns
-#[derive(Debug, Clone, PartialEq, BoundPattern)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcPattern {
     pub inner: Rc<Pattern>,
 }
 
+impl RcPattern {
+    /// Rename the variables in the pattern, as in `RcTerm::rename`
+    fn rename_at(
+        &self,
+        depth: usize,
+        on_var: &dyn Fn(VarIndex) -> Option<VarIndex>,
+    ) -> Option<RcPattern> {
+        match *self.inner {
+            Pattern::Ann(ref pattern, ref ty) => Some(RcPattern::from(Pattern::Ann(
+                pattern.rename_at(depth, on_var)?,
+                ty.rename_at(depth, on_var)?,
+            ))),
+            Pattern::Var(index, shift) => Some(RcPattern::from(Pattern::Var(
+                rename_index(index, depth, on_var)?,
+                shift,
+            ))),
+            Pattern::VariantIntro(ref label, ref patterns) => {
+                Some(RcPattern::from(Pattern::VariantIntro(
+                    label.clone(),
+                    patterns
+                        .iter()
+                        .map(|pattern| pattern.rename_at(depth, on_var))
+                        .collect::<Option<_>>()?,
+                )))
+            },
+            Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) | Pattern::Error => {
+                Some(self.clone())
+            },
+        }
+    }
+
+    fn mentions_at(&self, index: VarIndex) -> bool {
+        match *self.inner {
+            Pattern::Ann(ref pattern, ref ty) => pattern.mentions_at(index) || ty.mentions(index),
+            Pattern::Var(other, _) => other == index,
+            Pattern::VariantIntro(_, ref patterns) => {
+                patterns.iter().any(|pattern| pattern.mentions_at(index))
+            },
+            Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) | Pattern::Error => {
+                false
+            },
+        }
+    }
+}
+
 impl From<Pattern> for RcPattern {
     fn from(src: Pattern) -> RcPattern {
         RcPattern {
@@ -79,36 +176,66 @@ impl fmt::Display for RcPattern {
 }
 
 /// The core term syntax
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+///
+/// Binders are nameless, so the derived equality is alpha-equality.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Term {
     /// A term annotated with a type
     Ann(RcTerm, RcTerm),
     /// Universes
     Universe(Level),
+    /// Universe levels
+    Level(Level),
     /// Literals
     Literal(Literal),
-    /// A variable
-    Var(Var<String>, LevelShift),
+    /// A local variable
+    Var(VarIndex, LevelShift),
+    /// A global definition
+    Global(String, LevelShift),
     /// An imported definition
     Import(String),
-    /// Dependent function types
-    FunType(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
-    /// Function introductions
-    FunIntro(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    /// A metavariable
+    Meta(MetaVar),
+    /// Dependent function types, with the annotation of the parameter and the
+    /// body that it is bound in
+    FunType(Plicity, Name, RcTerm, RcTerm),
+    /// Function introductions, with the annotation of the parameter and the
+    /// body that it is bound in
+    FunIntro(Plicity, Name, RcTerm, RcTerm),
     /// Function applications
-    FunApp(RcTerm, RcTerm),
+    FunApp(RcTerm, Plicity, RcTerm),
     /// Dependent record types
-    RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()>),
+    ///
+    /// Each field is bound in the types of the fields that follow it.
+    RecordType(Vec<(Label, Name, RcTerm)>),
     /// Record introductions
     RecordIntro(Vec<(Label, RcTerm)>),
     /// Record field projection
     RecordProj(RcTerm, Label, LevelShift),
+    /// Variant types, with a binder for recursive occurrences of the type
+    VariantType(Name, Vec<(Label, Vec<RcTerm>)>),
+    /// Variant introductions
+    VariantIntro(Label, Vec<RcTerm>),
     /// Case expressions
-    Case(RcTerm, Vec<Scope<RcPattern, RcTerm>>),
+    Case(RcTerm, Vec<(RcPattern, RcTerm)>),
     /// Array literals
     ArrayIntro(Vec<RcTerm>),
-    /// Let bindings
-    Let(Scope<Nest<(Binder<String>, Embed<RcTerm>)>, RcTerm>),
+    /// Let bindings, along with the doc comments of each binding
+    ///
+    /// Each binding is bound in the bindings that follow it, and in the body.
+    Let(Vec<(Name, RcTerm, String)>, RcTerm),
+    /// Recursive definitions, with the annotation of the definition and the
+    /// body that recursive occurrences are bound in
+    ///
+    /// The index points to the parameter that the definition structurally
+    /// recurses on. Recursive occurrences are only unfolded once the argument
+    /// at that index is a variant introduction.
+    Fix(usize, Name, RcTerm, RcTerm),
+    /// A term that failed to parse, desugar, or elaborate
+    ///
+    /// The error has already been reported, so this is accepted wherever it
+    /// is used, and its type is also an error.
+    Error,
 }
 
 impl Term {
@@ -116,8 +243,23 @@ impl Term {
         Term::Universe(level.into())
     }
 
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Term {
-        Term::Var(var.into(), shift.into())
+    pub fn var(index: usize, shift: impl Into<LevelShift>) -> Term {
+        Term::Var(VarIndex(index), shift.into())
+    }
+
+    pub fn global(name: impl Into<String>, shift: impl Into<LevelShift>) -> Term {
+        Term::Global(name.into(), shift.into())
+    }
+
+    /// Returns the universe level that the term stands for, if it is in the
+    /// form that levels are elaborated to
+    pub fn as_level(&self) -> Option<Level> {
+        match *self {
+            Term::Ann(ref term, _) => term.as_level(),
+            Term::Level(ref level) => Some(level.clone()),
+            Term::Var(index, shift) => Some(Level::var(index) + shift),
+            _ => None,
+        }
     }
 
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
@@ -138,17 +280,29 @@ impl Term {
                 .append("import")
                 .append(Doc::space())
                 .append(format!("{:?}", name)),
-            Term::FunIntro(ref scope) => Doc::nil()
+            Term::FunIntro(Plicity::Explicit, ref name, ref ann, ref body) => Doc::nil()
                 .append("\\")
-                .append(Doc::as_string(&scope.unsafe_pattern.0))
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append((scope.unsafe_pattern.1).0.to_doc_arrow())
+                .append(ann.to_doc_arrow())
                 .append(Doc::space())
                 .append("=>")
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
+                .append(body.to_doc_expr()),
+            Term::FunIntro(Plicity::Implicit, ref name, ref ann, ref body) => Doc::nil()
+                .append("\\{")
+                .append(Doc::as_string(name))
+                .append(Doc::space())
+                .append(":")
+                .append(Doc::space())
+                .append(ann.to_doc_arrow())
+                .append("}")
+                .append(Doc::space())
+                .append("=>")
+                .append(Doc::space())
+                .append(body.to_doc_expr()),
             Term::Case(ref head, ref clauses) => Doc::nil()
                 .append("case")
                 .append(Doc::space())
@@ -157,74 +311,105 @@ impl Term {
                 .append("{")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    clauses.iter().map(|scope| {
+                    clauses.iter().map(|&(ref pattern, ref body)| {
                         Doc::nil()
-                            .append(scope.unsafe_pattern.to_doc())
+                            .append(pattern.to_doc())
                             .append(Doc::space())
                             .append("=>")
                             .append(Doc::space())
-                            .append(scope.unsafe_body.to_doc())
+                            .append(body.to_doc())
                             .append(";")
                     }),
                     Doc::newline(),
                 ))
                 .append(Doc::space())
                 .append("}"),
-            Term::Let(ref scope) => Doc::nil()
+            Term::Let(ref bindings, ref body) => Doc::nil()
                 .append("let")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref binder, Embed(ref term))| {
-                            Doc::nil()
-                                .append(Doc::as_string(binder))
-                                .append(Doc::space())
-                                .append("=")
-                                .append(Doc::space())
-                                .append(term.to_doc())
-                        },
-                    ),
+                    bindings.iter().map(|&(ref name, ref term, _)| {
+                        Doc::nil()
+                            .append(Doc::as_string(name))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(term.to_doc())
+                    }),
                     Doc::newline(),
                 ))
                 .append(Doc::space())
                 .append("in")
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
+                .append(body.to_doc_expr()),
+            Term::Fix(index, ref name, ref ann, ref body) => Doc::nil()
+                .append("fix")
+                .append(Doc::space())
+                .append(Doc::as_string(name))
+                .append(Doc::space())
+                .append(":")
+                .append(Doc::space())
+                .append(ann.to_doc_arrow())
+                .append(Doc::space())
+                .append(format!("@{}", index))
+                .append(Doc::space())
+                .append("=>")
+                .append(Doc::space())
+                .append(body.to_doc_expr()),
             ref term => term.to_doc_arrow(),
         }
     }
 
     fn to_doc_arrow(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunType(ref scope) => Doc::nil()
-                .append("(")
-                .append(Doc::as_string(&scope.unsafe_pattern.0))
+            Term::FunType(plicity, ref name, ref ann, ref body) => Doc::nil()
+                .append(match plicity {
+                    Plicity::Explicit => "(",
+                    Plicity::Implicit => "{",
+                })
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append((scope.unsafe_pattern.1).0.to_doc_arrow())
-                .append(")")
+                .append(ann.to_doc_arrow())
+                .append(match plicity {
+                    Plicity::Explicit => ")",
+                    Plicity::Implicit => "}",
+                })
                 .append(Doc::space())
                 .append("->")
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
+                .append(body.to_doc_expr()),
             ref term => term.to_doc_app(),
         }
     }
 
     fn to_doc_app(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunApp(ref fun, ref arg) => Doc::nil()
+            Term::FunApp(ref fun, Plicity::Explicit, ref arg) => Doc::nil()
                 .append(fun.to_doc_atomic())
                 .append(Doc::space())
                 .append(arg.to_doc_atomic()),
+            Term::FunApp(ref fun, Plicity::Implicit, ref arg) => Doc::nil()
+                .append(fun.to_doc_atomic())
+                .append(Doc::space())
+                .append("{")
+                .append(arg.to_doc())
+                .append("}"),
+            Term::VariantIntro(ref label, ref args) if !args.is_empty() => Doc::as_string(label)
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    args.iter().map(|arg| arg.to_doc_atomic()),
+                    Doc::space(),
+                )),
             ref term => term.to_doc_atomic(),
         }
     }
 
     fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::Universe(level) => Doc::text(format!("Type^{}", level)),
+            Term::Universe(ref level) => Doc::text("Type^").append(level.to_doc()),
+            Term::Level(ref level) => level.to_doc(),
             Term::ArrayIntro(ref elems) => Doc::nil()
                 .append("[")
                 .append(Doc::intersperse(
@@ -232,25 +417,26 @@ impl Term {
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append("]"),
-            Term::Var(ref var, ref level) => Doc::text(format!("{}^{}", var, level)),
-            Term::RecordType(ref scope) => Doc::nil()
+            Term::Var(index, shift) => Doc::text(format!("{}^{}", index, shift)),
+            Term::Global(ref name, shift) => Doc::text(format!("{}^{}", name, shift)),
+            Term::Meta(meta) => Doc::as_string(meta),
+            Term::Error => Doc::text("<error>"),
+            Term::RecordType(ref fields) => Doc::nil()
                 .append("Record {")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref label, ref binder, Embed(ref ann))| {
-                            Doc::nil()
-                                .append(Doc::as_string(label))
-                                .append(Doc::space())
-                                .append("as")
-                                .append(Doc::space())
-                                .append(Doc::as_string(binder))
-                                .append(Doc::space())
-                                .append(":")
-                                .append(Doc::space())
-                                .append(ann.to_doc())
-                        },
-                    ),
+                    fields.iter().map(|&(ref label, ref name, ref ann)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("as")
+                            .append(Doc::space())
+                            .append(Doc::as_string(name))
+                            .append(Doc::space())
+                            .append(":")
+                            .append(Doc::space())
+                            .append(ann.to_doc())
+                    }),
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append(Doc::space())
@@ -275,6 +461,25 @@ impl Term {
                 .append(expr.to_doc_atomic())
                 .append(".")
                 .append(format!("{}^{}", label, shift)),
+            Term::VariantType(ref name, ref arms) => Doc::nil()
+                .append("Variant")
+                .append(Doc::space())
+                .append(Doc::as_string(name))
+                .append(Doc::space())
+                .append("{")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    arms.iter().map(|&(ref label, ref args)| {
+                        Doc::as_string(label).append(Doc::concat(
+                            args.iter()
+                                .map(|arg| Doc::space().append(arg.to_doc_atomic())),
+                        ))
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Term::VariantIntro(ref label, ref args) if args.is_empty() => Doc::as_string(label),
             ref term => Doc::text("(").append(term.to_doc()).append(")"),
         }
     }
@@ -287,103 +492,212 @@ impl fmt::Display for Term {
 }
 
 /// Reference counted terms
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcTerm {
     pub inner: Rc<Term>,
 }
 
 impl RcTerm {
-    pub fn substs(&self, mappings: &[(FreeVar<String>, RcTerm)]) -> RcTerm {
+    /// Returns `true` if the term refers to the local variable at `index`
+    pub fn mentions(&self, index: VarIndex) -> bool {
+        let under = |binders: usize| VarIndex(index.0 + binders);
+
         match *self.inner {
-            Term::Ann(ref term, ref ty) => {
-                RcTerm::from(Term::Ann(term.substs(mappings), ty.substs(mappings)))
-            },
-            Term::Universe(_) | Term::Literal(_) => self.clone(),
-            Term::Var(ref var, _) => match mappings.iter().find(|&(ref name, _)| var == name) {
-                Some(&(_, ref term)) => term.clone(),
-                None => self.clone(),
+            Term::Ann(ref term, ref ty) => term.mentions(index) || ty.mentions(index),
+            Term::Universe(ref level) | Term::Level(ref level) => level.mentions(index),
+            Term::Var(other, _) => other == index,
+            Term::Literal(_)
+            | Term::Global(_, _)
+            | Term::Import(_)
+            | Term::Meta(_)
+            | Term::Error => false,
+            Term::FunType(_, _, ref ann, ref body)
+            | Term::FunIntro(_, _, ref ann, ref body)
+            | Term::Fix(_, _, ref ann, ref body) => ann.mentions(index) || body.mentions(under(1)),
+            Term::FunApp(ref head, _, ref arg) => head.mentions(index) || arg.mentions(index),
+            Term::RecordType(ref fields) => (fields.iter().enumerate())
+                .any(|(binders, &(_, _, ref ann))| ann.mentions(under(binders))),
+            Term::RecordIntro(ref fields) => {
+                fields.iter().any(|&(_, ref expr)| expr.mentions(index))
             },
-            Term::Import(ref name) => RcTerm::from(Term::Import(name.clone())),
-            Term::FunType(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::FunType(Scope {
-                    unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
+            Term::RecordProj(ref expr, _, _) => expr.mentions(index),
+            Term::VariantType(_, ref arms) => {
+                (arms.iter()).any(|&(_, ref args)| args.iter().any(|arg| arg.mentions(under(1))))
             },
-            Term::FunIntro(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::FunIntro(Scope {
-                    unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
+            Term::VariantIntro(_, ref args) | Term::ArrayIntro(ref args) => {
+                args.iter().any(|arg| arg.mentions(index))
             },
-            Term::Let(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref binder, Embed(ref term))| {
-                        (binder.clone(), Embed(term.substs(mappings)))
+            Term::Case(ref head, ref clauses) => {
+                head.mentions(index)
+                    || clauses.iter().any(|&(ref pattern, ref body)| {
+                        pattern.mentions_at(index) || body.mentions(under(pattern.binders().len()))
                     })
-                    .collect();
-
-                RcTerm::from(Term::Let(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
             },
-            Term::FunApp(ref head, ref arg) => {
-                RcTerm::from(Term::FunApp(head.substs(mappings), arg.substs(mappings)))
+            Term::Let(ref bindings, ref body) => {
+                (bindings.iter().enumerate())
+                    .any(|(binders, &(_, ref term, _))| term.mentions(under(binders)))
+                    || body.mentions(under(bindings.len()))
             },
-            Term::RecordType(ref scope) if scope.unsafe_pattern.unsafe_patterns.is_empty() => {
-                self.clone()
-            },
-            Term::RecordType(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref label, ref binder, Embed(ref ann))| {
-                        (label.clone(), binder.clone(), Embed(ann.substs(mappings)))
-                    })
-                    .collect();
+        }
+    }
 
-                RcTerm::from(Term::RecordType(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                }))
+    /// Rename the local variables that are free in the term
+    ///
+    /// `on_var` is given the indices of the free variables, relative to the
+    /// outside of the term, and returns the indices that they should be
+    /// replaced with. If `on_var` returns `None` for any of them, then so does
+    /// this function.
+    pub fn rename(&self, on_var: &dyn Fn(VarIndex) -> Option<VarIndex>) -> Option<RcTerm> {
+        self.rename_at(0, on_var)
+    }
+
+    /// Shift the free variables of the term, so that it can be moved under
+    /// `amount` more binders
+    pub fn shift(&self, amount: usize) -> RcTerm {
+        self.rename(&|index| Some(VarIndex(index.0 + amount)))
+            .expect("shifting should never fail")
+    }
+
+    fn rename_at(
+        &self,
+        depth: usize,
+        on_var: &dyn Fn(VarIndex) -> Option<VarIndex>,
+    ) -> Option<RcTerm> {
+        let term = match *self.inner {
+            Term::Ann(ref term, ref ty) => {
+                Term::Ann(term.rename_at(depth, on_var)?, ty.rename_at(depth, on_var)?)
             },
-            Term::RecordIntro(ref fields) if fields.is_empty() => self.clone(),
-            Term::RecordIntro(ref fields) => {
-                let fields = fields
+            Term::Universe(ref level) => Term::Universe(rename_level(level, depth, on_var)?),
+            Term::Level(ref level) => Term::Level(rename_level(level, depth, on_var)?),
+            Term::Var(index, shift) => Term::Var(rename_index(index, depth, on_var)?, shift),
+            Term::Literal(_)
+            | Term::Global(_, _)
+            | Term::Import(_)
+            | Term::Meta(_)
+            | Term::Error => return Some(self.clone()),
+            Term::FunType(plicity, ref name, ref ann, ref body) => Term::FunType(
+                plicity,
+                name.clone(),
+                ann.rename_at(depth, on_var)?,
+                body.rename_at(depth + 1, on_var)?,
+            ),
+            Term::FunIntro(plicity, ref name, ref ann, ref body) => Term::FunIntro(
+                plicity,
+                name.clone(),
+                ann.rename_at(depth, on_var)?,
+                body.rename_at(depth + 1, on_var)?,
+            ),
+            Term::Fix(index, ref name, ref ann, ref body) => Term::Fix(
+                index,
+                name.clone(),
+                ann.rename_at(depth, on_var)?,
+                body.rename_at(depth + 1, on_var)?,
+            ),
+            Term::FunApp(ref head, plicity, ref arg) => Term::FunApp(
+                head.rename_at(depth, on_var)?,
+                plicity,
+                arg.rename_at(depth, on_var)?,
+            ),
+            Term::RecordType(ref fields) => Term::RecordType(
+                (fields.iter().enumerate())
+                    .map(|(binders, &(ref label, ref name, ref ann))| {
+                        let ann = ann.rename_at(depth + binders, on_var)?;
+                        Some((label.clone(), name.clone(), ann))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::RecordIntro(ref fields) => Term::RecordIntro(
+                fields
                     .iter()
-                    .map(|&(ref label, ref expr)| (label.clone(), expr.substs(mappings)))
-                    .collect();
-
-                RcTerm::from(Term::RecordIntro(fields))
+                    .map(|&(ref label, ref expr)| {
+                        Some((label.clone(), expr.rename_at(depth, on_var)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::RecordProj(ref expr, ref label, shift) => {
+                Term::RecordProj(expr.rename_at(depth, on_var)?, label.clone(), shift)
             },
-            Term::RecordProj(ref expr, ref label, shift) => RcTerm::from(Term::RecordProj(
-                expr.substs(mappings),
+            Term::VariantType(ref name, ref arms) => Term::VariantType(
+                name.clone(),
+                arms.iter()
+                    .map(|&(ref label, ref args)| {
+                        let args = args
+                            .iter()
+                            .map(|arg| arg.rename_at(depth + 1, on_var))
+                            .collect::<Option<_>>()?;
+                        Some((label.clone(), args))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::VariantIntro(ref label, ref args) => Term::VariantIntro(
                 label.clone(),
-                shift,
-            )),
-            Term::Case(ref head, ref clauses) => RcTerm::from(Term::Case(
-                head.substs(mappings),
+                args.iter()
+                    .map(|arg| arg.rename_at(depth, on_var))
+                    .collect::<Option<_>>()?,
+            ),
+            Term::Case(ref head, ref clauses) => Term::Case(
+                head.rename_at(depth, on_var)?,
                 clauses
                     .iter()
-                    .map(|scope| {
-                        Scope {
-                            unsafe_pattern: scope.unsafe_pattern.clone(), // subst?
-                            unsafe_body: scope.unsafe_body.substs(mappings),
-                        }
+                    .map(|&(ref pattern, ref body)| {
+                        let binders = pattern.binders().len();
+                        Some((
+                            pattern.rename_at(depth, on_var)?,
+                            body.rename_at(depth + binders, on_var)?,
+                        ))
                     })
-                    .collect(),
-            )),
-            Term::ArrayIntro(ref elems) => RcTerm::from(Term::ArrayIntro(
-                elems.iter().map(|elem| elem.substs(mappings)).collect(),
-            )),
-        }
+                    .collect::<Option<_>>()?,
+            ),
+            Term::ArrayIntro(ref elems) => Term::ArrayIntro(
+                elems
+                    .iter()
+                    .map(|elem| elem.rename_at(depth, on_var))
+                    .collect::<Option<_>>()?,
+            ),
+            Term::Let(ref bindings, ref body) => Term::Let(
+                (bindings.iter().enumerate())
+                    .map(|(binders, &(ref name, ref term, ref doc))| {
+                        let term = term.rename_at(depth + binders, on_var)?;
+                        Some((name.clone(), term, doc.clone()))
+                    })
+                    .collect::<Option<_>>()?,
+                body.rename_at(depth + bindings.len(), on_var)?,
+            ),
+        };
+
+        Some(RcTerm::from(term))
+    }
+}
+
+/// Rename an index that was found under `depth` binders, leaving the indices
+/// that point to those binders alone
+fn rename_index(
+    index: VarIndex,
+    depth: usize,
+    on_var: &dyn Fn(VarIndex) -> Option<VarIndex>,
+) -> Option<VarIndex> {
+    if index.0 < depth {
+        Some(index)
+    } else {
+        on_var(VarIndex(index.0 - depth)).map(|index| VarIndex(index.0 + depth))
+    }
+}
+
+/// Rename the level variables in a level that was found under `depth` binders
+fn rename_level(
+    level: &Level,
+    depth: usize,
+    on_var: &dyn Fn(VarIndex) -> Option<VarIndex>,
+) -> Option<Level> {
+    match *level {
+        Level::Finite(constant, ref vars, ref metas) => {
+            let init = Level::Finite(constant, Vec::new(), metas.clone());
+            vars.iter().try_fold(init, |acc, &(index, shift)| {
+                let index = rename_index(index, depth, on_var)?;
+                Some(acc.max(&(Level::var(index) + shift)))
+            })
+        },
+        Level::Omega => Some(Level::Omega),
     }
 }
 
@@ -408,102 +722,3 @@ impl fmt::Display for RcTerm {
         fmt::Display::fmt(&self.inner, f)
     }
 }
-
-impl<'a> From<&'a Value> for Term {
-    fn from(src: &'a Value) -> Term {
-        // Bypassing `Scope::new` and `Scope::unbind` here should be fine
-        // because we aren't altering the structure of the scopes during this
-        // transformation. This should save on some traversals of the AST!
-        match *src {
-            Value::Universe(level) => Term::Universe(level),
-            Value::Literal(ref lit) => Term::Literal(lit.clone()),
-            Value::FunType(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::FunType(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
-            },
-            Value::FunIntro(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::FunIntro(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
-            },
-            Value::RecordType(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref label, ref binder, Embed(ref ann))| {
-                        (label.clone(), binder.clone(), Embed(RcTerm::from(&**ann)))
-                    })
-                    .collect();
-
-                Term::RecordType(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                })
-            },
-            Value::RecordIntro(ref fields) => {
-                let fields = fields
-                    .iter()
-                    .map(|&(ref label, ref expr)| (label.clone(), RcTerm::from(&**expr)))
-                    .collect();
-
-                Term::RecordIntro(fields)
-            },
-            Value::ArrayIntro(ref elems) => {
-                Term::ArrayIntro(elems.iter().map(|elem| RcTerm::from(&**elem)).collect())
-            },
-            Value::Neutral(ref neutral, ref spine) => {
-                spine.iter().fold(Term::from(&*neutral.inner), |acc, arg| {
-                    Term::FunApp(RcTerm::from(acc), RcTerm::from(&**arg))
-                })
-            },
-        }
-    }
-}
-
-impl<'a> From<&'a Value> for RcTerm {
-    fn from(src: &'a Value) -> RcTerm {
-        RcTerm::from(Term::from(src))
-    }
-}
-
-impl<'a> From<&'a Neutral> for Term {
-    fn from(src: &'a Neutral) -> Term {
-        match *src {
-            Neutral::Head(ref head) => Term::from(head),
-            Neutral::RecordProj(ref expr, ref name, shift) => {
-                Term::RecordProj(RcTerm::from(&**expr), name.clone(), shift)
-            },
-            Neutral::Case(ref head, ref clauses) => Term::Case(
-                RcTerm::from(&**head),
-                clauses
-                    .iter()
-                    .map(|clause| Scope {
-                        unsafe_pattern: clause.unsafe_pattern.clone(),
-                        unsafe_body: RcTerm::from(&*clause.unsafe_body),
-                    })
-                    .collect(),
-            ),
-        }
-    }
-}
-
-impl<'a> From<&'a Neutral> for RcTerm {
-    fn from(src: &'a Neutral) -> RcTerm {
-        RcTerm::from(Term::from(src))
-    }
-}
-
-impl<'a> From<&'a Head> for Term {
-    fn from(src: &'a Head) -> Term {
-        match *src {
-            Head::Var(ref var, shift) => Term::Var(var.clone(), shift),
-            Head::Import(ref name) => Term::Import(name.clone()),
-        }
-    }
-}
@@ -1,83 +1,118 @@
 //! The semantic domain of the language
+//!
+//! Variables in values are referred to by their de Bruijn levels, so values
+//! can be moved into larger contexts without needing to be shifted.
 
-use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
+use im;
+use std::cell::RefCell;
 use std::ops;
 use std::rc::Rc;
 
+use crate::nbe::{self, Env, NbeError};
 use crate::syntax::core::{RcPattern, RcTerm, Term};
-use crate::syntax::{Label, Level, LevelShift, Literal};
+use crate::syntax::{Label, Level, LevelShift, Literal, MetaVar, Name, Plicity, VarLevel};
 
 /// Values
 ///
-/// These are either in _normal form_ (they cannot be reduced further) or are
-/// _neutral terms_ (there is a possibility of reducing further depending
-/// on the bindings given in the context)
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+/// These are either in _weak head normal form_ (they cannot be reduced
+/// further at the top level) or are _neutral terms_ (there is a possibility of
+/// reducing further depending on the bindings given in the context). The
+/// bodies of binders are left unevaluated in closures until they are needed.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// Universes
-    Universe(Level),
+    Universe(Level<VarLevel>),
+    /// Universe levels
+    ///
+    /// Levels that consist of a single level variable are represented by
+    /// neutral variables instead, so that there is only one way of writing
+    /// each level.
+    Level(Level<VarLevel>),
     /// Literals
     Literal(Literal),
     /// Dependent function types
-    FunType(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    FunType(Plicity, RcValue, Closure),
     /// Function introductions
-    FunIntro(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    FunIntro(Plicity, RcValue, Closure),
     /// Dependent record types
-    RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcValue>)>, ()>),
+    RecordType(RecordTypeClosure),
     /// Dependent record introductions
     RecordIntro(Vec<(Label, RcValue)>),
+    /// Variant types, with a closure over recursive occurrences of the type
+    VariantType(VariantTypeClosure),
+    /// Variant introductions
+    VariantIntro(Label, Vec<RcValue>),
     /// Array literals
     ArrayIntro(Vec<RcValue>),
+    /// Terms that failed to elaborate, which are compatible with every type
+    Error,
     /// Neutral terms
     ///
     /// A term whose computation has stopped because of an attempt to compute an
     /// application `Head`.
     Neutral(RcNeutral, Spine),
+    /// Applications of definitions, glued to the values that they unfold to
+    ///
+    /// Keeping the definition around lets us read the value back without
+    /// unfolding it, while the unfolded value is only computed if it is
+    /// actually needed, for example when checking if two values are equal.
+    ///
+    /// Only references to definitions are glued. Projecting a field out of a
+    /// record unfolds the record, so the projection is not kept around.
+    Glued(Head, Spine, LazyValue),
 }
 
 impl Value {
-    pub fn universe(level: impl Into<Level>) -> Value {
+    pub fn universe(level: impl Into<Level<VarLevel>>) -> Value {
         Value::Universe(level.into())
     }
 
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Value {
-        Value::Neutral(RcNeutral::from(Neutral::var(var, shift)), Spine::new())
+    pub fn var(level: VarLevel, shift: impl Into<LevelShift>) -> Value {
+        Value::from(Neutral::Head(Head::Var(level, shift.into())))
+    }
+
+    pub fn global(name: impl Into<String>, shift: impl Into<LevelShift>) -> Value {
+        Value::from(Neutral::Head(Head::Global(name.into(), shift.into())))
+    }
+
+    pub fn level(level: impl Into<Level<VarLevel>>) -> Value {
+        match level.into() {
+            Level::Finite(0, ref vars, ref metas) if vars.len() == 1 && metas.is_empty() => {
+                let (var, shift) = vars[0];
+                Value::var(var, shift)
+            },
+            level => Value::Level(level),
+        }
     }
 
-    pub fn substs(&self, mappings: &[(FreeVar<String>, RcTerm)]) -> RcTerm {
-        // FIXME: This seems quite wasteful!
-        RcTerm::from(Term::from(self)).substs(mappings)
+    /// Returns the universe level that the value stands for, if it is a
+    /// level or a level variable
+    pub fn as_level(&self) -> Option<Level<VarLevel>> {
+        match *self {
+            Value::Level(ref level) => Some(level.clone()),
+            Value::Neutral(ref neutral, ref spine) if spine.is_empty() => match **neutral {
+                Neutral::Head(Head::Var(var, shift)) => Some(Level::var(var) + shift),
+                _ => None,
+            },
+            _ => None,
+        }
     }
 
     /// Returns `true` if the value is in weak head normal form
     pub fn is_whnf(&self) -> bool {
         match *self {
             Value::Universe(_)
+            | Value::Level(_)
             | Value::Literal(_)
-            | Value::FunType(_)
-            | Value::FunIntro(_)
+            | Value::FunType(_, _, _)
+            | Value::FunIntro(_, _, _)
             | Value::RecordType(_)
             | Value::RecordIntro(_)
-            | Value::ArrayIntro(_) => true,
-            Value::Neutral(_, _) => false,
-        }
-    }
-
-    /// Returns `true` if the value is in normal form (ie. it contains no neutral terms within it)
-    pub fn is_nf(&self) -> bool {
-        match *self {
-            Value::Universe(_) | Value::Literal(_) => true,
-            Value::FunType(ref scope) | Value::FunIntro(ref scope) => {
-                (scope.unsafe_pattern.1).0.is_nf() && scope.unsafe_body.is_nf()
-            },
-            Value::RecordType(ref scope) => scope
-                .unsafe_pattern
-                .unsafe_patterns
-                .iter()
-                .all(|(_, _, Embed(ref term))| term.is_nf()),
-            Value::RecordIntro(ref fields) => fields.iter().all(|&(_, ref term)| term.is_nf()),
-            Value::ArrayIntro(ref elems) => elems.iter().all(|elem| elem.is_nf()),
-            Value::Neutral(_, _) => false,
+            | Value::VariantType(_)
+            | Value::VariantIntro(_, _)
+            | Value::ArrayIntro(_)
+            | Value::Error => true,
+            Value::Neutral(_, _) | Value::Glued(_, _, _) => false,
         }
     }
 
@@ -90,39 +125,68 @@ impl Value {
         None
     }
 
-    pub fn free_var_app(&self) -> Option<(&FreeVar<String>, LevelShift, &[RcValue])> {
+    /// Returns the level and spine if the value is an application of a local
+    /// variable
+    pub fn var_app(&self) -> Option<(VarLevel, LevelShift, &[(Plicity, RcValue)])> {
+        self.head_app().and_then(|(head, spine)| match *head {
+            Head::Var(var, shift) => Some((var, shift, &spine[..])),
+            Head::Global(..) | Head::Import(..) | Head::Meta(_) | Head::Fix(..) => None,
+        })
+    }
+
+    /// Returns the name and spine if the value is an application of a global
+    /// definition
+    pub fn global_app(&self) -> Option<(&str, LevelShift, &[(Plicity, RcValue)])> {
+        self.head_app().and_then(|(head, spine)| match *head {
+            Head::Global(ref name, shift) => Some((name.as_str(), shift, &spine[..])),
+            Head::Var(..) | Head::Import(..) | Head::Meta(_) | Head::Fix(..) => None,
+        })
+    }
+
+    /// Returns the metavariable and spine if the value is an application of
+    /// a metavariable
+    pub fn meta_app(&self) -> Option<(MetaVar, &[(Plicity, RcValue)])> {
         self.head_app().and_then(|(head, spine)| match *head {
-            Head::Var(Var::Free(ref free_var), shift) => Some((free_var, shift, &spine[..])),
-            Head::Import(_) | Head::Var(Var::Bound(_), _) => None,
+            Head::Meta(meta) => Some((meta, &spine[..])),
+            Head::Var(..) | Head::Global(..) | Head::Import(..) | Head::Fix(..) => None,
         })
     }
 }
 
 /// Reference counted values
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcValue {
     pub inner: Rc<Value>,
 }
 
 impl RcValue {
     pub fn shift_universes(&mut self, shift: LevelShift) {
+        // Avoid copying the value and the locals of its closures when there
+        // is nothing to shift
+        if shift == LevelShift(0) {
+            return;
+        }
+
         match *Rc::make_mut(&mut self.inner) {
-            Value::Universe(ref mut level) => *level += shift,
-            Value::Literal(_) => {},
-            Value::FunType(ref mut scope) | Value::FunIntro(ref mut scope) => {
-                (scope.unsafe_pattern.1).0.shift_universes(shift);
-                scope.unsafe_body.shift_universes(shift);
-            },
-            Value::RecordType(ref mut scope) => {
-                for &mut (_, _, Embed(ref mut term)) in &mut scope.unsafe_pattern.unsafe_patterns {
-                    term.shift_universes(shift);
-                }
+            Value::Universe(ref mut level) | Value::Level(ref mut level) => *level += shift,
+            Value::Literal(_) | Value::Error => {},
+            Value::FunType(_, ref mut ann, ref mut closure)
+            | Value::FunIntro(_, ref mut ann, ref mut closure) => {
+                ann.shift_universes(shift);
+                closure.shift_universes(shift);
             },
+            Value::RecordType(ref mut closure) => closure.shift_universes(shift),
             Value::RecordIntro(ref mut fields) => {
                 for &mut (_, ref mut term) in fields {
                     term.shift_universes(shift);
                 }
             },
+            Value::VariantType(ref mut closure) => closure.shift_universes(shift),
+            Value::VariantIntro(_, ref mut args) => {
+                for arg in args {
+                    arg.shift_universes(shift);
+                }
+            },
             Value::ArrayIntro(ref mut elems) => {
                 for elem in elems {
                     elem.shift_universes(shift);
@@ -130,9 +194,23 @@ impl RcValue {
             },
             Value::Neutral(ref mut neutral, ref mut spine) => {
                 neutral.shift_universes(shift);
-                for arg in spine {
+                for &mut (_, ref mut arg) in spine {
+                    arg.shift_universes(shift);
+                }
+            },
+            Value::Glued(ref mut head, ref mut spine, ref mut unfolded) => {
+                // The head refers to the definition, so it needs to be shifted
+                // along with the value that it unfolds to
+                match *head {
+                    Head::Var(_, ref mut head_shift)
+                    | Head::Global(_, ref mut head_shift)
+                    | Head::Import(_, ref mut head_shift) => *head_shift += shift,
+                    Head::Meta(_) | Head::Fix(..) => {},
+                }
+                for &mut (_, ref mut arg) in spine {
                     arg.shift_universes(shift);
                 }
+                unfolded.shift_universes(shift);
             },
         }
     }
@@ -154,43 +232,371 @@ impl ops::Deref for RcValue {
     }
 }
 
+/// A value that is only computed the first time that it is needed, and is
+/// then remembered for later uses
+///
+/// This gives us call-by-need evaluation for the unfoldings of definitions.
+#[derive(Debug, Clone)]
+pub struct LazyValue {
+    inner: Rc<RefCell<Lazy>>,
+}
+
+/// The state of a lazy value
+#[derive(Debug, Clone)]
+enum Lazy {
+    /// A term that is waiting to be evaluated
+    Eval(Locals, LevelShift, RcTerm),
+    /// A lazy value that is waiting to be applied to an argument
+    Apply(LazyValue, Plicity, RcValue),
+    /// A lazy value that is waiting to have its universes shifted
+    Shift(LazyValue, LevelShift),
+    /// A value that has already been computed
+    Forced(RcValue),
+}
+
+impl LazyValue {
+    fn new(lazy: Lazy) -> LazyValue {
+        LazyValue {
+            inner: Rc::new(RefCell::new(lazy)),
+        }
+    }
+
+    /// Evaluate `term` under the given local variables once it is needed
+    pub fn eval(locals: Locals, shift: LevelShift, term: RcTerm) -> LazyValue {
+        LazyValue::new(Lazy::Eval(locals, shift, term))
+    }
+
+    /// Apply the value to `arg` once it is needed
+    pub fn apply(&self, plicity: Plicity, arg: RcValue) -> LazyValue {
+        LazyValue::new(Lazy::Apply(self.clone(), plicity, arg))
+    }
+
+    /// Compute the value, if it has not already been computed
+    pub fn force(&self, env: &dyn Env) -> Result<RcValue, NbeError> {
+        let lazy = self.inner.borrow().clone();
+        let value = match lazy {
+            Lazy::Eval(locals, shift, term) => nbe::eval_term(env, &locals, shift, &term)?,
+            Lazy::Apply(head, plicity, arg) => nbe::apply(env, &head.force(env)?, plicity, arg)?,
+            Lazy::Shift(value, shift) => {
+                let mut value = value.force(env)?;
+                value.shift_universes(shift);
+                value
+            },
+            Lazy::Forced(value) => return Ok(value),
+        };
+
+        *self.inner.borrow_mut() = Lazy::Forced(value.clone());
+        Ok(value)
+    }
+
+    fn shift_universes(&mut self, shift: LevelShift) {
+        *self = LazyValue::new(Lazy::Shift(self.clone(), shift));
+    }
+}
+
+/// Lazy values only appear alongside the definitions that they are the
+/// unfoldings of, so they are ignored when comparing values
+impl PartialEq for LazyValue {
+    fn eq(&self, _other: &LazyValue) -> bool {
+        true
+    }
+}
+
+/// The values of the local variables that were in scope when a closure was
+/// created, indexed by their de Bruijn levels
+pub type Locals = im::Vector<RcValue>;
+
+fn shift_locals(locals: &mut Locals, shift: LevelShift) {
+    for value in locals.iter_mut() {
+        value.shift_universes(shift);
+    }
+}
+
+/// A term with a single bound variable, waiting to be given a value for that
+/// variable before it is evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    /// The values of the variables that the body can refer to
+    pub locals: Locals,
+    /// The amount to shift the universes in the body by once it is evaluated
+    pub shift: LevelShift,
+    /// The name of the bound variable
+    pub name: Name,
+    /// The body of the closure
+    pub body: RcTerm,
+}
+
+impl Closure {
+    pub fn new(locals: Locals, shift: LevelShift, name: Name, body: RcTerm) -> Closure {
+        Closure {
+            locals,
+            shift,
+            name,
+            body,
+        }
+    }
+
+    /// A closure that ignores its argument, always returning `value`
+    pub fn constant(value: RcValue) -> Closure {
+        // The body skips over the argument to get to `value`
+        let body = RcTerm::from(Term::var(1, 0));
+        Closure::new(Locals::unit(value), LevelShift(0), Name::default(), body)
+    }
+
+    /// The name of the bound variable
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// Evaluate the body of the closure, using `arg` as the value of the bound
+    /// variable
+    pub fn apply(&self, env: &dyn Env, arg: RcValue) -> Result<RcValue, NbeError> {
+        let mut locals = self.locals.clone();
+        locals.push_back(arg);
+        nbe::eval_term(env, &locals, self.shift, &self.body)
+    }
+
+    fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        self.shift += shift;
+    }
+}
+
+/// The fields of a dependent record type, waiting to be evaluated one at a
+/// time as the values of the earlier fields become known
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordTypeClosure {
+    /// The values of the variables that the field types can refer to
+    pub locals: Locals,
+    /// The amount to shift the universes in the field types by
+    pub shift: LevelShift,
+    /// The labels, binder names, and types of the fields
+    pub fields: Vec<(Label, Name, RcTerm)>,
+}
+
+impl RecordTypeClosure {
+    pub fn new(
+        locals: Locals,
+        shift: LevelShift,
+        fields: Vec<(Label, Name, RcTerm)>,
+    ) -> RecordTypeClosure {
+        RecordTypeClosure {
+            locals,
+            shift,
+            fields,
+        }
+    }
+
+    /// The number of fields in the record type
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the record type has no fields
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The labels of the fields, in order
+    pub fn labels(&self) -> impl Iterator<Item = &Label> {
+        self.fields.iter().map(|&(ref label, _, _)| label)
+    }
+
+    /// Evaluate the types of the fields in order
+    ///
+    /// `on_field` is called with the label and the type of each field, and
+    /// returns the value that the types of the later fields should use in
+    /// place of that field.
+    pub fn for_each_field<E: From<NbeError>>(
+        &self,
+        env: &dyn Env,
+        mut on_field: impl FnMut(Label, RcType) -> Result<RcValue, E>,
+    ) -> Result<(), E> {
+        let mut locals = self.locals.clone();
+
+        for &(ref label, _, ref ann) in &self.fields {
+            let ty = nbe::eval_term(env, &locals, self.shift, ann)?;
+            let value = on_field(label.clone(), ty)?;
+            locals.push_back(value);
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate the types of the fields, using the variables at the levels
+    /// starting from `size` in place of the values of the earlier fields
+    pub fn fields(
+        &self,
+        env: &dyn Env,
+        size: VarLevel,
+    ) -> Result<Vec<(Label, Name, RcType)>, NbeError> {
+        let mut names = self.fields.iter().map(|&(_, ref name, _)| name.clone());
+        let mut fields = Vec::with_capacity(self.len());
+        let mut level = size;
+
+        self.for_each_field(env, |label, ty| {
+            let name = names.next().unwrap_or_default();
+            fields.push((label, name, ty));
+            let var = RcValue::from(Value::var(level, 0));
+            level = level.succ();
+            Ok::<_, NbeError>(var)
+        })?;
+
+        Ok(fields)
+    }
+
+    fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        self.shift += shift;
+    }
+}
+
+/// The constructors of a variant type, waiting to be given the type that
+/// recursive occurrences refer to
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantTypeClosure {
+    /// The values of the variables that the argument types can refer to
+    pub locals: Locals,
+    /// The amount to shift the universes in the argument types by
+    pub shift: LevelShift,
+    /// The name of the variable that refers to recursive occurrences
+    pub name: Name,
+    /// The labels of the constructors, along with the types of their arguments
+    pub arms: Vec<(Label, Vec<RcTerm>)>,
+}
+
+impl VariantTypeClosure {
+    pub fn new(
+        locals: Locals,
+        shift: LevelShift,
+        name: Name,
+        arms: Vec<(Label, Vec<RcTerm>)>,
+    ) -> VariantTypeClosure {
+        VariantTypeClosure {
+            locals,
+            shift,
+            name,
+            arms,
+        }
+    }
+
+    /// The name of the variable that refers to recursive occurrences
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The labels of the constructors, along with the number of arguments
+    /// that they take
+    pub fn arms(&self) -> impl Iterator<Item = (&Label, usize)> {
+        self.arms
+            .iter()
+            .map(|&(ref label, ref args)| (label, args.len()))
+    }
+
+    /// Evaluate the argument types of the constructors, using `ty` for the
+    /// recursive occurrences of the variant type
+    pub fn instantiate(
+        &self,
+        env: &dyn Env,
+        ty: RcType,
+    ) -> Result<Vec<(Label, Vec<RcType>)>, NbeError> {
+        let mut locals = self.locals.clone();
+        locals.push_back(ty);
+
+        self.arms
+            .iter()
+            .map(|&(ref label, ref args)| {
+                let args = args
+                    .iter()
+                    .map(|arg| nbe::eval_term(env, &locals, self.shift, arg))
+                    .collect::<Result<_, _>>()?;
+                Ok((label.clone(), args))
+            })
+            .collect()
+    }
+
+    fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        self.shift += shift;
+    }
+}
+
+/// The clauses of a case expression that is stuck on a neutral term
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseClosure {
+    /// The values of the variables that the clauses can refer to
+    pub locals: Locals,
+    /// The amount to shift the universes in the clauses by
+    pub shift: LevelShift,
+    /// The patterns and bodies of the clauses
+    pub clauses: Vec<(RcPattern, RcTerm)>,
+}
+
+impl CaseClosure {
+    pub fn new(
+        locals: Locals,
+        shift: LevelShift,
+        clauses: Vec<(RcPattern, RcTerm)>,
+    ) -> CaseClosure {
+        CaseClosure {
+            locals,
+            shift,
+            clauses,
+        }
+    }
+
+    fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        self.shift += shift;
+    }
+}
+
 /// The head of an application
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Head {
-    /// Variables that have not yet been replaced with a definition
-    Var(Var<String>, LevelShift),
+    /// Local variables that stand for parameters
+    Var(VarLevel, LevelShift),
+    /// Global definitions that have no definition to unfold to
+    Global(String, LevelShift),
     /// Imported definitions
-    Import(String),
-    // TODO: Metavariables
+    Import(String, LevelShift),
+    /// Metavariables that have not yet been solved
+    Meta(MetaVar),
+    /// Recursive definitions that are waiting for their decreasing argument
+    Fix(usize, RcValue, Closure),
 }
 
 /// The spine of a neutral term
 ///
 /// These are arguments that are awaiting application
-pub type Spine = Vec<RcValue>;
+pub type Spine = Vec<(Plicity, RcValue)>;
 
 /// Neutral values
 ///
 /// These might be able to be reduced further depending on the bindings in the
 /// context
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Neutral {
     /// Head of an application
     Head(Head),
     /// Field projection
     RecordProj(RcNeutral, Label, LevelShift),
     /// Case expressions
-    Case(RcNeutral, Vec<Scope<RcPattern, RcValue>>),
+    Case(RcNeutral, CaseClosure),
 }
 
 impl Neutral {
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Neutral {
-        Neutral::Head(Head::Var(var.into(), shift.into()))
+    /// The head that is blocking further computation
+    pub fn head(&self) -> &Head {
+        match *self {
+            Neutral::Head(ref head) => head,
+            Neutral::RecordProj(ref expr, _, _) | Neutral::Case(ref expr, _) => expr.head(),
+        }
     }
 }
 
 /// Reference counted neutral values
-#[derive(Debug, Clone, PartialEq, BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcNeutral {
     pub inner: Rc<Neutral>,
 }
@@ -198,18 +604,18 @@ pub struct RcNeutral {
 impl RcNeutral {
     pub fn shift_universes(&mut self, shift: LevelShift) {
         match *Rc::make_mut(&mut self.inner) {
-            // Neutral::Head(Head::Var(_, ref mut head_shift)) => {
-            //     *head_shift += shift; // NOTE: Not sure if this is correct!
-            // },
-            Neutral::Head(Head::Var(_, _)) | Neutral::Head(Head::Import(_)) => {},
+            Neutral::Head(Head::Var(_, ref mut head_shift))
+            | Neutral::Head(Head::Import(_, ref mut head_shift)) => *head_shift += shift,
+            Neutral::Head(Head::Global(_, _)) | Neutral::Head(Head::Meta(_)) => {},
+            Neutral::Head(Head::Fix(_, ref mut ann, ref mut closure)) => {
+                ann.shift_universes(shift);
+                closure.shift_universes(shift);
+            },
             Neutral::RecordProj(ref mut expr, _, _) => expr.shift_universes(shift),
-            Neutral::Case(ref mut expr, ref mut clauses) => {
+            Neutral::Case(ref mut expr, ref mut closure) => {
                 expr.shift_universes(shift);
-                for clause in clauses {
-                    // FIXME: implement shifting for patterns as well!
-                    // clause.unsafe_pattern.shift_universes(shift);
-                    clause.unsafe_body.shift_universes(shift);
-                }
+                // FIXME: implement shifting for patterns as well!
+                closure.shift_universes(shift);
             },
         }
     }
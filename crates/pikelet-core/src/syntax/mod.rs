@@ -1,5 +1,6 @@
-use moniker::{BoundPattern, BoundTerm};
+use moniker::{BoundPattern, BoundTerm, OnBoundFn, OnFreeFn, ScopeState, Var};
 use pretty::{BoxDoc, Doc};
+use std::cmp::Ordering;
 use std::fmt;
 use std::ops::{Add, AddAssign};
 
@@ -76,25 +77,426 @@ impl fmt::Display for Literal {
     }
 }
 
+/// Whether a function parameter is explicit or implicit
+///
+/// Implicit parameters are written in braces, for example `{a : Type} -> a -> a`,
+/// and their arguments are inferred during elaboration when they are not given.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, BoundTerm, BoundPattern)]
+pub enum Plicity {
+    Explicit,
+    Implicit,
+}
+
+/// A metavariable, standing in for a term that is yet to be solved for
+/// during elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, BoundTerm)]
+pub struct MetaVar(pub u32);
+
+impl fmt::Display for MetaVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "?{}", self.0)
+    }
+}
+
+/// A universe level metavariable, standing in for a level that is yet to be
+/// solved for during elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, BoundTerm)]
+pub struct LevelMeta(pub u32);
+
+impl fmt::Display for LevelMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "?l{}", self.0)
+    }
+}
+
+/// A de Bruijn index, which refers to a variable by counting the binders
+/// between it and the binder that introduced it
+///
+/// The innermost binder has an index of zero. Indices are used in the core
+/// syntax, so that terms can be moved around under binders without any
+/// renaming.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarIndex(pub usize);
+
+impl VarIndex {
+    /// Convert the index to a level, given the number of variables in scope
+    pub fn to_level(self, size: VarLevel) -> VarLevel {
+        VarLevel(size.0 - self.0 - 1)
+    }
+}
+
+impl fmt::Display for VarIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+/// A de Bruijn level, which refers to a variable by counting the binders
+/// from the outside of the term to the binder that introduced it
+///
+/// The outermost binder has a level of zero. Levels are used in the semantic
+/// domain, so that values can be moved into larger contexts without any
+/// shifting. The number of variables in scope is also the level of the next
+/// variable to be bound, so we use this to keep track of sizes of contexts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarLevel(pub usize);
+
+impl VarLevel {
+    /// Convert the level to an index, given the number of variables in scope
+    pub fn to_index(self, size: VarLevel) -> VarIndex {
+        VarIndex(size.0 - self.0 - 1)
+    }
+
+    /// The level after this one
+    pub fn succ(self) -> VarLevel {
+        VarLevel(self.0 + 1)
+    }
+}
+
+impl fmt::Display for VarLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The name of a binder, kept around for pretty printing and resugaring
+///
+/// Variables refer to their binders by position, so names are ignored when
+/// comparing terms for equality.
+#[derive(Debug, Clone, Default)]
+pub struct Name(pub Option<String>);
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Name {
+        Name(Some(name.into()))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_ref().map(String::as_str)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, _: &Name) -> bool {
+        true
+    }
+}
+
+impl From<Option<String>> for Name {
+    fn from(src: Option<String>) -> Name {
+        Name(src)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str().unwrap_or("_"))
+    }
+}
+
 /// A universe level
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, BoundTerm)]
-pub struct Level(pub u32);
+///
+/// Levels are kept in a normal form: the maximum of a constant, of some level
+/// variables, and of some level metavariables, each of which may be shifted
+/// up by some amount. For example, `level-max 2 (l^1)` would be stored as
+/// `Finite(2, [(l, 1)], [])`. Level metavariables only show up during
+/// elaboration, where they stand for the universes of holes.
+///
+/// Level variables are de Bruijn indices in the core syntax, and de Bruijn
+/// levels in the semantic domain.
+///
+/// Types that quantify over levels, like `(l : Level) -> Type^l`, are too big
+/// to live in any of these universes, and are instead placed in
+/// `Type^omega`. There is no way of writing this universe in the surface
+/// syntax, so it does not have a type of its own.
+///
+/// Shifting a level past the largest level that we can represent saturates,
+/// rather than overflowing. The elaborator uses `Level::succ` to check for
+/// this when finding the types of universes.
+#[derive(Debug, Clone)]
+pub enum Level<V = VarIndex> {
+    /// The maximum of a constant, of some shifted level variables, and of
+    /// some shifted level metavariables
+    Finite(u32, Vec<(V, LevelShift)>, Vec<(LevelMeta, LevelShift)>),
+    /// The limit of all of the finite levels
+    Omega,
+}
 
-impl Level {
-    pub fn succ(self) -> Level {
-        Level(self.0 + 1)
+impl<V: Copy + PartialEq> Level<V> {
+    /// A level variable
+    pub fn var(var: V) -> Level<V> {
+        Level::Finite(0, vec![(var, LevelShift(0))], Vec::new())
+    }
+
+    /// A level metavariable
+    pub fn meta(meta: LevelMeta) -> Level<V> {
+        Level::Finite(0, Vec::new(), vec![(meta, LevelShift(0))])
+    }
+
+    /// The level immediately above this one, or `None` if it is too large to
+    /// be represented
+    pub fn succ(&self) -> Option<Level<V>> {
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) => Some(Level::Finite(
+                constant.checked_add(1)?,
+                checked_shifts(vars)?,
+                checked_shifts(metas)?,
+            )),
+            Level::Omega => Some(Level::Omega),
+        }
+    }
+
+    /// The smallest level that is at least as large as this one once it has
+    /// been shifted up by `shift`
+    pub fn lower(&self, shift: LevelShift) -> Level<V> {
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) => Level::finite(
+                constant.saturating_sub(shift.0),
+                lowered_shifts(vars, shift),
+                lowered_shifts(metas, shift),
+            ),
+            Level::Omega => Level::Omega,
+        }
+    }
+
+    /// Returns the constant part of the level, if it mentions no level
+    /// variables or level metavariables
+    pub fn as_constant(&self) -> Option<u32> {
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) if vars.is_empty() && metas.is_empty() => {
+                Some(constant)
+            },
+            Level::Finite(_, _, _) | Level::Omega => None,
+        }
+    }
+
+    /// The smallest level that is greater than or equal to both levels
+    pub fn max(&self, other: &Level<V>) -> Level<V> {
+        match (self, other) {
+            (
+                &Level::Finite(constant1, ref vars1, ref metas1),
+                &Level::Finite(constant2, ref vars2, ref metas2),
+            ) => Level::finite(
+                u32::max(constant1, constant2),
+                vars1.iter().chain(vars2).cloned(),
+                metas1.iter().chain(metas2).cloned(),
+            ),
+            (&Level::Omega, _) | (_, &Level::Omega) => Level::Omega,
+        }
+    }
+
+    /// Returns `true` if the level is less than or equal to `other`, no matter
+    /// what the level variables and level metavariables are instantiated with
+    pub fn le(&self, other: &Level<V>) -> bool {
+        match (self, other) {
+            (
+                &Level::Finite(constant1, ref vars1, ref metas1),
+                &Level::Finite(constant2, ref vars2, ref metas2),
+            ) => {
+                // Level variables are at least zero, so the shifts on the
+                // variables of `other` also bound it from below
+                let lower_bound2 = Iterator::chain(
+                    vars2.iter().map(|&(_, shift)| shift.0),
+                    metas2.iter().map(|&(_, shift)| shift.0),
+                )
+                .fold(constant2, u32::max);
+
+                constant1 <= lower_bound2
+                    && vars1.iter().all(|&(var1, shift1)| {
+                        vars2
+                            .iter()
+                            .any(|&(var2, shift2)| var1 == var2 && shift1 <= shift2)
+                    })
+                    && metas1.iter().all(|&(meta1, shift1)| {
+                        metas2
+                            .iter()
+                            .any(|&(meta2, shift2)| meta1 == meta2 && shift1 <= shift2)
+                    })
+            },
+            (_, &Level::Omega) => true,
+            (&Level::Omega, &Level::Finite(_, _, _)) => false,
+        }
+    }
+
+    /// Returns `true` if the level mentions the given level variable
+    pub fn mentions(&self, var: V) -> bool {
+        match *self {
+            Level::Finite(_, ref vars, _) => vars.iter().any(|&(other, _)| other == var),
+            Level::Omega => false,
+        }
+    }
+
+    /// Replace the level variables in the level, using `on_var` to find their
+    /// replacements
+    pub fn map_vars<W: Copy + PartialEq>(&self, mut on_var: impl FnMut(V) -> Level<W>) -> Level<W> {
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) => {
+                let init = Level::Finite(constant, Vec::new(), metas.clone());
+                vars.iter()
+                    .fold(init, |acc, &(var, shift)| acc.max(&(on_var(var) + shift)))
+            },
+            Level::Omega => Level::Omega,
+        }
+    }
+
+    /// Replace the level metavariables in the level, using `on_meta` to find
+    /// their replacements
+    pub fn map_metas(&self, mut on_meta: impl FnMut(LevelMeta) -> Level<V>) -> Level<V> {
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) => {
+                let init = Level::Finite(constant, vars.clone(), Vec::new());
+                metas.iter().fold(init, |acc, &(meta, shift)| {
+                    acc.max(&(on_meta(meta) + shift))
+                })
+            },
+            Level::Omega => Level::Omega,
+        }
+    }
+
+    /// Construct a finite level, removing any redundant components
+    fn finite(
+        constant: u32,
+        vars: impl IntoIterator<Item = (V, LevelShift)>,
+        metas: impl IntoIterator<Item = (LevelMeta, LevelShift)>,
+    ) -> Level<V> {
+        let vars = normalize_components(vars);
+        let metas = normalize_components(metas);
+
+        let max_shift = Iterator::chain(
+            vars.iter().map(|&(_, shift)| shift.0),
+            metas.iter().map(|&(_, shift)| shift.0),
+        )
+        .max();
+        let constant = match max_shift {
+            Some(max_shift) if constant <= max_shift => 0,
+            _ => constant,
+        };
+
+        Level::Finite(constant, vars, metas)
     }
 }
 
-impl From<u32> for Level {
-    fn from(src: u32) -> Level {
-        Level(src)
+impl<V: fmt::Display> Level<V> {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        let component_doc = |name: String, shift: LevelShift| match shift {
+            LevelShift(0) => Doc::text(name),
+            shift => Doc::text(format!("{}^{}", name, shift)),
+        };
+
+        match *self {
+            Level::Finite(constant, ref vars, ref metas) => {
+                let mut components = Iterator::chain(
+                    vars.iter()
+                        .map(|&(ref var, shift)| component_doc(var.to_string(), shift)),
+                    metas
+                        .iter()
+                        .map(|&(meta, shift)| component_doc(meta.to_string(), shift)),
+                )
+                .collect::<Vec<_>>();
+
+                let last = match components.pop() {
+                    None => return Doc::as_string(constant),
+                    Some(last) if components.is_empty() && constant == 0 => return last,
+                    Some(last) => last,
+                };
+                if constant > 0 {
+                    components.insert(0, Doc::as_string(constant));
+                }
+
+                // Fold the components into nested applications of
+                // `level-max`, so that the level can be parsed again
+                components.into_iter().rev().fold(last, |acc, component| {
+                    Doc::text("(level-max")
+                        .append(Doc::space())
+                        .append(component)
+                        .append(Doc::space())
+                        .append(acc)
+                        .append(")")
+                })
+            },
+            Level::Omega => Doc::text("omega"),
+        }
+    }
+}
+
+/// Combine the shifts of any repeated variables, keeping the largest one
+fn normalize_components<V: PartialEq>(
+    components: impl IntoIterator<Item = (V, LevelShift)>,
+) -> Vec<(V, LevelShift)> {
+    let mut normalized = Vec::<(V, LevelShift)>::new();
+    for (var, shift) in components {
+        match normalized.iter_mut().find(|&&mut (ref v, _)| *v == var) {
+            Some(&mut (_, ref mut existing_shift)) => {
+                *existing_shift = LevelShift::max(*existing_shift, shift);
+            },
+            None => normalized.push((var, shift)),
+        }
+    }
+    normalized
+}
+
+/// Shift each of the components up by one, returning `None` on overflow
+fn checked_shifts<V: Copy>(components: &[(V, LevelShift)]) -> Option<Vec<(V, LevelShift)>> {
+    components
+        .iter()
+        .map(|&(var, shift)| Some((var, LevelShift(shift.0.checked_add(1)?))))
+        .collect()
+}
+
+/// Shift each of the components down by `shift`, stopping at zero
+fn lowered_shifts<V: Copy>(
+    components: &[(V, LevelShift)],
+    shift: LevelShift,
+) -> Vec<(V, LevelShift)> {
+    components
+        .iter()
+        .map(|&(var, s)| (var, LevelShift(s.0.saturating_sub(shift.0))))
+        .collect()
+}
+
+impl<V: Copy + PartialEq> PartialEq for Level<V> {
+    fn eq(&self, other: &Level<V>) -> bool {
+        self.le(other) && other.le(self)
     }
 }
 
-impl fmt::Display for Level {
+impl<V: Copy + PartialEq> PartialOrd for Level<V> {
+    fn partial_cmp(&self, other: &Level<V>) -> Option<Ordering> {
+        match (self.le(other), other.le(self)) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Levels in the raw syntax are always constants, and the level variables of
+/// the core syntax are de Bruijn indices rather than `moniker` variables, so
+/// there are no variables here to open or close
+impl BoundTerm<String> for Level {
+    fn term_eq(&self, other: &Level) -> bool {
+        self == other
+    }
+
+    fn close_term(&mut self, _: ScopeState, _: &impl OnFreeFn<String>) {}
+
+    fn open_term(&mut self, _: ScopeState, _: &impl OnBoundFn<String>) {}
+
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<String>)) {}
+
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<String>)) {}
+}
+
+impl<V> From<u32> for Level<V> {
+    fn from(src: u32) -> Level<V> {
+        Level::Finite(src, Vec::new(), Vec::new())
+    }
+}
+
+impl<V: fmt::Display> fmt::Display for Level<V> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
     }
 }
 
@@ -118,27 +520,36 @@ impl Add for LevelShift {
     type Output = LevelShift;
 
     fn add(self, other: LevelShift) -> LevelShift {
-        LevelShift(self.0 + other.0)
+        LevelShift(self.0.saturating_add(other.0))
     }
 }
 
 impl AddAssign for LevelShift {
     fn add_assign(&mut self, other: LevelShift) {
-        self.0 += other.0;
+        *self = *self + other;
     }
 }
 
-impl Add<LevelShift> for Level {
-    type Output = Level;
+impl<V> Add<LevelShift> for Level<V> {
+    type Output = Level<V>;
 
-    fn add(self, other: LevelShift) -> Level {
-        Level(self.0 + other.0)
+    fn add(mut self, other: LevelShift) -> Level<V> {
+        self += other;
+        self
     }
 }
 
-impl AddAssign<LevelShift> for Level {
+impl<V> AddAssign<LevelShift> for Level<V> {
     fn add_assign(&mut self, other: LevelShift) {
-        self.0 += other.0;
+        if let Level::Finite(ref mut constant, ref mut vars, ref mut metas) = *self {
+            *constant = constant.saturating_add(other.0);
+            for &mut (_, ref mut shift) in vars {
+                *shift += other;
+            }
+            for &mut (_, ref mut shift) in metas {
+                *shift += other;
+            }
+        }
     }
 }
 
@@ -0,0 +1,266 @@
+//! Diagnostics produced by the driver, and the machine-readable formats that
+//! they can be emitted in
+
+use codespan::{ByteIndex, ByteSpan, CodeMap, FileName};
+use codespan_reporting::{Label, LabelStyle, Severity};
+use serde_json::{json, Value};
+
+use pikelet_concrete::elaborate::TypeError;
+
+/// A diagnostic message, along with the resugared types that were involved in
+/// producing it
+///
+/// This mirrors `codespan_reporting::Diagnostic`, but has room for the
+/// structured information that editor integrations and CI annotators might
+/// want to consume without having to pick apart the message.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// The overall severity of the diagnostic
+    pub severity: Severity,
+    /// An optional code that identifies this diagnostic
+    pub code: Option<String>,
+    /// The main message associated with this diagnostic
+    pub message: String,
+    /// The labelled spans marking the regions of code that cause this
+    /// diagnostic to be raised
+    pub labels: Vec<Label>,
+    /// The resugared types that were involved, along with their role in the
+    /// diagnostic (eg. `"expected"` or `"found"`)
+    pub types: Vec<(String, String)>,
+}
+
+impl Diagnostic {
+    pub fn new_error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::from(codespan_reporting::Diagnostic::new_error(message))
+    }
+
+    /// Convert the diagnostic into a form that can be rendered by
+    /// `codespan_reporting`
+    pub fn to_codespan(&self) -> codespan_reporting::Diagnostic {
+        codespan_reporting::Diagnostic {
+            severity: self.severity,
+            code: self.code.clone(),
+            message: self.message.clone(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// Convert the diagnostic to JSON, looking up the locations of its labels
+    /// in the `code_map`
+    pub fn to_json(&self, code_map: &CodeMap) -> Value {
+        let labels = self.labels.iter().map(|label| {
+            let (file, start, end) = match span_locations(code_map, label.span) {
+                Some((file, start, end)) => {
+                    (json!(file.to_string()), start.to_json(), end.to_json())
+                },
+                None => (Value::Null, Value::Null, Value::Null),
+            };
+
+            json!({
+                "style": match label.style {
+                    LabelStyle::Primary => "primary",
+                    LabelStyle::Secondary => "secondary",
+                },
+                "message": label.message,
+                "file": file,
+                "start": start,
+                "end": end,
+            })
+        });
+
+        json!({
+            "severity": severity_name(self.severity),
+            "code": self.code,
+            "message": self.message,
+            "labels": labels.collect::<Vec<_>>(),
+            "types": types_to_json(&self.types),
+        })
+    }
+
+    /// Convert the diagnostic to a SARIF result object
+    fn to_sarif_result(&self, code_map: &CodeMap) -> Value {
+        let location = |label: &Label| {
+            let (file, start, end) = span_locations(code_map, label.span)?;
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": artifact_location(&file),
+                    "region": {
+                        "startLine": start.line,
+                        "startColumn": start.utf16_column,
+                        "endLine": end.line,
+                        "endColumn": end.utf16_column,
+                    },
+                },
+            });
+            if let Some(ref message) = label.message {
+                location["message"] = json!({ "text": message });
+            }
+            Some(location)
+        };
+        let locations = |style| {
+            let labels = self.labels.iter().filter(|label| label.style == style);
+            labels.filter_map(location).collect::<Vec<_>>()
+        };
+
+        let mut result = json!({
+            "level": match self.severity {
+                Severity::Bug | Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note | Severity::Help => "note",
+            },
+            "message": { "text": self.message },
+            "locations": locations(LabelStyle::Primary),
+            "relatedLocations": locations(LabelStyle::Secondary),
+            "properties": { "types": types_to_json(&self.types) },
+        });
+        if let Some(ref code) = self.code {
+            result["ruleId"] = json!(code);
+        }
+        result
+    }
+}
+
+impl From<codespan_reporting::Diagnostic> for Diagnostic {
+    fn from(src: codespan_reporting::Diagnostic) -> Diagnostic {
+        Diagnostic {
+            severity: src.severity,
+            code: src.code,
+            message: src.message,
+            labels: src.labels,
+            types: Vec::new(),
+        }
+    }
+}
+
+impl<'a> From<&'a TypeError> for Diagnostic {
+    fn from(src: &'a TypeError) -> Diagnostic {
+        let types = src.types().into_iter();
+
+        Diagnostic {
+            types: types
+                .map(|(role, ty)| (role.to_owned(), ty.to_string()))
+                .collect(),
+            ..Diagnostic::from(src.to_diagnostic())
+        }
+    }
+}
+
+/// Create a SARIF log containing the given diagnostics
+pub fn to_sarif<'a>(
+    code_map: &CodeMap,
+    diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+) -> Value {
+    let results = diagnostics.into_iter();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "pikelet",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                },
+            },
+            "results": results.map(|d| d.to_sarif_result(code_map)).collect::<Vec<_>>(),
+        }],
+    })
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    }
+}
+
+fn types_to_json(types: &[(String, String)]) -> Value {
+    let types = types
+        .iter()
+        .map(|&(ref role, ref ty)| json!({ "role": role, "type": ty }));
+    Value::Array(types.collect())
+}
+
+/// A location in a source file, with one-based line and column numbers
+struct Location {
+    byte: usize,
+    line: usize,
+    /// The column, counted in unicode scalar values
+    column: usize,
+    /// The column, counted in UTF-16 code units, as SARIF expects by default
+    utf16_column: usize,
+}
+
+impl Location {
+    fn to_json(&self) -> Value {
+        json!({ "byte": self.byte, "line": self.line, "column": self.column })
+    }
+}
+
+/// Find the name of the file that a span is in, along with the locations of
+/// its start and end
+///
+/// Returns `None` if the span does not point into a known file, as is the
+/// case for diagnostics that are not tied to any source code.
+fn span_locations(code_map: &CodeMap, span: ByteSpan) -> Option<(FileName, Location, Location)> {
+    let file = code_map.find_file(span.start())?;
+    let location = |index: ByteIndex| {
+        let (line, column) = file.location(index).ok()?;
+        let line_start = file.line_byte_index(line).ok()?;
+        let line_prefix = file.src_slice(ByteSpan::new(line_start, index)).ok()?;
+        Some(Location {
+            byte: (index - file.span().start()).to_usize(),
+            line: line.to_usize() + 1,
+            column: column.to_usize() + 1,
+            utf16_column: line_prefix.encode_utf16().count() + 1,
+        })
+    };
+
+    Some((
+        file.name().clone(),
+        location(span.start())?,
+        location(span.end())?,
+    ))
+}
+
+/// Describe the location of a file as a SARIF artifact location
+///
+/// Absolute paths are given as `file` URIs. Relative paths and virtual files
+/// are given as references relative to the `SRCROOT` base, which SARIF
+/// consumers resolve against the root of the project.
+fn artifact_location(name: &FileName) -> Value {
+    match *name {
+        FileName::Real(ref path) => {
+            let path = path.to_string_lossy().replace('\\', "/");
+            if path.starts_with('/') {
+                json!({ "uri": format!("file://{}", percent_encode(&path)) })
+            } else if path.get(1..2) == Some(":") {
+                // Windows paths that start with a drive letter
+                json!({ "uri": format!("file:///{}", percent_encode(&path)) })
+            } else {
+                json!({ "uri": percent_encode(&path), "uriBaseId": "SRCROOT" })
+            }
+        },
+        FileName::Virtual(ref name) => {
+            json!({ "uri": percent_encode(name), "uriBaseId": "SRCROOT" })
+        },
+    }
+}
+
+/// Percent-encode the characters of a path that are not allowed to appear
+/// unescaped in a URI
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'/' | b':' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,69 @@
+//! Extraction of the documentation of the items exported by a module
+
+use pikelet_concrete::syntax::concrete;
+use pikelet_core::syntax::{core, Label};
+
+/// The documentation of an item that is exported by a module
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemDoc {
+    /// The label of the field that the item is exported from
+    pub label: String,
+    /// The resugared type of the item
+    pub ty: concrete::Term,
+    /// The doc comments of the definition that the field refers to, with
+    /// lines separated by newlines, or an empty string if it is undocumented
+    pub doc: String,
+}
+
+/// Collect the items that are exported from the record at the end of a
+/// module, along with the doc comments of the definitions that they refer to
+///
+/// Returns `None` if the module does not end in a record.
+pub fn exported_docs(
+    term: &core::RcTerm,
+    fields: Vec<(String, concrete::Term)>,
+) -> Option<Vec<ItemDoc>> {
+    let mut docs = Vec::new();
+    let exports = exports(term, &mut docs)?;
+
+    Some(
+        fields
+            .into_iter()
+            .map(|(label, ty)| {
+                let doc = exports
+                    .iter()
+                    .find(|&&(ref export_label, _)| export_label.0 == label)
+                    .and_then(|&(_, ref term)| match *peel_ann(term).inner {
+                        core::Term::Var(index, _) => docs.get(docs.len().checked_sub(index.0 + 1)?),
+                        _ => None,
+                    })
+                    .cloned()
+                    .unwrap_or_default();
+
+                ItemDoc { label, ty, doc }
+            })
+            .collect(),
+    )
+}
+
+/// Find the fields of the record at the end of a module, recording the doc
+/// comments of any let bindings along the way, in the order that they were
+/// bound
+fn exports(term: &core::RcTerm, docs: &mut Vec<String>) -> Option<Vec<(Label, core::RcTerm)>> {
+    match *peel_ann(term).inner {
+        core::Term::Let(ref bindings, ref body) => {
+            docs.extend(bindings.iter().map(|&(_, _, ref doc)| doc.clone()));
+            exports(body, docs)
+        },
+        core::Term::RecordIntro(ref fields) => Some(fields.clone()),
+        _ => None,
+    }
+}
+
+/// Remove any type annotations from the outside of a term
+fn peel_ann(term: &core::RcTerm) -> &core::RcTerm {
+    match *term.inner {
+        core::Term::Ann(ref term, _) => peel_ann(term),
+        _ => term,
+    }
+}
@@ -0,0 +1,85 @@
+//! Errors that might be produced when loading files
+
+use codespan::ByteSpan;
+use codespan_reporting::{Diagnostic, Label};
+use failure::Fail;
+use std::path::PathBuf;
+
+/// An error produced while resolving the imports of a file
+#[derive(Debug, Fail, Clone, PartialEq)]
+pub enum ImportError {
+    #[fail(display = "Unable to find the import `{:?}`", path)]
+    NotFound {
+        span: ByteSpan,
+        path: String,
+        roots: Vec<PathBuf>,
+    },
+    #[fail(display = "Unable to read `{}`: {}", file_path, message)]
+    UnableToRead {
+        span: ByteSpan,
+        file_path: String,
+        message: String,
+    },
+    #[fail(display = "Cycle found while importing `{:?}`", path)]
+    Cycle {
+        span: ByteSpan,
+        path: String,
+        /// The imports that lead back to `path`, starting at `path`
+        cycle: Vec<String>,
+    },
+    #[fail(display = "The import `{:?}` has already been registered", path)]
+    AlreadyRegistered { path: String },
+}
+
+impl ImportError {
+    /// Convert the error into a diagnostic message
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match *self {
+            ImportError::NotFound {
+                span,
+                ref path,
+                ref roots,
+            } => {
+                let message = match roots.len() {
+                    0 => "no import roots were provided to search in".to_owned(),
+                    _ => format!(
+                        "searched in {}",
+                        roots
+                            .iter()
+                            .map(|root| format!("`{}`", root.display()))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                };
+
+                Diagnostic::new_error(format!("cannot find a file for the import `{:?}`", path))
+                    .with_label(Label::new_primary(span).with_message(message))
+            },
+            ImportError::UnableToRead {
+                span,
+                ref file_path,
+                ref message,
+            } => Diagnostic::new_error(format!("unable to read `{}`", file_path))
+                .with_label(Label::new_primary(span).with_message(message.clone())),
+            ImportError::Cycle {
+                span,
+                ref path,
+                ref cycle,
+            } => {
+                let cycle = cycle
+                    .iter()
+                    .chain(Some(path))
+                    .map(|path| format!("`{:?}`", path))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                Diagnostic::new_error(format!("cycle detected when importing `{:?}`", path))
+                    .with_label(Label::new_primary(span).with_message(cycle))
+            },
+            ImportError::AlreadyRegistered { ref path } => Diagnostic::new_error(format!(
+                "the import `{:?}` has already been registered",
+                path,
+            )),
+        }
+    }
+}
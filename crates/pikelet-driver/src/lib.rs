@@ -28,7 +28,7 @@
 //!                       |
 //!                       v
 //!  .------------------------------------------.
-//!  | pikelet_concrete::syntax::concrete::Term |---------> Code formatter (TODO)
+//!  | pikelet_concrete::syntax::concrete::Term |---------> pikelet_concrete::format
 //!  '------------------------------------------'
 //!                       |
 //!           pikelet_concrete::desugar
@@ -81,9 +81,18 @@
 //! ## Name binding
 //!
 //! Name binding is a surprisingly challenging thing to implement in type checkers
-//! and compilers. We use the [`moniker` crate](https://github.com/brendanzab/moniker)
-//! for this. Unfortunately this uses a quite slow method of name binding, and could
-//! result in performance blowouts in the future. This is something to keep an eye on!
+//! and compilers. The raw syntax uses the [`moniker` crate](https://github.com/brendanzab/moniker)
+//! to resolve names, but once a term has been elaborated we switch to a nameless
+//! representation: variables in the core syntax are [de Bruijn indices], counting
+//! the binders between a variable and its binder, and variables in the semantic
+//! domain are de Bruijn levels, counting the binders from the outside of the
+//! context. This means that terms never need to be renamed when evaluating, and
+//! that comparing terms for alpha-equivalence is a structural check. Binder names
+//! are kept around only for pretty printing and resugaring. Globals, like the
+//! built-in definitions and the bindings added to the top-level environment, are
+//! referred to by name.
+//!
+//! [de Bruijn indices]: https://en.wikipedia.org/wiki/De_Bruijn_index
 //!
 //! ## Performance considerations
 //!
@@ -125,17 +134,31 @@
 //! - [Queries: demand-driven compilation (Rustc Book)](https://rust-lang-nursery.github.io/rustc-guide/query.html)
 //! - [Anders Hejlsberg on Modern Compiler Construction (YouTube)](https://www.youtube.com/watch?v=wSdV1M7n4gQ)
 
-use codespan::CodeMap;
 pub use codespan::FileName;
-pub use codespan_reporting::{termcolor, ColorArg, Diagnostic};
+use codespan::{ByteIndex, ByteSpan, CodeMap};
+pub use codespan_reporting::{termcolor, ColorArg, Label, LabelStyle, Severity};
+use std::collections::HashMap;
 use std::io;
+use std::path::PathBuf;
 
 use pikelet_concrete::desugar::{Desugar, DesugarEnv};
-use pikelet_concrete::elaborate::Context;
+use pikelet_concrete::elaborate::{Context, HoleGoal};
 use pikelet_concrete::resugar::Resugar;
-use pikelet_concrete::syntax::raw;
+use pikelet_concrete::syntax::{concrete, raw};
 use pikelet_core::syntax::{core, domain, Import};
 
+mod diagnostics;
+mod docs;
+mod errors;
+
+pub use pikelet_concrete::desugar::{Binding, NameIndex, Occurrence, OccurrenceKind};
+pub use pikelet_concrete::elaborate::TermType;
+pub use pikelet_concrete::parse::is_identifier;
+
+pub use self::diagnostics::Diagnostic;
+pub use self::docs::ItemDoc;
+pub use self::errors::ImportError;
+
 /// An environment that keeps track of the state of a Pikelet program during
 /// compilation or interactive sessions
 #[derive(Debug, Clone)]
@@ -146,6 +169,11 @@ pub struct Driver {
     desugar_env: DesugarEnv,
     /// A codemap that owns the source code for any terms that are currently loaded
     code_map: CodeMap,
+    /// The directories to search in when resolving the paths of imports
+    import_roots: Vec<PathBuf>,
+    /// The files that have been loaded as imports, keyed by their canonical
+    /// paths, along with the import path that they were first loaded under
+    import_files: HashMap<PathBuf, String>,
 }
 
 impl Driver {
@@ -158,6 +186,8 @@ impl Driver {
             context,
             desugar_env,
             code_map: CodeMap::new(),
+            import_roots: Vec::new(),
+            import_files: HashMap::new(),
         }
     }
 
@@ -184,6 +214,22 @@ impl Driver {
         pikelet
     }
 
+    /// Add a directory to search in when resolving the paths of imports
+    ///
+    /// Roots are searched in the order that they were added.
+    pub fn add_import_root(&mut self, root: impl Into<PathBuf>) {
+        self.import_roots.push(root.into());
+    }
+
+    /// Add directories to search in when resolving the paths of imports,
+    /// searching the current directory if none were given
+    pub fn add_import_roots(&mut self, roots: Vec<PathBuf>) {
+        if roots.is_empty() {
+            self.add_import_root(".");
+        }
+        self.import_roots.extend(roots);
+    }
+
     /// Add a binding to the driver's top-level environment
     pub fn add_binding(&mut self, name: &str, term: core::RcTerm, ann: domain::RcType) {
         let fv = self.desugar_env.on_binding(&name);
@@ -198,8 +244,13 @@ impl Driver {
         name: FileName,
         src: String,
     ) -> Result<(), Vec<Diagnostic>> {
+        if self.context.get_import(&path).is_some() {
+            return Err(vec![ImportError::AlreadyRegistered { path }
+                .to_diagnostic()
+                .into()]);
+        }
+
         let (term, ty) = self.infer_file(name, src)?;
-        // FIXME: Check if import already exists
         self.context.insert_import(path, Import::Term(term), ty);
 
         Ok(())
@@ -210,15 +261,142 @@ impl Driver {
         &mut self,
         name: FileName,
         src: String,
+    ) -> Result<(core::RcTerm, domain::RcType), Vec<Diagnostic>> {
+        self.infer_file_importing(name, src, &mut Vec::new())
+    }
+
+    /// Infer the type of a file, after loading the files that it imports
+    ///
+    /// `importing` holds the canonical paths of the files that are currently
+    /// being loaded, along with the import paths that referred to them, which
+    /// we use to detect import cycles.
+    ///
+    /// Parts of the file that fail to parse are still desugared and
+    /// elaborated as errors, so that the parse errors are reported along with
+    /// any other errors that are found in the rest of the file. Imports that
+    /// fail to load stop us from going any further, seeing as their uses
+    /// would only produce more errors.
+    fn infer_file_importing(
+        &mut self,
+        name: FileName,
+        src: String,
+        importing: &mut Vec<(PathBuf, String)>,
     ) -> Result<(core::RcTerm, domain::RcType), Vec<Diagnostic>> {
         let file_map = self.code_map.add_filemap(name, src);
-        // TODO: follow import paths
-        let (concrete_term, _import_paths, errors) = pikelet_concrete::parse::term(&file_map);
-        if !errors.is_empty() {
-            return Err(errors.iter().map(|error| error.to_diagnostic()).collect());
+        let (concrete_term, import_paths, errors) = pikelet_concrete::parse::term(&file_map);
+        let mut diagnostics = errors
+            .iter()
+            .map(|error| error.to_diagnostic().into())
+            .collect::<Vec<_>>();
+
+        for (span, path) in import_paths {
+            if let Err(errors) = self.load_import(span, path, importing) {
+                diagnostics.extend(errors);
+                return Err(diagnostics);
+            }
+        }
+
+        let result = self
+            .desugar(&concrete_term)
+            .and_then(|raw_term| self.infer_term(&raw_term));
+
+        match result {
+            Ok(result) if diagnostics.is_empty() => Ok(result),
+            Ok(_) => Err(diagnostics),
+            Err(errors) => {
+                diagnostics.extend(errors);
+                Err(diagnostics)
+            },
+        }
+    }
+
+    /// Load and type check an imported file, along with its own imports,
+    /// caching the result in the context
+    ///
+    /// Imports that have already been loaded, or that were registered by
+    /// other means (like primitives), are left as-is. Files are identified by
+    /// their canonical paths, so import paths that are spelled differently
+    /// but refer to the same file share the same definitions.
+    fn load_import(
+        &mut self,
+        span: ByteSpan,
+        path: String,
+        importing: &mut Vec<(PathBuf, String)>,
+    ) -> Result<(), Vec<Diagnostic>> {
+        if self.context.get_import(&path).is_some() {
+            return Ok(());
         }
-        let raw_term = self.desugar(&concrete_term)?;
-        self.infer_term(&raw_term)
+
+        let file_path = match self.resolve_import(&path) {
+            Some(file_path) => file_path,
+            None => {
+                let roots = self.import_roots.clone();
+                return Err(vec![ImportError::NotFound { span, path, roots }
+                    .to_diagnostic()
+                    .into()]);
+            },
+        };
+        let unable_to_read = |error: io::Error| {
+            let file_path = file_path.display().to_string();
+            let message = error.to_string();
+            vec![ImportError::UnableToRead {
+                span,
+                file_path,
+                message,
+            }
+            .to_diagnostic()
+            .into()]
+        };
+        let canonical_path = file_path.canonicalize().map_err(unable_to_read)?;
+
+        if let Some(index) = importing
+            .iter()
+            .position(|(other, _)| *other == canonical_path)
+        {
+            let cycle = importing[index..]
+                .iter()
+                .map(|(_, path)| path.clone())
+                .collect();
+            return Err(vec![ImportError::Cycle { span, path, cycle }
+                .to_diagnostic()
+                .into()]);
+        }
+
+        let loaded = (self.import_files.get(&canonical_path))
+            .and_then(|loaded_path| self.context.get_import(loaded_path))
+            .cloned();
+        if let Some((import, ty)) = loaded {
+            self.context.insert_import(path, import, ty);
+            return Ok(());
+        }
+
+        let src = std::fs::read_to_string(&file_path).map_err(unable_to_read)?;
+
+        importing.push((canonical_path.clone(), path.clone()));
+        let (term, ty) = self.infer_file_importing(FileName::Real(file_path), src, importing)?;
+        importing.pop();
+
+        self.import_files.insert(canonical_path, path.clone());
+        self.context.insert_import(path, Import::Term(term), ty);
+
+        Ok(())
+    }
+
+    /// Find the file that an import path refers to, using the import roots
+    ///
+    /// The `.pi` extension is added to paths that don't have an extension.
+    fn resolve_import(&self, path: &str) -> Option<PathBuf> {
+        self.import_roots.iter().find_map(|root| {
+            let mut file_path = root.join(path);
+            if file_path.extension().is_none() {
+                file_path.set_extension("pi");
+            }
+            if file_path.is_file() {
+                Some(file_path)
+            } else {
+                None
+            }
+        })
     }
 
     /// Normalize the contents of a file
@@ -226,35 +404,231 @@ impl Driver {
         &mut self,
         name: FileName,
         src: String,
-    ) -> Result<domain::RcValue, Vec<Diagnostic>> {
+    ) -> Result<core::RcTerm, Vec<Diagnostic>> {
         use pikelet_concrete::elaborate::InternalError;
 
         let (term, _) = self.infer_file(name, src)?;
-        pikelet_core::nbe::nf_term(&self.context, &term)
-            .map_err(|err| vec![InternalError::from(err).to_diagnostic()])
+        self.context
+            .nf_term(&term)
+            .map_err(|err| vec![InternalError::from(err).to_diagnostic().into()])
     }
 
-    /// Infer the type of a term
+    /// Infer the type of a term, reporting all of the type errors that it
+    /// contains
     pub fn infer_term(
         &self,
         raw_term: &raw::RcTerm,
     ) -> Result<(core::RcTerm, domain::RcType), Vec<Diagnostic>> {
-        pikelet_concrete::elaborate::infer_term(&self.context, &raw_term)
-            .map_err(|err| vec![err.to_diagnostic()])
+        let (term, ty, errors) =
+            pikelet_concrete::elaborate::infer_term_recovering(&self.context, raw_term);
+
+        if errors.is_empty() {
+            Ok((term, ty))
+        } else {
+            Err(errors.iter().map(Diagnostic::from).collect())
+        }
+    }
+
+    /// Describe the goals of the holes in the contents of a file, along with
+    /// the diagnostics that were found in the rest of the file
+    ///
+    /// Like `infer_file`, parts of the file that fail to parse are still
+    /// desugared and elaborated as errors, so that the goals of the holes in
+    /// the rest of the file are still reported.
+    pub fn hole_goals_file(
+        &mut self,
+        name: FileName,
+        src: String,
+    ) -> (Vec<HoleGoal>, Vec<Diagnostic>) {
+        let file_map = self.code_map.add_filemap(name, src);
+        let (concrete_term, import_paths, errors) = pikelet_concrete::parse::term(&file_map);
+        let mut diagnostics = errors
+            .iter()
+            .map(|error| error.to_diagnostic().into())
+            .collect::<Vec<_>>();
+
+        for (span, path) in import_paths {
+            if let Err(errors) = self.load_import(span, path, &mut Vec::new()) {
+                diagnostics.extend(errors);
+                return (Vec::new(), diagnostics);
+            }
+        }
+
+        let result = self
+            .desugar(&concrete_term)
+            .and_then(|raw_term| self.hole_goals_term(&raw_term));
+
+        match result {
+            Ok(goals) => (goals, diagnostics),
+            Err(errors) => {
+                diagnostics.extend(errors);
+                (Vec::new(), diagnostics)
+            },
+        }
+    }
+
+    /// Describe the goals of the holes in a term
+    ///
+    /// Unlike `infer_term`, unsolved holes are not considered to be errors.
+    pub fn hole_goals_term(
+        &self,
+        raw_term: &raw::RcTerm,
+    ) -> Result<Vec<HoleGoal>, Vec<Diagnostic>> {
+        use pikelet_concrete::elaborate::{self, TypeError};
+
+        let metas_start = self.context.meta_count();
+        let (_, _, errors) = elaborate::infer_term_recovering(&self.context, raw_term);
+        let errors = errors
+            .iter()
+            .filter(|error| !matches!(error, TypeError::UnableToElaborateHole { .. }))
+            .map(Diagnostic::from)
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        elaborate::hole_goals(&self.context, metas_start)
+            .map_err(|err| vec![Diagnostic::from(&err)])
+    }
+
+    /// Document the items that are exported from the record at the end of a
+    /// file, in the order that they appear in its type
+    pub fn document_file(
+        &mut self,
+        name: FileName,
+        src: String,
+    ) -> Result<Vec<ItemDoc>, Vec<Diagnostic>> {
+        let (term, ty) = self.infer_file(name.clone(), src)?;
+        let fields = match self.resugar_value(&ty) {
+            concrete::Term::RecordType(_, fields) => fields
+                .into_iter()
+                .map(|field| (field.label.1, field.ann))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        docs::exported_docs(&term, fields).ok_or_else(|| {
+            let message = format!("`{}` does not export a record of items to document", name);
+            vec![Diagnostic::new_error(message)]
+        })
+    }
+
+    /// Format the source code of a file, preserving its comments
+    ///
+    /// Files that contain parse errors are left as-is, and the errors are
+    /// returned instead.
+    pub fn format_file(&mut self, name: FileName, src: String) -> Result<String, Vec<Diagnostic>> {
+        let file_map = self.code_map.add_filemap(name, src);
+        pikelet_concrete::format::format(&file_map).map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| error.to_diagnostic().into())
+                .collect()
+        })
     }
 
     /// Normalize a term
-    pub fn normalize_term(&self, term: &core::RcTerm) -> Result<domain::RcValue, Vec<Diagnostic>> {
+    pub fn normalize_term(&self, term: &core::RcTerm) -> Result<core::RcTerm, Vec<Diagnostic>> {
         use pikelet_concrete::elaborate::InternalError;
 
-        pikelet_core::nbe::nf_term(&self.context, term)
-            .map_err(|err| vec![InternalError::from(err).to_diagnostic()])
+        self.context
+            .nf_term(term)
+            .map_err(|err| vec![InternalError::from(err).to_diagnostic().into()])
+    }
+
+    /// Read a value back into a term
+    pub fn quote(&self, value: &domain::RcValue) -> Result<core::RcTerm, Vec<Diagnostic>> {
+        use pikelet_concrete::elaborate::InternalError;
+
+        self.context
+            .quote(value)
+            .map_err(|err| vec![InternalError::from(err).to_diagnostic().into()])
+    }
+
+    /// Remove the warnings that have been produced since the last time this
+    /// was called, returning them as diagnostics
+    pub fn take_warnings(&self) -> Vec<Diagnostic> {
+        self.context
+            .take_warnings()
+            .iter()
+            .map(|warning| warning.to_diagnostic().into())
+            .collect()
+    }
+
+    /// Start recording the names that are resolved during desugaring, for
+    /// use by editor tooling
+    pub fn record_names(&mut self) {
+        self.desugar_env.record_names();
+    }
+
+    /// Remove the names that have been recorded since the last time this was
+    /// called, returning them
+    pub fn take_names(&self) -> NameIndex {
+        self.desugar_env.take_names()
+    }
+
+    /// The names that are in scope at an index in the recorded source code,
+    /// along with their types, if they are known
+    ///
+    /// Only the names in the driver's top-level environment are given types.
+    /// If the index is outside of the recorded source code, only those names
+    /// are returned.
+    pub fn names_in_scope(
+        &self,
+        names: &NameIndex,
+        index: ByteIndex,
+    ) -> Vec<(String, Option<concrete::Term>)> {
+        let globals = self.context.mappings();
+        let scope = names.scope_at(index).unwrap_or(&globals);
+
+        scope
+            .iter()
+            .map(|(name, free_var)| {
+                let ty = self.context.lookup_global(free_var);
+                (
+                    name.clone(),
+                    ty.map(|(_, ty)| self.context.resugar_value(ty)),
+                )
+            })
+            .collect()
+    }
+
+    /// Start recording the types of the terms that are elaborated, for use
+    /// by editor tooling
+    pub fn record_term_types(&mut self) {
+        self.context.record_term_types();
+    }
+
+    /// Remove the term types that have been recorded since the last time this
+    /// was called, returning them in the order that they were elaborated
+    pub fn take_term_types(&self) -> Vec<TermType> {
+        self.context.take_term_types()
+    }
+
+    /// Resugar a recorded term type, filling in any metavariables that have
+    /// been solved since it was recorded
+    pub fn resugar_term_type(&self, term_type: &TermType) -> concrete::Term {
+        term_type.resugar(&self.context)
+    }
+
+    /// The labels and types of the fields of a recorded term type, if it is
+    /// a record type
+    pub fn record_fields(&self, term_type: &TermType) -> Option<Vec<(String, concrete::Term)>> {
+        match self.resugar_term_type(term_type) {
+            concrete::Term::RecordType(_, fields) => Some(
+                fields
+                    .into_iter()
+                    .map(|field| (field.label.1, field.ann))
+                    .collect(),
+            ),
+            _ => None,
+        }
     }
 
     /// Desugar a term
     pub fn desugar<T>(&self, src: &impl Desugar<T>) -> Result<T, Vec<Diagnostic>> {
         src.desugar(&self.desugar_env)
-            .map_err(|e| vec![e.to_diagnostic()])
+            .map_err(|e| vec![e.to_diagnostic().into()])
     }
 
     /// Resugar a term
@@ -262,6 +636,17 @@ impl Driver {
         self.context.resugar(src)
     }
 
+    /// Resugar a value, reading it back into a term first
+    pub fn resugar_value(&self, value: &domain::RcValue) -> concrete::Term {
+        self.context.resugar_value(value)
+    }
+
+    /// The source code that is currently loaded, for looking up the files and
+    /// locations that spans point to
+    pub fn code_map(&self) -> &CodeMap {
+        &self.code_map
+    }
+
     /// Emit the diagnostics using the given writer
     pub fn emit<'a>(
         &self,
@@ -269,8 +654,34 @@ impl Driver {
         diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
     ) -> io::Result<()> {
         for diagnostic in diagnostics {
-            codespan_reporting::emit(&mut writer, &self.code_map, diagnostic)?;
+            let diagnostic = diagnostic.to_codespan();
+            codespan_reporting::emit(&mut writer, &self.code_map, &diagnostic)?;
         }
         Ok(())
     }
+
+    /// Emit the diagnostics as JSON, with one object per line
+    pub fn emit_json<'a>(
+        &self,
+        mut writer: impl io::Write,
+        diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+    ) -> io::Result<()> {
+        for diagnostic in diagnostics {
+            serde_json::to_writer(&mut writer, &diagnostic.to_json(&self.code_map))?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Emit the diagnostics as a [SARIF](https://sarifweb.azurewebsites.net/)
+    /// log, for consumption by static analysis tooling
+    pub fn emit_sarif<'a>(
+        &self,
+        mut writer: impl io::Write,
+        diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+    ) -> io::Result<()> {
+        let log = diagnostics::to_sarif(&self.code_map, diagnostics);
+        serde_json::to_writer_pretty(&mut writer, &log)?;
+        writeln!(writer)
+    }
 }
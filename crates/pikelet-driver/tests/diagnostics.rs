@@ -0,0 +1,199 @@
+use serde_json::{json, Value};
+
+use pikelet_driver::{Driver, FileName};
+
+/// Infer the type of `src`, returning the diagnostics that were produced,
+/// along with the driver that produced them
+fn diagnostics(src: &str) -> (Driver, Vec<pikelet_driver::Diagnostic>) {
+    let mut driver = Driver::new();
+    let diagnostics = driver
+        .infer_file(FileName::virtual_("test"), src.to_owned())
+        .unwrap_err();
+
+    (driver, diagnostics)
+}
+
+#[test]
+fn mismatch_types() {
+    let (_, diagnostics) = diagnostics(r#""hello" : S32"#);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, Some("literal-mismatch".to_owned()));
+    assert_eq!(
+        diagnostics[0].types,
+        vec![("expected".to_owned(), "S32".to_owned())],
+    );
+}
+
+#[test]
+fn mismatch_types_folded() {
+    let (_, diagnostics) = diagnostics(
+        r#"
+        let
+            Id (A : Type) = A;
+            x : String = "hello";
+        in
+            (x : Id S32)
+    "#,
+    );
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, Some("mismatch".to_owned()));
+    assert_eq!(
+        diagnostics[0].types,
+        vec![
+            ("found".to_owned(), "String".to_owned()),
+            ("expected".to_owned(), "Id S32".to_owned()),
+        ],
+    );
+}
+
+#[test]
+fn emit_json() {
+    let (driver, diagnostics) = diagnostics("record {\n    x = true 1;\n}");
+
+    let mut output = Vec::new();
+    driver.emit_json(&mut output, &diagnostics).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(
+        serde_json::from_str::<Value>(lines[0]).unwrap(),
+        json!({
+            "severity": "error",
+            "code": "arg-applied-to-non-function",
+            "message": "applied an argument to a term that was not a function - found type `Bool`",
+            "labels": [
+                {
+                    "style": "primary",
+                    "message": "the term",
+                    "file": "<test>",
+                    "start": { "byte": 17, "line": 2, "column": 9 },
+                    "end": { "byte": 21, "line": 2, "column": 13 },
+                },
+                {
+                    "style": "secondary",
+                    "message": "the applied argument",
+                    "file": "<test>",
+                    "start": { "byte": 22, "line": 2, "column": 14 },
+                    "end": { "byte": 23, "line": 2, "column": 15 },
+                },
+            ],
+            "types": [{ "role": "found", "type": "Bool" }],
+        }),
+    );
+}
+
+#[test]
+fn emit_sarif() {
+    let (driver, diagnostics) = diagnostics("true 1");
+
+    let mut output = Vec::new();
+    driver.emit_sarif(&mut output, &diagnostics).unwrap();
+    let log = serde_json::from_slice::<Value>(&output).unwrap();
+
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "pikelet");
+
+    let result = &log["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "arg-applied-to-non-function");
+    assert_eq!(result["level"], "error");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"],
+        json!({
+            "artifactLocation": { "uri": "test", "uriBaseId": "SRCROOT" },
+            "region": { "startLine": 1, "startColumn": 1, "endLine": 1, "endColumn": 5 },
+        }),
+    );
+    assert_eq!(
+        result["relatedLocations"][0]["message"]["text"],
+        "the applied argument",
+    );
+    assert_eq!(
+        result["properties"]["types"],
+        json!([{ "role": "found", "type": "Bool" }]),
+    );
+}
+
+#[test]
+fn emit_sarif_file_uris_and_utf16_columns() {
+    let mut driver = Driver::new();
+    let diagnostics = driver
+        .infer_file(
+            FileName::real("/home/pikelet/my project/test.pi"),
+            "\"\u{1F600}\" 1".to_owned(),
+        )
+        .unwrap_err();
+
+    let mut output = Vec::new();
+    driver.emit_sarif(&mut output, &diagnostics).unwrap();
+    let log = serde_json::from_slice::<Value>(&output).unwrap();
+
+    // The emoji is one character, but two UTF-16 code units
+    assert_eq!(
+        log["runs"][0]["results"][0]["locations"][0]["physicalLocation"],
+        json!({
+            "artifactLocation": { "uri": "file:///home/pikelet/my%20project/test.pi" },
+            "region": { "startLine": 1, "startColumn": 1, "endLine": 1, "endColumn": 5 },
+        }),
+    );
+}
+
+#[test]
+fn parse_and_type_errors() {
+    // The field projection and applications on the erroneous record don't
+    // produce any further errors
+    let (_, diagnostics) = diagnostics(r#"record { a = (record { y = ; }).y 1 2; b = "x" : S32 }"#);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].code, None);
+    assert_eq!(diagnostics[1].code, Some("literal-mismatch".to_owned()));
+}
+
+#[test]
+fn pattern_errors() {
+    let src = r#"
+        record { g; h } where {
+            g = 1 : S32;
+            h (n : S32) : S32 = case n { ) => 1; _ => g };
+            k = "x" : S32;
+        }
+    "#;
+    let mut driver = Driver::new();
+    let diagnostics = driver
+        .infer_file(FileName::virtual_("test"), src.to_owned())
+        .unwrap_err();
+
+    // The clause after the erroneous pattern is not reported as unreachable
+    assert!(driver.take_warnings().is_empty());
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].code, None);
+    assert_eq!(diagnostics[1].code, Some("literal-mismatch".to_owned()));
+}
+
+#[test]
+fn multiple_type_errors() {
+    let src = r#"
+        record { x; y; z } where {
+            x : S32 = "one";
+            y : String = x;
+            z = 'z';
+            w = x 1;
+        }
+    "#;
+    let (_, diagnostics) = diagnostics(src);
+
+    let codes = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.code.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            Some("literal-mismatch"),
+            Some("mismatch"),
+            Some("arg-applied-to-non-function"),
+        ],
+    );
+}
@@ -0,0 +1,75 @@
+use pikelet_driver::{Driver, FileName};
+
+fn document(src: &str) -> Vec<(String, String, String)> {
+    let mut driver = Driver::with_prelude();
+    let items = driver
+        .document_file(FileName::virtual_("test"), src.to_owned())
+        .unwrap();
+
+    items
+        .into_iter()
+        .map(|item| (item.label, item.ty.to_string(), item.doc))
+        .collect()
+}
+
+fn item(label: &str, ty: &str, doc: &str) -> (String, String, String) {
+    (label.to_owned(), ty.to_owned(), doc.to_owned())
+}
+
+#[test]
+fn exported_items() {
+    let src = r#"
+        record { x; y; z = y }
+        where {
+            ||| The first item
+            x : S32;
+            x = 1;
+
+            y : String;
+            ||| The second item
+            |||
+            ||| Documented at its definition
+            y = "hello";
+        }
+    "#;
+
+    assert_eq!(
+        document(src),
+        vec![
+            item("x", "S32", "The first item"),
+            item(
+                "y",
+                "String",
+                "The second item\n\nDocumented at its definition"
+            ),
+            item(
+                "z",
+                "String",
+                "The second item\n\nDocumented at its definition"
+            ),
+        ],
+    );
+}
+
+#[test]
+fn prelude() {
+    let items = document(pikelet_library::PRELUDE);
+
+    assert_eq!(
+        items[0],
+        item(
+            "id",
+            "{a : Type} -> a -> a",
+            "The polymorphic identity function"
+        ),
+    );
+    assert!(items.iter().any(|&(ref label, _, _)| label == "Functor"));
+}
+
+#[test]
+fn no_exported_record() {
+    let mut driver = Driver::new();
+    let result = driver.document_file(FileName::virtual_("test"), "1 : S32".to_owned());
+
+    assert!(result.is_err());
+}
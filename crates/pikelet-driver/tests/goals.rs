@@ -0,0 +1,71 @@
+use pikelet_driver::{Driver, FileName};
+
+/// The expected type, local context, and solution of a hole
+type Goal = (String, Vec<(String, String)>, Option<String>);
+
+fn hole_goals(src: &str) -> Vec<Goal> {
+    let mut driver = Driver::new();
+    let (goals, diagnostics) = driver.hole_goals_file(FileName::virtual_("test"), src.to_owned());
+    assert!(
+        diagnostics.is_empty(),
+        "unexpected diagnostics: {:?}",
+        diagnostics
+    );
+
+    goals
+        .into_iter()
+        .map(|goal| {
+            let locals = goal
+                .locals
+                .iter()
+                .map(|(name, ty)| (name.clone(), ty.to_string()))
+                .collect();
+
+            (
+                goal.expected.to_string(),
+                locals,
+                goal.solution.map(|term| term.to_string()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn unsolved() {
+    assert_eq!(
+        hole_goals(r"\(a : Type) (x : a) => (? : a)"),
+        vec![(
+            "a".to_owned(),
+            vec![
+                ("a".to_owned(), "Type".to_owned()),
+                ("x".to_owned(), "a".to_owned()),
+            ],
+            None,
+        )],
+    );
+}
+
+#[test]
+fn solved() {
+    assert_eq!(
+        hole_goals(r#"record { x = "hello" } : Record { x : ? }"#),
+        vec![("Type".to_owned(), vec![], Some("String".to_owned()))],
+    );
+}
+
+#[test]
+fn no_holes() {
+    assert_eq!(hole_goals(r#""hello""#), vec![]);
+}
+
+#[test]
+fn unsolved_with_parse_error() {
+    let mut driver = Driver::new();
+    let src = r#"record { a = (record { y = ; }).y; b = (? : String) }"#;
+    let (goals, diagnostics) = driver.hole_goals_file(FileName::virtual_("test"), src.to_owned());
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, None);
+    assert_eq!(goals.len(), 1);
+    assert_eq!(goals[0].expected.to_string(), "String");
+}
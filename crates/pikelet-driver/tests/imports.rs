@@ -0,0 +1,256 @@
+use std::fs;
+use std::path::PathBuf;
+
+use pikelet_driver::{Driver, FileName};
+
+/// A temporary directory of files to import, which is removed when it is
+/// dropped, even if the test panics
+struct ImportRoot {
+    path: PathBuf,
+}
+
+impl Drop for ImportRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Create a fresh directory containing the given files
+fn import_root(name: &str, files: &[(&str, &str)]) -> ImportRoot {
+    let path = std::env::temp_dir().join(format!("pikelet-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    let root = ImportRoot { path };
+
+    for &(path, src) in files {
+        let path = root.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, src).unwrap();
+    }
+
+    root
+}
+
+/// Infer the type of `src` with the files imported from `root`, returning the
+/// resugared type, or the messages of the diagnostics that were produced
+fn infer(root: &ImportRoot, src: &str) -> Result<String, Vec<String>> {
+    let mut driver = Driver::new();
+    driver.add_import_root(root.path.clone());
+
+    match driver.infer_file(FileName::virtual_("test"), src.to_owned()) {
+        Ok((_, ty)) => Ok(driver.resugar_value(&ty).to_string()),
+        Err(diagnostics) => Err(diagnostics.into_iter().map(|d| d.message).collect()),
+    }
+}
+
+#[test]
+fn import_file() {
+    let root = import_root(
+        "import-file",
+        &[("greeting.pi", r#"record { message = "hello" }"#)],
+    );
+
+    assert_eq!(
+        infer(&root, r#"(import "greeting.pi").message"#),
+        Ok("String".to_owned()),
+    );
+}
+
+#[test]
+fn import_file_without_extension() {
+    let root = import_root(
+        "import-file-ext",
+        &[("greeting.pi", r#"record { message = "hello" }"#)],
+    );
+
+    assert_eq!(
+        infer(&root, r#"(import "greeting").message"#),
+        Ok("String".to_owned()),
+    );
+}
+
+#[test]
+fn import_file_different_spellings() {
+    let root = import_root(
+        "import-file-spellings",
+        &[("greeting.pi", r#"record { message = "hello" }"#)],
+    );
+
+    let mut driver = Driver::new();
+    driver.add_import_root(root.path.clone());
+    let src = r#"record { a = (import "greeting").message; b = (import "greeting.pi").message }"#;
+    let (_, ty) = driver
+        .infer_file(FileName::virtual_("test"), src.to_owned())
+        .unwrap();
+
+    assert_eq!(
+        driver.resugar_value(&ty).to_string(),
+        "Record { a : String; b : String }",
+    );
+
+    // The file is only loaded once, under the first spelling
+    let real_files = driver
+        .code_map()
+        .iter()
+        .filter(|file_map| match *file_map.name() {
+            FileName::Real(_) => true,
+            FileName::Virtual(_) => false,
+        })
+        .count();
+    assert_eq!(real_files, 1);
+}
+
+#[test]
+fn import_nested() {
+    let root = import_root(
+        "import-nested",
+        &[
+            ("a.pi", r#"record { b = import "b"; }"#),
+            ("b.pi", r#"record { c = import "data/c"; }"#),
+            ("data/c.pi", r#"record { value = "c" }"#),
+        ],
+    );
+
+    assert_eq!(
+        infer(&root, r#"(import "a").b.c.value"#),
+        Ok("String".to_owned()),
+    );
+}
+
+#[test]
+fn import_normalize() {
+    let root = import_root(
+        "import-normalize",
+        &[("greeting.pi", r#"record { message = "hello" }"#)],
+    );
+
+    let mut driver = Driver::new();
+    driver.add_import_root(root.path.clone());
+    let value = driver
+        .normalize_file(
+            FileName::virtual_("test"),
+            r#"(import "greeting").message"#.to_owned(),
+        )
+        .unwrap();
+
+    assert_eq!(driver.resugar(&value).to_string(), r#""hello""#);
+}
+
+#[test]
+fn import_fun_app_partial() {
+    let root = import_root(
+        "import-fun-app-partial",
+        &[("id.pi", r#"\(a : Type) (x : a) => x"#)],
+    );
+
+    assert_eq!(
+        infer(&root, r#"(import "id") String"#),
+        Ok("(x : String) -> String".to_owned()),
+    );
+
+    let mut driver = Driver::new();
+    driver.add_import_root(root.path.clone());
+    let value = driver
+        .normalize_file(FileName::virtual_("test"), r#"(import "id") String"#.to_owned())
+        .unwrap();
+
+    assert_eq!(driver.resugar(&value).to_string(), r#"\(x : String) => x"#);
+}
+
+#[test]
+fn import_fun_app_full() {
+    let root = import_root(
+        "import-fun-app-full",
+        &[("id.pi", r#"\(a : Type) (x : a) => x"#)],
+    );
+
+    assert_eq!(
+        infer(&root, r#"(import "id") String "hello""#),
+        Ok("String".to_owned()),
+    );
+
+    let mut driver = Driver::new();
+    driver.add_import_root(root.path.clone());
+    let value = driver
+        .normalize_file(
+            FileName::virtual_("test"),
+            r#"(import "id") String "hello""#.to_owned(),
+        )
+        .unwrap();
+
+    assert_eq!(driver.resugar(&value).to_string(), r#""hello""#);
+}
+
+#[test]
+fn import_not_found() {
+    let root = import_root("import-not-found", &[]);
+
+    assert_eq!(
+        infer(&root, r#"import "missing""#),
+        Err(vec![
+            r#"cannot find a file for the import `"missing"`"#.to_owned()
+        ]),
+    );
+}
+
+#[test]
+fn import_cycle() {
+    let root = import_root(
+        "import-cycle",
+        &[
+            ("a.pi", r#"record { b = import "b"; }"#),
+            ("b.pi", r#"record { a = import "a"; }"#),
+        ],
+    );
+
+    assert_eq!(
+        infer(&root, r#"import "a""#),
+        Err(vec![r#"cycle detected when importing `"a"`"#.to_owned()]),
+    );
+}
+
+#[test]
+fn import_cycle_different_spellings() {
+    let root = import_root(
+        "import-cycle-spellings",
+        &[
+            ("a.pi", r#"record { b = import "b"; }"#),
+            ("b.pi", r#"record { a = import "a.pi"; }"#),
+        ],
+    );
+
+    assert_eq!(
+        infer(&root, r#"import "a""#),
+        Err(vec![r#"cycle detected when importing `"a.pi"`"#.to_owned()]),
+    );
+}
+
+#[test]
+fn register_file_twice() {
+    let mut driver = Driver::new();
+
+    let src = r#"record { message = "hello" }"#;
+    driver
+        .register_file(
+            "greeting".to_owned(),
+            FileName::virtual_("greeting"),
+            src.to_owned(),
+        )
+        .unwrap();
+
+    let diagnostics = driver
+        .register_file(
+            "greeting".to_owned(),
+            FileName::virtual_("greeting"),
+            src.to_owned(),
+        )
+        .unwrap_err();
+    let messages = diagnostics
+        .into_iter()
+        .map(|d| d.message)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        messages,
+        vec![r#"the import `"greeting"` has already been registered"#.to_owned()],
+    );
+}
@@ -0,0 +1,66 @@
+use pikelet_driver::{Driver, FileName};
+
+/// Infer the type of `src`, returning the recorded types of its subterms,
+/// resugared and paired with the source that they were recorded for
+fn term_types(src: &str) -> Vec<(String, String)> {
+    let mut driver = Driver::with_prelude();
+    driver.record_term_types();
+    driver
+        .infer_file(FileName::virtual_("test"), src.to_owned())
+        .unwrap();
+
+    let file_start = driver.code_map().iter().last().unwrap().span().start();
+    let term_types = driver.take_term_types();
+    term_types
+        .iter()
+        .map(|term_type| {
+            let start = (term_type.span.start() - file_start).to_usize();
+            let end = (term_type.span.end() - file_start).to_usize();
+            let ty = driver.resugar_term_type(term_type).to_string();
+            (src[start..end].to_owned(), ty)
+        })
+        .collect()
+}
+
+#[test]
+fn records_subterms() {
+    let term_types = term_types(r#""hello" : String"#);
+
+    assert!(term_types.contains(&("String".to_owned(), "Type".to_owned())));
+    assert!(term_types.contains(&(r#""hello""#.to_owned(), "String".to_owned())));
+    assert!(term_types.contains(&(r#""hello" : String"#.to_owned(), "String".to_owned())));
+}
+
+#[test]
+fn fills_in_solved_metas() {
+    // The body of the function is elaborated before the type of its
+    // parameter is solved by the application
+    let term_types = term_types(r#"(\(x : ?) => x) "hello""#);
+
+    assert!(term_types.contains(&("x".to_owned(), "String".to_owned())));
+}
+
+#[test]
+fn keeps_definitions_folded() {
+    let term_types = term_types(
+        r#"
+        let
+            Id (A : Type) = A;
+            x : Id String = "hello";
+        in
+            x
+    "#,
+    );
+
+    assert!(term_types.contains(&(r#""hello""#.to_owned(), "Id String".to_owned())));
+}
+
+#[test]
+fn not_recorded_by_default() {
+    let mut driver = Driver::new();
+    driver
+        .infer_file(FileName::virtual_("test"), "1 : S32".to_owned())
+        .unwrap();
+
+    assert!(driver.take_term_types().is_empty());
+}
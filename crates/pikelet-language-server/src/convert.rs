@@ -0,0 +1,128 @@
+//! Conversions between the representations used by the driver and the
+//! Language Server Protocol
+//!
+//! The main thing to be careful of here is that LSP positions count
+//! characters in UTF-16 code units, whereas codespan works in bytes.
+
+use codespan::{ByteIndex, ByteSpan, CodeMap, FileMap, FileName, LineIndex, RawIndex};
+use lsp_ty::{self, Url};
+use pikelet_driver::{Diagnostic, LabelStyle, Severity};
+
+/// The name of a document, as seen by the driver
+pub fn file_name(uri: &Url) -> FileName {
+    match uri.to_file_path() {
+        Ok(path) => FileName::Real(path),
+        Err(()) => FileName::virtual_(uri.to_string()),
+    }
+}
+
+/// The URI of a file in the code map, if it has one
+///
+/// Virtual files only have a URI if they correspond to the `document` that
+/// we are currently checking.
+fn file_uri(file: &FileMap, document: &Url) -> Option<Url> {
+    if *file.name() == file_name(document) {
+        return Some(document.clone());
+    }
+    match *file.name() {
+        FileName::Real(ref path) => Url::from_file_path(path).ok(),
+        FileName::Virtual(_) => None,
+    }
+}
+
+/// Convert a byte index in a file to an LSP position
+pub fn byte_index_to_position(file: &FileMap, index: ByteIndex) -> Option<lsp_ty::Position> {
+    let line = file.find_line(index).ok()?;
+    let line_start = file.line_span(line).ok()?.start();
+    let line_prefix = file.src_slice(ByteSpan::new(line_start, index)).ok()?;
+
+    Some(lsp_ty::Position {
+        line: line.to_usize() as u64,
+        character: line_prefix.encode_utf16().count() as u64,
+    })
+}
+
+/// Convert an LSP position to a byte index in a file
+///
+/// Positions past the end of a line are clamped to the end of that line.
+pub fn position_to_byte_index(file: &FileMap, position: &lsp_ty::Position) -> Option<ByteIndex> {
+    let line_span = file.line_span(LineIndex(position.line as RawIndex)).ok()?;
+    let line_src = file.src_slice(line_span).ok()?;
+
+    let mut code_units = 0;
+    let mut offset = 0;
+    for ch in line_src.chars() {
+        if ch == '\n' || code_units >= position.character {
+            break;
+        }
+        code_units += ch.len_utf16() as u64;
+        offset += ch.len_utf8();
+    }
+
+    Some(ByteIndex(line_span.start().0 + offset as RawIndex))
+}
+
+/// Convert a span in a file to an LSP range
+pub fn byte_span_to_range(file: &FileMap, span: ByteSpan) -> Option<lsp_ty::Range> {
+    Some(lsp_ty::Range {
+        start: byte_index_to_position(file, span.start())?,
+        end: byte_index_to_position(file, span.end())?,
+    })
+}
+
+/// Convert a diagnostic that was produced while checking `document`
+///
+/// The first primary label that points into the document determines the
+/// range of the diagnostic. The remaining labels that can be located are
+/// attached as related information, which lets us point to problems in
+/// imported files.
+pub fn to_lsp_diagnostic(
+    code_map: &CodeMap,
+    document: &Url,
+    diagnostic: &Diagnostic,
+) -> lsp_ty::Diagnostic {
+    let document_name = file_name(document);
+    let locate = |span: ByteSpan| {
+        let file = code_map.find_file(span.start())?;
+        Some((file, byte_span_to_range(file, span)?))
+    };
+
+    let mut range = None;
+    let mut related_information = Vec::new();
+
+    for label in &diagnostic.labels {
+        let (file, label_range) = match locate(label.span) {
+            Some(location) => location,
+            None => continue,
+        };
+
+        if range.is_none() && label.style == LabelStyle::Primary && *file.name() == document_name {
+            range = Some(label_range);
+        } else if let Some(uri) = file_uri(file, document) {
+            related_information.push(lsp_ty::DiagnosticRelatedInformation {
+                location: lsp_ty::Location {
+                    uri,
+                    range: label_range,
+                },
+                message: label.message.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    lsp_ty::Diagnostic {
+        range: range.unwrap_or_default(),
+        severity: Some(match diagnostic.severity {
+            Severity::Bug | Severity::Error => lsp_ty::DiagnosticSeverity::Error,
+            Severity::Warning => lsp_ty::DiagnosticSeverity::Warning,
+            Severity::Note => lsp_ty::DiagnosticSeverity::Information,
+            Severity::Help => lsp_ty::DiagnosticSeverity::Hint,
+        }),
+        code: diagnostic.code.clone().map(lsp_ty::NumberOrString::String),
+        source: Some("pikelet".to_owned()),
+        message: diagnostic.message.clone(),
+        related_information: match related_information.len() {
+            0 => None,
+            _ => Some(related_information),
+        },
+    }
+}
@@ -1,5 +1,7 @@
 //! A language server for Pikelet
 
+extern crate codespan;
+#[macro_use]
 extern crate failure;
 extern crate languageserver_types as lsp_ty;
 extern crate pikelet_driver;
@@ -10,34 +12,51 @@ extern crate serde_json;
 #[macro_use]
 extern crate structopt;
 
+use codespan::{ByteIndex, ByteOffset, FileMap};
 use failure::Error;
+use lsp_ty::Url;
+use pikelet_driver::{Binding, Driver, NameIndex, OccurrenceKind, TermType};
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 
+mod convert;
 mod rpc;
 
-#[derive(Debug, StructOpt)]
+#[derive(Debug, Default, StructOpt)]
 pub struct Opts {
-    // TODO
+    /// Directories to search in when resolving imports, in order of
+    /// priority (defaults to the directory of each document)
+    #[structopt(long = "import-root", parse(from_os_str))]
+    pub import_roots: Vec<PathBuf>,
 }
 
 fn server_capabilities() -> lsp_ty::ServerCapabilities {
     lsp_ty::ServerCapabilities {
-        text_document_sync: None,
-        hover_provider: None,
-        completion_provider: None,
+        text_document_sync: Some(lsp_ty::TextDocumentSyncCapability::Kind(
+            lsp_ty::TextDocumentSyncKind::Full,
+        )),
+        hover_provider: Some(true),
+        completion_provider: Some(lsp_ty::CompletionOptions {
+            resolve_provider: Some(false),
+            trigger_characters: Some(vec![".".to_owned()]),
+        }),
         signature_help_provider: None,
-        definition_provider: None,
+        definition_provider: Some(true),
         type_definition_provider: None,
         implementation_provider: None,
-        references_provider: None,
-        document_highlight_provider: None,
+        references_provider: Some(true),
+        document_highlight_provider: Some(true),
         document_symbol_provider: None,
         workspace_symbol_provider: None,
         code_action_provider: None,
         code_lens_provider: None,
-        document_formatting_provider: None,
+        document_formatting_provider: Some(true),
         document_range_formatting_provider: None,
         document_on_type_formatting_provider: None,
-        rename_provider: None,
+        rename_provider: Some(lsp_ty::RenameProviderCapability::Simple(true)),
         color_provider: None,
         folding_range_provider: None,
         execute_command_provider: None,
@@ -46,36 +65,539 @@ fn server_capabilities() -> lsp_ty::ServerCapabilities {
 }
 
 /// Run `language-server` with the given options
-pub fn run(_opts: Opts) -> Result<(), Error> {
+pub fn run(opts: Opts) -> Result<(), Error> {
     // TODO: multi-threading
-    // FIXME: Just sketching this out - this currently doesn't work! :/
-
-    {
-        let stdin = std::io::stdin();
-        let init_content = rpc::recv_content(&mut stdin.lock())?;
-        match serde_json::from_str::<rpc::JsonRpc<rpc::LspCommand>>(&init_content) {
-            Ok(rpc::JsonRpc {
-                result: rpc::LspCommand::Initialize { .. },
-                ..
-            }) => {},
-            Ok(_) => unimplemented!(),
-            Err(_) => unimplemented!(),
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    serve(&opts, &mut stdin.lock(), &mut stdout.lock())
+}
+
+/// Handle messages from the `reader` until the client asks us to exit,
+/// sending responses and notifications to the `writer`
+pub fn serve(opts: &Opts, reader: &mut impl BufRead, writer: &mut impl Write) -> Result<(), Error> {
+    let mut server = Server::new(opts);
+
+    loop {
+        let content = match rpc::recv_content(reader) {
+            Ok(content) => content,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(format_err!("the client disconnected without exiting"));
+            },
+            // We can't tell where the next message starts, so there's no way
+            // to recover from here
+            Err(error) => return Err(error.into()),
+        };
+
+        // Once we have been asked to shut down, the only thing left for the
+        // client to do is to tell us to exit
+        if server.shutdown {
+            match serde_json::from_str::<rpc::UnknownMessage>(&content) {
+                Ok(rpc::UnknownMessage {
+                    method: Some(ref method),
+                    ..
+                }) if method == "exit" => return Ok(()),
+                Ok(rpc::UnknownMessage { id: Some(id), .. }) => {
+                    let error = rpc::ResponseError::invalid_request("the server is shutting down");
+                    send(writer, &rpc::JsonRpcError::new(id, error))?;
+                },
+                Ok(rpc::UnknownMessage { id: None, method }) => {
+                    eprintln!("skipping notification: {:?}", method);
+                },
+                Err(error) => eprintln!("error: malformed message: {}", error),
+            }
+            continue;
+        }
+
+        match serde_json::from_str::<rpc::LspCommand>(&content) {
+            Ok(rpc::LspCommand::Exit) => {
+                return Err(format_err!("exited without being asked to shut down"));
+            },
+            Ok(command) => server.handle(writer, command)?,
+            Err(error) => match serde_json::from_str::<rpc::UnknownMessage>(&content) {
+                // Requests need a response, even if we don't understand them
+                Ok(rpc::UnknownMessage {
+                    id: Some(id),
+                    method,
+                }) => {
+                    let method = method.unwrap_or_default();
+                    let error = rpc::ResponseError::method_not_found(&method);
+                    send(writer, &rpc::JsonRpcError::new(id, error))?;
+                },
+                // Notifications can be safely ignored
+                Ok(rpc::UnknownMessage { id: None, method }) => {
+                    eprintln!("skipping notification: {:?}", method);
+                },
+                Err(_) => eprintln!("error: malformed message: {}", error),
+            },
+        }
+    }
+}
+
+/// The state of the language server
+struct Server {
+    /// A driver with the prelude loaded, which is cloned in order to check
+    /// each version of a document
+    driver: Driver,
+    /// Whether imports should be resolved relative to the directory
+    /// containing each document, which is the case if no import roots were
+    /// given on the command line
+    document_import_root: bool,
+    /// The documents that the client has open
+    documents: HashMap<Url, Document>,
+    /// Whether the client has asked the server to shut down
+    shutdown: bool,
+}
+
+/// A document that the client has open
+struct Document {
+    /// The current contents of the document
+    file: FileMap,
+    /// The results of checking the current contents of the document, if they
+    /// could be found
+    analysis: Option<Analysis>,
+}
+
+/// The results of checking a version of a document
+struct Analysis {
+    /// The driver that was used to check the document, which holds the
+    /// solutions to its metavariables
+    driver: Driver,
+    /// The document, as it was loaded by the driver
+    file: Arc<FileMap>,
+    /// The types of the terms in the document, in the order that they were
+    /// elaborated
+    term_types: Vec<TermType>,
+    /// The names that are bound in the document, along with their uses
+    names: NameIndex,
+}
+
+impl Server {
+    fn new(opts: &Opts) -> Server {
+        let mut driver = Driver::with_prelude();
+        driver.record_names();
+        driver.record_term_types();
+        for root in &opts.import_roots {
+            driver.add_import_root(root.clone());
+        }
+
+        Server {
+            driver,
+            document_import_root: opts.import_roots.is_empty(),
+            documents: HashMap::new(),
+            shutdown: false,
         }
     }
 
-    {
-        let stdout = std::io::stdout();
-        let capabilities = server_capabilities();
-        let init_resp = rpc::JsonRpc::new(0, lsp_ty::InitializeResult { capabilities });
-        rpc::send_content(&mut stdout.lock(), serde_json::to_string(&init_resp)?)?;
+    fn handle(&mut self, writer: &mut impl Write, command: rpc::LspCommand) -> Result<(), Error> {
+        use rpc::LspCommand;
+
+        match command {
+            LspCommand::Initialize { id, .. } => {
+                let capabilities = server_capabilities();
+                send(
+                    writer,
+                    &rpc::JsonRpc::new(id, lsp_ty::InitializeResult { capabilities }),
+                )
+            },
+            LspCommand::Initialized | LspCommand::CancelRequest { .. } | LspCommand::Exit => Ok(()),
+            LspCommand::DidOpen { params } => {
+                let document = params.text_document;
+                self.update_document(writer, document.uri, document.text)
+            },
+            LspCommand::DidChange { params } => {
+                // We only ask for full updates, so the last change contains
+                // the entire contents of the document
+                match params.content_changes.into_iter().last() {
+                    Some(change) => {
+                        self.update_document(writer, params.text_document.uri, change.text)
+                    },
+                    None => Ok(()),
+                }
+            },
+            LspCommand::DidClose { params } => {
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                // Clear the diagnostics, now that the document is no longer open
+                publish_diagnostics(
+                    writer,
+                    lsp_ty::PublishDiagnosticsParams::new(uri, Vec::new()),
+                )
+            },
+            LspCommand::Hover { id, params } => {
+                let hover = self.hover(&params.text_document.uri, &params.position);
+                send(writer, &rpc::JsonRpc::new(id, hover))
+            },
+            LspCommand::Definition { id, params } => {
+                let locations = self.definition(&params.text_document.uri, &params.position);
+                send(writer, &rpc::JsonRpc::new(id, locations))
+            },
+            LspCommand::References { id, params } => {
+                let uri = &params.text_document.uri;
+                let include_declaration = params.context.include_declaration;
+                let locations = self.references(uri, &params.position, include_declaration);
+                send(writer, &rpc::JsonRpc::new(id, locations))
+            },
+            LspCommand::DocumentHighlight { id, params } => {
+                let uri = &params.text_document.uri;
+                let highlights = self.document_highlights(uri, &params.position);
+                send(writer, &rpc::JsonRpc::new(id, highlights))
+            },
+            LspCommand::Rename { id, params } => {
+                if !pikelet_driver::is_identifier(&params.new_name) {
+                    let message = format!("`{}` is not a valid name", params.new_name);
+                    let error = rpc::ResponseError::invalid_params(message);
+                    return send(writer, &rpc::JsonRpcError::new(id, error));
+                }
+
+                let uri = &params.text_document.uri;
+                let edit = self.rename(uri, &params.position, &params.new_name);
+                send(writer, &rpc::JsonRpc::new(id, edit))
+            },
+            LspCommand::Formatting { id, params } => {
+                let edits = self.format_document(&params.text_document.uri);
+                send(writer, &rpc::JsonRpc::new(id, edits))
+            },
+            LspCommand::Completion { id, params } => {
+                let uri = &params.text_document.uri;
+                let items = self.completion(uri, &params.position);
+                send(writer, &rpc::JsonRpc::new(id, items))
+            },
+            LspCommand::CompletionItemResolve { id, .. } => {
+                let error = rpc::ResponseError::method_not_found("completionItem/resolve");
+                send(writer, &rpc::JsonRpcError::new(id, error))
+            },
+            LspCommand::Shutdown { id } => {
+                self.shutdown = true;
+                send(writer, &rpc::JsonRpc::new(id, ()))
+            },
+        }
+    }
+
+    /// Record the new contents of a document, and publish its diagnostics
+    fn update_document(
+        &mut self,
+        writer: &mut impl Write,
+        uri: Url,
+        src: String,
+    ) -> Result<(), Error> {
+        let file = FileMap::new(convert::file_name(&uri), src.clone());
+        let (analysis, diagnostics) = self.check_document(&uri, src);
+
+        self.documents
+            .insert(uri.clone(), Document { file, analysis });
+
+        publish_diagnostics(
+            writer,
+            lsp_ty::PublishDiagnosticsParams::new(uri, diagnostics),
+        )
+    }
+
+    /// Type check a document, returning the diagnostics that were produced
+    ///
+    /// Unless some import roots were configured, imports are resolved relative
+    /// to the directory containing the document.
+    fn check_document(
+        &self,
+        uri: &Url,
+        src: String,
+    ) -> (Option<Analysis>, Vec<lsp_ty::Diagnostic>) {
+        let mut driver = self.driver.clone();
+        if self.document_import_root {
+            if let Ok(path) = uri.to_file_path() {
+                if let Some(dir) = path.parent() {
+                    driver.add_import_root(dir);
+                }
+            }
+        }
+
+        let name = convert::file_name(uri);
+        let mut diagnostics = match driver.infer_file(name.clone(), src) {
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics,
+        };
+        diagnostics.extend(driver.take_warnings());
+
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| convert::to_lsp_diagnostic(driver.code_map(), uri, diagnostic))
+            .collect();
+        // The document is loaded before any of the files that it imports
+        let file = driver.code_map().iter().find(|file| *file.name() == name);
+        let analysis = file.cloned().map(|file| Analysis {
+            names: driver.take_names(),
+            term_types: driver.take_term_types(),
+            driver,
+            file,
+        });
+
+        (analysis, diagnostics)
+    }
+
+    /// Find the binding of the name under the cursor, along with the file
+    /// that it was found in
+    fn find_binding<'a>(
+        &'a self,
+        uri: &Url,
+        position: &lsp_ty::Position,
+    ) -> Option<(&'a FileMap, &'a Binding)> {
+        let analysis = self.documents.get(uri)?.analysis.as_ref()?;
+        let file = &analysis.file;
+        let index = convert::position_to_byte_index(file, position)?;
+        let binding = analysis.names.binding_at(index)?;
+
+        Some((file, binding))
+    }
+
+    /// Find the places where the name under the cursor is bound
+    fn definition(&self, uri: &Url, position: &lsp_ty::Position) -> Option<Vec<lsp_ty::Location>> {
+        let (file, binding) = self.find_binding(uri, position)?;
+        let ranges = binding
+            .binders
+            .iter()
+            .filter_map(|binder| convert::byte_span_to_range(file, binder.span));
+
+        Some(
+            ranges
+                .map(|range| lsp_ty::Location::new(uri.clone(), range))
+                .collect(),
+        )
     }
 
-    // loop {
-    //     match rpc::recv_content(&mut stdin) {
-    //         Ok(_) => unimplemented!(),
-    //         Err(error) => eprintln!("error: {}", error),
-    //     }
-    // }
+    /// Find the occurrences of the name under the cursor
+    fn references(
+        &self,
+        uri: &Url,
+        position: &lsp_ty::Position,
+        include_declaration: bool,
+    ) -> Option<Vec<lsp_ty::Location>> {
+        let (file, binding) = self.find_binding(uri, position)?;
+        let occurrences = if include_declaration {
+            binding.occurrences().collect::<Vec<_>>()
+        } else {
+            binding.uses.iter().collect()
+        };
+        let ranges = occurrences
+            .into_iter()
+            .filter_map(|occurrence| convert::byte_span_to_range(file, occurrence.span));
 
+        Some(
+            ranges
+                .map(|range| lsp_ty::Location::new(uri.clone(), range))
+                .collect(),
+        )
+    }
+
+    /// Highlight the occurrences of the name under the cursor, distinguishing
+    /// the places where it is bound from the places where it is used
+    fn document_highlights(
+        &self,
+        uri: &Url,
+        position: &lsp_ty::Position,
+    ) -> Option<Vec<lsp_ty::DocumentHighlight>> {
+        use lsp_ty::DocumentHighlightKind;
+
+        let (file, binding) = self.find_binding(uri, position)?;
+        let binders = binding
+            .binders
+            .iter()
+            .map(|binder| (binder, DocumentHighlightKind::Write));
+        let uses = binding
+            .uses
+            .iter()
+            .map(|use_| (use_, DocumentHighlightKind::Read));
+        let highlights = binders.chain(uses).filter_map(|(occurrence, kind)| {
+            Some(lsp_ty::DocumentHighlight {
+                range: convert::byte_span_to_range(file, occurrence.span)?,
+                kind: Some(kind),
+            })
+        });
+
+        Some(highlights.collect())
+    }
+
+    /// Rename every occurrence of the name under the cursor
+    ///
+    /// Labels that double as binders have to keep their original name, so we
+    /// give them an explicit binder, or turn them into an explicit field if
+    /// they were punned.
+    fn rename(
+        &self,
+        uri: &Url,
+        position: &lsp_ty::Position,
+        new_name: &str,
+    ) -> Option<lsp_ty::WorkspaceEdit> {
+        let (file, binding) = self.find_binding(uri, position)?;
+        let edits = binding.occurrences().filter_map(|occurrence| {
+            let range = convert::byte_span_to_range(file, occurrence.span)?;
+            let new_text = match occurrence.kind {
+                OccurrenceKind::Name => new_name.to_owned(),
+                OccurrenceKind::Label => format!("{} as {}", binding.name, new_name),
+                OccurrenceKind::PunnedLabel => format!("{} = {}", binding.name, new_name),
+            };
+            Some(lsp_ty::TextEdit::new(range, new_text))
+        });
+
+        let mut changes = HashMap::new();
+        changes.insert(uri.clone(), edits.collect());
+        Some(lsp_ty::WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+        })
+    }
+
+    /// Format the current contents of a document, replacing all of it
+    ///
+    /// Documents that fail to parse are left alone.
+    fn format_document(&self, uri: &Url) -> Option<Vec<lsp_ty::TextEdit>> {
+        let file = &self.documents.get(uri)?.file;
+        let src = file.src().to_owned();
+        let formatted = Driver::new()
+            .format_file(convert::file_name(uri), src.clone())
+            .ok()?;
+
+        if formatted == src {
+            return Some(Vec::new());
+        }
+
+        let range = convert::byte_span_to_range(file, file.span())?;
+        Some(vec![lsp_ty::TextEdit::new(range, formatted)])
+    }
+
+    /// Show the type of the innermost term under the cursor, along with the
+    /// doc comments of the name under the cursor, if it has any
+    fn hover(&self, uri: &Url, position: &lsp_ty::Position) -> Option<lsp_ty::Hover> {
+        let analysis = self.documents.get(uri)?.analysis.as_ref()?;
+        let file = &analysis.file;
+        let index = convert::position_to_byte_index(file, position)?;
+
+        // Terms are recorded after their subterms, so the first of the
+        // innermost spans is the one whose type was inferred, rather than
+        // the one that was checked against an annotation
+        let term_type = analysis
+            .term_types
+            .iter()
+            .filter(|term_type| {
+                let span = term_type.span;
+                span.start() <= index && index < span.end()
+            })
+            .min_by_key(|term_type| term_type.span.end() - term_type.span.start())?;
+
+        let ty = analysis.driver.resugar_term_type(term_type);
+        let mut value = format!("```pikelet\n{}\n```", ty);
+        if let Some(binding) = analysis.names.binding_at(index) {
+            if !binding.doc.is_empty() {
+                value.push_str("\n\n");
+                value.push_str(&binding.doc);
+            }
+        }
+
+        Some(lsp_ty::Hover {
+            contents: lsp_ty::HoverContents::Markup(lsp_ty::MarkupContent {
+                kind: lsp_ty::MarkupKind::Markdown,
+                value,
+            }),
+            range: convert::byte_span_to_range(file, term_type.span),
+        })
+    }
+
+    /// Offer completions at the cursor
+    ///
+    /// After a `.` we offer the fields of the record before it, and otherwise
+    /// we offer the names that are in scope.
+    fn completion(
+        &self,
+        uri: &Url,
+        position: &lsp_ty::Position,
+    ) -> Option<Vec<lsp_ty::CompletionItem>> {
+        let document = self.documents.get(uri)?;
+        let analysis = document.analysis.as_ref()?;
+        let index = convert::position_to_byte_index(&document.file, position)?;
+        let offset = (index - document.file.span().start()).to_usize();
+
+        // Skip back over the part of the name that has been typed so far
+        let src = document.file.src();
+        let name_start = src[..offset].trim_end_matches(is_name_char).len();
+
+        let mut items = if src[..name_start].ends_with('.') {
+            field_completions(analysis, src, name_start - 1)?
+        } else {
+            name_completions(analysis, offset)
+        };
+        items.sort_by(|item1, item2| item1.label.cmp(&item2.label));
+
+        Some(items)
+    }
+}
+
+/// Returns `true` if the character can be part of a name
+///
+/// This is slightly more permissive than the lexer, which is fine for
+/// finding the start of a partially typed name.
+fn is_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '-'
+}
+
+/// The fields of the record whose term ends at the given offset
+fn field_completions(
+    analysis: &Analysis,
+    src: &str,
+    end: usize,
+) -> Option<Vec<lsp_ty::CompletionItem>> {
+    let file_span = analysis.file.span();
+    let offset = |index: ByteIndex| (index - file_span.start()).to_usize();
+
+    // Find the innermost term that ends just before the `.`, making sure that
+    // it hasn't changed since the document was last checked
+    let term_type = analysis
+        .term_types
+        .iter()
+        .filter(|term_type| file_span.contains(term_type.span))
+        .filter(|term_type| {
+            let range = offset(term_type.span.start())..offset(term_type.span.end());
+            range.end == end && analysis.file.src().get(range.clone()) == src.get(range)
+        })
+        .min_by_key(|term_type| term_type.span.end() - term_type.span.start())?;
+
+    let fields = analysis.driver.record_fields(term_type)?;
+    let items = fields
+        .into_iter()
+        .map(|(label, ty)| lsp_ty::CompletionItem {
+            kind: Some(lsp_ty::CompletionItemKind::Field),
+            ..lsp_ty::CompletionItem::new_simple(label, ty.to_string())
+        });
+
+    Some(items.collect())
+}
+
+/// The names that are in scope at the given offset
+fn name_completions(analysis: &Analysis, offset: usize) -> Vec<lsp_ty::CompletionItem> {
+    let file_span = analysis.file.span();
+    let offset = cmp::min(offset, analysis.file.src().len());
+    let index = file_span.start() + ByteOffset::from(offset as i64);
+    let names = analysis.driver.names_in_scope(&analysis.names, index);
+
+    names
+        .into_iter()
+        .map(|(name, ty)| lsp_ty::CompletionItem {
+            label: name,
+            kind: Some(lsp_ty::CompletionItemKind::Variable),
+            detail: ty.map(|ty| ty.to_string()),
+            ..lsp_ty::CompletionItem::default()
+        })
+        .collect()
+}
+
+/// Send a message to the client
+fn send(writer: &mut impl Write, message: &impl serde::Serialize) -> Result<(), Error> {
+    rpc::send_content(writer, serde_json::to_string(message)?)?;
     Ok(())
 }
+
+fn publish_diagnostics(
+    writer: &mut impl Write,
+    params: lsp_ty::PublishDiagnosticsParams,
+) -> Result<(), Error> {
+    let notification = rpc::JsonRpcNotification::new("textDocument/publishDiagnostics", params);
+    send(writer, &notification)
+}
@@ -27,7 +27,6 @@ use lsp_ty;
 use std::io::{self, BufRead, Write};
 
 /// Sends an RPC call containing the given content
-#[allow(dead_code)]
 pub fn send_content(writer: &mut impl Write, content: String) -> Result<(), io::Error> {
     let content_length = content.len();
     let content_type = "application/vscode-jsonrpc; charset=utf-8";
@@ -52,7 +51,6 @@ pub fn send_content(writer: &mut impl Write, content: String) -> Result<(), io::
 }
 
 /// Receives an RPC call from the given reader, returning the content as a string
-#[allow(dead_code)]
 pub fn recv_content(reader: &mut impl BufRead) -> Result<String, io::Error> {
     // Header part
     //
@@ -70,7 +68,12 @@ pub fn recv_content(reader: &mut impl BufRead) -> Result<String, io::Error> {
     // Loop through headers, collecting the relevant information
     let mut header_buffer = String::new();
     loop {
-        reader.read_line(&mut header_buffer)?;
+        if reader.read_line(&mut header_buffer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Reached the end of the input while reading headers",
+            ));
+        }
         {
             let mut splits = header_buffer.splitn(2, ": ");
             match (splits.next(), splits.next()) {
@@ -130,6 +133,7 @@ pub fn recv_content(reader: &mut impl BufRead) -> Result<String, io::Error> {
     }
 }
 
+/// A response to a request that was sent from the client
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonRpc<T> {
     pub jsonrpc: String,
@@ -138,7 +142,6 @@ pub struct JsonRpc<T> {
 }
 
 impl<T> JsonRpc<T> {
-    #[allow(dead_code)]
     pub fn new(id: usize, result: T) -> JsonRpc<T> {
         JsonRpc {
             jsonrpc: "2.0".into(),
@@ -148,6 +151,87 @@ impl<T> JsonRpc<T> {
     }
 }
 
+/// A response to a request that could not be handled
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub jsonrpc: String,
+    pub id: usize,
+    pub error: ResponseError,
+}
+
+impl JsonRpcError {
+    pub fn new(id: usize, error: ResponseError) -> JsonRpcError {
+        JsonRpcError {
+            jsonrpc: "2.0".into(),
+            id,
+            error,
+        }
+    }
+}
+
+/// The error returned in a `JsonRpcError`
+///
+/// https://microsoft.github.io/language-server-protocol/specification#response-message
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_REQUEST: i64 = -32600;
+
+    pub fn invalid_request(message: impl Into<String>) -> ResponseError {
+        ResponseError {
+            code: ResponseError::INVALID_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> ResponseError {
+        ResponseError {
+            code: ResponseError::INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> ResponseError {
+        ResponseError {
+            code: ResponseError::METHOD_NOT_FOUND,
+            message: format!("unsupported method `{}`", method),
+        }
+    }
+}
+
+/// A notification sent from the server to the client, which does not expect
+/// a response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonRpcNotification<T> {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: T,
+}
+
+impl<T> JsonRpcNotification<T> {
+    pub fn new(method: &str, params: T) -> JsonRpcNotification<T> {
+        JsonRpcNotification {
+            jsonrpc: "2.0".into(),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// The parts of a message that we can make sense of, even if we don't
+/// support its method
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnknownMessage {
+    pub id: Option<usize>,
+    pub method: Option<String>,
+}
+
 /// A Command that was sent from the client to the server
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "method")]
@@ -167,11 +251,40 @@ pub enum LspCommand {
     DidChange {
         params: lsp_ty::DidChangeTextDocumentParams,
     },
+    #[serde(rename = "textDocument/didClose")]
+    DidClose {
+        params: lsp_ty::DidCloseTextDocumentParams,
+    },
     #[serde(rename = "textDocument/hover")]
     Hover {
         id: usize,
         params: lsp_ty::TextDocumentPositionParams,
     },
+    #[serde(rename = "textDocument/definition")]
+    Definition {
+        id: usize,
+        params: lsp_ty::TextDocumentPositionParams,
+    },
+    #[serde(rename = "textDocument/references")]
+    References {
+        id: usize,
+        params: lsp_ty::ReferenceParams,
+    },
+    #[serde(rename = "textDocument/documentHighlight")]
+    DocumentHighlight {
+        id: usize,
+        params: lsp_ty::TextDocumentPositionParams,
+    },
+    #[serde(rename = "textDocument/rename")]
+    Rename {
+        id: usize,
+        params: lsp_ty::RenameParams,
+    },
+    #[serde(rename = "textDocument/formatting")]
+    Formatting {
+        id: usize,
+        params: lsp_ty::DocumentFormattingParams,
+    },
     #[serde(rename = "textDocument/completion")]
     Completion {
         id: usize,
@@ -184,6 +297,10 @@ pub enum LspCommand {
         id: usize,
         params: lsp_ty::CompletionItem,
     },
+    #[serde(rename = "shutdown")]
+    Shutdown { id: usize },
+    #[serde(rename = "exit")]
+    Exit,
 }
 
 #[cfg(test)]
@@ -223,6 +340,14 @@ mod tests {
             assert_eq!(recv_content(&mut cursor).unwrap(), "hello, world!");
         }
 
+        #[test]
+        fn invalid_eof() {
+            let message = "";
+            let mut cursor = io::Cursor::new(message);
+            let error = recv_content(&mut cursor).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
         #[test]
         fn valid_unknown_header() {
             let message = "Content-Length: 13\r\nX-Foo: silly\r\n\r\nhello, world!";
@@ -0,0 +1,539 @@
+extern crate pikelet_driver;
+extern crate pikelet_language_server;
+#[macro_use]
+extern crate serde_json;
+
+use pikelet_language_server::Opts;
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, Cursor, Read};
+
+/// Frame the messages as they would be sent by a client
+fn client_messages(messages: &[Value]) -> Cursor<Vec<u8>> {
+    let mut input = Vec::new();
+    for message in messages {
+        let content = message.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", content.len(), content).bytes());
+    }
+    Cursor::new(input)
+}
+
+/// Split the output of the server back into messages
+fn server_messages(output: Vec<u8>) -> Vec<Value> {
+    let mut reader = Cursor::new(output);
+    let mut messages = Vec::new();
+
+    loop {
+        let mut content_len = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap() == 0 {
+                return messages;
+            }
+            if header == "\r\n" {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                content_len = Some(value.trim().parse().unwrap());
+            }
+        }
+
+        let mut content = vec![0; content_len.unwrap()];
+        reader.read_exact(&mut content).unwrap();
+        messages.push(serde_json::from_slice(&content).unwrap());
+    }
+}
+
+/// Run a session with the server, returning the messages that it sent back
+fn session(messages: &[Value]) -> Vec<Value> {
+    session_with_opts(&Opts::default(), messages)
+}
+
+/// Run a session with a server that was started with the given options
+fn session_with_opts(opts: &Opts, messages: &[Value]) -> Vec<Value> {
+    let mut input = client_messages(messages);
+    let mut output = Vec::new();
+    pikelet_language_server::serve(opts, &mut input, &mut output).unwrap();
+    server_messages(output)
+}
+
+fn initialize() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": { "processId": null, "rootUri": null, "capabilities": {} },
+    })
+}
+
+fn shutdown_exit() -> Vec<Value> {
+    vec![
+        json!({ "jsonrpc": "2.0", "id": 99, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]
+}
+
+fn did_open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {
+            "textDocument": {
+                "uri": "untitled:test.pi",
+                "languageId": "pikelet",
+                "version": 1,
+                "text": text,
+            },
+        },
+    })
+}
+
+fn did_change(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": { "uri": "untitled:test.pi", "version": 2 },
+            "contentChanges": [{ "text": text }],
+        },
+    })
+}
+
+fn published_diagnostics(messages: &[Value]) -> Vec<&Value> {
+    messages
+        .iter()
+        .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+        .map(|message| &message["params"]["diagnostics"])
+        .collect()
+}
+
+#[test]
+fn initialize_shutdown() {
+    let mut messages = vec![
+        initialize(),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], 0);
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["textDocumentSync"],
+        1
+    );
+    assert_eq!(
+        responses[1],
+        json!({ "jsonrpc": "2.0", "id": 99, "result": null })
+    );
+}
+
+#[test]
+fn exit_without_shutdown() {
+    let mut input = client_messages(&[initialize(), json!({ "jsonrpc": "2.0", "method": "exit" })]);
+    let mut output = Vec::new();
+
+    assert!(pikelet_language_server::serve(&Opts::default(), &mut input, &mut output).is_err());
+}
+
+#[test]
+fn request_after_shutdown() {
+    let messages = [
+        initialize(),
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/codeLens", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ];
+    let responses = session(&messages);
+
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[2]["id"], 2);
+    assert_eq!(responses[2]["error"]["code"], -32600);
+}
+
+#[test]
+fn unknown_request() {
+    let mut messages = vec![
+        initialize(),
+        json!({ "jsonrpc": "2.0", "method": "workspace/didChangeConfiguration", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/codeLens", "params": {} }),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[1]["id"], 1);
+    assert_eq!(responses[1]["error"]["code"], -32601);
+}
+
+#[test]
+fn diagnostics_on_open_and_change() {
+    let mut messages = vec![
+        initialize(),
+        did_open("record {\n    x = \"hello\" : S32;\n}"),
+        did_change("record {\n    x = 1 : S32;\n}"),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didClose",
+            "params": { "textDocument": { "uri": "untitled:test.pi" } },
+        }),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let diagnostics = published_diagnostics(&responses);
+
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+        *diagnostics[0],
+        json!([{
+            "range": {
+                "start": { "line": 1, "character": 8 },
+                "end": { "line": 1, "character": 15 },
+            },
+            "severity": 1,
+            "code": "literal-mismatch",
+            "source": "pikelet",
+            "message": "found a string literal, but expected a type `S32`",
+        }]),
+    );
+    assert_eq!(*diagnostics[1], json!([]));
+    assert_eq!(*diagnostics[2], json!([]));
+}
+
+#[test]
+fn diagnostics_utf16_columns() {
+    let mut messages = vec![
+        initialize(),
+        did_open("record { x = \"λ😀\"; y = true 1; }"),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let diagnostics = published_diagnostics(&responses);
+
+    let range = &diagnostics[0][0]["range"];
+    // `😀` is encoded as two UTF-16 code units
+    assert_eq!(range["start"], json!({ "line": 0, "character": 24 }));
+    assert_eq!(range["end"], json!({ "line": 0, "character": 28 }));
+    assert_eq!(
+        diagnostics[0][0]["relatedInformation"][0]["location"]["range"]["start"],
+        json!({ "line": 0, "character": 29 }),
+    );
+}
+
+#[test]
+fn diagnostics_import_roots() {
+    let root = std::env::temp_dir().join(format!("pikelet-ls-imports-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("greeting.pi"), r#"record { message = "hello" }"#).unwrap();
+
+    let opts = Opts {
+        import_roots: vec![root],
+    };
+    let mut messages = vec![
+        initialize(),
+        did_open(r#"(import "greeting.pi").message : String"#),
+        did_change(r#"(import "missing.pi").message : String"#),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session_with_opts(&opts, &messages);
+    let diagnostics = published_diagnostics(&responses);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(*diagnostics[0], json!([]));
+    assert_eq!(diagnostics[1].as_array().unwrap().len(), 1);
+}
+
+fn hover(id: usize, line: usize, character: usize) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "textDocument/hover",
+        "params": {
+            "textDocument": { "uri": "untitled:test.pi" },
+            "position": { "line": line, "character": character },
+        },
+    })
+}
+
+#[test]
+fn hover_types() {
+    let mut messages = vec![
+        initialize(),
+        hover(1, 0, 0),
+        did_open("let\n    x = \"😀\" : String;\nin\n    record { y = x }"),
+        hover(2, 1, 17),
+        hover(3, 3, 17),
+        hover(4, 1, 9),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let result = |id| {
+        let response = responses.iter().find(|message| message["id"] == id);
+        response.unwrap()["result"].clone()
+    };
+
+    assert_eq!(result(0)["capabilities"]["hoverProvider"], json!(true));
+    assert_eq!(result(1), Value::Null);
+    assert_eq!(
+        result(2),
+        json!({
+            "contents": { "kind": "markdown", "value": "```pikelet\nType\n```" },
+            "range": {
+                "start": { "line": 1, "character": 15 },
+                "end": { "line": 1, "character": 21 },
+            },
+        }),
+    );
+    assert_eq!(
+        result(3),
+        json!({
+            "contents": { "kind": "markdown", "value": "```pikelet\nString\n```" },
+            "range": {
+                "start": { "line": 3, "character": 17 },
+                "end": { "line": 3, "character": 18 },
+            },
+        }),
+    );
+    // `😀` is encoded as two UTF-16 code units
+    assert_eq!(
+        result(4)["range"],
+        json!({
+            "start": { "line": 1, "character": 8 },
+            "end": { "line": 1, "character": 12 },
+        }),
+    );
+}
+
+#[test]
+fn hover_docs() {
+    let mut messages = vec![
+        initialize(),
+        did_open("let\n    ||| The answer\n    x = 42 : S32;\nin\n    record { y = x }"),
+        hover(1, 4, 17),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let response = responses.iter().find(|message| message["id"] == 1);
+
+    assert_eq!(
+        response.unwrap()["result"]["contents"]["value"],
+        json!("```pikelet\nS32\n```\n\nThe answer"),
+    );
+}
+
+fn position_request(id: usize, method: &str, line: usize, character: usize, extra: Value) -> Value {
+    let mut params = json!({
+        "textDocument": { "uri": "untitled:test.pi" },
+        "position": { "line": line, "character": character },
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        params[key] = value.clone();
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn range(line: usize, start: usize, end: usize) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+#[test]
+fn names() {
+    let mut messages = vec![
+        initialize(),
+        did_open("let\n    id : Type -> Type;\n    id a = a;\nin\n    record { id; t = id Type }"),
+        position_request(1, "textDocument/definition", 4, 22, json!({})),
+        position_request(2, "textDocument/definition", 1, 9, json!({})),
+        position_request(
+            3,
+            "textDocument/references",
+            2,
+            5,
+            json!({ "context": { "includeDeclaration": false } }),
+        ),
+        position_request(4, "textDocument/documentHighlight", 2, 11, json!({})),
+        position_request(
+            5,
+            "textDocument/rename",
+            1,
+            4,
+            json!({ "newName": "ident" }),
+        ),
+        position_request(6, "textDocument/rename", 1, 4, json!({ "newName": "in" })),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let response = |id| {
+        responses
+            .iter()
+            .find(|message| message["id"] == id)
+            .unwrap()
+    };
+    let location = |range| json!({ "uri": "untitled:test.pi", "range": range });
+
+    let capabilities = &response(0)["result"]["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], json!(true));
+    assert_eq!(capabilities["referencesProvider"], json!(true));
+    assert_eq!(capabilities["documentHighlightProvider"], json!(true));
+    assert_eq!(capabilities["renameProvider"], json!(true));
+
+    assert_eq!(
+        response(1)["result"],
+        json!([location(range(1, 4, 6)), location(range(2, 4, 6))]),
+    );
+    // Names from the prelude have no binding in the document
+    assert_eq!(response(2)["result"], Value::Null);
+    assert_eq!(
+        response(3)["result"],
+        json!([location(range(4, 13, 15)), location(range(4, 21, 23))]),
+    );
+    assert_eq!(
+        response(4)["result"],
+        json!([
+            { "range": range(2, 7, 8), "kind": 3 },
+            { "range": range(2, 11, 12), "kind": 2 },
+        ]),
+    );
+    assert_eq!(
+        response(5)["result"],
+        json!({
+            "changes": {
+                "untitled:test.pi": [
+                    { "range": range(1, 4, 6), "newText": "ident" },
+                    { "range": range(2, 4, 6), "newText": "ident" },
+                    { "range": range(4, 13, 15), "newText": "id = ident" },
+                    { "range": range(4, 21, 23), "newText": "ident" },
+                ],
+            },
+        }),
+    );
+    assert_eq!(response(6)["error"]["code"], -32602);
+}
+
+#[test]
+fn completion() {
+    let mut messages = vec![
+        initialize(),
+        did_open("let\n    prim = import \"prim\";\nin\n    prim.bool"),
+        // The document can't be parsed while the label is being typed
+        did_change("let\n    prim = import \"prim\";\nin\n    prim.bool.e"),
+        position_request(1, "textDocument/completion", 3, 15, json!({})),
+        did_change("let\n    prim = import \"prim\";\nin\n    \\(x : S32) => x"),
+        position_request(2, "textDocument/completion", 3, 19, json!({})),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let response = |id| {
+        responses
+            .iter()
+            .find(|message| message["id"] == id)
+            .unwrap()
+    };
+
+    let completion_provider = &response(0)["result"]["capabilities"]["completionProvider"];
+    assert_eq!(completion_provider["triggerCharacters"], json!(["."]));
+
+    let fields = response(1)["result"].as_array().unwrap();
+    let labels = fields.iter().map(|item| &item["label"]).collect::<Vec<_>>();
+    assert_eq!(labels, ["eq", "ge", "gt", "le", "lt", "ne"]);
+    assert_eq!(
+        fields[0],
+        json!({ "label": "eq", "kind": 5, "detail": "Bool -> Bool -> Bool" }),
+    );
+
+    let names = response(2)["result"].as_array().unwrap();
+    let name = |label| names.iter().find(|item| item["label"] == label).cloned();
+    assert_eq!(name("x"), Some(json!({ "label": "x", "kind": 6 })));
+    assert_eq!(name("prim"), Some(json!({ "label": "prim", "kind": 6 })));
+    assert_eq!(
+        name("S32"),
+        Some(json!({ "label": "S32", "kind": 6, "detail": "Type" })),
+    );
+}
+
+#[test]
+fn completion_after_breaking_edit() {
+    let mut messages = vec![
+        initialize(),
+        did_open("let\n    x = 1 : S32;\nin\n    x"),
+        did_change("\""),
+        position_request(1, "textDocument/completion", 0, 1, json!({})),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let response = responses.iter().find(|message| message["id"] == 1);
+
+    // Names from the previous version of the document are not offered
+    let names = response.unwrap()["result"].as_array().unwrap();
+    assert!(names.iter().all(|item| item["label"] != "x"));
+}
+
+#[test]
+fn change_without_open() {
+    let mut messages = vec![
+        initialize(),
+        did_change("record { x = \"a\" }"),
+        hover(1, 0, 13),
+        position_request(2, "textDocument/definition", 0, 9, json!({})),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let result = |id| {
+        let response = responses.iter().find(|message| message["id"] == id);
+        response.unwrap()["result"].clone()
+    };
+
+    assert_eq!(published_diagnostics(&responses), vec![&json!([])]);
+    assert_eq!(result(1)["contents"]["value"], "```pikelet\nString\n```");
+    assert_eq!(result(2), Value::Null);
+}
+
+fn formatting(id: usize) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "textDocument/formatting",
+        "params": {
+            "textDocument": { "uri": "untitled:test.pi" },
+            "options": { "tabSize": 4, "insertSpaces": true },
+        },
+    })
+}
+
+#[test]
+fn formatting_documents() {
+    let mut messages = vec![
+        initialize(),
+        did_open("record {x=1;\n-- the answer\n}"),
+        formatting(1),
+        did_change("record {\n    -- the answer\n    x = 1;\n}\n"),
+        formatting(2),
+        did_change("record { x = }"),
+        formatting(3),
+    ];
+    messages.extend(shutdown_exit());
+    let responses = session(&messages);
+    let result = |id| {
+        let response = responses.iter().find(|message| message["id"] == id);
+        response.unwrap()["result"].clone()
+    };
+
+    assert_eq!(
+        result(0)["capabilities"]["documentFormattingProvider"],
+        json!(true),
+    );
+    assert_eq!(
+        result(1),
+        json!([{
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 2, "character": 1 },
+            },
+            "newText": "record {\n    x = 1;\n    -- the answer\n}\n",
+        }]),
+    );
+    assert_eq!(result(2), json!([]));
+    assert_eq!(result(3), Value::Null);
+}
@@ -40,6 +40,11 @@ pub struct Opts {
     )]
     pub history_file: PathBuf,
 
+    /// Directories to search in when resolving imports, in order of
+    /// priority (defaults to the current directory)
+    #[structopt(long = "import-root", parse(from_os_str))]
+    pub import_roots: Vec<PathBuf>,
+
     /// Files to preload into the REPL
     #[structopt(name = "FILE", parse(from_os_str))]
     pub files: Vec<PathBuf>,
@@ -75,6 +80,7 @@ fn print_help_text() {
         "<term>                         normalize a term",
         ":? :h :help                    display this help text",
         ":core         <term>           print the core representation of a term",
+        ":g :goals     <term>           show the goals of the holes in a term",
         ":let          <name> = <term>  add a named term to the REPL context",
         ":q :quit                       quit the repl",
         ":t :type      <term>           infer the type of a term",
@@ -94,6 +100,7 @@ pub fn run(opts: Opts) -> Result<(), Error> {
     let interface = Interface::new("repl")?;
     let writer = StandardStream::stderr(opts.color.into());
     let mut driver = Driver::with_prelude();
+    driver.add_import_roots(opts.import_roots.clone());
 
     interface.set_prompt(&opts.prompt)?;
     interface.set_report_signal(Signal::Interrupt, true);
@@ -117,7 +124,9 @@ pub fn run(opts: Opts) -> Result<(), Error> {
         let mut src = String::new();
         file.read_to_string(&mut src)?;
 
-        if let Err(diagnostics) = driver.register_file(internal_path, external_path, src) {
+        let result = driver.register_file(internal_path, external_path, src);
+        driver.emit(writer.lock(), &driver.take_warnings()).unwrap();
+        if let Err(diagnostics) = result {
             driver.emit(writer.lock(), &diagnostics).unwrap();
             return Err(failure::format_err!("encountered an error!"));
         }
@@ -138,7 +147,10 @@ pub fn run(opts: Opts) -> Result<(), Error> {
                     },
                 };
 
-                match eval_print(&mut driver, repl_command) {
+                let result = eval_print(&mut driver, &writer, repl_command);
+                driver.emit(writer.lock(), &driver.take_warnings()).unwrap();
+
+                match result {
                     Ok(ControlFlow::Continue) => {},
                     Ok(ControlFlow::Break) => break,
                     Err(diagnostics) => driver.emit(writer.lock(), &diagnostics).unwrap(),
@@ -180,6 +192,13 @@ pub enum ReplCommand {
     /// :core <term>
     /// ```
     Core(String),
+    /// Show the goals of the holes in a term, along with their local contexts
+    ///
+    /// ```text
+    /// :g <term>
+    /// :goals <term>
+    /// ```
+    Goals(String),
     /// Print some help about using the REPL
     ///
     /// ```text
@@ -248,6 +267,12 @@ impl FromStr for ReplCommand {
                         .with(anys1())
                         .map(ReplCommand::Core),
                 ),
+                attempt(
+                    choice((attempt(string("goals")), string("g")))
+                        .with(spaces1())
+                        .with(anys1())
+                        .map(ReplCommand::Goals),
+                ),
                 attempt(
                     choice((attempt(string("type")), string("t")))
                         .with(spaces1())
@@ -282,6 +307,7 @@ impl FromStr for ReplCommand {
 
 fn eval_print(
     driver: &mut Driver,
+    writer: &StandardStream,
     repl_command: ReplCommand,
 ) -> Result<ControlFlow, Vec<Diagnostic>> {
     use codespan::ByteSpan;
@@ -305,26 +331,44 @@ fn eval_print(
 
             let ann_term = Term::Ann(
                 Box::new(driver.resugar(&evaluated)),
-                Box::new(driver.resugar(&inferred)),
+                Box::new(driver.resugar_value(&inferred)),
             );
 
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
         },
         ReplCommand::Core(term_src) => {
-            use pikelet_core::syntax::core::{RcTerm, Term};
+            use pikelet_core::syntax::core::Term;
 
             let (term, inferred) = driver.infer_file(file_name, term_src)?;
-            let ann_term = Term::Ann(term, RcTerm::from(Term::from(&*inferred)));
+            let ann_term = Term::Ann(term, driver.quote(&inferred)?);
 
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
         },
+        ReplCommand::Goals(term_src) => {
+            let (goals, diagnostics) = driver.hole_goals_file(file_name, term_src);
+
+            if goals.is_empty() && diagnostics.is_empty() {
+                println!("no holes found");
+            } else {
+                let goals = goals
+                    .iter()
+                    .map(|goal| Diagnostic::from(goal.to_diagnostic()));
+                driver
+                    .emit(writer.lock(), &goals.collect::<Vec<_>>())
+                    .unwrap();
+            }
+
+            if !diagnostics.is_empty() {
+                return Err(diagnostics);
+            }
+        },
         ReplCommand::Let(name, term_src) => {
             let (term, inferred) = driver.infer_file(file_name, term_src)?;
             driver.add_binding(&name, term.clone(), inferred.clone());
 
             let ann_term = Term::Ann(
                 Box::new(Term::Name(ByteSpan::default(), name, None)),
-                Box::new(driver.resugar(&inferred)),
+                Box::new(driver.resugar_value(&inferred)),
             );
 
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
@@ -333,7 +377,7 @@ fn eval_print(
         },
         ReplCommand::TypeOf(term_src) => {
             let (_, inferred) = driver.infer_file(file_name, term_src)?;
-            let inferred = driver.resugar(&inferred);
+            let inferred = driver.resugar_value(&inferred);
 
             println!("{}", inferred.to_doc().group().pretty(term_width()));
         },
@@ -0,0 +1,114 @@
+//! Non-interactive type checking of files
+
+use failure::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{ColorArg, Diagnostic, FileName};
+
+use crate::ImportOpts;
+
+/// Options for the `check` subcommand
+#[derive(Debug, structopt::StructOpt)]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS")
+    )]
+    pub color: ColorArg,
+
+    /// The format to report diagnostics in
+    ///
+    /// Human readable diagnostics are written to stderr, while `json` (one
+    /// object per line) and `sarif` diagnostics are written to stdout.
+    #[structopt(
+        long = "message-format",
+        parse(try_from_str),
+        default_value = "human",
+        raw(possible_values = "MessageFormat::VARIANTS")
+    )]
+    pub message_format: MessageFormat,
+
+    #[structopt(flatten)]
+    pub imports: ImportOpts,
+
+    /// The files to check
+    #[structopt(name = "FILE", parse(from_os_str), raw(required = "true"))]
+    pub files: Vec<PathBuf>,
+}
+
+/// The format to report diagnostics in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MessageFormat {
+    Human,
+    Json,
+    Sarif,
+}
+
+impl MessageFormat {
+    /// The names of the formats, as accepted on the command line
+    pub const VARIANTS: &'static [&'static str] = &["human", "json", "sarif"];
+}
+
+impl FromStr for MessageFormat {
+    type Err = &'static str;
+
+    fn from_str(src: &str) -> Result<MessageFormat, &'static str> {
+        match src {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "sarif" => Ok(MessageFormat::Sarif),
+            _ => Err("valid values: human, json, sarif"),
+        }
+    }
+}
+
+/// Run the `check` subcommand with the given options
+///
+/// Every file is checked, even if an earlier one fails. An error is returned
+/// if any diagnostics were reported along the way, including warnings.
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut driver = crate::driver(&opts.imports);
+    let mut diagnostics = Vec::new();
+
+    for path in &opts.files {
+        let src = match fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(err) => {
+                let message = format!("unable to read `{}`: {}", path.display(), err);
+                diagnostics.push(Diagnostic::new_error(message));
+                continue;
+            },
+        };
+
+        let result = driver.infer_file(FileName::Real(path.clone()), src);
+        diagnostics.extend(driver.take_warnings());
+        if let Err(errors) = result {
+            diagnostics.extend(errors);
+        }
+    }
+
+    match opts.message_format {
+        MessageFormat::Human => {
+            let writer = StandardStream::stderr(opts.color.into());
+            driver.emit(writer.lock(), &diagnostics)?;
+        },
+        MessageFormat::Json => driver.emit_json(io::stdout().lock(), &diagnostics)?,
+        MessageFormat::Sarif => driver.emit_sarif(io::stdout().lock(), &diagnostics)?,
+    }
+
+    match diagnostics.len() {
+        0 => Ok(()),
+        1 => Err(failure::format_err!("checking failed with 1 diagnostic")),
+        count => Err(failure::format_err!(
+            "checking failed with {} diagnostics",
+            count,
+        )),
+    }
+}
@@ -0,0 +1,174 @@
+//! Generation of API documentation from doc comments
+
+use failure::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{ColorArg, Diagnostic, FileName, ItemDoc};
+
+use crate::ImportOpts;
+
+/// The width that type signatures are wrapped to
+const SIGNATURE_WIDTH: usize = 80;
+
+/// Options for the `doc` subcommand
+#[derive(Debug, structopt::StructOpt)]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS")
+    )]
+    pub color: ColorArg,
+
+    /// The format to write the documentation in
+    #[structopt(
+        long = "format",
+        parse(try_from_str),
+        default_value = "markdown",
+        raw(possible_values = "DocFormat::VARIANTS")
+    )]
+    pub format: DocFormat,
+
+    #[structopt(flatten)]
+    pub imports: ImportOpts,
+
+    /// The module to document
+    #[structopt(name = "FILE", parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+/// The format to write documentation in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    /// The names of the formats, as accepted on the command line
+    pub const VARIANTS: &'static [&'static str] = &["markdown", "html"];
+}
+
+impl FromStr for DocFormat {
+    type Err = &'static str;
+
+    fn from_str(src: &str) -> Result<DocFormat, &'static str> {
+        match src {
+            "markdown" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            _ => Err("valid values: markdown, html"),
+        }
+    }
+}
+
+/// Run the `doc` subcommand with the given options, printing the
+/// documentation of the items exported by the module to stdout
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut driver = crate::driver(&opts.imports);
+    let writer = StandardStream::stderr(opts.color.into());
+
+    let result = match fs::read_to_string(&opts.file) {
+        Ok(src) => driver.document_file(FileName::Real(opts.file.clone()), src),
+        Err(err) => {
+            let message = format!("unable to read `{}`: {}", opts.file.display(), err);
+            Err(vec![Diagnostic::new_error(message)])
+        },
+    };
+    driver.emit(writer.lock(), &driver.take_warnings())?;
+
+    match result {
+        Ok(items) => {
+            let title = opts.file.file_stem().map_or_else(
+                || opts.file.display().to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+            match opts.format {
+                DocFormat::Markdown => print!("{}", markdown(&title, &items)),
+                DocFormat::Html => print!("{}", html(&title, &items)),
+            }
+            Ok(())
+        },
+        Err(diagnostics) => {
+            driver.emit(writer.lock(), &diagnostics)?;
+            Err(failure::format_err!("documentation failed"))
+        },
+    }
+}
+
+/// The type signature of an item, as it would be declared in the source code
+fn signature(item: &ItemDoc) -> String {
+    let ty = item.ty.to_doc().group();
+    format!("{} : {}", item.label, ty.pretty(SIGNATURE_WIDTH))
+}
+
+/// Render the documentation of a module as Markdown
+pub fn markdown(title: &str, items: &[ItemDoc]) -> String {
+    let mut output = format!("# `{}`\n", title);
+
+    for item in items {
+        write!(output, "\n## `{}`\n\n", item.label).unwrap();
+        write!(output, "```pikelet\n{}\n```\n", signature(item)).unwrap();
+        if !item.doc.is_empty() {
+            write!(output, "\n{}\n", item.doc).unwrap();
+        }
+    }
+
+    output
+}
+
+/// Render the documentation of a module as an HTML page
+pub fn html(title: &str, items: &[ItemDoc]) -> String {
+    let mut output = String::new();
+
+    writeln!(output, "<!DOCTYPE html>").unwrap();
+    writeln!(output, "<html>").unwrap();
+    writeln!(output, "<head>").unwrap();
+    writeln!(output, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(output, "<title>{}</title>", escape_html(title)).unwrap();
+    writeln!(output, "</head>").unwrap();
+    writeln!(output, "<body>").unwrap();
+    writeln!(output, "<h1><code>{}</code></h1>", escape_html(title)).unwrap();
+
+    for item in items {
+        let label = escape_html(&item.label);
+        writeln!(output, "<section id=\"{}\">", label).unwrap();
+        writeln!(output, "<h2><code>{}</code></h2>", label).unwrap();
+        writeln!(
+            output,
+            "<pre><code>{}</code></pre>",
+            escape_html(&signature(item))
+        )
+        .unwrap();
+        for paragraph in item.doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+            writeln!(output, "<p>{}</p>", escape_html(paragraph.trim())).unwrap();
+        }
+        writeln!(output, "</section>").unwrap();
+    }
+
+    writeln!(output, "</body>").unwrap();
+    writeln!(output, "</html>").unwrap();
+
+    output
+}
+
+/// Escape the characters that have special meanings in HTML
+fn escape_html(src: &str) -> String {
+    let mut escaped = String::with_capacity(src.len());
+    for ch in src.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
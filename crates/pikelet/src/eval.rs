@@ -0,0 +1,36 @@
+//! Non-interactive evaluation of expressions
+
+use failure::Error;
+
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{ColorArg, FileName};
+
+use crate::ImportOpts;
+
+/// Options for the `eval` subcommand
+#[derive(Debug, structopt::StructOpt)]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS")
+    )]
+    pub color: ColorArg,
+
+    #[structopt(flatten)]
+    pub imports: ImportOpts,
+
+    /// The expression to evaluate
+    #[structopt(name = "EXPR")]
+    pub expr: String,
+}
+
+/// Run the `eval` subcommand with the given options
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let writer = StandardStream::stderr(opts.color.into());
+    let mut driver = crate::driver(&opts.imports);
+
+    crate::normalize_print(&mut driver, &writer, FileName::virtual_("eval"), opts.expr)
+}
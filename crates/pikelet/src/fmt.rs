@@ -0,0 +1,85 @@
+//! Formatting of source files
+
+use failure::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{ColorArg, Diagnostic, Driver, FileName};
+
+/// Options for the `fmt` subcommand
+#[derive(Debug, structopt::StructOpt)]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS")
+    )]
+    pub color: ColorArg,
+
+    /// Check that the files are formatted without modifying them, listing
+    /// the files that would be changed on stdout
+    #[structopt(long = "check")]
+    pub check: bool,
+
+    /// The files to format in place
+    #[structopt(name = "FILE", parse(from_os_str), raw(required = "true"))]
+    pub files: Vec<PathBuf>,
+}
+
+/// Run the `fmt` subcommand with the given options
+///
+/// Files that fail to parse are reported and left untouched, without
+/// stopping the remaining files from being formatted.
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let mut driver = Driver::new();
+    let mut diagnostics = Vec::new();
+    let mut unformatted = 0;
+
+    for path in &opts.files {
+        let src = match fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(err) => {
+                let message = format!("unable to read `{}`: {}", path.display(), err);
+                diagnostics.push(Diagnostic::new_error(message));
+                continue;
+            },
+        };
+
+        let formatted = match driver.format_file(FileName::Real(path.clone()), src.clone()) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                diagnostics.extend(errors);
+                continue;
+            },
+        };
+
+        if formatted == src {
+            continue;
+        } else if opts.check {
+            println!("{}", path.display());
+            unformatted += 1;
+        } else if let Err(err) = fs::write(path, formatted) {
+            let message = format!("unable to write `{}`: {}", path.display(), err);
+            diagnostics.push(Diagnostic::new_error(message));
+        }
+    }
+
+    let writer = StandardStream::stderr(opts.color.into());
+    driver.emit(writer.lock(), &diagnostics)?;
+
+    if !diagnostics.is_empty() {
+        Err(failure::format_err!("formatting failed"))
+    } else if unformatted == 1 {
+        Err(failure::format_err!("1 file is not formatted"))
+    } else if unformatted > 1 {
+        Err(failure::format_err!(
+            "{} files are not formatted",
+            unformatted
+        ))
+    } else {
+        Ok(())
+    }
+}
@@ -2,7 +2,15 @@
 
 use failure::Error;
 
-// TODO: test using https://github.com/killercup/assert_cli
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{Driver, FileName};
+use std::path::PathBuf;
+
+pub mod check;
+pub mod doc;
+pub mod eval;
+pub mod fmt;
+pub mod normalize;
 
 #[derive(Debug, structopt::StructOpt)]
 #[structopt(name = "pikelet")]
@@ -14,6 +22,21 @@ pub struct Opts {
 
 #[derive(Debug, structopt::StructOpt)]
 pub enum Command {
+    /// Type check some files, failing if any diagnostics are reported
+    #[structopt(name = "check")]
+    Check(check::Opts),
+    /// Generate documentation for the items exported by a module
+    #[structopt(name = "doc")]
+    Doc(doc::Opts),
+    /// Evaluate an expression, printing its normal form and type
+    #[structopt(name = "eval")]
+    Eval(eval::Opts),
+    /// Format some files, preserving their comments
+    #[structopt(name = "fmt")]
+    Fmt(fmt::Opts),
+    /// Normalize a file, printing its normal form and type
+    #[structopt(name = "normalize")]
+    Normalize(normalize::Opts),
     /// A REPL for running expressions
     #[structopt(name = "repl")]
     Repl(pikelet_repl::Opts),
@@ -22,10 +45,67 @@ pub enum Command {
     LanguageServer(pikelet_language_server::Opts),
 }
 
+/// Options for configuring how imports are resolved
+///
+/// These are shared between the subcommands that load files, and can be
+/// included in their options using `#[structopt(flatten)]`.
+#[derive(Debug, Clone, Default, structopt::StructOpt)]
+pub struct ImportOpts {
+    /// Directories to search in when resolving imports, in order of
+    /// priority (defaults to the current directory)
+    #[structopt(long = "import-root", parse(from_os_str))]
+    pub import_roots: Vec<PathBuf>,
+}
+
 /// Run `pikelet` with the given options
 pub fn run(opts: Opts) -> Result<(), Error> {
     match opts.command {
+        Command::Check(opts) => check::run(opts),
+        Command::Doc(opts) => doc::run(opts),
+        Command::Eval(opts) => eval::run(opts),
+        Command::Fmt(opts) => fmt::run(opts),
         Command::LanguageServer(opts) => pikelet_language_server::run(opts),
+        Command::Normalize(opts) => normalize::run(opts),
         Command::Repl(opts) => pikelet_repl::run(opts),
     }
 }
+
+/// Create a driver with the prelude loaded, searching the given import roots
+/// (or the current directory if there are none)
+fn driver(imports: &ImportOpts) -> Driver {
+    let mut driver = Driver::with_prelude();
+    driver.add_import_roots(imports.import_roots.clone());
+    driver
+}
+
+/// Normalize the given source, printing the normal form annotated with its
+/// type to stdout, and any diagnostics to the `writer`
+fn normalize_print(
+    driver: &mut Driver,
+    writer: &StandardStream,
+    name: FileName,
+    src: String,
+) -> Result<(), Error> {
+    use pikelet_concrete::syntax::concrete::Term;
+
+    let result = driver.infer_file(name, src).and_then(|(term, inferred)| {
+        let evaluated = driver.normalize_term(&term)?;
+        Ok(Term::Ann(
+            Box::new(driver.resugar(&evaluated)),
+            Box::new(driver.resugar_value(&inferred)),
+        ))
+    });
+    driver.emit(writer.lock(), &driver.take_warnings())?;
+
+    match result {
+        Ok(ann_term) => {
+            let width = term_size::dimensions().map_or(1_000_000, |(width, _)| width);
+            println!("{}", ann_term.to_doc().group().pretty(width));
+            Ok(())
+        },
+        Err(diagnostics) => {
+            driver.emit(writer.lock(), &diagnostics)?;
+            Err(failure::format_err!("encountered an error!"))
+        },
+    }
+}
@@ -0,0 +1,41 @@
+//! Non-interactive normalization of files
+
+use failure::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use pikelet_driver::termcolor::StandardStream;
+use pikelet_driver::{ColorArg, FileName};
+
+use crate::ImportOpts;
+
+/// Options for the `normalize` subcommand
+#[derive(Debug, structopt::StructOpt)]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS")
+    )]
+    pub color: ColorArg,
+
+    #[structopt(flatten)]
+    pub imports: ImportOpts,
+
+    /// The file to normalize
+    #[structopt(name = "FILE", parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+/// Run the `normalize` subcommand with the given options
+pub fn run(opts: Opts) -> Result<(), Error> {
+    let writer = StandardStream::stderr(opts.color.into());
+    let mut driver = crate::driver(&opts.imports);
+
+    let src = fs::read_to_string(&opts.file)
+        .map_err(|err| failure::format_err!("unable to read `{}`: {}", opts.file.display(), err))?;
+
+    crate::normalize_print(&mut driver, &writer, FileName::Real(opts.file.clone()), src)
+}
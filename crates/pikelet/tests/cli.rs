@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Run the `pikelet` binary with the given arguments, with colors disabled
+fn pikelet(args: &[&str]) -> Output {
+    let (subcommand, args) = args.split_first().unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_pikelet"))
+        .arg(subcommand)
+        .args(["--color", "never"])
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap()
+}
+
+/// Write a file to a temporary directory, returning its path
+fn write_file(name: &str, src: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pikelet-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, src).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn check_ok() {
+    let path = write_file("check-ok.pi", "record { x = 1 : S32 }");
+    let output = pikelet(&["check", path.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn check_error() {
+    let ok = write_file("check-error-ok.pi", "record { x = 1 : S32 }");
+    let bad = write_file("check-error-bad.pi", "record { x = true 1 }");
+    let output = pikelet(&["check", bad.to_str().unwrap(), ok.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("applied an argument to a term that was not a function"));
+    assert!(stderr(&output).contains("checking failed with 1 diagnostic"));
+}
+
+#[test]
+fn check_missing_file() {
+    let output = pikelet(&["check", "this/file/does/not/exist.pi"]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("unable to read `this/file/does/not/exist.pi`"));
+}
+
+#[test]
+fn normalize_file() {
+    let path = write_file("normalize.pi", r#"record { greeting = "hello" }.greeting"#);
+    let output = pikelet(&["normalize", path.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "\"hello\" : String\n");
+}
+
+#[test]
+fn eval_expr() {
+    let output = pikelet(&["eval", r#"(\x => x) "hello" : String"#]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "\"hello\" : String\n");
+}
+
+#[test]
+fn eval_error() {
+    let output = pikelet(&["eval", "true 1"]);
+
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("applied an argument to a term that was not a function"));
+}
+
+#[test]
+fn check_json() {
+    let path = write_file("check-json.pi", r#""hello" : S32"#);
+    let output = pikelet(&["check", "--message-format", "json", path.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    let diagnostic = stdout(&output);
+    assert_eq!(diagnostic.lines().count(), 1);
+    assert!(diagnostic.contains(r#""code":"literal-mismatch""#));
+    assert!(diagnostic.contains(r#""types":[{"role":"expected","type":"S32"}]"#));
+}
+
+#[test]
+fn fmt_check() {
+    let formatted = write_file("fmt-check-formatted.pi", "record { x = 1 }\n");
+    let unformatted = write_file("fmt-check-unformatted.pi", "record {x=1}");
+    let output = pikelet(&[
+        "fmt",
+        "--check",
+        formatted.to_str().unwrap(),
+        unformatted.to_str().unwrap(),
+    ]);
+
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), format!("{}\n", unformatted.display()));
+    assert!(stderr(&output).contains("1 file is not formatted"));
+    assert_eq!(fs::read_to_string(&unformatted).unwrap(), "record {x=1}");
+}
+
+#[test]
+fn fmt_in_place() {
+    let path = write_file("fmt-in-place.pi", "record {x=1; -- the answer\n}");
+    let output = pikelet(&["fmt", path.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "record {\n    x = 1; -- the answer\n}\n",
+    );
+}
+
+#[test]
+fn fmt_parse_error() {
+    let path = write_file("fmt-parse-error.pi", "record { x = }");
+    let output = pikelet(&["fmt", path.to_str().unwrap()]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("formatting failed"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "record { x = }");
+}
+
+#[test]
+fn doc_markdown() {
+    let path = write_file(
+        "doc-markdown.pi",
+        "record { answer } where {\n||| The answer\nanswer = 42 : S32;\n}",
+    );
+    let output = pikelet(&["doc", path.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "# `doc-markdown`\n\n## `answer`\n\n```pikelet\nanswer : S32\n```\n\nThe answer\n",
+    );
+}
+
+#[test]
+fn doc_html() {
+    let path = write_file(
+        "doc-html.pi",
+        "record { x } where {\n||| Less <than>\nx = 1 : S32;\n}",
+    );
+    let output = pikelet(&["doc", "--format", "html", path.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("<pre><code>x : S32</code></pre>\n<p>Less &lt;than&gt;</p>\n"));
+}
+
+#[test]
+fn doc_html_escapes_labels() {
+    use pikelet_concrete::syntax::concrete;
+    use pikelet_driver::ItemDoc;
+
+    let item = ItemDoc {
+        label: "<x>".to_owned(),
+        ty: concrete::Term::Name(Default::default(), "S32".to_owned(), None),
+        doc: String::new(),
+    };
+    let output = pikelet::doc::html("doc-html", &[item]);
+
+    assert!(output.contains("<section id=\"&lt;x&gt;\">\n<h2><code>&lt;x&gt;</code></h2>\n"));
+}